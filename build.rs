@@ -0,0 +1,32 @@
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+
+/// Compile the icon set into a GResource bundle, which is embedded in
+/// the viewer. Without `glib-compile-resources`, an empty bundle is
+/// embedded and the viewer falls back to text and theme icons.
+fn main() {
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    let target = Path::new(&out_dir).join("icons.gresource");
+
+    println!("cargo:rerun-if-changed=src/icons");
+
+    let status = Command::new("glib-compile-resources")
+        .arg("--sourcedir=src/icons")
+        .arg(format!("--target={}", target.display()))
+        .arg("src/icons/icons.gresource.xml")
+        .status();
+
+    match status {
+        Ok(ref status) if status.success() => (),
+        Ok(status) => panic!("glib-compile-resources failed: {}", status),
+        Err(err) => {
+            println!(
+                "cargo:warning=Cannot run glib-compile-resources, icons are not embedded: {}",
+                err
+            );
+            File::create(&target).expect("Cannot create empty icon bundle");
+        }
+    }
+}
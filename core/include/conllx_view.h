@@ -0,0 +1,47 @@
+#ifndef CONLLX_VIEW_H
+#define CONLLX_VIEW_H
+
+/* Generated with cbindgen, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Get the message of the last error on this thread, `NULL` when there
+ * was no error. The message is valid until the next rendering call.
+ */
+const char *conllx_view_last_error(void);
+
+/**
+ * Render the first sentence of a CoNLL text as SVG. Rendering SVG
+ * requires Graphviz `dot`.
+ *
+ * # Safety
+ *
+ * `conll` must be `NULL` or a NUL-terminated string.
+ */
+char *conllx_view_render_svg(const char *conll);
+
+/**
+ * Render the first sentence of a CoNLL text as a standalone TikZ
+ * document.
+ *
+ * # Safety
+ *
+ * `conll` must be `NULL` or a NUL-terminated string.
+ */
+char *conllx_view_render_tikz(const char *conll);
+
+/**
+ * Free a string that was returned by one of the rendering functions.
+ *
+ * # Safety
+ *
+ * `s` must be `NULL` or a string from a rendering function that was not
+ * freed yet.
+ */
+void conllx_view_string_free(char *s);
+
+#endif /* CONLLX_VIEW_H */
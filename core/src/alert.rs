@@ -0,0 +1,158 @@
+use std::str::FromStr;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use search::{MatchOptions, PreparedQuery, Query};
+
+/// A rule that flags the trees that are added to the treebank, e.g. to
+/// monitor the output of a parser.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AlertRule {
+    /// The name that flagged trees are shown with.
+    pub name: String,
+
+    /// The condition of flagged trees, see `Condition`.
+    pub condition: String,
+
+    /// Show a desktop notification when trees are flagged.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+/// An alert rule with its parsed condition.
+pub struct Alert {
+    pub rule: AlertRule,
+    condition: Condition,
+}
+
+impl Alert {
+    pub fn new(rule: AlertRule) -> Result<Self, ViewerError> {
+        let condition = rule.condition.parse()?;
+        Ok(Alert { rule, condition })
+    }
+
+    /// Check whether a tree is flagged by the alert.
+    pub fn matches(&self, graph: &DependencyGraph) -> bool {
+        self.condition.matches(graph)
+    }
+}
+
+/// A condition on trees.
+///
+/// A condition consists of whitespace-separated terms that must all hold.
+/// `length>N` and `length<N` compare the number of tokens of the tree.
+/// The other terms are query terms that must match the same token, where
+/// `root` matches tokens that are attached to the root, e.g.
+/// `root cpos=PUNCT`.
+pub struct Condition {
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    root: bool,
+    query: Option<PreparedQuery>,
+}
+
+impl FromStr for Condition {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ViewerError::InvalidQuery(s.to_owned());
+
+        let mut condition = Condition {
+            min_len: None,
+            max_len: None,
+            root: false,
+            query: None,
+        };
+        let mut terms = Vec::new();
+
+        for term in s.split_whitespace() {
+            if term == "root" {
+                condition.root = true;
+            } else if let Some(len) = term.strip_prefix("length>") {
+                condition.min_len = Some(len.parse::<usize>().map_err(|_| invalid())? + 1);
+            } else if let Some(len) = term.strip_prefix("length<") {
+                let len = len.parse::<usize>().map_err(|_| invalid())?;
+                condition.max_len = Some(len.checked_sub(1).ok_or_else(invalid)?);
+            } else {
+                terms.push(term);
+            }
+        }
+
+        if !terms.is_empty() {
+            let query: Query = terms.join(" ").parse()?;
+            condition.query = Some(query.prepare(MatchOptions::default()));
+        } else if !condition.root && condition.min_len.is_none() && condition.max_len.is_none() {
+            return Err(invalid());
+        }
+
+        Ok(condition)
+    }
+}
+
+impl Condition {
+    /// Check whether a tree meets the condition.
+    pub fn matches(&self, graph: &DependencyGraph) -> bool {
+        let len = graph.0.node_count();
+        if self.min_len.is_some_and(|min_len| len < min_len)
+            || self.max_len.is_some_and(|max_len| len > max_len)
+        {
+            return false;
+        }
+
+        if !self.root && self.query.is_none() {
+            return true;
+        }
+
+        graph.0.node_indices().any(|idx| {
+            let token = &graph.0[idx].token;
+            (!self.root || token.head() == Some(0))
+                && self.query.as_ref().is_none_or(|query| query.matches(token))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::Condition;
+    use graph::DependencyGraph;
+
+    fn graph() -> DependencyGraph {
+        let mut hi = Token::new("Hi");
+        hi.set_cpos(Some("INTJ"));
+        hi.set_head(Some(2));
+
+        let mut stop = Token::new(".");
+        stop.set_cpos(Some("PUNCT"));
+        stop.set_head(Some(0));
+
+        DependencyGraph::from(vec![hi, stop])
+    }
+
+    fn matches(condition: &str) -> bool {
+        condition.parse::<Condition>().unwrap().matches(&graph())
+    }
+
+    #[test]
+    fn matches_root_tokens() {
+        assert!(matches("root cpos=PUNCT"));
+        assert!(!matches("root cpos=INTJ"));
+        assert!(matches("cpos=INTJ"));
+    }
+
+    #[test]
+    fn compares_lengths() {
+        assert!(matches("length>1"));
+        assert!(!matches("length>2"));
+        assert!(matches("length<3"));
+        assert!(!matches("length<2 root"));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        assert!("".parse::<Condition>().is_err());
+        assert!("length>many".parse::<Condition>().is_err());
+        assert!("length<0".parse::<Condition>().is_err());
+    }
+}
@@ -0,0 +1,428 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+use conllx::{Features, Sentence, Token};
+use petgraph::graph::NodeIndex;
+
+use graph::DependencyGraph;
+use layer::{Layer, LayerValue};
+use search::PreparedQuery;
+use storage::TreebankStorage;
+use symbol::Symbol;
+
+/// Marker for absent values.
+const ABSENT: u32 = u32::MAX;
+
+/// Strings that are stored once, and referred to by their number.
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Arc<str>, u32>,
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    fn intern(&mut self, s: Option<&str>) -> u32 {
+        let s = match s {
+            Some(s) => s,
+            None => return ABSENT,
+        };
+
+        if let Some(&id) = self.ids.get(s) {
+            return id;
+        }
+
+        let id = self.strings.len() as u32;
+        let s: Arc<str> = Arc::from(s);
+        self.strings.push(s.clone());
+        self.ids.insert(s, id);
+        id
+    }
+
+    fn get(&self, id: u32) -> Option<&str> {
+        if id == ABSENT {
+            None
+        } else {
+            Some(&self.strings[id as usize])
+        }
+    }
+}
+
+/// A token, with its forms, lemmas and features stored in the interner
+/// of the arena and its tags and relations as symbols.
+struct CompactToken {
+    form: u32,
+    lemma: u32,
+    cpos: Option<Symbol>,
+    pos: Option<Symbol>,
+    features: u32,
+    head: u32,
+    head_rel: Option<Symbol>,
+    p_head: u32,
+    p_head_rel: Option<Symbol>,
+    space_after: Option<bool>,
+}
+
+/// Enhanced dependencies of a token as (head, relation) pairs. Enhanced
+/// relations can contain words, such as `obl:in`, so they are stored in
+/// the interner of the arena.
+type CompactDeps = Box<[(u32, u32)]>;
+
+struct CompactSentence {
+    tokens: Box<[CompactToken]>,
+
+    /// Enhanced dependencies of the tokens, empty when the sentence
+    /// has none.
+    enhanced: Box<[CompactDeps]>,
+
+    /// Multiword tokens as (first offset, last offset, form) triples.
+    multiwords: Box<[(u32, u32, u32)]>,
+}
+
+/// Compact storage of the sentences of a treebank.
+///
+/// Treebanks repeat the same forms, lemmas, tags and relations many
+/// times, so every string is stored once. Sentences are converted to
+/// dependency graphs when they are needed, searches run on the compact
+/// tokens.
+#[derive(Default)]
+pub struct SentenceArena {
+    interner: Interner,
+    sentences: Vec<CompactSentence>,
+
+    /// The number of strings after the interner was last rebuilt, or
+    /// when sentences were first removed.
+    compacted: usize,
+}
+
+impl SentenceArena {
+    pub fn new() -> Self {
+        SentenceArena::default()
+    }
+
+    pub fn push(&mut self, graph: &DependencyGraph) {
+        let sentence = self.compact(graph);
+        self.sentences.push(sentence);
+    }
+
+    /// Replace a sentence, for instance after it was edited.
+    pub fn replace(&mut self, idx: usize, graph: &DependencyGraph) {
+        self.sentences[idx] = self.compact(graph);
+    }
+
+    /// Remove the first `n` sentences. The interner is rebuilt when it
+    /// has doubled in size since it was last rebuilt, to drop the
+    /// strings that only removed sentences used.
+    pub fn remove_front(&mut self, n: usize) {
+        let n = n.min(self.sentences.len());
+        self.sentences.drain(..n);
+
+        // The strings of the arena as it was built are the baseline.
+        if self.compacted == 0 {
+            self.compacted = self.interner.strings.len();
+        } else if self.interner.strings.len() > 2 * self.compacted {
+            self.rebuild_interner();
+        }
+    }
+
+    fn rebuild_interner(&mut self) {
+        let old = mem::take(&mut self.interner);
+        let interner = &mut self.interner;
+
+        for sentence in &mut self.sentences {
+            for token in sentence.tokens.iter_mut() {
+                token.form = interner.intern(old.get(token.form));
+                token.lemma = interner.intern(old.get(token.lemma));
+                token.features = interner.intern(old.get(token.features));
+            }
+
+            for deps in sentence.enhanced.iter_mut() {
+                for dep in deps.iter_mut() {
+                    dep.1 = interner.intern(old.get(dep.1));
+                }
+            }
+
+            for multiword in sentence.multiwords.iter_mut() {
+                multiword.2 = interner.intern(old.get(multiword.2));
+            }
+        }
+
+        self.compacted = interner.strings.len();
+    }
+
+    fn compact(&mut self, graph: &DependencyGraph) -> CompactSentence {
+        let interner = &mut self.interner;
+        let mut tokens = Vec::with_capacity(graph.0.node_count());
+        let mut enhanced = Vec::new();
+        let mut multiwords = Vec::new();
+
+        for idx in graph.0.node_indices() {
+            let node = &graph.0[idx];
+            let token = &node.token;
+
+            tokens.push(CompactToken {
+                form: interner.intern(Some(token.form())),
+                lemma: interner.intern(token.lemma()),
+                cpos: token.cpos().map(Symbol::intern),
+                pos: token.pos().map(Symbol::intern),
+                features: interner.intern(token.features().map(Features::as_str)),
+                head: token.head().map(|head| head as u32).unwrap_or(ABSENT),
+                head_rel: token.head_rel().map(Symbol::intern),
+                p_head: token.p_head().map(|head| head as u32).unwrap_or(ABSENT),
+                p_head_rel: token.p_head_rel().map(Symbol::intern),
+                space_after: node.space_after,
+            });
+
+            if let Some((last, ref form)) = node.multiword {
+                multiwords.push((node.offset as u32, last as u32, interner.intern(Some(form))));
+            }
+
+            enhanced.push(
+                node.enhanced
+                    .iter()
+                    .map(|&(head, ref rel)| (head as u32, interner.intern(Some(rel))))
+                    .collect::<Vec<_>>()
+                    .into_boxed_slice(),
+            );
+        }
+
+        if enhanced.iter().all(|deps| deps.is_empty()) {
+            enhanced.clear();
+        }
+
+        CompactSentence {
+            tokens: tokens.into_boxed_slice(),
+            enhanced: enhanced.into_boxed_slice(),
+            multiwords: multiwords.into_boxed_slice(),
+        }
+    }
+
+    fn str(&self, id: u32) -> Option<&str> {
+        self.interner.get(id)
+    }
+
+    fn token(&self, compact: &CompactToken) -> Token {
+        let mut token = Token::new(self.str(compact.form).unwrap_or_default());
+        token.set_lemma(self.str(compact.lemma));
+        token.set_cpos(compact.cpos.map(Symbol::as_str));
+        token.set_pos(compact.pos.map(Symbol::as_str));
+        token.set_features(self.str(compact.features).map(Features::from_string));
+        token.set_head(number(compact.head));
+        token.set_head_rel(compact.head_rel.map(Symbol::as_str));
+        token.set_p_head(number(compact.p_head));
+        token.set_p_head_rel(compact.p_head_rel.map(Symbol::as_str));
+        token
+    }
+}
+
+/// A compact token in its arena, for matching queries.
+struct TokenRef<'a> {
+    arena: &'a SentenceArena,
+    token: &'a CompactToken,
+}
+
+impl<'a> LayerValue for TokenRef<'a> {
+    fn value(&self, layer: Layer) -> Option<&str> {
+        match layer {
+            Layer::Form => self.arena.str(self.token.form),
+            Layer::Lemma => self.arena.str(self.token.lemma),
+            _ => self.symbol(layer).map(Symbol::as_str),
+        }
+    }
+
+    fn symbol(&self, layer: Layer) -> Option<Symbol> {
+        match layer {
+            Layer::CPos => self.token.cpos,
+            Layer::Pos => self.token.pos,
+            Layer::HeadRel => self.token.head_rel,
+            Layer::PHeadRel => self.token.p_head_rel,
+            Layer::Form | Layer::Lemma => self.value(layer).and_then(Symbol::lookup),
+        }
+    }
+}
+
+impl TreebankStorage for SentenceArena {
+    fn find(&self, idx: usize, query: &PreparedQuery) -> Option<Vec<usize>> {
+        let sentence = self.sentences.get(idx)?;
+
+        Some(
+            sentence
+                .tokens
+                .iter()
+                .enumerate()
+                .filter(|&(_, token)| query.matches(&TokenRef { arena: self, token }))
+                .map(|(offset, _)| offset)
+                .collect(),
+        )
+    }
+
+    fn graph(&self, idx: usize) -> Option<DependencyGraph> {
+        let sentence = self.sentences.get(idx)?;
+        let mut graph = DependencyGraph::from(self.sentence(idx)?);
+
+        for (offset, deps) in sentence.enhanced.iter().enumerate() {
+            graph.0[NodeIndex::new(offset)].enhanced = deps
+                .iter()
+                .map(|&(head, rel)| (head as usize, self.str(rel).unwrap_or_default().to_owned()))
+                .collect();
+        }
+
+        for (offset, token) in sentence.tokens.iter().enumerate() {
+            graph.0[NodeIndex::new(offset)].space_after = token.space_after;
+        }
+
+        for &(first, last, form) in sentence.multiwords.iter() {
+            graph.0[NodeIndex::new(first as usize)].multiword =
+                Some((last as usize, self.str(form).unwrap_or_default().to_owned()));
+        }
+
+        Some(graph)
+    }
+
+    fn lemmas(&self, idx: usize) -> Option<Vec<Cow<'_, str>>> {
+        let sentence = self.sentences.get(idx)?;
+        Some(
+            sentence
+                .tokens
+                .iter()
+                .filter_map(|token| self.str(token.lemma).or_else(|| self.str(token.form)))
+                .map(Cow::Borrowed)
+                .collect(),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.sentences.len()
+    }
+
+    fn n_tokens(&self, idx: usize) -> Option<usize> {
+        self.sentences
+            .get(idx)
+            .map(|sentence| sentence.tokens.len())
+    }
+
+    fn sentence(&self, idx: usize) -> Option<Sentence> {
+        let sentence = self.sentences.get(idx)?;
+
+        Some(
+            sentence
+                .tokens
+                .iter()
+                .map(|token| self.token(token))
+                .collect(),
+        )
+    }
+}
+
+fn number(n: u32) -> Option<usize> {
+    if n == ABSENT {
+        None
+    } else {
+        Some(n as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::{Features, Sentence, Token};
+    use petgraph::graph::NodeIndex;
+
+    use super::SentenceArena;
+    use graph::DependencyGraph;
+    use storage::TreebankStorage;
+    use symbol::Symbol;
+
+    fn graph(forms: &[&str]) -> DependencyGraph {
+        let sentence: Sentence = forms
+            .iter()
+            .enumerate()
+            .map(|(offset, &form)| {
+                let mut token = Token::new(form);
+                token.set_head(Some(offset));
+                token.set_head_rel(Some("DEP"));
+                token
+            })
+            .collect();
+
+        DependencyGraph::from(sentence)
+    }
+
+    fn forms(arena: &SentenceArena, idx: usize) -> Vec<String> {
+        arena
+            .sentence(idx)
+            .unwrap()
+            .iter()
+            .map(|token| token.form().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn removing_sentences_keeps_the_newest() {
+        let mut arena = SentenceArena::new();
+        for forms in &[["a", "b"], ["c", "d"], ["e", "f"]] {
+            arena.push(&graph(forms));
+        }
+
+        arena.remove_front(2);
+        assert_eq!(arena.len(), 1);
+        assert_eq!(forms(&arena, 0), vec!["e", "f"]);
+
+        arena.remove_front(5);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn interner_is_rebuilt_when_it_doubled() {
+        let mut arena = SentenceArena::new();
+        arena.push(&graph(&["a", "b"]));
+        arena.push(&graph(&["c", "d"]));
+
+        // The first trim sets the baseline, without rebuilding.
+        arena.remove_front(1);
+        assert_eq!(arena.interner.strings.len(), 4);
+        assert_eq!(arena.compacted, 4);
+
+        arena.push(&graph(&["e", "f", "g", "h", "i"]));
+        arena.remove_front(1);
+        assert_eq!(arena.interner.strings.len(), 5);
+        assert_eq!(arena.compacted, 5);
+        assert_eq!(forms(&arena, 0), vec!["e", "f", "g", "h", "i"]);
+
+        // Strings are interned again after the rebuild.
+        arena.push(&graph(&["e", "j"]));
+        assert_eq!(arena.interner.strings.len(), 6);
+        assert_eq!(forms(&arena, 1), vec!["e", "j"]);
+    }
+
+    #[test]
+    fn features_and_enhanced_relations_are_not_symbols() {
+        let mut graph = graph(&["a", "b"]);
+        graph.0[NodeIndex::new(0)]
+            .token
+            .set_features(Some(Features::from_string("Case=arena-test")));
+        graph.0[NodeIndex::new(1)].enhanced = vec![(1, "obl:arena-test".to_owned())];
+
+        let mut arena = SentenceArena::new();
+        arena.push(&graph);
+        arena.push(&graph);
+        assert_eq!(Symbol::lookup("Case=arena-test"), None);
+        assert_eq!(Symbol::lookup("obl:arena-test"), None);
+
+        // The values are kept when the interner is rebuilt.
+        arena.remove_front(1);
+        arena.rebuild_interner();
+        let stored = arena.graph(0).unwrap();
+        assert_eq!(
+            stored.0[NodeIndex::new(0)]
+                .token
+                .features()
+                .map(Features::as_str),
+            Some("Case=arena-test")
+        );
+        assert_eq!(
+            stored.0[NodeIndex::new(1)].enhanced,
+            vec![(1, "obl:arena-test".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+/// A small cache that evicts the least recently used entry when it is
+/// full.
+///
+/// Entries are looked up by linear search, so the cache is meant for a
+/// modest number of entries.
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: VecDeque<(K, V)>,
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: PartialEq,
+{
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Look up an entry, marking it as most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let idx = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(idx)?;
+        self.entries.push_front(entry);
+        self.entries.front().map(|(_, v)| v)
+    }
+
+    /// Remove an entry, returning its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let idx = self.entries.iter().position(|(k, _)| k == key)?;
+        self.entries.remove(idx).map(|(_, v)| v)
+    }
+
+    /// Add an entry, replacing an entry with the same key.
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some(idx) = self.entries.iter().position(|(k, _)| *k == key) {
+            self.entries.remove(idx);
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+
+        self.entries.push_front((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn cache_with_capacity_one_keeps_the_last_entry() {
+        let mut cache = LruCache::new(1);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&"two"));
+
+        cache.insert(2, "deux");
+        assert_eq!(cache.get(&2), Some(&"deux"));
+    }
+
+    #[test]
+    fn get_marks_entries_as_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        assert_eq!(cache.get(&1), Some(&"one"));
+
+        // The second entry is now the least recently used.
+        cache.insert(3, "three");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some(&"one"));
+        assert_eq!(cache.get(&3), Some(&"three"));
+    }
+
+    #[test]
+    fn removed_and_disabled_entries_are_not_found() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "one");
+        assert_eq!(cache.remove(&1), Some("one"));
+        assert_eq!(cache.get(&1), None);
+
+        let mut disabled = LruCache::new(0);
+        disabled.insert(1, "one");
+        assert_eq!(disabled.get(&1), None);
+    }
+}
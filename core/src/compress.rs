@@ -0,0 +1,92 @@
+use std::io::{self, BufRead, BufReader};
+
+use bzip2::read::BzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+/// Compression of a treebank, detected from its first bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    Bzip2,
+    Gzip,
+    Xz,
+}
+
+impl Compression {
+    /// Detect the compression of a treebank, without consuming its
+    /// data. Returns `None` for uncompressed treebanks.
+    pub fn detect<R>(read: &mut R) -> io::Result<Option<Self>>
+    where
+        R: BufRead,
+    {
+        let buf = read.fill_buf()?;
+
+        Ok(if buf.starts_with(b"\x1f\x8b") {
+            Some(Compression::Gzip)
+        } else if buf.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if buf.starts_with(b"\xfd7zXZ\x00") {
+            Some(Compression::Xz)
+        } else {
+            None
+        })
+    }
+}
+
+/// Decompress a treebank when it is compressed.
+pub fn decompress<'a, R>(mut read: R) -> io::Result<Box<dyn BufRead + 'a>>
+where
+    R: BufRead + 'a,
+{
+    Ok(match Compression::detect(&mut read)? {
+        None => Box::new(read),
+        Some(Compression::Bzip2) => Box::new(BufReader::new(BzDecoder::new(read))),
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(read))),
+        Some(Compression::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(read))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read};
+
+    use super::{decompress, Compression};
+
+    fn detect(data: &[u8]) -> Option<Compression> {
+        Compression::detect(&mut &*data).unwrap()
+    }
+
+    #[test]
+    fn compression_is_detected_by_magic() {
+        assert_eq!(detect(b"\x1f\x8b\x08\x00\x00\x00"), Some(Compression::Gzip));
+        assert_eq!(detect(b"BZh91AY&SY"), Some(Compression::Bzip2));
+        assert_eq!(detect(b"\xfd7zXZ\x00\x00\x04"), Some(Compression::Xz));
+
+        assert_eq!(detect(b"1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n"), None);
+        assert_eq!(detect(b"\x1f"), None);
+        assert_eq!(detect(b"BZ"), None);
+        assert_eq!(detect(b"\xfd7zX"), None);
+        assert_eq!(detect(b""), None);
+    }
+
+    #[test]
+    fn detection_does_not_consume_data() {
+        let mut read: &[u8] = b"BZh91AY&SY";
+        assert_eq!(
+            Compression::detect(&mut read).unwrap(),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(read.fill_buf().unwrap(), b"BZh91AY&SY");
+    }
+
+    #[test]
+    fn uncompressed_data_is_passed_through() {
+        let data = "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n";
+        let mut decompressed = String::new();
+        decompress(data.as_bytes())
+            .unwrap()
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use serde_json;
+
+use alert::AlertRule;
+use dirs::config_dir;
+use error::{ResultExt, ViewerError};
+use export::SubtreeFormat;
+use graph::{LayoutEngine, RenderOptions, TreeRenderer};
+use profile::Palette;
+use theme::Theme;
+
+const CONFIG_FILE: &str = "config.json";
+
+/// Configuration of the viewer.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    /// The file the configuration is stored in, `None` when it is not
+    /// stored.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+
+    /// Rules that flag trees, e.g. `{"name": "punctuation root",
+    /// "condition": "root cpos=PUNCT", "notify": true}`.
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+
+    /// Accelerators of actions, e.g. `"next": ["n", "Page_Down"]`. These
+    /// replace the default accelerators of the actions.
+    #[serde(default)]
+    pub keymap: BTreeMap<String, Vec<String>>,
+
+    /// Number of trees that `Page_Up` and `Page_Down` move, 10 by
+    /// default.
+    #[serde(default)]
+    pub page_stride: Option<usize>,
+
+    /// Settings of the preferences dialog.
+    #[serde(flatten)]
+    pub preferences: Preferences,
+
+    /// Seconds between the trees of the slideshow, 5 by default.
+    #[serde(default)]
+    pub slideshow_interval: Option<f64>,
+
+    /// Navigate with Vim-style keys: `j`/`k`, `gg`/`G` and counts.
+    #[serde(default)]
+    pub vim: bool,
+}
+
+impl Config {
+    /// Open the configuration. The default configuration is used when
+    /// the file does not exist.
+    pub fn open(path: Option<PathBuf>) -> Result<Self, ViewerError> {
+        let mut config: Config = match path {
+            Some(ref path) if path.exists() => serde_json::from_reader(BufReader::new(
+                File::open(path).context("Cannot open configuration")?,
+            ))
+            .context("Cannot read configuration")?,
+            _ => Config::default(),
+        };
+        config.path = path;
+
+        Ok(config)
+    }
+
+    /// Write the configuration to disk.
+    pub fn save(&self) -> Result<(), ViewerError> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Cannot create configuration directory")?;
+        }
+
+        let writer = BufWriter::new(File::create(path).context("Cannot create configuration")?);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+}
+
+/// Settings that are changed in the preferences dialog while the viewer
+/// runs.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Renderer of the trees, `graphviz` (the default) or `diagram`.
+    pub renderer: Option<TreeRenderer>,
+
+    /// Font of the word forms, e.g. `"DejaVu Sans"`. The font of the
+    /// language profile is used by default.
+    pub font: Option<String>,
+
+    /// Colors of the word forms by part of speech: `plain`, `universal`
+    /// or `stts`. The palette of the language profile is used by
+    /// default.
+    pub palette: Option<Palette>,
+
+    /// Feature that marks tokens, `mark` by default.
+    pub mark_feature: Option<String>,
+
+    /// Theme of trees: `light`, `dark` or `high-contrast`. The viewer
+    /// follows the GTK theme by default.
+    pub theme: Option<Theme>,
+
+    /// Format of saved subtrees: `conll` (the default), `dot`, `svg` or
+    /// `tikz`.
+    pub export_format: Option<SubtreeFormat>,
+
+    /// Directory of saved trees and subtrees, the working directory by
+    /// default.
+    pub export_directory: Option<PathBuf>,
+
+    /// Path of the Graphviz program, e.g. `/opt/graphviz/bin/dot`.
+    pub graphviz: Option<PathBuf>,
+
+    /// Graphviz layout engine: `dot` (the default), `neato`, `fdp` or
+    /// `twopi`.
+    pub layout_engine: Option<LayoutEngine>,
+
+    /// Extra arguments of the Graphviz program, e.g.
+    /// `["-Gnodesep=0.5"]`.
+    pub graphviz_args: Vec<String>,
+}
+
+impl Preferences {
+    /// Override render options with the settings of the preferences.
+    pub fn apply(&self, options: &mut RenderOptions) {
+        if let Some(renderer) = self.renderer {
+            options.renderer = renderer;
+        }
+        if let Some(ref font) = self.font {
+            options.font = Some(font.clone());
+        }
+        if let Some(palette) = self.palette {
+            options.profile.palette = palette;
+        }
+        if let Some(ref mark_feature) = self.mark_feature {
+            options.mark_feature = mark_feature.clone();
+        }
+        if let Some(theme) = self.theme {
+            options.theme = theme;
+        }
+
+        // Graphviz settings on the command line take precedence.
+        let graphviz = &mut options.graphviz;
+        if graphviz.program.is_none() {
+            graphviz.program = self.graphviz.clone();
+        }
+        if graphviz.engine.is_none() {
+            graphviz.engine = self.layout_engine;
+        }
+        if graphviz.args.is_empty() {
+            graphviz.args = self.graphviz_args.clone();
+        }
+    }
+
+    /// The path of a saved file in the export directory.
+    pub fn export_path(&self, filename: &str) -> PathBuf {
+        match self.export_directory {
+            Some(ref dir) => dir.join(filename),
+            None => PathBuf::from(filename),
+        }
+    }
+}
+
+/// The default location of the configuration.
+pub fn default_config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::Config;
+    use export::SubtreeFormat;
+    use graph::{LayoutEngine, RenderOptions, TreeRenderer};
+    use profile::Palette;
+    use theme::Theme;
+
+    #[test]
+    fn preferences_are_read_with_the_configuration() {
+        let config: Config = serde_json::from_str(
+            r#"{"page_stride": 50, "renderer": "diagram", "palette": "universal",
+                "mark_feature": "check", "export_format": "tikz", "theme": "high-contrast",
+                "layout_engine": "neato", "graphviz_args": ["-Gnodesep=0.5"]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.page_stride, Some(50));
+        assert_eq!(config.preferences.export_format, Some(SubtreeFormat::Tikz));
+        assert_eq!(
+            config.preferences.export_path("s1.dot").to_str(),
+            Some("s1.dot")
+        );
+
+        let mut options = RenderOptions::default();
+        config.preferences.apply(&mut options);
+        assert_eq!(options.renderer, TreeRenderer::Diagram);
+        assert_eq!(options.profile.palette, Palette::Universal);
+        assert_eq!(options.mark_feature, "check");
+        assert_eq!(options.font, None);
+        assert_eq!(options.theme, Theme::HighContrast);
+        assert_eq!(options.graphviz.program, None);
+        assert_eq!(options.graphviz.engine, Some(LayoutEngine::Neato));
+        assert_eq!(options.graphviz.args, ["-Gnodesep=0.5"]);
+
+        let mut options = RenderOptions::default();
+        options.graphviz.engine = Some(LayoutEngine::Twopi);
+        config.preferences.apply(&mut options);
+        assert_eq!(options.graphviz.engine, Some(LayoutEngine::Twopi));
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["renderer"], "diagram");
+        assert!(json.get("preferences").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{BufRead, Write};
+
+use conllx::{Features, ReadError, Sentence, Token};
+
+use conllu::{format_numeric_field, format_string_field, parse_numeric_field, parse_string_field};
+use error::ViewerError;
+use graph::DependencyGraph;
+
+/// Reader for CoNLL-2009 treebanks.
+///
+/// The gold columns (LEMMA, POS, FEAT, HEAD and DEPREL) are read, the
+/// predicted columns are only used when a gold column is empty, as in
+/// blind test sets. POS is read into both part-of-speech layers. The
+/// semantic role columns are skipped.
+pub struct Conll2009Reader<R> {
+    read: R,
+}
+
+impl<R: BufRead> Conll2009Reader<R> {
+    pub fn new(read: R) -> Self {
+        Conll2009Reader { read }
+    }
+
+    /// Read the next sentence. Returns `None` at the end of the input.
+    pub fn read_graph(&mut self) -> Result<Option<DependencyGraph>, ViewerError> {
+        let mut line = String::new();
+        let mut tokens = Sentence::new();
+
+        loop {
+            line.clear();
+
+            if self.read.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = line.trim();
+
+            if line.is_empty() {
+                if tokens.is_empty() {
+                    continue;
+                }
+
+                break;
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<_> = line.split('\t').collect();
+            let field = |gold: usize, predicted: usize| {
+                parse_string_field(fields.get(gold).cloned())
+                    .or_else(|| parse_string_field(fields.get(predicted).cloned()))
+            };
+
+            parse_numeric_field(fields.first().cloned())?;
+
+            let mut token = Token::new(*fields.get(1).ok_or(ReadError::MissingFormField)?);
+            token.set_lemma(field(2, 3));
+            token.set_cpos(field(4, 5));
+            token.set_pos(field(4, 5));
+            token.set_features(field(6, 7).map(Features::from_string));
+            token.set_head(parse_numeric_field(field(8, 9).as_deref())?);
+            token.set_head_rel(field(10, 11));
+
+            tokens.push(token);
+        }
+
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(DependencyGraph::from(tokens)))
+    }
+}
+
+impl<R: BufRead> Iterator for Conll2009Reader<R> {
+    type Item = Result<DependencyGraph, ViewerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_graph() {
+            Ok(None) => None,
+            Ok(Some(graph)) => Some(Ok(graph)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Write a tree as a CoNLL-2009 sentence. The gold columns are also
+/// written as the predicted columns, the fine-grained part-of-speech tag
+/// is preferred as POS. There are no semantic roles.
+pub fn write_graph<W>(write: &mut W, graph: &DependencyGraph) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    for (offset, token) in graph.to_sentence().iter().enumerate() {
+        let lemma = format_string_field(token.lemma());
+        let pos = format_string_field(token.pos().or_else(|| token.cpos()));
+        let features = format_string_field(token.features().map(Features::as_str));
+        let head = format_numeric_field(token.head());
+        let rel = format_string_field(token.head_rel());
+
+        writeln!(
+            write,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t_\t_",
+            offset + 1,
+            token.form(),
+            lemma,
+            lemma,
+            pos,
+            pos,
+            features,
+            features,
+            head,
+            head,
+            rel,
+            rel
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use std::io::{BufRead, Write};
+
+use conllx::{Features, ReadError, Sentence, Token};
+use petgraph::graph::NodeIndex;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+
+/// Reader for CoNLL-U treebanks.
+///
+/// UPOS and XPOS are read into the coarse-grained and fine-grained
+/// part-of-speech tags, the DEPS column into the enhanced dependencies
+/// of the graph nodes. The surface forms of multiword tokens and the
+/// SpaceAfter attributes are kept for displaying the sentence. Comments
+/// and empty nodes are skipped, as are enhanced dependencies on empty
+/// nodes.
+pub struct ConlluReader<R> {
+    read: R,
+}
+
+impl<R: BufRead> ConlluReader<R> {
+    pub fn new(read: R) -> Self {
+        ConlluReader { read }
+    }
+
+    /// Read the next sentence. Returns `None` at the end of the input.
+    pub fn read_graph(&mut self) -> Result<Option<DependencyGraph>, ViewerError> {
+        let mut line = String::new();
+        let mut tokens = Sentence::new();
+        let mut enhanced = Vec::new();
+        let mut space_after = Vec::new();
+
+        // Multiword tokens as (first token, last token, form, space
+        // after), with 1-based token positions.
+        let mut multiwords = Vec::new();
+
+        loop {
+            line.clear();
+
+            if self.read.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = line.trim();
+
+            if line.is_empty() {
+                if tokens.is_empty() {
+                    continue;
+                }
+
+                break;
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let mut iter = line.split('\t');
+
+            let id = iter.next().unwrap_or("");
+            if id.contains('.') {
+                continue;
+            }
+
+            if id.contains('-') {
+                let mut range = id.splitn(2, '-');
+                let first = parse_numeric_field(range.next())?;
+                let last = parse_numeric_field(range.next())?;
+                let form = iter.next().ok_or(ReadError::MissingFormField)?;
+                if let (Some(first), Some(last)) = (first, last) {
+                    multiwords.push((first, last, form.to_owned(), parse_space_after(iter.nth(7))));
+                }
+
+                continue;
+            }
+            parse_numeric_field(Some(id))?;
+
+            let mut token = Token::new(iter.next().ok_or(ReadError::MissingFormField)?);
+            token.set_lemma(parse_string_field(iter.next()));
+            token.set_cpos(parse_string_field(iter.next()));
+            token.set_pos(parse_string_field(iter.next()));
+            token.set_features(parse_string_field(iter.next()).map(Features::from_string));
+            token.set_head(parse_numeric_field(iter.next())?);
+            token.set_head_rel(parse_string_field(iter.next()));
+
+            tokens.push(token);
+            enhanced.push(parse_deps_field(iter.next())?);
+            space_after.push(parse_space_after(iter.next()));
+        }
+
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let mut graph = DependencyGraph::from(tokens);
+        for (offset, (deps, space_after)) in enhanced.into_iter().zip(space_after).enumerate() {
+            let node = &mut graph.0[NodeIndex::new(offset)];
+            node.enhanced = deps;
+            node.space_after = space_after;
+        }
+
+        // The spacing of a multiword token applies to its last token.
+        let n_tokens = graph.0.node_count();
+        for (first, last, form, space_after) in multiwords {
+            if first == 0 || first > last || last > n_tokens {
+                continue;
+            }
+
+            graph.0[NodeIndex::new(first - 1)].multiword = Some((last - 1, form));
+            if space_after.is_some() {
+                graph.0[NodeIndex::new(last - 1)].space_after = space_after;
+            }
+        }
+
+        Ok(Some(graph))
+    }
+}
+
+impl<R: BufRead> Iterator for ConlluReader<R> {
+    type Item = Result<DependencyGraph, ViewerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_graph() {
+            Ok(None) => None,
+            Ok(Some(graph)) => Some(Ok(graph)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Write a tree as a CoNLL-U sentence. Multiword tokens and SpaceAfter
+/// attributes are written back, the coarse-grained and fine-grained
+/// part-of-speech tags are written as UPOS and XPOS.
+pub fn write_graph<W>(write: &mut W, graph: &DependencyGraph) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    let graph = &graph.0;
+
+    // The last token of the multiword token that is written.
+    let mut multiword_last = None;
+
+    for idx in graph.node_indices() {
+        let node = &graph[idx];
+        let id = idx.index() + 1;
+
+        if let Some((last, ref form)) = node.multiword {
+            writeln!(
+                write,
+                "{}-{}\t{}\t_\t_\t_\t_\t_\t_\t_\t{}",
+                id,
+                last + 1,
+                form,
+                format_space_after(graph[NodeIndex::new(last)].space_after)
+            )?;
+            multiword_last = Some(last);
+        }
+
+        // The spacing of a multiword token is written on its own line.
+        let misc = match multiword_last {
+            Some(last) if idx.index() <= last => "_",
+            _ => format_space_after(node.space_after),
+        };
+
+        let token = &node.token;
+        let deps = if node.enhanced.is_empty() {
+            "_".to_owned()
+        } else {
+            node.enhanced
+                .iter()
+                .map(|&(head, ref rel)| format!("{}:{}", head, rel))
+                .collect::<Vec<_>>()
+                .join("|")
+        };
+
+        writeln!(
+            write,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            id,
+            token.form(),
+            format_string_field(token.lemma()),
+            format_string_field(token.cpos()),
+            format_string_field(token.pos()),
+            format_string_field(token.features().map(Features::as_str)),
+            format_numeric_field(token.head()),
+            format_string_field(token.head_rel()),
+            deps,
+            misc
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Format an optional field, `_` when it is absent.
+pub fn format_string_field(field: Option<&str>) -> &str {
+    field.unwrap_or("_")
+}
+
+/// Format an optional numeric field, `_` when it is absent.
+pub fn format_numeric_field(field: Option<usize>) -> String {
+    field
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| "_".to_owned())
+}
+
+/// Format the SpaceAfter attribute as a MISC field.
+fn format_space_after(space_after: Option<bool>) -> &'static str {
+    match space_after {
+        Some(false) => "SpaceAfter=No",
+        _ => "_",
+    }
+}
+
+pub fn parse_string_field(field: Option<&str>) -> Option<String> {
+    field.filter(|&s| s != "_").map(str::to_owned)
+}
+
+pub fn parse_numeric_field(field: Option<&str>) -> Result<Option<usize>, ReadError> {
+    match parse_string_field(field) {
+        Some(s) => s
+            .parse()
+            .map(Some)
+            .map_err(|_| ReadError::ParseIntField { value: s }),
+        None => Ok(None),
+    }
+}
+
+/// Parse the SpaceAfter attribute of a MISC field, `None` if the
+/// attribute is absent.
+fn parse_space_after(field: Option<&str>) -> Option<bool> {
+    parse_string_field(field)?
+        .split('|')
+        .find(|attr| attr.starts_with("SpaceAfter="))
+        .map(|attr| attr != "SpaceAfter=No")
+}
+
+/// Parse a DEPS field, such as `2:nsubj|4:nsubj:xsubj`.
+fn parse_deps_field(field: Option<&str>) -> Result<Vec<(usize, String)>, ReadError> {
+    let field = match parse_string_field(field) {
+        Some(field) => field,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut deps = Vec::new();
+    for dep in field.split('|') {
+        let mut parts = dep.splitn(2, ':');
+        let head = parts.next().unwrap_or("");
+        let rel = parts.next().unwrap_or("_");
+
+        // Empty nodes are not part of the graph.
+        if head.contains('.') {
+            continue;
+        }
+
+        let head = head.parse().map_err(|_| ReadError::ParseIntField {
+            value: head.to_owned(),
+        })?;
+        deps.push((head, rel.to_owned()));
+    }
+
+    Ok(deps)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use petgraph::graph::NodeIndex;
+
+    use super::{write_graph, ConlluReader};
+    use graph::DependencyGraph;
+
+    static SENTENCE: &str = "1\tJe\til\tPRON\t_\tNumber=Sing\t2\tnsubj\t2:nsubj\t_\n\
+                             2\tvais\taller\tVERB\t_\t_\t0\troot\t0:root\t_\n\
+                             3-4\tau\t_\t_\t_\t_\t_\t_\t_\t_\n\
+                             3\tà\tà\tADP\t_\t_\t5\tcase\t5:case\t_\n\
+                             4\tle\tle\tDET\t_\t_\t5\tdet\t5:det\t_\n\
+                             5\tmarché\tmarché\tNOUN\t_\t_\t2\tobl\t2:obl|4:nmod\tSpaceAfter=No\n\
+                             6\t.\t.\tPUNCT\t_\t_\t2\tpunct\t2:punct\t_\n";
+
+    fn read_graphs(data: &str) -> Vec<DependencyGraph> {
+        ConlluReader::new(Cursor::new(data))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn write(graph: &DependencyGraph) -> String {
+        let mut data = Vec::new();
+        write_graph(&mut data, graph).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn sentences_round_trip() {
+        let graphs = read_graphs(SENTENCE);
+        assert_eq!(graphs.len(), 1);
+
+        let written = write(&graphs[0]);
+        assert_eq!(written, SENTENCE);
+
+        let reread = read_graphs(&written);
+        assert_eq!(reread.len(), 1);
+        assert_eq!(reread[0].to_sentence(), graphs[0].to_sentence());
+        assert_eq!(write(&reread[0]), written);
+    }
+
+    #[test]
+    fn multiword_tokens_span_their_tokens() {
+        let graph = &read_graphs(SENTENCE)[0];
+        let node = |offset| &graph.0[NodeIndex::new(offset)];
+
+        assert_eq!(node(2).multiword, Some((3, "au".to_owned())));
+        assert!(node(3).multiword.is_none());
+        assert_eq!(node(4).space_after, Some(false));
+        assert_eq!(
+            node(4).enhanced,
+            vec![(2, "obl".to_owned()), (4, "nmod".to_owned())]
+        );
+    }
+
+    #[test]
+    fn invalid_multiword_ranges_are_ignored() {
+        let data = "0-1\tx\t_\t_\t_\t_\t_\t_\t_\t_\n\
+                    2-1\ty\t_\t_\t_\t_\t_\t_\t_\t_\n\
+                    1-3\tz\t_\t_\t_\t_\t_\t_\t_\t_\n\
+                    1\ta\t_\t_\t_\t_\t0\troot\t_\t_\n\
+                    2\tb\t_\t_\t_\t_\t1\tdep\t_\t_\n";
+
+        let graph = &read_graphs(data)[0];
+        assert_eq!(graph.0.node_count(), 2);
+        assert!(graph
+            .0
+            .node_indices()
+            .all(|idx| graph.0[idx].multiword.is_none()));
+    }
+
+    #[test]
+    fn comments_and_empty_nodes_are_skipped() {
+        let data = "# sent_id = 1\n\
+                    # text = Sue likes\n\
+                    1\tSue\tSue\tPROPN\t_\t_\t2\tnsubj\t2:nsubj|2.1:nsubj\t_\n\
+                    2\tlikes\tlike\tVERB\t_\t_\t0\troot\t0:root\t_\n\
+                    2.1\tlikes\tlike\tVERB\t_\t_\t_\t_\t0:root\t_\n\
+                    \n\
+                    # sent_id = 2\n\
+                    1\tyes\tyes\tINTJ\t_\t_\t0\troot\t0:root\t_\n";
+
+        let graphs = read_graphs(data);
+        assert_eq!(graphs.len(), 2);
+        assert_eq!(graphs[0].0.node_count(), 2);
+        assert_eq!(
+            graphs[0].0[NodeIndex::new(0)].enhanced,
+            vec![(2, "nsubj".to_owned())]
+        );
+        assert_eq!(graphs[1].0.node_count(), 1);
+        assert_eq!(graphs[1].0[NodeIndex::new(0)].token.form(), "yes");
+    }
+
+    #[test]
+    fn malformed_columns_are_rejected() {
+        for data in &[
+            "x\tword\t_\t_\t_\t_\t0\troot\t_\t_\n",
+            "1\tword\t_\t_\t_\t_\tone\troot\t_\t_\n",
+            "1\tword\t_\t_\t_\t_\t0\troot\tone:root\t_\n",
+            "1-x\tword\t_\t_\t_\t_\t_\t_\t_\t_\n",
+            "1\n",
+        ] {
+            let mut reader = ConlluReader::new(Cursor::new(*data));
+            assert!(reader.read_graph().is_err(), "{:?}", data);
+        }
+    }
+}
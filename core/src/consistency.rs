@@ -0,0 +1,294 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use search::Match;
+use storage::TreebankStorage;
+
+/// The words of contexts before the start and after the end of a
+/// sentence.
+const SENTENCE_START: &str = "<s>";
+const SENTENCE_END: &str = "</s>";
+
+/// An annotation of the tokens with the same form in the same context.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Variant {
+    /// The relation of the tokens to their heads.
+    pub relation: String,
+
+    /// The position of the heads relative to the tokens, e.g. `-1` for
+    /// the previous token, `None` for root attachments.
+    pub head: Option<isize>,
+
+    pub matches: Vec<Match>,
+}
+
+impl Variant {
+    /// The annotation as the relation and the head position, e.g.
+    /// `nsubj +1` or `root ROOT`.
+    pub fn annotation(&self) -> String {
+        match self.head {
+            Some(head) => format!("{} {:+}", self.relation, head),
+            None => format!("{} ROOT", self.relation),
+        }
+    }
+}
+
+/// Tokens with the same form in the same context that have different
+/// heads or relations. Usually, all but one of the variants are
+/// annotation errors.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Inconsistency {
+    /// The words before the token, the form of the token and the words
+    /// after the token.
+    pub left: Vec<String>,
+    pub form: String,
+    pub right: Vec<String>,
+
+    /// The annotations of the tokens, the most frequent first.
+    pub variants: Vec<Variant>,
+}
+
+impl Inconsistency {
+    /// The context with the token in brackets, e.g. `the [dog] barked`.
+    pub fn context(&self) -> String {
+        let mut words: Vec<_> = self.left.iter().map(String::as_str).collect();
+        let form = format!("[{}]", self.form);
+        words.push(&form);
+        words.extend(self.right.iter().map(String::as_str));
+        words.join(" ")
+    }
+
+    /// The number of tokens with the form in the context.
+    pub fn count(&self) -> usize {
+        self.variants
+            .iter()
+            .map(|variant| variant.matches.len())
+            .sum()
+    }
+
+    /// The tokens of all variants, in treebank order.
+    pub fn matches(&self) -> Vec<Match> {
+        let mut matches: Vec<_> = self
+            .variants
+            .iter()
+            .flat_map(|variant| variant.matches.iter().cloned())
+            .collect();
+        matches.sort();
+        matches
+    }
+}
+
+/// The annotations of tokens by their form and context of `window` words
+/// on either side.
+type Contexts = HashMap<Vec<String>, BTreeMap<(String, Option<isize>), Vec<Match>>>;
+
+/// Add the tokens of a tree to the contexts.
+fn add_contexts(contexts: &mut Contexts, sentence: usize, graph: &DependencyGraph, window: usize) {
+    let tokens: Vec<_> = graph
+        .0
+        .node_indices()
+        .map(|idx| &graph.0[idx].token)
+        .collect();
+
+    for (offset, token) in tokens.iter().enumerate() {
+        let head = match token.head() {
+            Some(0) => None,
+            Some(head) => Some(head as isize - (offset + 1) as isize),
+            None => continue,
+        };
+
+        let context = (offset as isize - window as isize..=(offset + window) as isize)
+            .map(|position| match position {
+                position if position < 0 => SENTENCE_START.to_owned(),
+                position if position as usize >= tokens.len() => SENTENCE_END.to_owned(),
+                position => tokens[position as usize].form().to_owned(),
+            })
+            .collect();
+
+        contexts
+            .entry(context)
+            .or_default()
+            .entry((token.head_rel().unwrap_or("_").to_owned(), head))
+            .or_default()
+            .push(Match {
+                sentence,
+                token: offset,
+            });
+    }
+}
+
+fn merge_contexts(mut contexts: Contexts, other: Contexts) -> Contexts {
+    for (context, variants) in other {
+        let merged = contexts.entry(context).or_default();
+        for (annotation, matches) in variants {
+            merged.entry(annotation).or_default().extend(matches);
+        }
+    }
+
+    contexts
+}
+
+/// Find the tokens with the same form and the same `window` words on
+/// either side that are attached to different heads or with different
+/// relations. The inconsistencies with the most tokens come first.
+pub fn find_inconsistencies(
+    storage: &dyn TreebankStorage,
+    sentences: Vec<usize>,
+    window: usize,
+) -> Vec<Inconsistency> {
+    let contexts = sentences
+        .into_par_iter()
+        .filter_map(|sentence| Some((sentence, storage.graph(sentence)?)))
+        .map(|(sentence, graph)| {
+            let mut contexts = Contexts::new();
+            add_contexts(&mut contexts, sentence, &graph, window);
+            contexts
+        })
+        .reduce(Contexts::new, merge_contexts);
+
+    let mut inconsistencies: Vec<_> = contexts
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .map(|(mut context, variants)| {
+            let right = context.split_off(window + 1);
+            let form = context.pop().unwrap_or_default();
+
+            let mut variants: Vec<_> = variants
+                .into_iter()
+                .map(|((relation, head), mut matches)| {
+                    matches.sort();
+                    Variant {
+                        relation,
+                        head,
+                        matches,
+                    }
+                })
+                .collect();
+            variants.sort_by_key(|variant| Reverse(variant.matches.len()));
+
+            Inconsistency {
+                left: context,
+                form,
+                right,
+                variants,
+            }
+        })
+        .collect();
+    inconsistencies.sort_by(|a, b| {
+        b.count()
+            .cmp(&a.count())
+            .then_with(|| a.context().cmp(&b.context()))
+    });
+
+    inconsistencies
+}
+
+/// Write inconsistencies as a context line per inconsistency, followed
+/// by the annotations with their counts and the 1-based numbers of the
+/// sentences.
+pub fn write_inconsistencies<W>(
+    mut writer: W,
+    inconsistencies: &[Inconsistency],
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    for inconsistency in inconsistencies {
+        writeln!(writer, "{}", inconsistency.context())?;
+        for variant in &inconsistency.variants {
+            let sentences: Vec<_> = variant
+                .matches
+                .iter()
+                .map(|m| (m.sentence + 1).to_string())
+                .collect();
+            writeln!(
+                writer,
+                "{:>10} {:<20} {}",
+                variant.matches.len(),
+                variant.annotation(),
+                sentences.join(",")
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::find_inconsistencies;
+    use arena::SentenceArena;
+    use graph::DependencyGraph;
+    use search::Match;
+
+    fn graph(tokens: &[(&str, usize, &str)]) -> DependencyGraph {
+        DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn finds_tokens_annotated_differently_in_the_same_context() {
+        let mut arena = SentenceArena::new();
+        arena.push(&graph(&[
+            ("saw", 0, "root"),
+            ("her", 1, "obj"),
+            ("duck", 1, "obj"),
+        ]));
+        arena.push(&graph(&[
+            ("saw", 0, "root"),
+            ("her", 3, "nmod:poss"),
+            ("duck", 1, "obj"),
+        ]));
+        arena.push(&graph(&[
+            ("saw", 0, "root"),
+            ("her", 1, "obj"),
+            ("duck", 1, "obj"),
+        ]));
+        arena.push(&graph(&[("saw", 0, "root"), ("her", 1, "obj")]));
+
+        let inconsistencies = find_inconsistencies(&arena, vec![0, 1, 2, 3], 1);
+        assert_eq!(inconsistencies.len(), 1);
+
+        let inconsistency = &inconsistencies[0];
+        assert_eq!(inconsistency.context(), "saw [her] duck");
+        assert_eq!(inconsistency.count(), 3);
+        assert_eq!(inconsistency.variants[0].annotation(), "obj -1");
+        assert_eq!(
+            inconsistency.variants[0].matches,
+            [
+                Match {
+                    sentence: 0,
+                    token: 1
+                },
+                Match {
+                    sentence: 2,
+                    token: 1
+                }
+            ]
+        );
+        assert_eq!(inconsistency.variants[1].annotation(), "nmod:poss +1");
+
+        // The context of her in the last sentence ends with the sentence.
+        let inconsistencies = find_inconsistencies(&arena, vec![1, 3], 1);
+        assert!(inconsistencies.is_empty());
+
+        let inconsistencies = find_inconsistencies(&arena, vec![0, 1], 2);
+        assert_eq!(inconsistencies[0].context(), "<s> saw [her] duck </s>");
+    }
+}
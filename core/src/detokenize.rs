@@ -0,0 +1,321 @@
+use std::str::FromStr;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+
+/// Tokens that are not preceded by a space.
+const CLOSING: &[&str] = &[
+    ".", ",", ";", ":", "!", "?", ")", "]", "}", "»", "%", "...", "…",
+];
+
+/// Tokens that are not followed by a space.
+const OPENING: &[&str] = &["(", "[", "{", "«", "¿", "¡"];
+
+/// In French, these are preceded and followed by a space.
+const FRENCH_SPACED: &[&str] = &[";", ":", "!", "?", "«", "»"];
+
+/// Quotes that open and close alternately.
+const QUOTES: &[&str] = &["\"", "'"];
+
+/// Punctuation spacing rules, used for sentences without SpaceAfter
+/// annotations.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Spacing {
+    /// No space before closing and after opening punctuation.
+    Default,
+
+    /// Like `Default`, but with spaces around `;`, `:`, `!`, `?` and
+    /// guillemets.
+    French,
+
+    /// No spaces between tokens, e.g. for Chinese and Japanese.
+    None,
+}
+
+impl FromStr for Spacing {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Spacing::Default),
+            "french" => Ok(Spacing::French),
+            "none" => Ok(Spacing::None),
+            _ => Err(ViewerError::UnknownSpacing(s.to_owned())),
+        }
+    }
+}
+
+impl Spacing {
+    fn is_closing(self, text: &str) -> bool {
+        CLOSING.contains(&text) && !(self == Spacing::French && FRENCH_SPACED.contains(&text))
+    }
+
+    fn is_opening(self, text: &str) -> bool {
+        OPENING.contains(&text) && !(self == Spacing::French && FRENCH_SPACED.contains(&text))
+    }
+}
+
+/// A piece of the text of a sentence: a token or the surface form of a
+/// multiword token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Piece<'a> {
+    /// The offset of the first token of the piece.
+    pub first: usize,
+
+    /// The offset of the last token of the piece.
+    pub last: usize,
+
+    pub text: &'a str,
+
+    /// Whether the piece is followed by a space.
+    pub space_after: bool,
+}
+
+impl<'a> Piece<'a> {
+    /// Check whether the piece contains a token.
+    pub fn contains(&self, token: usize) -> bool {
+        token >= self.first && token <= self.last
+    }
+}
+
+/// Split the text of a sentence into pieces. Multiword tokens are shown
+/// by their surface forms. Spaces follow the SpaceAfter annotations of
+/// the sentence; sentences without such annotations are spaced by the
+/// punctuation rules.
+pub fn pieces(graph: &DependencyGraph, spacing: Spacing) -> Vec<Piece<'_>> {
+    let nodes: Vec<_> = graph.0.node_indices().map(|idx| &graph.0[idx]).collect();
+
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    while offset < nodes.len() {
+        let (last, text) = match nodes[offset].multiword {
+            Some((last, ref form)) if last >= offset && last < nodes.len() => (last, form.as_str()),
+            _ => (offset, nodes[offset].token.form()),
+        };
+
+        pieces.push(Piece {
+            first: offset,
+            last,
+            text,
+            space_after: true,
+        });
+
+        offset = last + 1;
+    }
+
+    let annotated = nodes.iter().any(|node| node.space_after.is_some());
+    let mut quote_open = false;
+    let mut opening = Vec::with_capacity(pieces.len());
+    let mut closing = Vec::with_capacity(pieces.len());
+    for piece in &pieces {
+        if QUOTES.contains(&piece.text) {
+            opening.push(!quote_open);
+            closing.push(quote_open);
+            quote_open = !quote_open;
+        } else {
+            opening.push(spacing.is_opening(piece.text));
+            closing.push(spacing.is_closing(piece.text));
+        }
+    }
+
+    let n_pieces = pieces.len();
+    for (idx, piece) in pieces.iter_mut().enumerate() {
+        piece.space_after = if idx + 1 == n_pieces {
+            false
+        } else if annotated {
+            nodes[piece.last].space_after.unwrap_or(true)
+        } else {
+            spacing != Spacing::None && !opening[idx] && !closing[idx + 1]
+        };
+    }
+
+    pieces
+}
+
+/// Get the text of a sentence, as it is displayed and exported.
+pub fn detokenize(graph: &DependencyGraph, spacing: Spacing) -> String {
+    let mut text = String::new();
+    for piece in pieces(graph, spacing) {
+        text.push_str(piece.text);
+        if piece.space_after {
+            text.push(' ');
+        }
+    }
+
+    text
+}
+
+/// A line of a keyword-in-context (KWIC) concordance: a token with the
+/// text to its left and right.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KwicLine {
+    pub left: String,
+    pub keyword: String,
+    pub right: String,
+}
+
+/// Get the concordance line of a token, with up to `width` characters
+/// of context on either side. Context that is cut off is marked with an
+/// ellipsis. The keyword is the piece that contains the token, e.g. the
+/// surface form of a multiword token.
+pub fn kwic(graph: &DependencyGraph, token: usize, spacing: Spacing, width: usize) -> KwicLine {
+    let mut line = KwicLine {
+        left: String::new(),
+        keyword: String::new(),
+        right: String::new(),
+    };
+
+    let mut seen_keyword = false;
+    for piece in pieces(graph, spacing) {
+        let text = if piece.contains(token) {
+            seen_keyword = true;
+            line.keyword.push_str(piece.text);
+            if piece.space_after {
+                line.right.push(' ');
+            }
+            continue;
+        } else if seen_keyword {
+            &mut line.right
+        } else {
+            &mut line.left
+        };
+
+        text.push_str(piece.text);
+        if piece.space_after {
+            text.push(' ');
+        }
+    }
+
+    let n_left = line.left.chars().count();
+    if n_left > width {
+        line.left = format!(
+            "\u{2026}{}",
+            line.left.chars().skip(n_left - width).collect::<String>()
+        );
+    }
+    if line.right.chars().count() > width {
+        line.right = format!(
+            "{}\u{2026}",
+            line.right.chars().take(width).collect::<String>()
+        );
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use conllu::ConlluReader;
+
+    use super::{detokenize, kwic, KwicLine, Spacing};
+
+    const SNAPSHOT: &str = "src/snapshots/detokenize.txt";
+
+    /// Sentences and the spacing rules they are detokenized with.
+    const CASES: &[(&str, Spacing, &str)] = &[
+        (
+            "punctuation",
+            Spacing::Default,
+            "1\tHello\n2\t,\n3\tworld\n4\t(\n5\tagain\n6\t)\n7\t!\n",
+        ),
+        (
+            "quotes",
+            Spacing::Default,
+            "1\tHe\n2\tsaid\n3\t\"\n4\tyes\n5\t\"\n6\t.\n",
+        ),
+        (
+            "space-after",
+            Spacing::Default,
+            "1\tdon\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n2\t't\n3\tgo\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n4\t!\n",
+        ),
+        (
+            "space-after-overrides-rules",
+            Spacing::Default,
+            "1\tWait\n2\t:\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n3\tnow\n",
+        ),
+        (
+            "multiword",
+            Spacing::Default,
+            "1-2\tdu\n1\tde\n2\tle\n3\tpain\n",
+        ),
+        (
+            "multiword-space-after",
+            Spacing::Default,
+            "1\tVoy\n2-4\tdámelo\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n2\tdá\n3\tme\n4\tlo\n5\t.\n",
+        ),
+        (
+            "french",
+            Spacing::French,
+            "1\tPourquoi\n2\t?\n3\t«\n4\tParce\n5\tque\n6\t»\n7\t.\n",
+        ),
+        (
+            "no-spaces",
+            Spacing::None,
+            "1\t我\n2\t爱\n3\t你\n4\t。\n",
+        ),
+        (
+            "inverted-question-mark",
+            Spacing::Default,
+            "1\t¿\n2\tQué\n3\t?\n",
+        ),
+    ];
+
+    fn render() -> String {
+        let mut snapshot = String::new();
+
+        for &(name, spacing, conllu) in CASES {
+            let graph = ConlluReader::new(conllu.as_bytes())
+                .next()
+                .expect("Case without sentence")
+                .expect("Cannot read case");
+            snapshot.push_str(&format!("== {}\n{}\n", name, detokenize(&graph, spacing)));
+        }
+
+        snapshot
+    }
+
+    /// Compare the detokenized cases with the snapshot. Run with
+    /// `UPDATE_SNAPSHOTS=1` to update the snapshot after a deliberate
+    /// change.
+    #[test]
+    fn detokenize_matches_snapshot() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT);
+        let snapshot = render();
+
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, &snapshot).expect("Cannot write snapshot");
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).expect("Cannot read snapshot");
+        assert_eq!(snapshot, expected);
+    }
+
+    #[test]
+    fn kwic_cuts_off_context() {
+        let graph = ConlluReader::new("1\tThe\n2\tdog\n3\tbarks\n4\tloudly\n5\t.\n".as_bytes())
+            .next()
+            .expect("No sentence")
+            .expect("Cannot read sentence");
+
+        assert_eq!(
+            kwic(&graph, 2, Spacing::Default, 5),
+            KwicLine {
+                left: "\u{2026} dog ".to_owned(),
+                keyword: "barks".to_owned(),
+                right: " loud\u{2026}".to_owned(),
+            }
+        );
+        assert_eq!(kwic(&graph, 0, Spacing::Default, 5).left, "");
+    }
+
+    #[test]
+    fn spacing_names() {
+        assert_eq!("french".parse::<Spacing>().ok(), Some(Spacing::French));
+        assert!("klingon".parse::<Spacing>().is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+use std::io::BufRead;
+
+use conllx::{ReadSentence, Reader, Sentence, Token};
+
+use error::ViewerError;
+
+/// The changes to the tokens of a sentence.
+///
+/// A diff is written as a hunk that starts with a `@@ sentence N @@`
+/// header, followed by the original and changed version of every changed
+/// token as (tab-separated) CoNLL-X lines, prefixed by `-` and `+`:
+///
+/// ```text
+/// @@ sentence 3 @@
+/// -2 Haus Haus NN NN _ 3 SUBJ _ _
+/// +2 Haus Haus NN NN _ 3 OBJA _ _
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentenceDiff {
+    /// Index of the sentence in the treebank.
+    pub sentence: usize,
+
+    /// The changed tokens as (offset, original, changed) triples.
+    pub tokens: Vec<(usize, Token, Token)>,
+}
+
+impl fmt::Display for SentenceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "@@ sentence {} @@", self.sentence + 1)?;
+        for &(offset, ref original, ref changed) in &self.tokens {
+            writeln!(f, "-{}\t{}", offset + 1, original)?;
+            writeln!(f, "+{}\t{}", offset + 1, changed)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare two versions of a sentence. Returns `None` when the versions
+/// are identical.
+pub fn diff_sentence(
+    idx: usize,
+    original: &Sentence,
+    edited: &Sentence,
+) -> Result<Option<SentenceDiff>, ViewerError> {
+    if original.len() != edited.len() {
+        return Err(ViewerError::TokenCountMismatch(idx + 1));
+    }
+
+    let tokens: Vec<_> = changed_tokens(original, edited)
+        .into_iter()
+        .map(|offset| (offset, original[offset].clone(), edited[offset].clone()))
+        .collect();
+
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(SentenceDiff {
+        sentence: idx,
+        tokens,
+    }))
+}
+
+/// Compare two versions of a treebank. The versions must have the same
+/// sentences and tokens, only the annotations can differ.
+pub fn diff_treebanks(
+    original: &[Sentence],
+    edited: &[Sentence],
+) -> Result<Vec<SentenceDiff>, ViewerError> {
+    if original.len() != edited.len() {
+        return Err(ViewerError::SentenceCountMismatch(
+            original.len(),
+            edited.len(),
+        ));
+    }
+
+    let mut diffs = Vec::new();
+    for (idx, (original, edited)) in original.iter().zip(edited).enumerate() {
+        diffs.extend(diff_sentence(idx, original, edited)?);
+    }
+
+    Ok(diffs)
+}
+
+/// Return the offsets of the tokens that differ between two versions
+/// of a sentence.
+pub fn changed_tokens(original: &Sentence, edited: &Sentence) -> Vec<usize> {
+    original
+        .iter()
+        .zip(edited)
+        .enumerate()
+        .filter(|&(_, (original, edited))| original != edited)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Read sentence diffs in the format written by `SentenceDiff`.
+pub fn read_diffs<R>(read: R) -> Result<Vec<SentenceDiff>, ViewerError>
+where
+    R: BufRead,
+{
+    let mut diffs: Vec<SentenceDiff> = Vec::new();
+    let mut original = None;
+
+    for line in read.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.starts_with("@@ sentence ") {
+            let sentence = line
+                .trim_matches(|c: char| !c.is_ascii_digit())
+                .parse::<usize>()
+                .ok()
+                .filter(|&sentence| sentence > 0)
+                .ok_or_else(|| ViewerError::InvalidPatch(line.clone()))?;
+            diffs.push(SentenceDiff {
+                sentence: sentence - 1,
+                tokens: Vec::new(),
+            });
+            continue;
+        }
+
+        let diff = match diffs.last_mut() {
+            Some(diff) => diff,
+            None => return Err(ViewerError::InvalidPatch(line)),
+        };
+
+        match (line.chars().next(), original.take()) {
+            (Some('-'), None) => original = Some(parse_token_line(&line[1..])?),
+            (Some('+'), Some((offset, token))) => {
+                let (changed_offset, changed) = parse_token_line(&line[1..])?;
+                if changed_offset != offset {
+                    return Err(ViewerError::InvalidPatch(line));
+                }
+                diff.tokens.push((offset, token, changed));
+            }
+            _ => return Err(ViewerError::InvalidPatch(line)),
+        }
+    }
+
+    if original.is_some() {
+        return Err(ViewerError::InvalidPatch(
+            "patch ends in a removed token".to_owned(),
+        ));
+    }
+
+    Ok(diffs)
+}
+
+/// Apply sentence diffs to a treebank. Fails without changing the
+/// treebank when the original version of a token in the diff does not
+/// match the treebank.
+pub fn apply_diffs(sentences: &mut [Sentence], diffs: &[SentenceDiff]) -> Result<(), ViewerError> {
+    for diff in diffs {
+        for &(offset, ref original, _) in &diff.tokens {
+            let token = sentences
+                .get(diff.sentence)
+                .and_then(|sentence| sentence.get(offset));
+            if token != Some(original) {
+                return Err(ViewerError::PatchConflict(diff.sentence + 1, offset + 1));
+            }
+        }
+    }
+
+    for diff in diffs {
+        for &(offset, _, ref changed) in &diff.tokens {
+            sentences[diff.sentence][offset] = changed.clone();
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a CoNLL-X token line, returning the token offset and the token.
+fn parse_token_line(line: &str) -> Result<(usize, Token), ViewerError> {
+    let offset = line
+        .split('\t')
+        .next()
+        .and_then(|id| id.parse::<usize>().ok())
+        .filter(|&id| id > 0)
+        .ok_or_else(|| ViewerError::InvalidPatch(line.to_owned()))?;
+
+    let mut sentence = Reader::new(line.as_bytes())
+        .read_sentence()?
+        .ok_or_else(|| ViewerError::InvalidPatch(line.to_owned()))?;
+
+    Ok((offset - 1, sentence.remove(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::{ReadSentence, Reader, Sentence};
+
+    use super::{apply_diffs, diff_sentence, diff_treebanks, read_diffs};
+    use error::ViewerError;
+
+    fn read_sentences(data: &str) -> Vec<Sentence> {
+        Reader::new(data.as_bytes())
+            .sentences()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn original() -> Vec<Sentence> {
+        read_sentences(
+            "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n\
+             2\tHaus\tHaus\tNN\tNN\t_\t0\tROOT\t_\t_\n\
+             \n\
+             1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n\
+             2\tschläft\tschlafen\tVVFIN\tVVFIN\t_\t0\tROOT\t_\t_\n",
+        )
+    }
+
+    fn edited() -> Vec<Sentence> {
+        read_sentences(
+            "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n\
+             2\tHaus\tHaus\tNN\tNN\t_\t0\tROOT\t_\t_\n\
+             \n\
+             1\tEr\ter\tPPER\tPPER\t_\t2\tOBJA\t_\t_\n\
+             2\tschläft\tschlafen\tVVFIN\tVVFIN\t_\t0\tROOT\t_\t_\n",
+        )
+    }
+
+    #[test]
+    fn diffs_round_trip_and_apply() {
+        let mut sentences = original();
+        let diffs = diff_treebanks(&sentences, &edited()).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].sentence, 1);
+        assert_eq!(diffs[0].tokens.len(), 1);
+
+        let patch: String = diffs.iter().map(ToString::to_string).collect();
+        assert!(patch.starts_with("@@ sentence 2 @@\n-1\tEr\t"));
+        assert_eq!(read_diffs(patch.as_bytes()).unwrap(), diffs);
+
+        apply_diffs(&mut sentences, &diffs).unwrap();
+        assert_eq!(sentences, edited());
+    }
+
+    #[test]
+    fn identical_sentences_have_no_diff() {
+        let sentences = original();
+        assert!(diff_sentence(0, &sentences[0], &sentences[0])
+            .unwrap()
+            .is_none());
+        assert!(diff_treebanks(&sentences, &sentences).unwrap().is_empty());
+        assert!(diff_sentence(0, &sentences[0], &sentences[1]).is_ok());
+        assert!(diff_treebanks(&sentences, &sentences[..1]).is_err());
+    }
+
+    #[test]
+    fn malformed_diffs_are_rejected() {
+        for patch in &[
+            "@@ sentence 0 @@\n",
+            "@@ sentence x @@\n",
+            "-1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n",
+            "@@ sentence 2 @@\n+1\tEr\ter\tPPER\tPPER\t_\t2\tOBJA\t_\t_\n",
+            "@@ sentence 2 @@\n-1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n",
+            "@@ sentence 2 @@\n-1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n\
+             +2\tEr\ter\tPPER\tPPER\t_\t2\tOBJA\t_\t_\n",
+            "@@ sentence 2 @@\n-0\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n\
+             +0\tEr\ter\tPPER\tPPER\t_\t2\tOBJA\t_\t_\n",
+            "@@ sentence 2 @@\n 1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n",
+        ] {
+            assert!(read_diffs(patch.as_bytes()).is_err(), "{:?}", patch);
+        }
+    }
+
+    #[test]
+    fn out_of_range_and_conflicting_diffs_are_rejected() {
+        let diffs = diff_treebanks(&original(), &edited()).unwrap();
+
+        // The sentence of the diff is not in the treebank.
+        let mut sentences = original();
+        sentences.truncate(1);
+        let err = apply_diffs(&mut sentences, &diffs).unwrap_err();
+        assert!(matches!(err, ViewerError::PatchConflict(2, 1)));
+
+        // The token of the diff is not in the sentence.
+        let mut out_of_range = diffs.clone();
+        out_of_range[0].tokens[0].0 = 5;
+        let mut sentences = original();
+        let err = apply_diffs(&mut sentences, &out_of_range).unwrap_err();
+        assert!(matches!(err, ViewerError::PatchConflict(2, 6)));
+        assert_eq!(sentences, original());
+
+        // The diff was made against another version of the treebank.
+        let mut sentences = edited();
+        assert!(apply_diffs(&mut sentences, &diffs).is_err());
+        assert_eq!(sentences, edited());
+    }
+}
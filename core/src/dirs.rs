@@ -0,0 +1,124 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Environment variable with the directory that holds the configuration
+/// and data of a relocated installation.
+const HOME_VAR: &str = "CONLLX_VIEW_HOME";
+
+/// Directory next to the executable that makes an installation
+/// portable, with the configuration and data of the viewer.
+const PORTABLE_DIR: &str = "conllx-view.home";
+
+/// Environment variable with the path of the Graphviz dot program.
+const DOT_VAR: &str = "CONLLX_VIEW_DOT";
+
+/// The directory of the running executable. For a bundle such as an
+/// AppImage or Flatpak, this is the `bin` directory of the bundle.
+fn bundle_dir() -> Option<PathBuf> {
+    env::current_exe().ok()?.parent().map(Path::to_owned)
+}
+
+/// The directory that the viewer is installed in. For an AppImage, this
+/// is the directory of the AppImage file rather than of its read-only
+/// mount.
+fn install_dir() -> Option<PathBuf> {
+    match env::var_os("APPIMAGE") {
+        Some(appimage) => Path::new(&appimage).parent().map(Path::to_owned),
+        None => bundle_dir(),
+    }
+}
+
+/// The directory with the configuration and data of a relocated
+/// installation: `CONLLX_VIEW_HOME`, or otherwise a `conllx-view.home`
+/// directory next to the executable (or AppImage).
+fn relocated_home() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(HOME_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+
+    let dir = install_dir()?.join(PORTABLE_DIR);
+    if dir.is_dir() {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
+/// A directory of the XDG base directory specification, e.g.
+/// `XDG_CONFIG_HOME` with the default `.config`.
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    let dir = match env::var_os(var) {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&env::var_os("HOME")?).join(default),
+    };
+
+    Some(dir.join("conllx-view"))
+}
+
+/// The configuration directory of the viewer. This is the `config`
+/// directory of a relocated installation, or otherwise follows the XDG
+/// base directory specification.
+pub fn config_dir() -> Option<PathBuf> {
+    match relocated_home() {
+        Some(home) => Some(home.join("config")),
+        None => xdg_dir("XDG_CONFIG_HOME", ".config"),
+    }
+}
+
+/// The data directory of the viewer. This is the `data` directory of a
+/// relocated installation, or otherwise follows the XDG base directory
+/// specification.
+pub fn data_dir() -> Option<PathBuf> {
+    match relocated_home() {
+        Some(home) => Some(home.join("data")),
+        None => xdg_dir("XDG_DATA_HOME", ".local/share"),
+    }
+}
+
+/// Create a command that runs Graphviz dot. The program is `program`
+/// when it is given, `CONLLX_VIEW_DOT`, a `dot` that is bundled with the
+/// executable, or otherwise `dot` from the `PATH`.
+///
+/// A bundled dot loads its plugins from the `lib/graphviz` directory of
+/// the bundle, since the plugin directory that Graphviz was built with
+/// does not exist on the machine it is run on.
+pub fn dot_command(program: Option<&Path>) -> Command {
+    if let Some(program) = program {
+        return Command::new(program);
+    }
+
+    if let Some(dot) = env::var_os(DOT_VAR) {
+        return Command::new(dot);
+    }
+
+    let bundle = match bundle_dir() {
+        Some(dir) => dir,
+        None => return Command::new("dot"),
+    };
+
+    let dot = bundle.join("dot");
+    if !dot.is_file() {
+        return Command::new("dot");
+    }
+
+    let mut command = Command::new(dot);
+    let plugins = bundle.join("../lib/graphviz");
+    if plugins.is_dir() && env::var_os("GVBINDIR").is_none() {
+        command.env("GVBINDIR", plugins);
+    }
+
+    command
+}
+
+/// Check whether Graphviz dot (or `program`) can be run, by asking for
+/// its version.
+pub fn dot_available(program: Option<&Path>) -> bool {
+    dot_command(program)
+        .arg("-V")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,82 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use reqwest::{self, Response};
+
+use error::ViewerError;
+
+/// Minimum time between progress reports of a download.
+const PROGRESS_INTERVAL_MS: u64 = 100;
+
+/// Progress of downloading a treebank.
+#[derive(Clone, Debug)]
+pub struct Download {
+    pub url: String,
+    pub received: u64,
+
+    /// The size of the treebank, if the server reports it.
+    pub size: Option<u64>,
+}
+
+/// Get the URL of a treebank that is given as a path, `None` for local
+/// files.
+pub fn treebank_url(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    if path.starts_with("http://") || path.starts_with("https://") {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Start downloading a treebank. The treebank is streamed while it is
+/// read, `progress` is called as data is received.
+pub fn download<F>(url: &str, progress: F) -> Result<ProgressRead<Response, F>, ViewerError>
+where
+    F: FnMut(&Download),
+{
+    let response = reqwest::get(url)?.error_for_status()?;
+    let size = response.content_length();
+
+    Ok(ProgressRead {
+        inner: response,
+        download: Download {
+            url: url.to_owned(),
+            received: 0,
+            size,
+        },
+        progress,
+        reported: None,
+    })
+}
+
+/// Reader that reports the progress of a download.
+pub struct ProgressRead<R, F> {
+    inner: R,
+    download: Download,
+    progress: F,
+    reported: Option<Instant>,
+}
+
+impl<R, F> Read for ProgressRead<R, F>
+where
+    R: Read,
+    F: FnMut(&Download),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.download.received += n as u64;
+
+        // Report at intervals and at the end of the download.
+        let due = self.reported.is_none_or(|reported| {
+            reported.elapsed() >= Duration::from_millis(PROGRESS_INTERVAL_MS)
+        });
+        if due || n == 0 {
+            (self.progress)(&self.download);
+            self.reported = Some(Instant::now());
+        }
+
+        Ok(n)
+    }
+}
@@ -0,0 +1,20 @@
+use diff::diff_sentence;
+use model::StatefulTreebankModel;
+
+/// Summarize the changes that were made in the current session.
+///
+/// The summary is a list of sentence diffs: for every edited tree, the
+/// original and edited versions of the changed tokens are listed as
+/// CoNLL-X lines, prefixed by `-` and `+`. The summary can be applied
+/// to the treebank with `conllx-view patch`.
+pub fn changes_report(model: &StatefulTreebankModel) -> String {
+    model
+        .edited()
+        .filter_map(|(idx, original, graph)| {
+            // Edits do not add or remove tokens, so the versions can
+            // always be compared.
+            diff_sentence(idx, original, &graph.to_sentence()).ok()?
+        })
+        .map(|diff| diff.to_string())
+        .collect()
+}
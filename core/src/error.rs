@@ -0,0 +1,263 @@
+use std::ffi::NulError;
+use std::fmt::{self, Display};
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+use conllx::ReadError;
+use failure;
+use reqwest;
+#[cfg(feature = "rsvg")]
+use rsvg;
+use serde_json;
+#[cfg(feature = "zmq")]
+use zmq;
+
+/// Errors of the viewer.
+///
+/// Fallible functions of the crate return `Result<_, ViewerError>`. I/O,
+/// parse, rendering and GTK errors are wrapped in their own variants,
+/// `ResultExt::context` adds what failed to an error.
+#[derive(Debug, Fail)]
+pub enum ViewerError {
+    #[fail(display = "reading the treebank was canceled")]
+    Canceled,
+    #[fail(display = "cannot save treebank: {}", _0)]
+    CannotSave(String),
+
+    /// An error with a description of what failed.
+    #[fail(display = "{}: {}", _0, _1)]
+    Context(String, Box<ViewerError>),
+    #[fail(display = "dependency relation cannot be empty")]
+    EmptyRelation,
+    #[fail(display = "sentence has no tokens")]
+    EmptySentence,
+    #[fail(display = "git failed: {}", _0)]
+    Git(String),
+
+    /// An error of GLib or GTK, e.g. of reading an image.
+    #[fail(display = "{}", _0)]
+    Gtk(String),
+    #[fail(display = "library metadata and sentences do not match: {}", _0)]
+    InconsistentLibrary(String),
+    #[fail(display = "invalid dependency: {}", _0)]
+    InvalidDependency(String),
+    #[fail(display = "invalid head: {}", _0)]
+    InvalidHead(usize),
+    #[fail(display = "invalid length range: {}", _0)]
+    InvalidLengthRange(String),
+    #[fail(display = "invalid patch line: {}", _0)]
+    InvalidPatch(String),
+    #[fail(display = "invalid query: {}", _0)]
+    InvalidQuery(String),
+    #[fail(display = "invalid sentence range: {}", _0)]
+    InvalidRange(String),
+    #[fail(display = "invalid style: {}", _0)]
+    InvalidStyle(String),
+    #[fail(display = "{}", _0)]
+    Io(io::Error),
+    #[fail(display = "cannot create review link: {}", _0)]
+    NoReviewLink(String),
+    #[fail(display = "no graph is selected")]
+    NoGraphSelected,
+    #[fail(display = "no sentence at byte offset {}", _0)]
+    NoSentenceAt(u64),
+    #[fail(display = "no sentences in {}", _0)]
+    NoSentences(String),
+    #[fail(display = "no token is selected")]
+    NoTokenSelected,
+    #[fail(display = "{} is NULL", _0)]
+    NullArgument(String),
+    #[fail(display = "{} panicked", _0)]
+    Panicked(String),
+
+    /// A treebank, configuration file or argument that cannot be parsed.
+    #[fail(display = "{}", _0)]
+    Parse(String),
+    #[fail(display = "patch does not apply to sentence {}, token {}", _0, _1)]
+    PatchConflict(usize, usize),
+    #[fail(display = "cannot render tree: {}", _0)]
+    Render(String),
+    #[fail(display = "{} requires an input file", _0)]
+    RequiresInputFile(String),
+    #[fail(display = "{} requires an output prefix", _0)]
+    RequiresOutputPrefix(String),
+    #[fail(display = "{} requires a single treebank file", _0)]
+    RequiresSingleFile(String),
+    #[fail(display = "{} requires an uncompressed treebank", _0)]
+    RequiresUncompressed(String),
+    #[fail(
+        display = "treebanks have different numbers of sentences: {} and {}",
+        _0, _1
+    )]
+    SentenceCountMismatch(usize, usize),
+    #[fail(display = "sentence {} has a different number of tokens", _0)]
+    TokenCountMismatch(usize),
+    #[fail(display = "unknown input format: {}", _0)]
+    UnknownFormat(String),
+    #[fail(display = "unknown export format: {}", _0)]
+    UnknownExportFormat(String),
+    #[fail(display = "unknown image format: {}", _0)]
+    UnknownImageFormat(String),
+    #[fail(display = "unknown language: {}", _0)]
+    UnknownLanguage(String),
+    #[fail(display = "unknown layer: {}", _0)]
+    UnknownLayer(String),
+    #[fail(display = "unknown layout engine: {}", _0)]
+    UnknownLayoutEngine(String),
+    #[fail(display = "unknown quiz mode: {}", _0)]
+    UnknownQuizMode(String),
+    #[fail(display = "unknown ranking: {}", _0)]
+    UnknownRanking(String),
+    #[fail(display = "unknown spacing rules: {}", _0)]
+    UnknownSpacing(String),
+    #[fail(display = "unknown theme: {}", _0)]
+    UnknownTheme(String),
+}
+
+impl From<io::Error> for ViewerError {
+    fn from(err: io::Error) -> Self {
+        ViewerError::Io(err)
+    }
+}
+
+/// Errors of the conllx crate, which are I/O errors or errors of parsing
+/// sentences.
+impl From<failure::Error> for ViewerError {
+    fn from(err: failure::Error) -> Self {
+        match err.downcast::<io::Error>() {
+            Ok(err) => ViewerError::Io(err),
+            Err(err) => ViewerError::Parse(err.to_string()),
+        }
+    }
+}
+
+impl From<ReadError> for ViewerError {
+    fn from(err: ReadError) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+/// Errors of writing trees as text, e.g. as TikZ.
+impl From<fmt::Error> for ViewerError {
+    fn from(err: fmt::Error) -> Self {
+        ViewerError::Render(err.to_string())
+    }
+}
+
+impl From<FromUtf8Error> for ViewerError {
+    fn from(err: FromUtf8Error) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+impl From<NulError> for ViewerError {
+    fn from(err: NulError) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+impl From<ParseFloatError> for ViewerError {
+    fn from(err: ParseFloatError) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+impl From<ParseIntError> for ViewerError {
+    fn from(err: ParseIntError) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ViewerError {
+    fn from(err: serde_json::Error) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+/// Errors of downloading treebanks.
+impl From<reqwest::Error> for ViewerError {
+    fn from(err: reqwest::Error) -> Self {
+        ViewerError::Io(io::Error::other(err.to_string()))
+    }
+}
+
+impl From<Utf8Error> for ViewerError {
+    fn from(err: Utf8Error) -> Self {
+        ViewerError::Parse(err.to_string())
+    }
+}
+
+/// Errors of GLib, which librsvg uses.
+#[cfg(feature = "rsvg")]
+impl From<rsvg::Error> for ViewerError {
+    fn from(err: rsvg::Error) -> Self {
+        ViewerError::Gtk(err.to_string())
+    }
+}
+
+/// Errors of subscribing to treebanks with ZeroMQ.
+#[cfg(feature = "zmq")]
+impl From<zmq::Error> for ViewerError {
+    fn from(err: zmq::Error) -> Self {
+        ViewerError::Io(io::Error::other(err.to_string()))
+    }
+}
+
+/// Add a description of what failed to errors.
+pub trait ResultExt<T> {
+    fn context<D>(self, context: D) -> Result<T, ViewerError>
+    where
+        D: Display;
+
+    fn with_context<D, F>(self, context: F) -> Result<T, ViewerError>
+    where
+        D: Display,
+        F: FnOnce() -> D;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<ViewerError>,
+{
+    fn context<D>(self, context: D) -> Result<T, ViewerError>
+    where
+        D: Display,
+    {
+        self.with_context(|| context)
+    }
+
+    fn with_context<D, F>(self, context: F) -> Result<T, ViewerError>
+    where
+        D: Display,
+        F: FnOnce() -> D,
+    {
+        self.map_err(|err| ViewerError::Context(context().to_string(), Box::new(err.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use failure;
+
+    use super::{ResultExt, ViewerError};
+
+    #[test]
+    fn contexts_are_shown_before_errors() {
+        let result: Result<(), _> = Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let err = result.context("Cannot open treebank").unwrap_err();
+        assert_eq!(err.to_string(), "Cannot open treebank: no such file");
+    }
+
+    #[test]
+    fn io_errors_of_conllx_are_kept() {
+        let err = failure::Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        match ViewerError::from(err) {
+            ViewerError::Io(err) => assert_eq!(err.kind(), io::ErrorKind::NotFound),
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use error::ViewerError;
+use graph::{DependencyGraph, RenderOptions, Tikz};
+use quiz::{QuizAnswers, QuizMode};
+
+/// Printable exercises with their answer key, as LaTeX documents.
+pub struct Exercises {
+    pub exercises: String,
+    pub answers: String,
+}
+
+/// Create an exercise sheet from a set of trees. In the exercises, the
+/// relations or the arcs are stripped from the trees, depending on the
+/// quiz mode. The answer key contains the full trees.
+pub fn exercises(
+    graphs: &[&DependencyGraph],
+    mode: QuizMode,
+    options: &RenderOptions,
+) -> Result<Exercises, ViewerError> {
+    let mut exercise_options = options.clone();
+    exercise_options.tikz_preamble = false;
+    exercise_options.quiz = Some(QuizAnswers::new(mode));
+
+    let mut answer_options = options.clone();
+    answer_options.tikz_preamble = false;
+    answer_options.quiz = None;
+
+    let instruction = match mode {
+        QuizMode::Labels => "Fill in the dependency relations.",
+        QuizMode::Heads => "Draw the dependency arcs and their relations.",
+    };
+
+    Ok(Exercises {
+        exercises: exercise_document("Exercises", instruction, graphs, &exercise_options)?,
+        answers: exercise_document("Answer key", "", graphs, &answer_options)?,
+    })
+}
+
+fn exercise_document(
+    title: &str,
+    instruction: &str,
+    graphs: &[&DependencyGraph],
+    options: &RenderOptions,
+) -> Result<String, ViewerError> {
+    let mut doc = String::new();
+
+    doc.push_str("\\documentclass{article}\n\n");
+    doc.push_str("\\usepackage{adjustbox}\n");
+    doc.push_str("\\usepackage{tikz-dependency}\n\n");
+    doc.push_str("\\begin{document}\n\n");
+    doc.push_str(&format!("\\section*{{{}}}\n\n", title));
+
+    if !instruction.is_empty() {
+        doc.push_str(instruction);
+        doc.push_str("\n\n");
+    }
+
+    for (idx, graph) in graphs.iter().enumerate() {
+        doc.push_str(&format!("\\subsection*{{Exercise {}}}\n\n", idx + 1));
+        doc.push_str("\\begin{adjustbox}{max width=\\linewidth}\n");
+        doc.push_str(&graph.tikz(options)?);
+        doc.push_str("\n\\end{adjustbox}\n\n");
+    }
+
+    doc.push_str("\\end{document}\n");
+
+    Ok(doc)
+}
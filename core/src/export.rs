@@ -0,0 +1,300 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use conllx::{DisplaySentence, ReadSentence, Reader, Sentence, WriteSentence, Writer};
+use rand::Rng;
+
+use conll2009;
+use conllu;
+use error::ViewerError;
+use graph::{DependencyGraph, Dot, RenderOptions, Subtree, Svg, Tikz};
+use input::{read_graphs, InputFormat};
+use stanford;
+
+/// Read all trees of a treebank, for exporting without the viewer.
+pub fn read_treebank<R>(read: R, format: InputFormat) -> Result<Vec<DependencyGraph>, ViewerError>
+where
+    R: BufRead,
+{
+    read_graphs(read, format).collect()
+}
+
+/// Read all sentences of a CoNLL-X treebank.
+pub fn read_sentences<R>(read: R) -> Result<Vec<Sentence>, ViewerError>
+where
+    R: BufRead,
+{
+    Reader::new(read)
+        .sentences()
+        .map(|sentence| sentence.map_err(ViewerError::from))
+        .collect()
+}
+
+/// Write sentences as a CoNLL-X treebank.
+pub fn write_sentences<W>(mut write: W, sentences: &[Sentence]) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    {
+        let mut writer = Writer::new(&mut write);
+        for sentence in sentences {
+            writer.write_sentence(sentence)?;
+        }
+    }
+
+    // The writer separates sentences, but does not end the last one.
+    if !sentences.is_empty() {
+        write.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Write trees in the given format, separated by empty lines.
+pub fn write_graphs<W>(
+    mut write: W,
+    graphs: &[DependencyGraph],
+    format: InputFormat,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    for (idx, graph) in graphs.iter().enumerate() {
+        if idx != 0 {
+            write.write_all(b"\n")?;
+        }
+
+        write_graph(&mut write, graph, format)?;
+    }
+
+    Ok(())
+}
+
+/// Write the lines of a tree in the given format.
+pub fn write_graph<W>(
+    write: &mut W,
+    graph: &DependencyGraph,
+    format: InputFormat,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    match format {
+        InputFormat::Conllx => Ok(writeln!(
+            write,
+            "{}",
+            DisplaySentence(&graph.to_sentence())
+        )?),
+        InputFormat::Conllu => conllu::write_graph(write, graph),
+        InputFormat::Conll2009 => conll2009::write_graph(write, graph),
+        InputFormat::Stanford => stanford::write_graph(write, graph),
+    }
+}
+
+/// A format that subtrees are exported in.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtreeFormat {
+    /// A CoNLL fragment in the format of the treebank.
+    Conll,
+    Dot,
+    Svg,
+    Tikz,
+}
+
+impl SubtreeFormat {
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtreeFormat::Conll => "conll",
+            SubtreeFormat::Dot => "dot",
+            SubtreeFormat::Svg => "svg",
+            SubtreeFormat::Tikz => "tikz",
+        }
+    }
+}
+
+impl FromStr for SubtreeFormat {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conll" => Ok(SubtreeFormat::Conll),
+            "dot" => Ok(SubtreeFormat::Dot),
+            "svg" => Ok(SubtreeFormat::Svg),
+            "tikz" => Ok(SubtreeFormat::Tikz),
+            _ => Err(ViewerError::UnknownExportFormat(s.to_owned())),
+        }
+    }
+}
+
+/// Write the subtree of the token at `offset` as a standalone tree, with
+/// re-indexed tokens. CoNLL fragments are written in the treebank
+/// `format`. The selection, original tree and quiz of the render options
+/// refer to the whole tree and are not used.
+pub fn write_subtree<W>(
+    mut write: W,
+    graph: &DependencyGraph,
+    offset: usize,
+    subtree_format: SubtreeFormat,
+    format: InputFormat,
+    options: &RenderOptions,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    let subtree = DependencyGraph::from(graph.subtree(offset));
+    let options = RenderOptions {
+        original: None,
+        path: None,
+        quiz: None,
+        selection: None,
+        ..options.clone()
+    };
+
+    match subtree_format {
+        SubtreeFormat::Conll => write_graph(&mut write, &subtree, format),
+        SubtreeFormat::Dot => Ok(write.write_all(subtree.dot(&options)?.as_bytes())?),
+        SubtreeFormat::Svg => Ok(write.write_all(subtree.svg(&options)?.as_bytes())?),
+        SubtreeFormat::Tikz => Ok(write.write_all(subtree.tikz(&options)?.as_bytes())?),
+    }
+}
+
+/// Parse sentence ranges, such as `1-10,15`. Sentence numbers start at
+/// 1, the returned ranges are `(start, end)` pairs of indices that start
+/// at 0, with an exclusive end. The ranges are clipped to the treebank
+/// by `select_trees`.
+pub fn parse_ranges(s: &str) -> Result<Vec<(usize, usize)>, ViewerError> {
+    let mut ranges = Vec::new();
+
+    for range in s.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        let mut bounds = range.splitn(2, '-');
+        let start = parse_sentence_number(bounds.next().unwrap_or(""), range)?;
+        let end = match bounds.next() {
+            Some(end) => parse_sentence_number(end, range)?,
+            None => start,
+        };
+
+        if end < start {
+            return Err(ViewerError::InvalidRange(range.to_owned()));
+        }
+
+        ranges.push((start - 1, end));
+    }
+
+    Ok(ranges)
+}
+
+fn parse_sentence_number(s: &str, range: &str) -> Result<usize, ViewerError> {
+    match s.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(ViewerError::InvalidRange(range.to_owned())),
+    }
+}
+
+/// Select the indices of the trees to export from a treebank with `len`
+/// trees. If `ranges` is given, only trees in the ranges are selected.
+/// If `sample` is given, a random sample of that size is taken from the
+/// selected trees, in random order.
+pub fn select_trees<R>(
+    len: usize,
+    ranges: Option<&[(usize, usize)]>,
+    sample: Option<usize>,
+    rng: &mut R,
+) -> Vec<usize>
+where
+    R: Rng,
+{
+    let mut indices: Vec<_> = match ranges {
+        Some(ranges) => ranges
+            .iter()
+            .flat_map(|&(start, end)| start.min(len)..end.min(len))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        None => (0..len).collect(),
+    };
+
+    if let Some(sample) = sample {
+        rng.shuffle(&mut indices);
+        indices.truncate(sample);
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{parse_ranges, select_trees, write_subtree, SubtreeFormat};
+    use graph::tests::tree;
+    use graph::RenderOptions;
+    use input::InputFormat;
+
+    #[test]
+    fn subtree_is_reindexed() {
+        let graph = tree(&[("the", 2, "det"), ("dog", 3, "nsubj"), ("barks", 0, "root")]);
+
+        let mut conll = Vec::new();
+        write_subtree(
+            &mut conll,
+            &graph,
+            1,
+            SubtreeFormat::Conll,
+            InputFormat::Conllx,
+            &RenderOptions::default(),
+        )
+        .unwrap();
+
+        let heads: Vec<_> = String::from_utf8(conll)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let columns: Vec<_> = line.split('\t').collect();
+                (
+                    columns[1].to_owned(),
+                    columns[6].to_owned(),
+                    columns[7].to_owned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            heads,
+            vec![
+                ("the".to_owned(), "2".to_owned(), "det".to_owned()),
+                ("dog".to_owned(), "0".to_owned(), "ROOT".to_owned()),
+            ]
+        );
+        assert!("pdf".parse::<SubtreeFormat>().is_err());
+    }
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(parse_ranges("1-3").unwrap(), vec![(0, 3)]);
+        assert_eq!(parse_ranges(" 2 - 4 , 7,").unwrap(), vec![(1, 4), (6, 7)]);
+        assert_eq!(parse_ranges("").unwrap(), vec![]);
+
+        for invalid in &["0", "0-2", "5-3", "-", "-3", "2-", "a-b", "1-2-3"] {
+            assert!(parse_ranges(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn ranges_are_clipped_to_the_treebank() {
+        let ranges = parse_ranges("3-4000000000,2").unwrap();
+        assert_eq!(
+            select_trees(5, Some(&ranges), None, &mut thread_rng()),
+            vec![1, 2, 3, 4]
+        );
+
+        let ranges = parse_ranges("7-9").unwrap();
+        assert!(select_trees(5, Some(&ranges), None, &mut thread_rng()).is_empty());
+        assert_eq!(
+            select_trees(3, None, None, &mut thread_rng()),
+            vec![0, 1, 2]
+        );
+    }
+}
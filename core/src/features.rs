@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use conllx::{Features, Token};
+use rayon::prelude::*;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use search::Match;
+use storage::TreebankStorage;
+
+/// The tag of tokens without a universal part-of-speech tag.
+const NO_UPOS: &str = "_";
+
+/// The number of tokens with a feature value and a universal
+/// part-of-speech tag, e.g. `Case=Nom` on `ADJ`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeatureCell {
+    pub feature: String,
+
+    /// The value of the feature, empty for features without a value.
+    pub value: String,
+    pub upos: String,
+    pub count: usize,
+
+    /// Number of tokens with the feature value, over all tags.
+    pub value_count: usize,
+}
+
+impl FeatureCell {
+    /// The share of the tokens with the feature value that have the tag
+    /// of the cell. Tags with a small share are often annotation errors.
+    pub fn share(&self) -> f64 {
+        self.count as f64 / self.value_count as f64
+    }
+}
+
+/// Counts of feature values by universal part-of-speech tag.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureTable {
+    counts: BTreeMap<(String, String, String), usize>,
+}
+
+impl FeatureTable {
+    pub fn new() -> Self {
+        FeatureTable::default()
+    }
+
+    pub fn add(&mut self, graph: &DependencyGraph) {
+        for idx in graph.0.node_indices() {
+            let token = &graph.0[idx].token;
+            for (feature, value) in feature_values(token) {
+                let upos = token.cpos().unwrap_or(NO_UPOS);
+                *self
+                    .counts
+                    .entry((feature.to_owned(), value.to_owned(), upos.to_owned()))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn merge(mut self, other: FeatureTable) -> Self {
+        for (key, count) in other.counts {
+            *self.counts.entry(key).or_insert(0) += count;
+        }
+
+        self
+    }
+
+    pub fn count(&self, feature: &str, value: &str, upos: &str) -> usize {
+        self.counts
+            .get(&(feature.to_owned(), value.to_owned(), upos.to_owned()))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// The cells of the table, ordered by feature and value and then the
+    /// most frequent tag first.
+    pub fn cells(&self) -> Vec<FeatureCell> {
+        let mut value_counts = BTreeMap::new();
+        for ((feature, value, _), &count) in &self.counts {
+            *value_counts.entry((feature, value)).or_insert(0) += count;
+        }
+
+        let mut cells: Vec<_> = self
+            .counts
+            .iter()
+            .map(|((feature, value, upos), &count)| FeatureCell {
+                feature: feature.clone(),
+                value: value.clone(),
+                upos: upos.clone(),
+                count,
+                value_count: value_counts[&(feature, value)],
+            })
+            .collect();
+        cells.sort_by(|a, b| {
+            (&a.feature, &a.value)
+                .cmp(&(&b.feature, &b.value))
+                .then(b.count.cmp(&a.count))
+                .then_with(|| a.upos.cmp(&b.upos))
+        });
+
+        cells
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The tags of the tokens that have features, in alphabetical order.
+    pub fn upos(&self) -> Vec<&str> {
+        let upos: BTreeSet<_> = self
+            .counts
+            .keys()
+            .map(|(_, _, upos)| upos.as_str())
+            .collect();
+        upos.into_iter().collect()
+    }
+}
+
+/// The features of a token with their values, as in CoNLL-U:
+/// `Case=Nom|Number=Sing`. `Features::as_map` splits features at `:`
+/// instead.
+fn feature_values(token: &Token) -> Vec<(&str, &str)> {
+    token
+        .features()
+        .map(Features::as_str)
+        .into_iter()
+        .flat_map(|features| features.split('|'))
+        .filter(|feature| !feature.is_empty())
+        .map(|feature| {
+            let mut parts = feature.splitn(2, '=');
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        })
+        .collect()
+}
+
+/// Count the feature values by universal part-of-speech tag in the
+/// given trees.
+pub fn count_features(storage: &dyn TreebankStorage, sentences: Vec<usize>) -> FeatureTable {
+    sentences
+        .into_par_iter()
+        .filter_map(|sentence| storage.graph(sentence))
+        .map(|graph| {
+            let mut table = FeatureTable::new();
+            table.add(&graph);
+            table
+        })
+        .reduce(FeatureTable::new, FeatureTable::merge)
+}
+
+/// Find the tokens with the feature value and tag of a cell, in
+/// treebank order.
+pub fn feature_instances(
+    storage: &dyn TreebankStorage,
+    sentences: Vec<usize>,
+    cell: &FeatureCell,
+) -> Vec<Match> {
+    let matches: Vec<Vec<Match>> = sentences
+        .into_par_iter()
+        .filter_map(|sentence| Some((sentence, storage.graph(sentence)?)))
+        .map(|(sentence, graph)| {
+            graph
+                .0
+                .node_indices()
+                .filter(|&idx| {
+                    let token = &graph.0[idx].token;
+                    token.cpos().unwrap_or(NO_UPOS) == cell.upos
+                        && feature_values(token).contains(&(&cell.feature, &cell.value))
+                })
+                .map(|idx| Match {
+                    sentence,
+                    token: idx.index(),
+                })
+                .collect()
+        })
+        .collect();
+
+    matches.into_iter().flatten().collect()
+}
+
+/// Quote a CSV field when it contains separators, quotes or newlines.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Write the table as CSV, with a row per feature value and a column
+/// with the counts of every tag.
+pub fn write_feature_csv<W>(mut writer: W, table: &FeatureTable) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    let upos = table.upos();
+
+    let mut header = vec!["feature".to_owned(), "value".to_owned()];
+    header.extend(upos.iter().map(|upos| csv_field(upos)));
+    writeln!(writer, "{}", header.join(","))?;
+
+    let values: BTreeSet<_> = table
+        .counts
+        .keys()
+        .map(|(feature, value, _)| (feature, value))
+        .collect();
+    for (feature, value) in values {
+        let mut row = vec![csv_field(feature), csv_field(value)];
+        row.extend(
+            upos.iter()
+                .map(|upos| table.count(feature, value, upos).to_string()),
+        );
+        writeln!(writer, "{}", row.join(","))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::{Features, Token};
+
+    use super::{write_feature_csv, FeatureTable};
+    use graph::DependencyGraph;
+
+    fn table() -> FeatureTable {
+        let tokens = [
+            ("oude", "ADJ", "Case=Nom|Degree=Pos"),
+            ("man", "NOUN", "Case=Nom|Number=Sing"),
+            ("mannen", "NOUN", "Case=Nom|Number=Plur"),
+            ("wat", "X", "Foreign|Case=Nom|PronType=Int,Rel"),
+        ];
+        let graph = DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, upos, features)| {
+                    let mut token = Token::new(form);
+                    token.set_cpos(Some(upos));
+                    token.set_features(Some(Features::from_string(features)));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let mut table = FeatureTable::new();
+        table.add(&graph);
+        table
+    }
+
+    #[test]
+    fn counts_feature_values_by_upos() {
+        let table = table();
+        assert_eq!(table.upos(), ["ADJ", "NOUN", "X"]);
+        assert_eq!(table.count("Case", "Nom", "NOUN"), 2);
+        assert_eq!(table.count("Foreign", "", "X"), 1);
+
+        let cells = table.cells();
+        let case: Vec<_> = cells
+            .iter()
+            .filter(|cell| cell.feature == "Case")
+            .map(|cell| (cell.upos.as_str(), cell.count, cell.share()))
+            .collect();
+        assert_eq!(case, [("NOUN", 2, 0.5), ("ADJ", 1, 0.25), ("X", 1, 0.25)]);
+    }
+
+    #[test]
+    fn writes_csv_cross_tabulation() {
+        let mut csv = Vec::new();
+        write_feature_csv(&mut csv, &table()).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "feature,value,ADJ,NOUN,X\n\
+             Case,Nom,1,2,1\n\
+             Degree,Pos,1,0,0\n\
+             Foreign,,0,0,1\n\
+             Number,Plur,0,1,0\n\
+             Number,Sing,0,1,0\n\
+             PronType,\"Int,Rel\",0,0,1\n"
+        );
+    }
+}
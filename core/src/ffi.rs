@@ -0,0 +1,130 @@
+//! C API for rendering trees.
+//!
+//! The functions take the first sentence of a CoNLL text, in any of the
+//! input formats, and return the rendering as a string that must be
+//! freed with `conllx_view_string_free`. The style comments of the
+//! sentence are applied, as in the `render` subcommand. On failure,
+//! `NULL` is returned and the error message is available from
+//! `conllx_view_last_error`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use error::ViewerError;
+use graph::{RenderOptions, Svg, Tikz};
+use input::read_sentence;
+use style::sentence_options;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Render the first sentence of a CoNLL text as SVG. Rendering SVG
+/// requires Graphviz `dot`.
+///
+/// # Safety
+///
+/// `conll` must be `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn conllx_view_render_svg(conll: *const c_char) -> *mut c_char {
+    render(conll, |text| {
+        read_sentence(text, None)?.svg(&sentence_options(text, &RenderOptions::default())?)
+    })
+}
+
+/// Render the first sentence of a CoNLL text as a standalone TikZ
+/// document.
+///
+/// # Safety
+///
+/// `conll` must be `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn conllx_view_render_tikz(conll: *const c_char) -> *mut c_char {
+    render(conll, |text| {
+        read_sentence(text, None)?.tikz(&sentence_options(text, &RenderOptions::default())?)
+    })
+}
+
+/// Free a string that was returned by one of the rendering functions.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a string from a rendering function that was not
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn conllx_view_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Get the message of the last error on this thread, `NULL` when there
+/// was no error. The message is valid until the next rendering call.
+#[no_mangle]
+pub extern "C" fn conllx_view_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map(|message| message.as_ptr())
+            .unwrap_or(ptr::null())
+    })
+}
+
+unsafe fn render<F>(conll: *const c_char, f: F) -> *mut c_char
+where
+    F: FnOnce(&str) -> Result<String, ViewerError>,
+{
+    let result = if conll.is_null() {
+        Err(ViewerError::NullArgument("sentence".to_owned()))
+    } else {
+        let conll = CStr::from_ptr(conll);
+        panic::catch_unwind(AssertUnwindSafe(|| f(conll.to_str()?)))
+            .unwrap_or_else(|_| Err(ViewerError::Panicked("rendering".to_owned())))
+    };
+
+    let result = result.and_then(|rendering| Ok(CString::new(rendering)?));
+
+    LAST_ERROR.with(|last_error| {
+        *last_error.borrow_mut() = result
+            .as_ref()
+            .err()
+            .map(|err| CString::new(err.to_string().replace('\0', "")).unwrap());
+    });
+
+    result.map(CString::into_raw).unwrap_or(ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+
+    use super::{conllx_view_last_error, conllx_view_render_tikz, conllx_view_string_free};
+
+    #[test]
+    fn renders_tikz() {
+        let conll = CString::new("1\tHi\thi\tITJ\tITJ\t_\t0\tROOT\t_\t_\n").unwrap();
+
+        unsafe {
+            let tikz = conllx_view_render_tikz(conll.as_ptr());
+            assert!(!tikz.is_null());
+            assert!(CStr::from_ptr(tikz).to_str().unwrap().contains("Hi"));
+            assert!(conllx_view_last_error().is_null());
+            conllx_view_string_free(tikz);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let conll = CString::new("").unwrap();
+
+        unsafe {
+            assert!(conllx_view_render_tikz(conll.as_ptr()).is_null());
+            let error = CStr::from_ptr(conllx_view_last_error());
+            assert_eq!(error.to_str().unwrap(), "no sentences in text");
+        }
+    }
+}
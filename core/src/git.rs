@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use conllx::Sentence;
+
+use compress::decompress;
+use error::{ResultExt, ViewerError};
+use export::read_treebank;
+use input::InputFormat;
+
+/// A treebank file that is tracked in a git repository.
+///
+/// Git is invoked as an external program, like Graphviz.
+pub struct GitFile {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl GitFile {
+    /// Look up the repository of a treebank file. Returns `None` when the
+    /// file is not tracked in a git repository.
+    pub fn open<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = fs::canonicalize(path).ok()?;
+        let dir = path.parent()?;
+
+        let toplevel = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+        let repo = fs::canonicalize(String::from_utf8(toplevel).ok()?.trim()).ok()?;
+        let path = path.strip_prefix(&repo).ok()?.to_owned();
+
+        let file = GitFile { repo, path };
+        git(
+            &file.repo,
+            &["ls-files", "--error-unmatch", &file.path_str()],
+        )
+        .ok()?;
+
+        Some(file)
+    }
+
+    /// Read the sentences of the treebank as they are in `HEAD`.
+    pub fn head_sentences(&self, format: InputFormat) -> Result<Vec<Sentence>, ViewerError> {
+        let data = git(&self.repo, &["show", &format!("HEAD:{}", self.path_str())])?;
+
+        let graphs = read_treebank(decompress(&data[..])?, format)?;
+
+        Ok(graphs.iter().map(|graph| graph.to_sentence()).collect())
+    }
+
+    /// Stage the saved treebank and commit it.
+    pub fn commit(&self, message: &str) -> Result<(), ViewerError> {
+        git(&self.repo, &["add", "--", &self.path_str()])?;
+        git(
+            &self.repo,
+            &["commit", "-m", message, "--", &self.path_str()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Path of the treebank, relative to the repository.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn path_str(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+/// Run git in a directory, returning its output.
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, ViewerError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Could not start git")?;
+
+    if !output.status.success() {
+        // Some commands, such as commit, report problems on stdout.
+        let message = if output.stderr.is_empty() {
+            &output.stdout
+        } else {
+            &output.stderr
+        };
+        return Err(ViewerError::Git(
+            String::from_utf8_lossy(message).trim().to_owned(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,2061 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use conllx::{Features, Sentence, Token};
+use itertools::Itertools;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{Dfs, EdgeRef};
+use petgraph::{Directed, Direction, Graph};
+#[cfg(feature = "rsvg")]
+use rsvg::Handle;
+
+use dirs::dot_command;
+use error::{ResultExt, ViewerError};
+use layer::{Layer, LayerValue};
+use profile::Profile;
+use quiz::{QuizAnswers, QuizMode};
+use theme::{Theme, ThemeColors};
+
+#[derive(Clone, Debug)]
+pub struct DependencyNode {
+    pub token: Token,
+    pub offset: usize,
+
+    /// Enhanced dependencies (CoNLL-U DEPS) of the token, as (head,
+    /// relation) pairs. Heads are 1-based, 0 is the root.
+    pub enhanced: Vec<(usize, String)>,
+
+    /// Whether the token is followed by a space (CoNLL-U SpaceAfter),
+    /// `None` when this is not annotated.
+    pub space_after: Option<bool>,
+
+    /// The multiword token that starts at this token, as the offset of
+    /// its last token and its surface form.
+    pub multiword: Option<(usize, String)>,
+}
+
+#[derive(Clone)]
+pub struct DependencyGraph(pub Graph<DependencyNode, String, Directed>);
+
+impl DependencyGraph {
+    /// Convert the graph back into a sentence.
+    pub fn to_sentence(&self) -> Sentence {
+        self.0
+            .node_indices()
+            .map(|idx| self.0[idx].token.clone())
+            .collect()
+    }
+
+    /// Edit the token with the given offset. The edges are updated to
+    /// reflect the (possibly changed) head of the token.
+    pub fn edit_token<F>(&mut self, offset: usize, edit: F)
+    where
+        F: FnOnce(&mut Token),
+    {
+        edit(&mut self.0[NodeIndex::new(offset)].token);
+        self.0.clear_edges();
+        add_edges(&mut self.0);
+    }
+
+    /// Get the shortest dependency path between two tokens, through
+    /// their lowest common ancestor. Returns `None` when a token is out
+    /// of range or the tokens are not in the same tree.
+    pub fn path(&self, from: usize, to: usize) -> Option<DependencyPath> {
+        if from >= self.0.node_count() || to >= self.0.node_count() {
+            return None;
+        }
+
+        let up = self.head_chain(from);
+        let down = self.head_chain(to);
+        let (up_len, down_len) = up.iter().enumerate().find_map(|(i, &(offset, _))| {
+            Some((
+                i,
+                down.iter().position(|&(ancestor, _)| ancestor == offset)?,
+            ))
+        })?;
+
+        let mut path = DependencyPath {
+            tokens: up[..=up_len].iter().map(|&(offset, _)| offset).collect(),
+            relations: Vec::new(),
+        };
+        path.tokens
+            .extend(down[..down_len].iter().rev().map(|&(offset, _)| offset));
+        path.relations
+            .extend(up[..up_len].iter().map(|&(_, rel)| (rel.to_owned(), true)));
+        path.relations.extend(
+            down[..down_len]
+                .iter()
+                .rev()
+                .map(|&(_, rel)| (rel.to_owned(), false)),
+        );
+
+        Some(path)
+    }
+
+    /// The chain from a token up to the root of its tree, as (offset,
+    /// relation to the head) pairs. The chain stops at a cycle.
+    fn head_chain(&self, offset: usize) -> Vec<(usize, &str)> {
+        let mut chain = Vec::new();
+        let mut idx = NodeIndex::new(offset);
+        loop {
+            let head = self.0.edges_directed(idx, Direction::Incoming).next();
+            chain.push((
+                idx.index(),
+                head.map(|edge| edge.weight().as_str()).unwrap_or("_"),
+            ));
+
+            idx = match head {
+                Some(edge)
+                    if chain
+                        .iter()
+                        .all(|&(offset, _)| offset != edge.source().index()) =>
+                {
+                    edge.source()
+                }
+                _ => return chain,
+            };
+        }
+    }
+}
+
+/// A dependency path between two tokens.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DependencyPath {
+    /// Offsets of the tokens on the path, from the first to the last
+    /// token.
+    pub tokens: Vec<usize>,
+
+    /// Relations of the arcs on the path, with whether the arc is
+    /// traversed upwards, from the dependent to its head.
+    pub relations: Vec<(String, bool)>,
+}
+
+impl DependencyPath {
+    /// Check whether the arc from `head` to `dependent` is on the path.
+    pub fn contains_arc(&self, head: usize, dependent: usize) -> bool {
+        self.tokens.windows(2).any(|pair| {
+            (pair[0], pair[1]) == (head, dependent) || (pair[0], pair[1]) == (dependent, head)
+        })
+    }
+}
+
+impl fmt::Display for DependencyPath {
+    /// Format the relation sequence, e.g. `↑nsubj ↓obj`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relations = self
+            .relations
+            .iter()
+            .map(|&(ref rel, up)| format!("{}{}", if up { '\u{2191}' } else { '\u{2193}' }, rel))
+            .join(" ");
+        write!(f, "{}", relations)
+    }
+}
+
+impl From<Sentence> for DependencyGraph {
+    fn from(sentence: Sentence) -> Self {
+        let mut g = Graph::new();
+
+        for (offset, token) in sentence.into_iter().enumerate() {
+            g.add_node(DependencyNode {
+                token,
+                offset,
+                enhanced: Vec::new(),
+                space_after: None,
+                multiword: None,
+            });
+        }
+
+        add_edges(&mut g);
+
+        DependencyGraph(g)
+    }
+}
+
+/// Add an edge from the head of every token to the token. Missing and
+/// out-of-range heads are skipped, so that invalid trees can still be
+/// shown and validated.
+fn add_edges(g: &mut Graph<DependencyNode, String, Directed>) {
+    let n_nodes = g.node_count();
+
+    for node_idx in g.node_indices() {
+        let head = match g[node_idx].token.head() {
+            Some(head) if head != 0 && head <= n_nodes => head,
+            _ => continue,
+        };
+
+        let rel = g[node_idx].token.head_rel().unwrap_or("_").to_owned();
+
+        g.add_edge(NodeIndex::new(head - 1), node_idx, rel);
+    }
+}
+
+/// Options that control the output of the exporters.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Draw the basic dependencies (HEAD/DEPREL).
+    pub basic: bool,
+
+    /// Draw the enhanced dependencies (CoNLL-U DEPS) as a second layer
+    /// of arcs. These edges are not drawn in quizzes.
+    pub enhanced: bool,
+
+    /// Original version of an edited sentence. Arcs that were changed are
+    /// highlighted and the original arcs are drawn as dashed edges.
+    pub original: Option<Sentence>,
+
+    /// Another version of the sentence whose lemmas, universal
+    /// part-of-speech tags and features are compared token by token.
+    /// Tokens that differ are outlined, with their values in the other
+    /// version under them.
+    pub morphology: Option<Sentence>,
+
+    /// Draw the projective heads (PHEAD/PDEPREL) as dashed secondary
+    /// edges. These edges are not drawn in quizzes.
+    pub projective_heads: bool,
+
+    /// Draw an explicit root node with the root attachments.
+    pub root: bool,
+
+    /// Layers that are rendered as additional label rows under the
+    /// word form, e.g. the lemma and part-of-speech tag.
+    pub rows: Vec<Layer>,
+
+    /// Offset of the token that is highlighted as the current selection.
+    pub selection: Option<usize>,
+
+    /// Dependency path whose tokens and arcs are highlighted.
+    pub path: Option<DependencyPath>,
+
+    /// Offsets of the tokens whose subtrees are collapsed into a single
+    /// placeholder with the words of the subtree.
+    pub collapsed: Vec<usize>,
+
+    /// Offsets of the tokens that are highlighted, e.g. for a figure.
+    pub highlighted: Vec<usize>,
+
+    /// Colors of the word forms of tokens as (offset, color) pairs. The
+    /// colors are Graphviz color names or `#rrggbb` values.
+    pub colors: Vec<(usize, String)>,
+
+    /// Quiz answers for the tree. Labels or arcs that were not answered
+    /// yet are hidden.
+    pub quiz: Option<QuizAnswers>,
+
+    /// Display settings of the language of the treebank.
+    pub profile: Profile,
+
+    /// Wrap TikZ output in a standalone LaTeX document. When disabled,
+    /// only the `dependency` environment is emitted, so that the output
+    /// can be `\input` into an existing document.
+    pub tikz_preamble: bool,
+
+    /// Draw arc diagrams with ASCII characters rather than Unicode box
+    /// drawing characters.
+    pub diagram_ascii: bool,
+
+    /// Abbreviate the labels and relations of arc diagrams that are
+    /// longer than this number of characters.
+    pub diagram_label_width: Option<usize>,
+
+    /// Cut off the lines of arc diagrams that are longer than this number
+    /// of characters.
+    pub diagram_width: Option<usize>,
+
+    /// Renderer of SVG images of trees.
+    pub renderer: TreeRenderer,
+
+    /// Font of the word forms, replacing the font of the profile.
+    pub font: Option<String>,
+
+    /// Feature that marks tokens, which are drawn in red (underlined in
+    /// TikZ).
+    pub mark_feature: String,
+
+    /// Colors of the background, the tokens and the arcs.
+    pub theme: Theme,
+
+    /// The Graphviz program and layout engine of SVG and PNG images.
+    pub graphviz: GraphvizOptions,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            basic: true,
+            enhanced: false,
+            original: None,
+            morphology: None,
+            projective_heads: false,
+            root: false,
+            rows: Vec::new(),
+            selection: None,
+            path: None,
+            collapsed: Vec::new(),
+            highlighted: Vec::new(),
+            colors: Vec::new(),
+            quiz: None,
+            profile: Profile::default(),
+            tikz_preamble: true,
+            diagram_ascii: false,
+            diagram_label_width: None,
+            diagram_width: None,
+            renderer: TreeRenderer::Graphviz,
+            font: None,
+            mark_feature: "mark".to_owned(),
+            theme: Theme::Light,
+            graphviz: GraphvizOptions::default(),
+        }
+    }
+}
+
+/// Renderer of SVG images of trees.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeRenderer {
+    /// Trees are laid out by Graphviz dot.
+    Graphviz,
+
+    /// Trees are drawn as plain-text arc diagrams, which does not
+    /// require Graphviz.
+    Diagram,
+}
+
+/// Graphviz layout engine of trees. Engines other than `dot` ignore the
+/// ranks and ordering of the tokens, which suits large or non-projective
+/// graphs better than reading order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutEngine {
+    Dot,
+    Neato,
+    Fdp,
+    Twopi,
+}
+
+impl LayoutEngine {
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutEngine::Dot => "dot",
+            LayoutEngine::Neato => "neato",
+            LayoutEngine::Fdp => "fdp",
+            LayoutEngine::Twopi => "twopi",
+        }
+    }
+}
+
+impl FromStr for LayoutEngine {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(LayoutEngine::Dot),
+            "neato" => Ok(LayoutEngine::Neato),
+            "fdp" => Ok(LayoutEngine::Fdp),
+            "twopi" => Ok(LayoutEngine::Twopi),
+            _ => Err(ViewerError::UnknownLayoutEngine(s.to_owned())),
+        }
+    }
+}
+
+/// How Graphviz is run to render images.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GraphvizOptions {
+    /// Path of the Graphviz program. By default, the program is found as
+    /// described for `dot_command`.
+    pub program: Option<PathBuf>,
+
+    /// Layout engine, passed to the program as `-K`. Without an engine,
+    /// the program lays out the graph itself, e.g. `neato` with neato.
+    pub engine: Option<LayoutEngine>,
+
+    /// Extra arguments of the program, e.g. `-Gnodesep=0.5`.
+    pub args: Vec<String>,
+}
+
+impl GraphvizOptions {
+    /// Create a command that runs the program with the layout engine and
+    /// extra arguments.
+    pub fn command(&self) -> Command {
+        let mut command = dot_command(self.program.as_deref());
+        if let Some(engine) = self.engine {
+            command.arg(format!("-K{}", engine.name()));
+        }
+        command.args(&self.args);
+
+        command
+    }
+}
+
+pub trait Dot {
+    fn dot(&self, options: &RenderOptions) -> Result<String, ViewerError>;
+}
+
+impl Dot for DependencyGraph {
+    fn dot(&self, options: &RenderOptions) -> Result<String, ViewerError> {
+        graph_to_dot(self, options)
+    }
+}
+
+pub trait Tikz {
+    fn tikz(&self, options: &RenderOptions) -> Result<String, ViewerError>;
+}
+
+impl Tikz for DependencyGraph {
+    fn tikz(&self, options: &RenderOptions) -> Result<String, ViewerError> {
+        graph_to_tikz(self, options)
+    }
+}
+
+pub trait Diagram {
+    /// Render the tree as a plain-text arc diagram, with the arcs above
+    /// the tokens, e.g. for code comments and commit messages. Only the
+    /// basic dependencies are drawn.
+    fn diagram(&self, options: &RenderOptions) -> String;
+}
+
+impl Diagram for DependencyGraph {
+    fn diagram(&self, options: &RenderOptions) -> String {
+        graph_to_diagram(self, options)
+    }
+}
+
+pub trait Subtree {
+    /// Extract the subtree rooted at the token with the given offset as
+    /// a standalone sentence. Tokens are re-indexed and the head of the
+    /// subtree is attached to the root with the `ROOT` relation.
+    fn subtree(&self, offset: usize) -> Sentence;
+}
+
+impl Subtree for DependencyGraph {
+    fn subtree(&self, offset: usize) -> Sentence {
+        let root = NodeIndex::new(offset);
+
+        let mut nodes = Vec::new();
+        let mut dfs = Dfs::new(&self.0, root);
+        while let Some(node) = dfs.next(&self.0) {
+            nodes.push(node);
+        }
+        nodes.sort_by_key(|node| self.0[*node].offset);
+
+        // Map token offsets in the original sentence to 1-based offsets
+        // in the extracted sentence.
+        let new_idx: HashMap<_, _> = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (self.0[*node].offset + 1, idx + 1))
+            .collect();
+
+        nodes
+            .iter()
+            .map(|node| {
+                let mut token = self.0[*node].token.clone();
+
+                if *node == root {
+                    token.set_head(Some(0));
+                    token.set_head_rel(Some("ROOT"));
+                } else {
+                    let head = token.head().and_then(|head| new_idx.get(&head).cloned());
+                    token.set_head(head);
+                }
+
+                let p_head = token.p_head().and_then(|head| new_idx.get(&head).cloned());
+                if p_head.is_none() {
+                    token.set_p_head_rel(None::<String>);
+                }
+                token.set_p_head(p_head);
+
+                token
+            })
+            .collect()
+    }
+}
+
+pub trait Svg {
+    fn svg(&self, options: &RenderOptions) -> Result<String, ViewerError>;
+}
+
+impl Svg for DependencyGraph {
+    fn svg(&self, options: &RenderOptions) -> Result<String, ViewerError> {
+        if options.renderer == TreeRenderer::Diagram {
+            return Ok(diagram_svg(&self.diagram(options), options.theme.colors()));
+        }
+
+        let dot = self.dot(options)?;
+        let svg = run_dot(&dot, "svg", &options.graphviz)?;
+        String::from_utf8(svg).context("Graphviz dot wrote invalid SVG")
+    }
+}
+
+/// Font size of the text of diagram images, in points.
+const DIAGRAM_FONT_SIZE: usize = 14;
+
+/// Draw an arc diagram as an SVG image, with a line of monospace text per
+/// line of the diagram.
+fn diagram_svg(diagram: &str, colors: &ThemeColors) -> String {
+    let lines: Vec<_> = diagram.lines().collect();
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let line_height = DIAGRAM_FONT_SIZE * 6 / 5;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}pt" height="{}pt" font-family="monospace" font-size="{}pt" fill="{}">"#,
+        (width + 2) * DIAGRAM_FONT_SIZE * 3 / 5,
+        (lines.len() + 1) * line_height,
+        DIAGRAM_FONT_SIZE,
+        colors.foreground
+    );
+    svg.push('\n');
+    svg.push_str(&format!(
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        colors.background
+    ));
+    for (idx, line) in lines.iter().enumerate() {
+        // Runs of spaces are kept as no-break spaces.
+        let text = line
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace(' ', "\u{a0}");
+        svg.push_str(&format!(
+            "<text x=\"{}pt\" y=\"{}pt\">{}</text>\n",
+            DIAGRAM_FONT_SIZE * 3 / 5,
+            (idx + 1) * line_height,
+            text
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// Render trees as librsvg handles, e.g. to draw them with cairo.
+#[cfg(feature = "rsvg")]
+pub trait SvgHandle {
+    fn svg_handle(&self, options: &RenderOptions) -> Result<Handle, ViewerError>;
+}
+
+#[cfg(feature = "rsvg")]
+impl SvgHandle for DependencyGraph {
+    fn svg_handle(&self, options: &RenderOptions) -> Result<Handle, ViewerError> {
+        let svg = self.svg(options)?;
+        Ok(Handle::new_from_data(svg.as_bytes())?)
+    }
+}
+
+pub trait Png {
+    fn png(&self, options: &RenderOptions) -> Result<Vec<u8>, ViewerError>;
+}
+
+impl Png for DependencyGraph {
+    fn png(&self, options: &RenderOptions) -> Result<Vec<u8>, ViewerError> {
+        let dot = self.dot(options)?;
+        run_dot(&dot, "png", &options.graphviz)
+    }
+}
+
+/// Render a graph with Graphviz in the given output format, e.g. `svg` or
+/// `png`.
+fn run_dot(dot: &str, format: &str, graphviz: &GraphvizOptions) -> Result<Vec<u8>, ViewerError> {
+    // FIXME: bind against C library?
+
+    // Spawn Graphviz dot for rendering (Fixme: bind against C library?).
+    let mut process = graphviz
+        .command()
+        .arg(format!("-T{}", format))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Could not start Graphviz dot")?;
+
+    // Dropping stdin closes it, so that dot renders the graph. A program
+    // that fails may stop reading before the graph is written.
+    let written = process
+        .stdin
+        .take()
+        .expect("Graphviz dot has no stdin")
+        .write_all(dot.as_bytes());
+
+    let output = process
+        .wait_with_output()
+        .context("Could not read rendered graph from dot stdout")?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(ViewerError::Render(format!(
+            "Graphviz failed: {}",
+            if message.is_empty() {
+                output.status.to_string()
+            } else {
+                message
+            }
+        )));
+    }
+    written.context("Could not write graph to dot stdin")?;
+
+    Ok(output.stdout)
+}
+
+/// Escape a string for use in a quoted Graphviz string.
+fn escape_str<S>(s: S) -> String
+where
+    S: AsRef<str>,
+{
+    s.as_ref().replace('\\', r"\\").replace('"', r#"\""#)
+}
+
+/// Escape the special characters of LaTeX in a string. `\&` separates
+/// the columns of a `deptext`, so ampersands are written as symbols.
+fn escape_latex<S>(s: S) -> String
+where
+    S: AsRef<str>,
+{
+    let mut escaped = String::new();
+    for c in s.as_ref().chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' => escaped.push_str("\\symbol{38}"),
+            '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Get the label rows of a token: the form, followed by the values of
+/// the requested layers. Absent layer values are rendered as `_`.
+fn label_rows<'a>(token: &'a Token, rows: &[Layer]) -> Vec<&'a str> {
+    let mut label = vec![token.form()];
+    label.extend(rows.iter().map(|&layer| token.value(layer).unwrap_or("_")));
+    label
+}
+
+/// Get the projective head attachments for rendering as secondary edges,
+/// as (head offset, dependent offset, relation) triples. The head offset
+/// is `None` for attachments to the root. Heads that are out of range
+/// are skipped.
+fn projective_attachments(graph: &DependencyGraph) -> Vec<(Option<usize>, usize, &str)> {
+    let n_nodes = graph.0.node_count();
+
+    graph
+        .0
+        .node_indices()
+        .map(|idx| &graph.0[idx])
+        .filter_map(|node| {
+            let head = node.token.p_head().filter(|&head| head <= n_nodes)?;
+            let rel = node.token.p_head_rel().unwrap_or("_");
+            let head = if head == 0 { None } else { Some(head - 1) };
+            Some((head, node.offset, rel))
+        })
+        .collect()
+}
+
+/// Get the enhanced dependencies for rendering, as (head offset,
+/// dependent offset, relation) triples. The head offset is `None` for
+/// attachments to the root. Heads that are out of range are skipped.
+fn enhanced_attachments(graph: &DependencyGraph) -> Vec<(Option<usize>, usize, &str)> {
+    let n_nodes = graph.0.node_count();
+
+    graph
+        .0
+        .node_indices()
+        .map(|idx| &graph.0[idx])
+        .flat_map(|node| {
+            node.enhanced
+                .iter()
+                .filter(move |&&(head, _)| head <= n_nodes)
+                .map(move |&(head, ref rel)| {
+                    let head = if head == 0 { None } else { Some(head - 1) };
+                    (head, node.offset, rel.as_str())
+                })
+        })
+        .collect()
+}
+
+/// Get the offsets and relations of the tokens that are attached to
+/// the root.
+fn root_attachments(graph: &DependencyGraph) -> Vec<(usize, &str)> {
+    graph
+        .0
+        .node_indices()
+        .map(|idx| &graph.0[idx])
+        .filter(|node| node.token.head() == Some(0))
+        .map(|node| (node.offset, node.token.head_rel().unwrap_or("_")))
+        .collect()
+}
+
+/// Get the label of the edge to a dependent, `None` if the edge is
+/// hidden in a quiz. If the dependent was answered in a quiz, whether
+/// the answer was correct is also returned.
+fn quiz_edge<'a>(
+    options: &RenderOptions,
+    dependent: usize,
+    label: &'a str,
+) -> Option<(&'a str, Option<bool>)> {
+    let answers = match options.quiz {
+        Some(ref answers) => answers,
+        None => return Some((label, None)),
+    };
+
+    match answers.answer(dependent) {
+        Some(correct) => Some((label, Some(correct))),
+        None => match answers.mode {
+            QuizMode::Labels => Some(("?", None)),
+            QuizMode::Heads => None,
+        },
+    }
+}
+
+/// Dot edge attributes that color an edge by the correctness of a
+/// quiz answer, or by whether the edge was changed in an edit.
+fn edge_color(colors: &ThemeColors, answer: Option<bool>, changed: bool) -> String {
+    let color = match answer {
+        Some(true) => colors.correct,
+        Some(false) => colors.incorrect,
+        None if changed => colors.changed,
+        None => return String::new(),
+    };
+
+    format!(r#", color="{0}", fontcolor="{0}""#, color)
+}
+
+/// The color of the word form of a token that is set in the options.
+fn token_color(options: &RenderOptions, offset: usize) -> Option<&str> {
+    options
+        .colors
+        .iter()
+        .rev()
+        .find(|&&(token, _)| token == offset)
+        .map(|(_, color)| color.as_str())
+}
+
+/// The lemma, universal part-of-speech tag and features of a token that
+/// differ from another version of the token, as (name, other value)
+/// pairs.
+fn morphology_changes<'a>(token: &Token, other: &'a Token) -> Vec<(&'static str, &'a str)> {
+    let mut changes = Vec::new();
+
+    if token.lemma() != other.lemma() {
+        changes.push(("lemma", other.lemma().unwrap_or("_")));
+    }
+    if token.cpos() != other.cpos() {
+        changes.push(("upos", other.cpos().unwrap_or("_")));
+    }
+    if token.features().map(Features::as_map) != other.features().map(Features::as_map) {
+        changes.push((
+            "feats",
+            other.features().map(Features::as_str).unwrap_or("_"),
+        ));
+    }
+
+    changes
+}
+
+/// Check whether the attachment of a token differs from the original
+/// sentence.
+fn is_changed(options: &RenderOptions, token: &Token, offset: usize) -> bool {
+    match options
+        .original
+        .as_ref()
+        .and_then(|original| original.get(offset))
+    {
+        Some(original) => {
+            original.head() != token.head() || original.head_rel() != token.head_rel()
+        }
+        None => false,
+    }
+}
+
+/// The subtrees of a graph that are collapsed into placeholders.
+struct CollapsedSubtrees {
+    /// Whether a token is hidden in a collapsed subtree.
+    hidden: Vec<bool>,
+
+    /// The words of the collapsed subtrees, by the token that heads the
+    /// subtree.
+    words: HashMap<usize, String>,
+}
+
+impl CollapsedSubtrees {
+    fn new(graph: &DependencyGraph, collapsed: &[usize]) -> Self {
+        let mut hidden = vec![false; graph.0.node_count()];
+        let mut subtrees = HashMap::new();
+
+        // A subtree that is collapsed within another collapsed subtree is
+        // hidden, tokens of a cycle are only hidden once.
+        for &offset in collapsed {
+            if hidden.get(offset).cloned().unwrap_or(true) {
+                continue;
+            }
+
+            let mut nodes = Vec::new();
+            let mut dfs = Dfs::new(&graph.0, NodeIndex::new(offset));
+            while let Some(node) = dfs.next(&graph.0) {
+                if node.index() != offset {
+                    hidden[node.index()] = true;
+                }
+                nodes.push(node);
+            }
+            nodes.sort();
+            subtrees.insert(offset, nodes);
+        }
+        subtrees.retain(|&offset, _| !hidden[offset]);
+
+        let words = subtrees
+            .into_iter()
+            .map(|(offset, nodes)| {
+                (
+                    offset,
+                    nodes
+                        .iter()
+                        .map(|&node| graph.0[node].token.form())
+                        .join(" "),
+                )
+            })
+            .collect();
+
+        CollapsedSubtrees { hidden, words }
+    }
+
+    fn is_hidden(&self, offset: usize) -> bool {
+        self.hidden.get(offset).cloned().unwrap_or(false)
+    }
+}
+
+fn graph_to_dot(graph: &DependencyGraph, options: &RenderOptions) -> Result<String, ViewerError> {
+    let colors = options.theme.colors();
+    let mut dot = String::new();
+
+    dot.push_str("digraph deptree {\n");
+    writeln!(
+        &mut dot,
+        r#"graph [charset = "UTF-8", bgcolor="{}"]"#,
+        colors.background
+    )?;
+    writeln!(
+        &mut dot,
+        r#"node [shape=plaintext, height=0, width=0, fontsize=12, fontname="{}", fontcolor="{}", color="{}"]"#,
+        escape_str(options.font.as_deref().unwrap_or(options.profile.font)),
+        colors.foreground,
+        colors.foreground
+    )?;
+
+    // Nodes are laid out in the order in which they are declared.
+    let collapsed = CollapsedSubtrees::new(graph, &options.collapsed);
+    let mut nodes: Vec<_> = graph
+        .0
+        .node_indices()
+        .filter(|node| !collapsed.is_hidden(node.index()))
+        .collect();
+    if options.profile.rtl {
+        nodes.reverse();
+    }
+
+    for node_idx in nodes {
+        let token = &graph.0[node_idx].token;
+
+        let marked = token
+            .features()
+            .map(Features::as_map)
+            .map(|m| m.contains_key(&options.mark_feature))
+            .unwrap_or(false);
+
+        let offset = graph.0[node_idx].offset;
+        let changes = options
+            .morphology
+            .as_ref()
+            .and_then(|other| other.get(offset))
+            .map(|other| morphology_changes(token, other))
+            .unwrap_or_default();
+        let mut attrs = match collapsed.words.get(&offset) {
+            Some(words) => format!(
+                r#"id="n{}", label="{}", shape=triangle, margin=0"#,
+                offset,
+                escape_str(words)
+            ),
+            None => format!(
+                r#"id="n{}", label="{}""#,
+                offset,
+                label_rows(token, &options.rows)
+                    .into_iter()
+                    .map(escape_str)
+                    .chain(
+                        changes
+                            .iter()
+                            .map(|&(name, value)| escape_str(format!("{} was {}", name, value)))
+                    )
+                    .join("\\n")
+            ),
+        };
+        if !changes.is_empty() && !collapsed.words.contains_key(&offset) {
+            write!(&mut attrs, r#", shape=box, color="{}""#, colors.outline)?;
+        }
+        if marked {
+            write!(&mut attrs, r#", fontcolor="{}""#, colors.marked)?;
+        } else if let Some(color) =
+            token_color(options, offset).or_else(|| options.profile.palette.color(token, colors))
+        {
+            write!(&mut attrs, r#", fontcolor="{}""#, escape_str(color))?;
+        }
+        if options.selection == Some(offset) {
+            write!(
+                &mut attrs,
+                r#", style=filled, fillcolor="{}""#,
+                colors.selection
+            )?;
+        } else if options
+            .path
+            .as_ref()
+            .is_some_and(|path| path.tokens.contains(&offset))
+        {
+            write!(&mut attrs, r#", style=filled, fillcolor="{}""#, colors.path)?;
+        } else if options.highlighted.contains(&offset) {
+            write!(
+                &mut attrs,
+                r#", style=filled, fillcolor="{}""#,
+                colors.highlight
+            )?;
+        }
+
+        writeln!(&mut dot, "n{}[{}];", node_idx.index(), attrs)?;
+    }
+
+    writeln!(
+        &mut dot,
+        r#"edge [color="{}", fontcolor="{}", penwidth={}, fontsize="8", fontname="Courier New"]"#,
+        colors.edge, colors.foreground, colors.pen_width
+    )?;
+
+    for edge_idx in graph.0.edge_indices().filter(|_| options.basic) {
+        let weight = &graph.0[edge_idx];
+        let (source, target) = graph.0.edge_endpoints(edge_idx).unwrap();
+        if collapsed.is_hidden(source.index()) || collapsed.is_hidden(target.index()) {
+            continue;
+        }
+
+        let (label, answer) = match quiz_edge(options, graph.0[target].offset, weight) {
+            Some(edge) => edge,
+            None => continue,
+        };
+        let changed = is_changed(options, &graph.0[target].token, graph.0[target].offset);
+        let on_path = options
+            .path
+            .as_ref()
+            .is_some_and(|path| path.contains_arc(graph.0[source].offset, graph.0[target].offset));
+        let color = if on_path {
+            format!(
+                r#", color="{0}", fontcolor="{0}", penwidth={1}"#,
+                colors.path_edge,
+                colors.pen_width + 1
+            )
+        } else {
+            edge_color(colors, answer, changed)
+        };
+
+        writeln!(
+            &mut dot,
+            r#"n{} -> n{}[label="{}"{}];"#,
+            source.index(),
+            target.index(),
+            escape_str(label),
+            color
+        )?;
+    }
+
+    if options.root {
+        dot.push_str("root[label=\"ROOT\"];\n");
+    }
+
+    if options.root && options.basic {
+        for (offset, rel) in root_attachments(graph) {
+            if collapsed.is_hidden(offset) {
+                continue;
+            }
+
+            let (label, answer) = match quiz_edge(options, offset, rel) {
+                Some(edge) => edge,
+                None => continue,
+            };
+
+            let changed = is_changed(options, &graph.0[NodeIndex::new(offset)].token, offset);
+
+            writeln!(
+                &mut dot,
+                r#"root -> n{}[label="{}"{}];"#,
+                offset,
+                escape_str(label),
+                edge_color(colors, answer, changed)
+            )?;
+        }
+    }
+
+    if let Some(original) = options.original.as_ref().filter(|_| options.basic) {
+        let n_nodes = graph.0.node_count();
+        for (offset, token) in original.iter().enumerate().take(n_nodes) {
+            if !is_changed(options, &graph.0[NodeIndex::new(offset)].token, offset)
+                || collapsed.is_hidden(offset)
+            {
+                continue;
+            }
+
+            let head = match token.head() {
+                Some(0) if options.root => "root".to_owned(),
+                Some(0) | None => continue,
+                Some(head) if head > n_nodes || collapsed.is_hidden(head - 1) => continue,
+                Some(head) => format!("n{}", head - 1),
+            };
+
+            writeln!(
+                &mut dot,
+                r#"{} -> n{}[label="{}", style=dashed, constraint=false, color="{3}", fontcolor="{3}"];"#,
+                head,
+                offset,
+                escape_str(token.head_rel().unwrap_or("_")),
+                colors.original
+            )?;
+        }
+    }
+
+    if options.projective_heads && options.quiz.is_none() {
+        for (head, dependent, rel) in projective_attachments(graph) {
+            if collapsed.is_hidden(dependent) || head.is_some_and(|head| collapsed.is_hidden(head))
+            {
+                continue;
+            }
+
+            let head = match head {
+                Some(head) => format!("n{}", head),
+                None if options.root => "root".to_owned(),
+                None => continue,
+            };
+
+            writeln!(
+                &mut dot,
+                r#"{} -> n{}[label="{}", style=dashed, constraint=false, color="{3}", fontcolor="{3}"];"#,
+                head,
+                dependent,
+                escape_str(rel),
+                colors.projective
+            )?;
+        }
+    }
+
+    if options.enhanced && options.quiz.is_none() {
+        // Without the basic dependencies, the enhanced dependencies
+        // determine the layout.
+        let constraint = if options.basic {
+            ", constraint=false"
+        } else {
+            ""
+        };
+
+        for (head, dependent, rel) in enhanced_attachments(graph) {
+            if collapsed.is_hidden(dependent) || head.is_some_and(|head| collapsed.is_hidden(head))
+            {
+                continue;
+            }
+
+            let head = match head {
+                Some(head) => format!("n{}", head),
+                None if options.root => "root".to_owned(),
+                None => continue,
+            };
+
+            writeln!(
+                &mut dot,
+                r#"{} -> n{}[label="{}"{}, color="{4}", fontcolor="{4}"];"#,
+                head,
+                dependent,
+                escape_str(rel),
+                constraint,
+                colors.enhanced
+            )?;
+        }
+    }
+
+    dot.push('}');
+
+    Ok(dot)
+}
+
+/// Define a LaTeX color from a Graphviz color that is a `#rrggbb` value
+/// or a name that xcolor knows as well.
+fn latex_color(name: &str, color: &str) -> String {
+    match color.strip_prefix('#') {
+        Some(hex) => format!(
+            "\\definecolor{{{}}}{{HTML}}{{{}}}\n",
+            name,
+            hex.to_uppercase()
+        ),
+        None => format!("\\colorlet{{{}}}{{{}}}\n", name, color),
+    }
+}
+
+fn graph_to_tikz(graph: &DependencyGraph, options: &RenderOptions) -> Result<String, ViewerError> {
+    let mut dot = String::new();
+
+    if options.tikz_preamble {
+        dot.push_str("\\documentclass{standalone}\n\n");
+        dot.push_str("\\usepackage{tikz-dependency}\n\n");
+    }
+
+    // The light theme keeps the colors of tikz-dependency.
+    let colors = options.theme.colors();
+    let themed = options.theme != Theme::Light;
+    if themed {
+        dot.push_str(&latex_color("treebackground", colors.background));
+        dot.push_str(&latex_color("treeforeground", colors.foreground));
+        dot.push_str(&latex_color("treeedge", colors.edge));
+        dot.push('\n');
+    }
+
+    if options.tikz_preamble {
+        dot.push_str("\\begin{document}\n\n");
+        if themed {
+            dot.push_str("\\pagecolor{treebackground}\n\n");
+        }
+    }
+
+    if themed {
+        writeln!(
+            &mut dot,
+            "\\begin{{dependency}}[text=treeforeground, edge style={{treeedge, line width={}pt}}, \
+             label style={{text=treeforeground, fill=treebackground, draw=treeedge}}]",
+            colors.pen_width as f64 * 0.4
+        )?;
+    } else {
+        dot.push_str("\\begin{dependency}\n");
+    }
+    dot.push_str("\\begin{deptext}");
+
+    // The 1-based deptext column of each token that is shown.
+    let collapsed = CollapsedSubtrees::new(graph, &options.collapsed);
+    let nodes: Vec<_> = graph
+        .0
+        .node_indices()
+        .filter(|node| !collapsed.is_hidden(node.index()))
+        .collect();
+    let mut columns = vec![None; graph.0.node_count()];
+    for (column, node) in nodes.iter().enumerate() {
+        columns[node.index()] = Some(column + 1);
+    }
+
+    dot.push_str(
+        &nodes
+            .iter()
+            .map(|&idx| {
+                if let Some(words) = collapsed.words.get(&idx.index()) {
+                    return escape_latex(words);
+                }
+
+                let marked = graph.0[idx]
+                    .token
+                    .features()
+                    .map(Features::as_map)
+                    .map(|m| m.contains_key(&options.mark_feature))
+                    .unwrap_or(false);
+
+                let mut form = escape_latex(graph.0[idx].token.form());
+                if let Some(color) = token_color(options, idx.index()) {
+                    form = if let Some(hex) = color.strip_prefix('#') {
+                        format!("\\textcolor[HTML]{{{}}}{{{}}}", hex.to_uppercase(), form)
+                    } else {
+                        format!("\\textcolor{{{}}}{{{}}}", color, form)
+                    };
+                }
+
+                if marked {
+                    format!("\\underline{{{}}}", form)
+                } else {
+                    form
+                }
+            })
+            .join(" \\& "),
+    );
+    dot.push_str("\\\\\n");
+
+    for &layer in &options.rows {
+        dot.push_str(
+            &nodes
+                .iter()
+                .map(|&idx| match collapsed.words.get(&idx.index()) {
+                    Some(_) => String::new(),
+                    None => escape_latex(graph.0[idx].token.value(layer).unwrap_or("_")),
+                })
+                .join(" \\& "),
+        );
+        dot.push_str("\\\\\n");
+    }
+
+    dot.push_str("\\end{deptext}\n");
+
+    for (column, node) in nodes.iter().enumerate() {
+        if options.highlighted.contains(&node.index()) {
+            writeln!(
+                &mut dot,
+                "\\wordgroup{{1}}{{{0}}}{{{0}}}{{hl{0}}}",
+                column + 1
+            )?;
+        }
+
+        // Collapsed subtrees are drawn as a triangle over their words.
+        if collapsed.words.contains_key(&node.index()) {
+            writeln!(
+                &mut dot,
+                "\\draw (\\wordref{{1}}{{{0}}}.north west) -- ([yshift=1.5ex]\\wordref{{1}}{{{0}}}.north) -- (\\wordref{{1}}{{{0}}}.north east) -- cycle;",
+                column + 1
+            )?;
+        }
+    }
+
+    for edge_idx in graph.0.edge_indices().filter(|_| options.basic) {
+        let weight = &graph.0[edge_idx];
+        let (source, target) = graph.0.edge_endpoints(edge_idx).unwrap();
+        let (source_column, target_column) =
+            match (columns[source.index()], columns[target.index()]) {
+                (Some(source), Some(target)) => (source, target),
+                _ => continue,
+            };
+
+        let (label, _) = match quiz_edge(options, graph.0[target].offset, weight) {
+            Some(edge) => edge,
+            None => continue,
+        };
+
+        writeln!(
+            &mut dot,
+            "\\depedge{{{}}}{{{}}}{{{}}}",
+            source_column,
+            target_column,
+            escape_latex(label)
+        )?;
+    }
+
+    if options.root && options.basic {
+        for (offset, rel) in root_attachments(graph) {
+            let column = match columns[offset] {
+                Some(column) => column,
+                None => continue,
+            };
+            let (label, _) = match quiz_edge(options, offset, rel) {
+                Some(edge) => edge,
+                None => continue,
+            };
+
+            writeln!(
+                &mut dot,
+                "\\deproot{{{}}}{{{}}}",
+                column,
+                escape_latex(label)
+            )?;
+        }
+    }
+
+    if options.projective_heads && options.quiz.is_none() {
+        for (head, dependent, rel) in projective_attachments(graph) {
+            let dependent = match columns[dependent] {
+                Some(column) => column,
+                None => continue,
+            };
+
+            match head.map(|head| columns[head]) {
+                Some(Some(head)) => writeln!(
+                    &mut dot,
+                    "\\depedge[edge style={{dashed, orange}}, label style={{text=orange}}, edge below]{{{}}}{{{}}}{{{}}}",
+                    head,
+                    dependent,
+                    escape_latex(rel)
+                )?,
+                None if options.root => writeln!(
+                    &mut dot,
+                    "\\deproot[edge style={{dashed, orange}}, label style={{text=orange}}, edge below]{{{}}}{{{}}}",
+                    dependent,
+                    escape_latex(rel)
+                )?,
+                _ => (),
+            }
+        }
+    }
+
+    if options.enhanced && options.quiz.is_none() {
+        for (head, dependent, rel) in enhanced_attachments(graph) {
+            let dependent = match columns[dependent] {
+                Some(column) => column,
+                None => continue,
+            };
+
+            match head.map(|head| columns[head]) {
+                Some(Some(head)) => writeln!(
+                    &mut dot,
+                    "\\depedge[edge style={{teal}}, label style={{text=teal}}]{{{}}}{{{}}}{{{}}}",
+                    head,
+                    dependent,
+                    escape_latex(rel)
+                )?,
+                None if options.root => writeln!(
+                    &mut dot,
+                    "\\deproot[edge style={{teal}}, label style={{text=teal}}]{{{}}}{{{}}}",
+                    dependent,
+                    escape_latex(rel)
+                )?,
+                _ => (),
+            }
+        }
+    }
+
+    dot.push_str("\\end{dependency}");
+
+    if options.tikz_preamble {
+        dot.push_str("\n\n\\end{document}");
+    }
+
+    Ok(dot)
+}
+
+/// Directions of the lines that meet in a cell of an arc diagram.
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// The character of a cell of an arc diagram, by the directions of its
+/// lines.
+fn diagram_char(lines: u8, ascii: bool) -> char {
+    match (lines, ascii) {
+        (0, _) => ' ',
+        (l, true) if l == LEFT | RIGHT => '-',
+        (l, true) if l == UP | DOWN => '|',
+        (_, true) => '+',
+        (l, false) if l == LEFT | RIGHT => '\u{2500}',
+        (l, false) if l == UP | DOWN => '\u{2502}',
+        (l, false) if l == DOWN | RIGHT => '\u{250c}',
+        (l, false) if l == DOWN | LEFT => '\u{2510}',
+        (l, false) if l == UP | DOWN | RIGHT => '\u{251c}',
+        (l, false) if l == UP | DOWN | LEFT => '\u{2524}',
+        (l, false) if l == DOWN | LEFT | RIGHT => '\u{252c}',
+        (_, false) => '\u{253c}',
+    }
+}
+
+/// Cut off a string that is longer than `width` characters, marking the
+/// cut with an ellipsis.
+fn abbreviate(s: &str, width: Option<usize>, ascii: bool) -> String {
+    match width {
+        Some(width) if s.chars().count() > width => {
+            let mut abbreviated: String = s.chars().take(width.saturating_sub(1)).collect();
+            abbreviated.push(if ascii { '~' } else { '\u{2026}' });
+            abbreviated
+        }
+        _ => s.to_owned(),
+    }
+}
+
+fn graph_to_diagram(graph: &DependencyGraph, options: &RenderOptions) -> String {
+    let ascii = options.diagram_ascii;
+    let label_width = options.diagram_label_width;
+
+    let labels: Vec<Vec<String>> = graph
+        .0
+        .node_indices()
+        .map(|idx| {
+            label_rows(&graph.0[idx].token, &options.rows)
+                .into_iter()
+                .map(|row| abbreviate(row, label_width, ascii))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = labels
+        .iter()
+        .map(|rows| {
+            rows.iter()
+                .map(|row| row.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(1)
+        })
+        .collect();
+
+    // Arcs as (left token, right token, dependent, relation).
+    let mut arcs = Vec::new();
+    for edge in graph.0.edge_references().filter(|_| options.basic) {
+        let (head, dependent) = (edge.source().index(), edge.target().index());
+        if head == dependent {
+            continue;
+        }
+
+        let (label, _) = match quiz_edge(options, graph.0[edge.target()].offset, edge.weight()) {
+            Some(edge) => edge,
+            None => continue,
+        };
+        arcs.push((
+            head.min(dependent),
+            head.max(dependent),
+            dependent,
+            abbreviate(label, label_width, ascii),
+        ));
+    }
+
+    // The column of the center of each token. Tokens are separated by
+    // a space and moved apart to fit the relations of their arcs.
+    let mut columns: Vec<usize> = Vec::with_capacity(widths.len());
+    for (idx, &width) in widths.iter().enumerate() {
+        let mut column = match idx {
+            0 => width / 2,
+            _ => columns[idx - 1] - widths[idx - 1] / 2 + widths[idx - 1] + 1 + width / 2,
+        };
+        for &(left, right, _, ref label) in &arcs {
+            if right == idx {
+                column = column.max(columns[left] + label.chars().count() + 3);
+            }
+        }
+        columns.push(column);
+    }
+
+    // Short arcs are drawn first, on the lowest level that has no arc
+    // that overlaps them, so that arcs are drawn above the arcs that
+    // they span.
+    let mut order: Vec<_> = (0..arcs.len()).collect();
+    order.sort_by_key(|&arc| columns[arcs[arc].1] - columns[arcs[arc].0]);
+    let mut levels = vec![0; arcs.len()];
+    let mut occupied: Vec<Vec<(usize, usize)>> = Vec::new();
+    for arc in order {
+        let (left, right) = (columns[arcs[arc].0], columns[arcs[arc].1]);
+        let level = occupied
+            .iter()
+            .position(|spans| spans.iter().all(|&(l, r)| r < left || l > right))
+            .unwrap_or(occupied.len());
+        if level == occupied.len() {
+            occupied.push(Vec::new());
+        }
+        occupied[level].push((left, right));
+        levels[arc] = level;
+    }
+
+    // The arcs are drawn in the rows above the tokens, the last row has
+    // the arrow heads.
+    let width = columns
+        .last()
+        .map(|&column| column - widths[widths.len() - 1] / 2 + widths[widths.len() - 1])
+        .unwrap_or(0);
+    let n_rows = occupied.len() + 1;
+    let mut cells = vec![vec![0u8; width]; n_rows];
+    for (arc, &level) in levels.iter().enumerate() {
+        let (left, right) = (columns[arcs[arc].0], columns[arcs[arc].1]);
+        let row = occupied.len() - 1 - level;
+
+        cells[row][left] |= DOWN | RIGHT;
+        for cell in &mut cells[row][left + 1..right] {
+            *cell |= LEFT | RIGHT;
+        }
+        cells[row][right] |= DOWN | LEFT;
+        for below in &mut cells[row + 1..] {
+            below[left] |= UP | DOWN;
+            below[right] |= UP | DOWN;
+        }
+    }
+
+    let mut grid: Vec<Vec<char>> = cells
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&lines| diagram_char(lines, ascii))
+                .collect()
+        })
+        .collect();
+    for (arc, &level) in levels.iter().enumerate() {
+        let (left, right, dependent, ref label) = arcs[arc];
+        let (left, right) = (columns[left], columns[right]);
+        let row = occupied.len() - 1 - level;
+
+        let start = left + 1 + (right - left - 1 - label.chars().count()) / 2;
+        for (idx, c) in label.chars().enumerate() {
+            grid[row][start + idx] = c;
+        }
+        grid[n_rows - 1][columns[dependent]] = if ascii { 'v' } else { '\u{25bc}' };
+    }
+
+    for row in 0..labels.first().map(Vec::len).unwrap_or(0) {
+        let mut line = vec![' '; width];
+        for (idx, rows) in labels.iter().enumerate() {
+            let len = rows[row].chars().count();
+            let start = columns[idx] - widths[idx] / 2 + (widths[idx] - len) / 2;
+            for (offset, c) in rows[row].chars().enumerate() {
+                line[start + offset] = c;
+            }
+        }
+        grid.push(line);
+    }
+
+    // Trees without arcs only have the token rows.
+    if arcs.is_empty() {
+        grid.remove(0);
+    }
+
+    grid.into_iter()
+        .map(|line| {
+            let line: String = line.into_iter().collect();
+            abbreviate(line.trim_end(), options.diagram_width, ascii)
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use conllx::{Features, Token};
+    use petgraph::graph::NodeIndex;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::{
+        DependencyGraph, Diagram, Dot, GraphvizOptions, LayoutEngine, RenderOptions, Svg, Tikz,
+        TreeRenderer,
+    };
+    use dirs::dot_available;
+    use error::ViewerError;
+    use layer::Layer;
+    use theme::Theme;
+
+    /// Forms and relations, including the characters that must be
+    /// escaped in Graphviz and LaTeX.
+    const LABEL: &str = "[a-zA-Z0-9 \"\\\\&%$#_{}~^]{1,8}";
+
+    /// A tree of tokens with their forms, heads and relations.
+    pub(crate) fn tree(tokens: &[(&str, usize, &str)]) -> DependencyGraph {
+        DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// A token with its enhanced dependencies.
+    type EnhancedToken = (Token, Vec<(usize, String)>);
+
+    /// Generate a token of a sentence of `n` tokens, with its enhanced
+    /// dependencies. Heads may be out of range, as in invalid treebanks.
+    fn token(n: usize) -> BoxedStrategy<EnhancedToken> {
+        (
+            LABEL,
+            LABEL,
+            prop::option::of(0..n + 2),
+            prop::option::of(0..n + 2),
+            any::<bool>(),
+            vec((0..n + 2, LABEL), 0..3),
+        )
+            .prop_map(|(form, rel, head, p_head, marked, enhanced)| {
+                let mut token = Token::new(form);
+                token.set_lemma(Some(rel.to_lowercase()));
+                token.set_head(head);
+                token.set_head_rel(Some(rel.clone()));
+                token.set_p_head(p_head);
+                token.set_p_head_rel(Some(rel));
+                if marked {
+                    token.set_features(Some(Features::from_string("mark")));
+                }
+                (token, enhanced)
+            })
+            .boxed()
+    }
+
+    fn sentence() -> BoxedStrategy<Vec<EnhancedToken>> {
+        (1..12usize).prop_flat_map(|n| vec(token(n), n)).boxed()
+    }
+
+    fn graph(tokens: Vec<EnhancedToken>) -> DependencyGraph {
+        let (sentence, enhanced): (Vec<_>, Vec<_>) = tokens.into_iter().unzip();
+        let mut graph = DependencyGraph::from(sentence);
+        for (offset, enhanced) in enhanced.into_iter().enumerate() {
+            graph.0[NodeIndex::new(offset)].enhanced = enhanced;
+        }
+
+        graph
+    }
+
+    fn options() -> BoxedStrategy<RenderOptions> {
+        (
+            any::<[bool; 5]>(),
+            prop::option::of(0..12usize),
+            prop::option::of(sentence()),
+        )
+            .prop_map(|(flags, selection, original)| RenderOptions {
+                basic: flags[0],
+                enhanced: flags[1],
+                projective_heads: flags[2],
+                root: flags[3],
+                rows: if flags[4] {
+                    vec![Layer::Lemma, Layer::HeadRel]
+                } else {
+                    Vec::new()
+                },
+                selection,
+                original: original
+                    .map(|tokens| tokens.into_iter().map(|(token, _)| token).collect()),
+                ..RenderOptions::default()
+            })
+            .boxed()
+    }
+
+    /// Get the unescaped quoted strings of a Graphviz graph, `None` when
+    /// a string is not terminated or contains an unknown escape.
+    fn dot_strings(dot: &str) -> Option<Vec<String>> {
+        let mut strings = Vec::new();
+        let mut chars = dot.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '"' {
+                continue;
+            }
+
+            let mut string = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        c @ '\\' | c @ '"' => string.push(c),
+                        'n' => string.push('\n'),
+                        _ => return None,
+                    },
+                    c => string.push(c),
+                }
+            }
+            strings.push(string);
+        }
+
+        Some(strings)
+    }
+
+    /// Get the node indices `nN` that a Graphviz graph refers to outside
+    /// quoted strings.
+    fn dot_nodes(dot: &str) -> Vec<usize> {
+        let mut nodes = Vec::new();
+        let mut quoted = false;
+        let mut escaped = false;
+        let mut prev = ' ';
+        let mut chars = dot.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if quoted {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => quoted = false,
+                    _ => (),
+                }
+            } else if c == '"' {
+                quoted = true;
+            } else if c == 'n' && !prev.is_alphanumeric() {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                if let Ok(node) = digits.parse() {
+                    nodes.push(node);
+                }
+            }
+
+            prev = c;
+        }
+
+        nodes
+    }
+
+    /// Unescape a LaTeX string that was escaped with `escape_latex`,
+    /// `None` when it contains unescaped special characters.
+    fn unescape_latex(s: &str) -> Option<String> {
+        let mut unescaped = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some(&c) = chars.peek().filter(|c| "%$#_{}".contains(**c)) {
+                        unescaped.push(c);
+                        chars.next();
+                        continue;
+                    }
+
+                    let mut command = String::new();
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                        command.push(c);
+                        chars.next();
+                    }
+
+                    let (c, argument) = match command.as_str() {
+                        "textbackslash" => ('\\', "{}"),
+                        "textasciitilde" => ('~', "{}"),
+                        "textasciicircum" => ('^', "{}"),
+                        "symbol" => ('&', "{38}"),
+                        _ => return None,
+                    };
+                    for expected in argument.chars() {
+                        if chars.next()? != expected {
+                            return None;
+                        }
+                    }
+                    unescaped.push(c);
+                }
+                '&' | '%' | '$' | '#' | '_' | '{' | '}' | '~' | '^' => return None,
+                c => unescaped.push(c),
+            }
+        }
+
+        Some(unescaped)
+    }
+
+    /// Get the token indices of the `\depedge` and `\deproot` commands
+    /// of a TikZ dependency.
+    fn tikz_indices(tikz: &str) -> Vec<usize> {
+        let mut indices = Vec::new();
+
+        for line in tikz.lines() {
+            let (mut rest, n_indices) = if let Some(rest) = line.strip_prefix("\\depedge") {
+                (rest, 2)
+            } else if let Some(rest) = line.strip_prefix("\\deproot") {
+                (rest, 1)
+            } else {
+                continue;
+            };
+
+            if rest.starts_with('[') {
+                rest = &rest[rest.find(']').unwrap() + 1..];
+            }
+
+            for _ in 0..n_indices {
+                let end = rest.find('}').unwrap();
+                indices.push(rest[1..end].parse().unwrap());
+                rest = &rest[end + 1..];
+            }
+        }
+
+        indices
+    }
+
+    /// Get the word forms in the `deptext` of a TikZ dependency.
+    fn tikz_forms(tikz: &str) -> Vec<String> {
+        let start = tikz.find("\\begin{deptext}").unwrap() + "\\begin{deptext}".len();
+        let end = tikz[start..].find("\\\\\n").unwrap() + start;
+
+        tikz[start..end]
+            .split(" \\& ")
+            .map(|form| {
+                form.strip_prefix("\\underline{")
+                    .and_then(|form| form.strip_suffix('}'))
+                    .unwrap_or(form)
+            })
+            .map(|form| unescape_latex(form).unwrap_or_else(|| panic!("Unescaped form: {}", form)))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn dot_is_valid(tokens in sentence(), options in options()) {
+            let forms: Vec<_> = tokens.iter().map(|(token, _)| token.form().to_owned()).collect();
+            let graph = graph(tokens);
+
+            let dot = graph.dot(&options).unwrap();
+
+            for node in dot_nodes(&dot) {
+                prop_assert!(node < forms.len(), "Node n{} out of range in:\n{}", node, dot);
+            }
+
+            let strings = dot_strings(&dot);
+            prop_assert!(strings.is_some(), "Invalid string in:\n{}", dot);
+            let labels = strings.unwrap();
+            for form in &forms {
+                prop_assert!(
+                    labels.iter().any(|label| label.split('\n').next() == Some(form)),
+                    "Missing form {:?} in:\n{}",
+                    form,
+                    dot
+                );
+            }
+        }
+
+        #[test]
+        fn tikz_is_valid(tokens in sentence(), options in options()) {
+            let forms: Vec<_> = tokens.iter().map(|(token, _)| token.form().to_owned()).collect();
+            let graph = graph(tokens);
+
+            let tikz = graph.tikz(&options).unwrap();
+
+            for idx in tikz_indices(&tikz) {
+                prop_assert!(idx >= 1 && idx <= forms.len(), "Index {} out of range in:\n{}", idx, tikz);
+            }
+
+            prop_assert_eq!(tikz_forms(&tikz), forms);
+        }
+
+        #[test]
+        fn diagram_has_forms(tokens in sentence(), options in options()) {
+            let forms: Vec<_> = tokens.iter().map(|(token, _)| token.form().to_owned()).collect();
+            let graph = graph(tokens);
+
+            let diagram = graph.diagram(&options);
+            let lines: Vec<_> = diagram.split('\n').collect();
+            let form_line = lines[lines.len() - 1 - options.rows.len()];
+            for form in &forms {
+                prop_assert!(form_line.contains(form.trim()), "Missing form {:?} in:\n{}", form, diagram);
+            }
+        }
+
+        #[test]
+        fn svg_is_rendered(tokens in sentence(), options in options()) {
+            if dot_available(None) {
+                prop_assert!(graph(tokens).svg(&options).is_ok());
+            }
+        }
+
+        #[test]
+        fn path_connects_tokens(tokens in sentence(), from in 0..12usize, to in 0..12usize) {
+            if let Some(path) = graph(tokens).path(from, to) {
+                prop_assert_eq!(path.tokens.first(), Some(&from));
+                prop_assert_eq!(path.tokens.last(), Some(&to));
+                prop_assert_eq!(path.relations.len() + 1, path.tokens.len());
+            }
+        }
+    }
+
+    #[test]
+    fn collapsed_subtree_is_one_word() {
+        let graph = tree(&[
+            ("the", 2, "det"),
+            ("dog", 3, "nsubj"),
+            ("chased", 0, "root"),
+            ("cats", 3, "obj"),
+        ]);
+        let options = RenderOptions {
+            collapsed: vec![1, 0],
+            root: true,
+            tikz_preamble: false,
+            ..RenderOptions::default()
+        };
+
+        let tikz = graph.tikz(&options).unwrap();
+        assert_eq!(tikz_forms(&tikz), vec!["the dog", "chased", "cats"]);
+        assert_eq!(tikz_indices(&tikz), vec![2, 1, 2, 3, 2]);
+        assert!(tikz.contains("\\wordref{1}{1}.north west"));
+
+        let dot = graph.dot(&options).unwrap();
+        assert!(!dot_nodes(&dot).contains(&0));
+        assert!(dot_strings(&dot).unwrap().contains(&"the dog".to_owned()));
+    }
+
+    #[test]
+    fn morphology_changes_are_outlined() {
+        let token = |lemma: &str, cpos: &str, features: &str| {
+            let mut token = Token::new("dogs");
+            token.set_lemma(Some(lemma));
+            token.set_cpos(Some(cpos));
+            token.set_features(Some(Features::from_string(features)));
+            token
+        };
+        let graph = DependencyGraph::from(vec![token("dog", "NOUN", "Number=Plur|Case=Nom")]);
+        let options = RenderOptions {
+            morphology: Some(vec![token("dog", "NOUN", "Case=Nom|Number=Plur")]),
+            ..RenderOptions::default()
+        };
+        assert!(!graph.dot(&options).unwrap().contains("crimson"));
+
+        let options = RenderOptions {
+            morphology: Some(vec![token("dogs", "PROPN", "Number=Plur|Case=Nom")]),
+            ..RenderOptions::default()
+        };
+        let dot = graph.dot(&options).unwrap();
+        assert!(dot.contains(
+            r#"label="dogs\nlemma was dogs\nupos was PROPN", shape=box, color="crimson""#
+        ));
+    }
+
+    #[test]
+    fn colored_and_highlighted_tokens() {
+        let graph = DependencyGraph::from(vec![Token::new("dogs"), Token::new("bark")]);
+        let options = RenderOptions {
+            colors: vec![(0, "red".to_owned()), (1, "#00ff00".to_owned())],
+            highlighted: vec![1],
+            tikz_preamble: false,
+            ..RenderOptions::default()
+        };
+
+        let tikz = graph.tikz(&options).unwrap();
+        assert!(tikz.contains("\\textcolor{red}{dogs} \\& \\textcolor[HTML]{00FF00}{bark}"));
+        assert!(tikz.contains("\\wordgroup{1}{2}{2}{hl2}"));
+
+        let dot = graph.dot(&options).unwrap();
+        assert!(dot.contains(r#"n0[id="n0", label="dogs", fontcolor="red"];"#));
+        assert!(dot.contains(r##"fontcolor="#00ff00", style=filled, fillcolor="lightblue""##));
+    }
+
+    #[test]
+    fn render_preferences_change_fonts_and_marks() {
+        let mut token = Token::new("dogs");
+        token.set_features(Some(Features::from_string("check")));
+        let graph = DependencyGraph::from(vec![token, Token::new("<bark>")]);
+        let options = RenderOptions {
+            font: Some("DejaVu Sans".to_owned()),
+            mark_feature: "check".to_owned(),
+            ..RenderOptions::default()
+        };
+
+        let dot = graph.dot(&options).unwrap();
+        assert!(dot.contains(r#"fontname="DejaVu Sans""#));
+        assert!(dot.contains(r#"label="dogs", fontcolor="firebrick3""#));
+        assert!(!graph
+            .dot(&RenderOptions::default())
+            .unwrap()
+            .contains("firebrick3"));
+
+        let options = RenderOptions {
+            renderer: TreeRenderer::Diagram,
+            ..RenderOptions::default()
+        };
+        let svg = graph.svg(&options).unwrap();
+        assert!(svg.contains("dogs\u{a0}&lt;bark&gt;</text>"));
+    }
+
+    #[test]
+    fn themes_color_canvas_and_exports() {
+        let mut token = Token::new("dogs");
+        token.set_head(Some(2));
+        token.set_head_rel(Some("nsubj"));
+        let graph = DependencyGraph::from(vec![token, Token::new("bark")]);
+        let options = RenderOptions {
+            theme: Theme::Dark,
+            highlighted: vec![1],
+            ..RenderOptions::default()
+        };
+
+        let dot = graph.dot(&options).unwrap();
+        assert!(dot.contains(r##"bgcolor="#1e1e1e""##));
+        assert!(dot.contains(r##"edge [color="#b39ddb", fontcolor="#e6e6e6", penwidth=1"##));
+        assert!(dot.contains(r##"fillcolor="#1f4e5f""##));
+        assert!(!dot.contains(r#""white""#));
+
+        let tikz = graph.tikz(&options).unwrap();
+        assert!(tikz.contains("\\definecolor{treebackground}{HTML}{1E1E1E}"));
+        assert!(tikz.contains("\\pagecolor{treebackground}"));
+        assert!(!graph
+            .tikz(&RenderOptions::default())
+            .unwrap()
+            .contains("treebackground"));
+
+        let options = RenderOptions {
+            renderer: TreeRenderer::Diagram,
+            ..options
+        };
+        assert!(graph
+            .svg(&options)
+            .unwrap()
+            .contains(r##"<rect width="100%" height="100%" fill="#1e1e1e"/>"##));
+    }
+
+    #[test]
+    fn graphviz_failures_are_reported() {
+        let options = RenderOptions {
+            graphviz: GraphvizOptions {
+                program: Some("false".into()),
+                ..GraphvizOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let err = DependencyGraph::from(vec![Token::new("dogs")])
+            .svg(&options)
+            .unwrap_err();
+        assert!(matches!(err, ViewerError::Render(_)));
+    }
+
+    #[test]
+    fn graphviz_runs_the_program_with_the_layout_engine() {
+        let graphviz = GraphvizOptions {
+            program: Some("/opt/graphviz/bin/dot".into()),
+            engine: Some("neato".parse().unwrap()),
+            args: vec!["-Gnodesep=0.5".to_owned()],
+        };
+        let command = graphviz.command();
+        assert_eq!(command.get_program(), "/opt/graphviz/bin/dot");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-Kneato", "-Gnodesep=0.5"]
+        );
+
+        assert_eq!(
+            "twopi".parse::<LayoutEngine>().unwrap(),
+            LayoutEngine::Twopi
+        );
+        assert!("circo".parse::<LayoutEngine>().is_err());
+        assert_eq!(GraphvizOptions::default().command().get_args().count(), 0);
+    }
+
+    #[test]
+    fn diagram_draws_arcs_above_tokens() {
+        let graph = tree(&[
+            ("the", 2, "det"),
+            ("dog", 3, "nsubj"),
+            ("chased", 0, "root"),
+            ("cats", 3, "obj"),
+        ]);
+
+        assert_eq!(
+            graph.diagram(&RenderOptions::default()),
+            concat!(
+                "       \u{250c}\u{2500}nsubj\u{2500}\u{2510}\n",
+                " \u{250c}\u{2500}det\u{2500}\u{2524}       \u{251c}\u{2500}obj\u{2500}\u{2510}\n",
+                " \u{25bc}     \u{25bc}       \u{2502}     \u{25bc}\n",
+                "the   dog   chased cats"
+            )
+        );
+
+        let options = RenderOptions {
+            diagram_ascii: true,
+            diagram_label_width: Some(3),
+            diagram_width: Some(18),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            graph.diagram(&options),
+            concat!(
+                "       +-ns~-+\n",
+                " +-det-+     +-ob~\n",
+                " v     v     |   ~\n",
+                "the   dog   ch~  ~"
+            )
+        );
+    }
+
+    #[test]
+    fn path_through_common_ancestor() {
+        let graph = tree(&[
+            ("the", 2, "det"),
+            ("dog", 3, "nsubj"),
+            ("chased", 0, "root"),
+            ("cats", 3, "obj"),
+        ]);
+
+        let path = graph.path(0, 3).expect("No path");
+        assert_eq!(path.tokens, vec![0, 1, 2, 3]);
+        assert_eq!(path.to_string(), "\u{2191}det \u{2191}nsubj \u{2193}obj");
+        assert!(path.contains_arc(2, 1));
+        assert!(!path.contains_arc(2, 0));
+
+        assert_eq!(graph.path(1, 1).map(|path| path.tokens), Some(vec![1]));
+    }
+}
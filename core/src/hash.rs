@@ -0,0 +1,174 @@
+use std::fmt;
+
+use conllx::Token;
+use sha2::{Digest, Sha256};
+
+use graph::DependencyGraph;
+
+/// Version of the encoding of sentences that is hashed. It is part of
+/// the hashed data, so that a changed encoding yields other hashes.
+const ENCODING: &[u8] = b"conllx-view sentence 1\n";
+
+/// SHA-256 hash of the content of a sentence.
+///
+/// Two sentences have the same hash when their tokens have the same
+/// fields. The hash does not depend on the input format, the
+/// platform, or the version of the viewer, so that it can be stored.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ContentHash({})", self)
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub trait SentenceHash {
+    /// Hash the content of the sentence: the form, lemma, tags,
+    /// features, heads and relations of its tokens.
+    fn content_hash(&self) -> ContentHash;
+}
+
+impl SentenceHash for [Token] {
+    fn content_hash(&self) -> ContentHash {
+        hash_tokens(self.len(), self.iter())
+    }
+}
+
+impl SentenceHash for DependencyGraph {
+    /// The hash is the hash of the sentence of the graph.
+    fn content_hash(&self) -> ContentHash {
+        hash_tokens(
+            self.0.node_count(),
+            self.0.node_indices().map(|idx| &self.0[idx].token),
+        )
+    }
+}
+
+/// Hash tokens. Every field is written with its length, and absent
+/// fields differ from fields with the value `_`, so that different
+/// sentences have different encodings.
+fn hash_tokens<'a, I>(len: usize, tokens: I) -> ContentHash
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let mut hasher = Sha256::default();
+    hasher.input(ENCODING);
+    input_number(&mut hasher, Some(len));
+
+    for token in tokens {
+        input_str(&mut hasher, Some(token.form()));
+        input_str(&mut hasher, token.lemma());
+        input_str(&mut hasher, token.cpos());
+        input_str(&mut hasher, token.pos());
+        input_str(
+            &mut hasher,
+            token.features().map(|features| features.as_str()),
+        );
+        input_number(&mut hasher, token.head());
+        input_str(&mut hasher, token.head_rel());
+        input_number(&mut hasher, token.p_head());
+        input_str(&mut hasher, token.p_head_rel());
+    }
+
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&hasher.result());
+    ContentHash(hash)
+}
+
+fn input_number(hasher: &mut Sha256, number: Option<usize>) {
+    match number {
+        Some(number) => {
+            hasher.input([1]);
+            hasher.input(u64_bytes(number as u64));
+        }
+        None => hasher.input([0]),
+    }
+}
+
+fn input_str(hasher: &mut Sha256, s: Option<&str>) {
+    match s {
+        Some(s) => {
+            input_number(hasher, Some(s.len()));
+            hasher.input(s.as_bytes());
+        }
+        None => hasher.input([0]),
+    }
+}
+
+/// Little-endian bytes of a number.
+fn u64_bytes(n: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (n >> (8 * i)) as u8;
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::{Features, Token};
+
+    use super::SentenceHash;
+    use graph::DependencyGraph;
+
+    fn sentence() -> Vec<Token> {
+        let mut hi = Token::new("Hi");
+        hi.set_lemma(Some("hi"));
+        hi.set_pos(Some("ITJ"));
+        hi.set_head(Some(0));
+        hi.set_head_rel(Some("ROOT"));
+
+        let mut there = Token::new("there");
+        there.set_features(Some(Features::from_string("a=b")));
+        there.set_head(Some(1));
+        there.set_head_rel(Some("MOD"));
+
+        vec![hi, there]
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(
+            sentence().content_hash().to_string(),
+            "285e6d133695d5c5c0e279a0f2f6abfe063f3db52e6038315b9f7c025818947c"
+        );
+    }
+
+    #[test]
+    fn graph_has_hash_of_sentence() {
+        let sentence = sentence();
+        assert_eq!(
+            DependencyGraph::from(sentence.clone()).content_hash(),
+            sentence.content_hash()
+        );
+    }
+
+    #[test]
+    fn absent_fields_differ_from_underscores() {
+        let mut changed = sentence();
+        changed[1].set_lemma(Some("_"));
+        assert_ne!(changed.content_hash(), sentence().content_hash());
+
+        let mut changed = sentence();
+        changed[0].set_head(None);
+        assert_ne!(changed.content_hash(), sentence().content_hash());
+    }
+}
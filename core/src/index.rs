@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde_json;
+
+use error::{ResultExt, ViewerError};
+use graph::DependencyGraph;
+use layer::{Layer, LayerValue};
+use search::{normalize, MatchOptions, Query};
+
+/// Values are indexed case- and diacritic-insensitively, so that the
+/// index finds candidates for searches with any match options.
+const INDEX_OPTIONS: MatchOptions = MatchOptions {
+    case_insensitive: true,
+    diacritic_insensitive: true,
+    lemma_fallback: false,
+};
+
+/// Size and modification time of the treebank an index was built from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Source {
+    pub len: u64,
+    pub modified: u64,
+}
+
+impl Source {
+    /// Get the size and modification time of a treebank.
+    pub fn of(treebank: &Path) -> Result<Self, ViewerError> {
+        let metadata = fs::metadata(treebank).context("Cannot read treebank metadata")?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+
+        Ok(Source {
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// An inverted index from forms, lemmas, part-of-speech tags and
+/// relations to the sentences that contain them.
+///
+/// The index is stored next to the treebank as `TREEBANK.index` and is
+/// rebuilt when the treebank was changed since.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SearchIndex {
+    source: Option<Source>,
+    n_sentences: usize,
+    forms: HashMap<String, Vec<usize>>,
+    lemmas: HashMap<String, Vec<usize>>,
+    tags: HashMap<String, Vec<usize>>,
+    relations: HashMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex::default()
+    }
+
+    /// Open the index of a treebank. Returns `None` when the treebank
+    /// has no index or the index is out of date.
+    pub fn open<P>(treebank: P) -> Result<Option<Self>, ViewerError>
+    where
+        P: AsRef<Path>,
+    {
+        let treebank = treebank.as_ref();
+        let path = index_path(treebank);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let index: SearchIndex = serde_json::from_reader(BufReader::new(
+            File::open(&path).context("Cannot open search index")?,
+        ))
+        .context("Cannot read search index")?;
+
+        if index.source.is_none() || index.source != Some(Source::of(treebank)?) {
+            return Ok(None);
+        }
+
+        Ok(Some(index))
+    }
+
+    /// Save the index next to the treebank it was built from.
+    pub fn save<P>(&mut self, treebank: P) -> Result<(), ViewerError>
+    where
+        P: AsRef<Path>,
+    {
+        let treebank = treebank.as_ref();
+        self.source = Some(Source::of(treebank)?);
+
+        let writer = BufWriter::new(
+            File::create(index_path(treebank)).context("Cannot create search index")?,
+        );
+        serde_json::to_writer(writer, self)?;
+
+        Ok(())
+    }
+
+    /// Add the next sentence of the treebank to the index.
+    pub fn add(&mut self, graph: &DependencyGraph) {
+        let sentence = self.n_sentences;
+        self.n_sentences += 1;
+
+        for idx in graph.0.node_indices() {
+            let token = &graph.0[idx].token;
+            for &layer in &[Layer::Form, Layer::Lemma, Layer::Pos, Layer::HeadRel] {
+                let value = match token.value(layer) {
+                    Some(value) => normalize(value, INDEX_OPTIONS),
+                    None => continue,
+                };
+
+                let postings = self
+                    .postings_mut(layer)
+                    .expect("Layer is not indexed")
+                    .entry(value)
+                    .or_default();
+                if postings.last() != Some(&sentence) {
+                    postings.push(sentence);
+                }
+            }
+        }
+    }
+
+    /// The number of indexed sentences.
+    pub fn len(&self) -> usize {
+        self.n_sentences
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_sentences == 0
+    }
+
+    /// Get the sorted indices of the sentences that can contain matches
+    /// of a query. Returns `None` when none of the query terms is on an
+    /// indexed layer, so that all sentences must be searched.
+    pub fn candidates(&self, query: &Query, options: MatchOptions) -> Option<Vec<usize>> {
+        let mut candidates: Option<Vec<usize>> = None;
+
+        for &(layer, ref value) in query.terms() {
+            let value = normalize(value, INDEX_OPTIONS);
+            let lookup = |layer| {
+                self.postings(layer)
+                    .and_then(|postings| postings.get(&value))
+                    .cloned()
+                    .unwrap_or_default()
+            };
+
+            if self.postings(layer).is_none() {
+                continue;
+            }
+
+            let mut sentences = lookup(layer);
+            if layer == Layer::Form && options.lemma_fallback {
+                sentences.extend(lookup(Layer::Lemma));
+                sentences.sort();
+                sentences.dedup();
+            }
+
+            candidates = Some(match candidates {
+                Some(candidates) => intersect(&candidates, &sentences),
+                None => sentences,
+            });
+        }
+
+        candidates
+    }
+
+    fn postings(&self, layer: Layer) -> Option<&HashMap<String, Vec<usize>>> {
+        match layer {
+            Layer::Form => Some(&self.forms),
+            Layer::Lemma => Some(&self.lemmas),
+            Layer::Pos => Some(&self.tags),
+            Layer::HeadRel => Some(&self.relations),
+            Layer::CPos | Layer::PHeadRel => None,
+        }
+    }
+
+    fn postings_mut(&mut self, layer: Layer) -> Option<&mut HashMap<String, Vec<usize>>> {
+        match layer {
+            Layer::Form => Some(&mut self.forms),
+            Layer::Lemma => Some(&mut self.lemmas),
+            Layer::Pos => Some(&mut self.tags),
+            Layer::HeadRel => Some(&mut self.relations),
+            Layer::CPos | Layer::PHeadRel => None,
+        }
+    }
+}
+
+/// The path of the index of a treebank.
+pub fn index_path(treebank: &Path) -> PathBuf {
+    let mut path = treebank.as_os_str().to_owned();
+    path.push(".index");
+    PathBuf::from(path)
+}
+
+/// Intersect two sorted lists.
+fn intersect(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::{env, process};
+
+    use conllx::{ReadSentence, Reader};
+
+    use super::{index_path, SearchIndex};
+    use graph::DependencyGraph;
+    use search::{MatchOptions, Query};
+
+    static TREEBANK: &str = "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n\
+                             2\tHaus\tHaus\tNN\tNN\t_\t0\tROOT\t_\t_\n\
+                             \n\
+                             1\tKäse\tKäse\tNN\tNN\t_\t2\tSUBJ\t_\t_\n\
+                             2\tgeht\tgehen\tVVFIN\tVVFIN\t_\t0\tROOT\t_\t_\n\
+                             \n\
+                             1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n\
+                             2\tging\tgehen\tVVFIN\tVVFIN\t_\t0\tROOT\t_\t_\n\
+                             3\tnach\tnach\tAPPR\tAPPR\t_\t2\tPP\t_\t_\n\
+                             4\tHaus\tHaus\tNN\tNN\t_\t3\tPN\t_\t_\n";
+
+    fn build_index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        for sentence in Reader::new(TREEBANK.as_bytes()).sentences() {
+            index.add(&DependencyGraph::from(sentence.unwrap()));
+        }
+
+        index
+    }
+
+    fn candidates(index: &SearchIndex, query: &str, options: MatchOptions) -> Option<Vec<usize>> {
+        index.candidates(&query.parse::<Query>().unwrap(), options)
+    }
+
+    #[test]
+    fn candidates_are_looked_up_by_layer() {
+        let index = build_index();
+        let options = MatchOptions::default();
+        assert_eq!(index.len(), 3);
+
+        assert_eq!(candidates(&index, "Haus", options), Some(vec![0, 2]));
+        assert_eq!(candidates(&index, "lemma=gehen", options), Some(vec![1, 2]));
+        assert_eq!(
+            candidates(&index, "lemma=gehen headrel=PP", options),
+            Some(vec![2])
+        );
+        assert_eq!(candidates(&index, "Baum", options), Some(vec![]));
+        assert_eq!(candidates(&index, "Kase HAUS", options), Some(vec![]));
+        assert_eq!(candidates(&index, "gehen", options), Some(vec![]));
+
+        let fallback = MatchOptions {
+            lemma_fallback: true,
+            ..options
+        };
+        assert_eq!(candidates(&index, "gehen", fallback), Some(vec![1, 2]));
+
+        // Values are indexed case- and diacritic-insensitively.
+        assert_eq!(candidates(&index, "kase", options), Some(vec![1]));
+        assert_eq!(candidates(&index, "HAUS", options), Some(vec![0, 2]));
+
+        // Without terms on indexed layers, all sentences are candidates.
+        assert_eq!(candidates(&index, "cpos=NN", options), None);
+    }
+
+    #[test]
+    fn indices_round_trip_and_go_stale() {
+        let treebank = env::temp_dir().join(format!("conllx-view-index-{}.conll", process::id()));
+        fs::write(&treebank, TREEBANK).unwrap();
+
+        assert!(SearchIndex::open(&treebank).unwrap().is_none());
+
+        let mut index = build_index();
+        index.save(&treebank).unwrap();
+
+        let opened = SearchIndex::open(&treebank)
+            .unwrap()
+            .expect("Index is not opened");
+        assert_eq!(opened.len(), index.len());
+        for query in &["Haus", "lemma=gehen pos=VVFIN", "Käse"] {
+            assert_eq!(
+                candidates(&opened, query, MatchOptions::default()),
+                candidates(&index, query, MatchOptions::default())
+            );
+        }
+
+        // The index is out of date when the treebank changes.
+        fs::write(&treebank, &TREEBANK[..TREEBANK.len() / 2]).unwrap();
+        assert!(SearchIndex::open(&treebank).unwrap().is_none());
+
+        fs::remove_file(index_path(&treebank)).unwrap();
+        fs::remove_file(&treebank).unwrap();
+    }
+}
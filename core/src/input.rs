@@ -0,0 +1,359 @@
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use conllx::{ReadSentence, Reader};
+use rayon::prelude::*;
+
+use conll2009::Conll2009Reader;
+use conllu::ConlluReader;
+use error::ViewerError;
+use graph::DependencyGraph;
+use lazy::{parse_block, SentenceBlocks};
+use stanford::{parse_dependency, StanfordReader};
+
+/// Universal part-of-speech tags, which distinguish CoNLL-U from CoNLL-X
+/// treebanks.
+const UPOS: &[&str] = &[
+    "ADJ", "ADP", "ADV", "AUX", "CCONJ", "DET", "INTJ", "NOUN", "NUM", "PART", "PRON", "PROPN",
+    "PUNCT", "SCONJ", "SYM", "VERB", "X",
+];
+
+/// Number of lines that are inspected to detect the format of a
+/// treebank.
+const DETECT_LINES: usize = 200;
+
+/// Treebank formats that can be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputFormat {
+    Conllx,
+    Conllu,
+    Conll2009,
+    Stanford,
+}
+
+impl InputFormat {
+    /// Detect the format of a treebank from the buffered data of a
+    /// reader, without consuming it. Falls back to CoNLL-X when the
+    /// format cannot be determined.
+    pub fn detect<R>(read: &mut R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let buf = read.fill_buf()?;
+        Ok(detect_format(&String::from_utf8_lossy(buf)))
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conllx" => Ok(InputFormat::Conllx),
+            "conllu" => Ok(InputFormat::Conllu),
+            "conll2009" | "conll09" => Ok(InputFormat::Conll2009),
+            "stanford" => Ok(InputFormat::Stanford),
+            _ => Err(ViewerError::UnknownFormat(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            InputFormat::Conllx => "conllx",
+            InputFormat::Conllu => "conllu",
+            InputFormat::Conll2009 => "conll2009",
+            InputFormat::Stanford => "stanford",
+        };
+
+        f.pad(name)
+    }
+}
+
+/// Read the dependency graphs of a treebank in the given format.
+pub fn read_graphs<'a, R>(
+    read: R,
+    format: InputFormat,
+) -> Box<dyn Iterator<Item = Result<DependencyGraph, ViewerError>> + 'a>
+where
+    R: BufRead + 'a,
+{
+    match format {
+        InputFormat::Conllx => Box::new(Reader::new(read).sentences().map(|sentence| {
+            sentence
+                .map(DependencyGraph::from)
+                .map_err(ViewerError::from)
+        })),
+        InputFormat::Conllu => Box::new(ConlluReader::new(read)),
+        InputFormat::Conll2009 => Box::new(Conll2009Reader::new(read)),
+        InputFormat::Stanford => Box::new(StanfordReader::new(read)),
+    }
+}
+
+/// Read the first sentence of a CoNLL text. The format is detected when
+/// `format` is `None`.
+pub fn read_sentence(
+    text: &str,
+    format: Option<InputFormat>,
+) -> Result<DependencyGraph, ViewerError> {
+    let format = format.unwrap_or_else(|| detect_format(text));
+    read_graphs(text.as_bytes(), format)
+        .next()
+        .ok_or_else(|| ViewerError::NoSentences("text".to_owned()))?
+}
+
+/// Detect the format of a treebank from its first lines.
+///
+/// CoNLL-2009 has at least 14 columns, CoNLL-X and CoNLL-U have 10. A
+/// 10-column treebank is CoNLL-U when it has `key = value` comments,
+/// multiword tokens, empty nodes, DEPS or MISC values, or only universal
+/// part-of-speech tags.
+pub fn detect_format(sample: &str) -> InputFormat {
+    let mut n_tokens = 0;
+    let mut n_upos = 0;
+
+    for line in sample_lines(sample) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.starts_with('#') {
+            if line.contains(" = ") {
+                return InputFormat::Conllu;
+            }
+
+            continue;
+        }
+
+        if parse_dependency(line).is_some() {
+            return InputFormat::Stanford;
+        }
+
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() >= 14 {
+            return InputFormat::Conll2009;
+        }
+
+        if fields.len() != 10 {
+            continue;
+        }
+
+        if fields[0].contains('-') || fields[0].contains('.') {
+            return InputFormat::Conllu;
+        }
+
+        // DEPS contains heads with relations, MISC attributes.
+        if fields[8].contains(':') || fields[9].contains('=') {
+            return InputFormat::Conllu;
+        }
+
+        n_tokens += 1;
+        if UPOS.contains(&fields[3]) {
+            n_upos += 1;
+        }
+    }
+
+    if n_tokens != 0 && n_upos == n_tokens {
+        InputFormat::Conllu
+    } else {
+        InputFormat::Conllx
+    }
+}
+
+/// Parse sentences in parallel. The graphs are in the order of the
+/// sentences.
+pub fn parse_blocks<S>(
+    blocks: &[S],
+    format: InputFormat,
+) -> Result<Vec<DependencyGraph>, ViewerError>
+where
+    S: AsRef<str> + Sync,
+{
+    blocks
+        .par_iter()
+        .map(|block| parse_block(block.as_ref(), format))
+        .collect()
+}
+
+/// Iterator over the dependency graphs of a treebank in batches. The
+/// sentences of a batch are parsed in parallel.
+pub struct GraphBatches<R> {
+    blocks: SentenceBlocks<R>,
+    format: InputFormat,
+    batch_size: usize,
+}
+
+impl<R: BufRead> GraphBatches<R> {
+    pub fn new(read: R, format: InputFormat, batch_size: usize) -> Self {
+        GraphBatches {
+            blocks: SentenceBlocks::new(read),
+            format,
+            batch_size,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GraphBatches<R> {
+    type Item = Result<Vec<DependencyGraph>, ViewerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let blocks = self
+            .blocks
+            .by_ref()
+            .take(self.batch_size)
+            .map(|block| block.map(|(_, block)| block))
+            .collect::<Result<Vec<_>, _>>();
+
+        match blocks {
+            Ok(ref blocks) if blocks.is_empty() => None,
+            Ok(blocks) => Some(parse_blocks(&blocks, self.format)),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+/// Find the columns in the first lines of a treebank that cannot be
+/// represented, and are therefore not shown or saved.
+pub fn unsupported_columns(sample: &str, format: InputFormat) -> Vec<&'static str> {
+    let mut columns = Vec::new();
+
+    for line in sample_lines(sample) {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split('\t').collect();
+        let is_set = |idx: usize| {
+            fields
+                .get(idx)
+                .is_some_and(|&field| !field.is_empty() && field != "_")
+        };
+
+        let found = match format {
+            InputFormat::Conllx => vec![],
+            InputFormat::Conllu => vec![
+                ("empty nodes", fields[0].contains('.')),
+                (
+                    "MISC attributes other than SpaceAfter",
+                    is_set(9)
+                        && fields[9]
+                            .split('|')
+                            .any(|attr| !attr.starts_with("SpaceAfter=")),
+                ),
+            ],
+            InputFormat::Conll2009 => vec![
+                (
+                    "predicted columns that differ from the gold columns",
+                    (2..12).step_by(2).any(|gold| {
+                        is_set(gold) && is_set(gold + 1) && fields[gold] != fields[gold + 1]
+                    }),
+                ),
+                (
+                    "semantic roles (FILLPRED, PRED, APRED)",
+                    (12..fields.len()).any(&is_set),
+                ),
+            ],
+            InputFormat::Stanford => vec![("copy nodes", parse_dependency(line) == Some(None))],
+        };
+
+        for (column, unsupported) in found {
+            if unsupported && !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+    }
+
+    columns
+}
+
+/// The complete lines of the start of a treebank, without line endings.
+fn sample_lines(sample: &str) -> impl Iterator<Item = &str> {
+    // The last line may be cut off.
+    let sample = match sample.rfind('\n') {
+        Some(end) => &sample[..end],
+        None => sample,
+    };
+
+    sample
+        .lines()
+        .take(DETECT_LINES)
+        .map(|line| line.trim_end_matches('\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::{detect_format, InputFormat};
+
+    #[test]
+    fn formats_are_detected() {
+        let conllx = "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n\
+                      2\tHaus\tHaus\tNN\tNN\t_\t0\tROOT\t_\t_\n";
+        assert_eq!(detect_format(conllx), InputFormat::Conllx);
+
+        let conll2009 = "1\tDas\tdas\tdas\tART\tART\t_\t_\t2\t2\tNK\tNK\t_\t_\n";
+        assert_eq!(detect_format(conll2009), InputFormat::Conll2009);
+
+        let stanford = "det(Haus-2, Das-1)\nroot(ROOT-0, Haus-2)\n";
+        assert_eq!(detect_format(stanford), InputFormat::Stanford);
+    }
+
+    #[test]
+    fn conllu_is_detected_by_its_annotations() {
+        for sample in &[
+            "# sent_id = 1\n1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n",
+            "1-2\tzum\t_\t_\t_\t_\t_\t_\t_\t_\n",
+            "1.1\tDas\tdas\tART\tART\t_\t_\t_\t0:root\t_\n",
+            "1\tDas\tdas\tART\tART\t_\t2\tDET\t2:det\t_\n",
+            "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\tSpaceAfter=No\n",
+            "1\tHaus\tHaus\tNOUN\tNN\t_\t0\tROOT\t_\t_\n2\t.\t.\tPUNCT\t$.\t_\t1\tpunct\t_\t_\n",
+        ] {
+            assert_eq!(detect_format(sample), InputFormat::Conllu, "{:?}", sample);
+        }
+
+        // A comment without a value or a single universal tag does not
+        // make a treebank CoNLL-U.
+        let mixed = "# newdoc\n\
+                     1\tHaus\tHaus\tNOUN\tNN\t_\t0\tROOT\t_\t_\n\
+                     2\t.\t.\t$.\t$.\t_\t1\tPUNCT\t_\t_\n";
+        assert_eq!(detect_format(mixed), InputFormat::Conllx);
+    }
+
+    #[test]
+    fn undetected_formats_fall_back_to_conllx() {
+        assert_eq!(detect_format(""), InputFormat::Conllx);
+        assert_eq!(detect_format("Das Haus\n"), InputFormat::Conllx);
+
+        // The last line of a sample may be cut off, and is not used.
+        let cut_off = "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n1-2\tzu";
+        assert_eq!(detect_format(cut_off), InputFormat::Conllx);
+    }
+
+    #[test]
+    fn formats_are_detected_without_consuming_data() {
+        let data = "# text = Das Haus\n1\tDas\tdas\tDET\tART\t_\t2\tdet\t_\t_\n";
+        let mut read = data.as_bytes();
+        assert_eq!(InputFormat::detect(&mut read).unwrap(), InputFormat::Conllu);
+        assert_eq!(read.fill_buf().unwrap(), data.as_bytes());
+    }
+
+    #[test]
+    fn format_names_round_trip() {
+        for &format in &[
+            InputFormat::Conllx,
+            InputFormat::Conllu,
+            InputFormat::Conll2009,
+            InputFormat::Stanford,
+        ] {
+            assert_eq!(format.to_string().parse::<InputFormat>().unwrap(), format);
+        }
+        assert_eq!(
+            "conll09".parse::<InputFormat>().unwrap(),
+            InputFormat::Conll2009
+        );
+        assert!("tiger".parse::<InputFormat>().is_err());
+    }
+}
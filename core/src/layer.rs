@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+
+use conllx::Token;
+
+use error::ViewerError;
+use symbol::Symbol;
+
+/// Annotation layers of a CoNLL-X token.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Layer {
+    Form,
+    Lemma,
+    CPos,
+    Pos,
+    HeadRel,
+    PHeadRel,
+}
+
+impl Layer {
+    /// Check whether the values of the layer come from a small tag set,
+    /// so that they are interned as symbols.
+    pub fn is_interned(self) -> bool {
+        match self {
+            Layer::CPos | Layer::Pos | Layer::HeadRel | Layer::PHeadRel => true,
+            Layer::Form | Layer::Lemma => false,
+        }
+    }
+}
+
+impl FromStr for Layer {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "form" => Ok(Layer::Form),
+            "lemma" => Ok(Layer::Lemma),
+            "cpos" => Ok(Layer::CPos),
+            "pos" => Ok(Layer::Pos),
+            "headrel" => Ok(Layer::HeadRel),
+            "pheadrel" => Ok(Layer::PHeadRel),
+            _ => Err(ViewerError::UnknownLayer(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Layer::Form => "form",
+            Layer::Lemma => "lemma",
+            Layer::CPos => "cpos",
+            Layer::Pos => "pos",
+            Layer::HeadRel => "headrel",
+            Layer::PHeadRel => "pheadrel",
+        };
+
+        f.pad(name)
+    }
+}
+
+pub trait LayerValue {
+    /// Get the value of an annotation layer, `None` if the layer is absent.
+    fn value(&self, layer: Layer) -> Option<&str>;
+
+    /// Get the value of an interned layer as a symbol, `None` if the
+    /// layer is absent or its value was never interned.
+    fn symbol(&self, layer: Layer) -> Option<Symbol> {
+        self.value(layer).and_then(Symbol::lookup)
+    }
+}
+
+impl LayerValue for Token {
+    fn value(&self, layer: Layer) -> Option<&str> {
+        match layer {
+            Layer::Form => Some(self.form()),
+            Layer::Lemma => self.lemma(),
+            Layer::CPos => self.cpos(),
+            Layer::Pos => self.pos(),
+            Layer::HeadRel => self.head_rel(),
+            Layer::PHeadRel => self.p_head_rel(),
+        }
+    }
+}
+
+/// Parse a comma-separated list of layers, e.g. `lemma,pos`.
+pub fn parse_layers(s: &str) -> Result<Vec<Layer>, ViewerError> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::parse)
+        .collect()
+}
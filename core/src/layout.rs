@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use serde_json;
+
+use dirs::data_dir;
+use error::{ResultExt, ViewerError};
+
+const LAYOUTS_FILE: &str = "layouts.json";
+
+/// Position and size of a window.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Layout of a side panel.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PanelLayout {
+    pub shown: bool,
+
+    /// The window of the panel, when it is detached.
+    pub detached: Option<WindowGeometry>,
+}
+
+/// Zoom level and scroll position of a zoomed tree.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ZoomLayout {
+    pub scale: f64,
+    pub scroll: (f64, f64),
+}
+
+/// Layout of the viewer: the main window and the side panels by name,
+/// and where the session left off.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Layout {
+    pub window: Option<WindowGeometry>,
+    pub panels: BTreeMap<String, PanelLayout>,
+
+    /// The index of the tree that was shown.
+    pub tree: Option<usize>,
+
+    /// The zoom of the tree that was shown, `None` when it fit the
+    /// window.
+    pub zoom: Option<ZoomLayout>,
+
+    /// The query in the search bar, when the search bar was shown.
+    pub query: Option<String>,
+}
+
+/// The layouts of the viewer per project, a project being the treebank
+/// files that are viewed.
+#[derive(Default)]
+pub struct Layouts {
+    path: Option<PathBuf>,
+    layouts: BTreeMap<String, Layout>,
+}
+
+impl Layouts {
+    /// Open the layouts. Layouts are not stored when `path` is `None`,
+    /// there are no layouts yet when the file does not exist.
+    pub fn open(path: Option<PathBuf>) -> Result<Self, ViewerError> {
+        let layouts = match path {
+            Some(ref path) if path.exists() => serde_json::from_reader(BufReader::new(
+                File::open(path).context("Cannot open layouts")?,
+            ))
+            .context("Cannot read layouts")?,
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Layouts { path, layouts })
+    }
+
+    /// Get the layout of a project.
+    pub fn get(&self, paths: &[PathBuf]) -> Option<&Layout> {
+        self.layouts.get(&project_key(paths))
+    }
+
+    /// Set the layout of a project and write the layouts to disk.
+    pub fn set(&mut self, paths: &[PathBuf], layout: Layout) -> Result<(), ViewerError> {
+        self.layouts.insert(project_key(paths), layout);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), ViewerError> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Cannot create data directory")?;
+        }
+
+        let writer = BufWriter::new(File::create(path).context("Cannot create layouts")?);
+        serde_json::to_writer_pretty(writer, &self.layouts)?;
+
+        Ok(())
+    }
+}
+
+/// The default location of the layouts.
+pub fn default_layouts_path() -> Option<PathBuf> {
+    Some(data_dir()?.join(LAYOUTS_FILE))
+}
+
+/// Get the key of a project. Treebanks from the standard input share
+/// the empty key.
+fn project_key(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| {
+            fs::canonicalize(path)
+                .unwrap_or_else(|_| path.clone())
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::Layout;
+
+    #[test]
+    fn reads_layouts_without_session() {
+        let layout: Layout = serde_json::from_str(r#"{"window": null, "panels": {}}"#).unwrap();
+        assert_eq!(layout.tree, None);
+        assert!(layout.zoom.is_none());
+
+        let layout: Layout = serde_json::from_str(
+            r#"{"window": null, "panels": {}, "tree": 41, "query": "lemma:dog"}"#,
+        )
+        .unwrap();
+        assert_eq!(layout.tree, Some(41));
+        assert_eq!(layout.query.as_deref(), Some("lemma:dog"));
+    }
+}
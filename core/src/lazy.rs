@@ -0,0 +1,447 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use memmap::Mmap;
+
+use arena::SentenceArena;
+use error::{ResultExt, ViewerError};
+use graph::DependencyGraph;
+use index::Source;
+use input::{read_graphs, InputFormat};
+use storage::TreebankStorage;
+
+/// Identifies offset files, including the version of their layout.
+const OFFSETS_MAGIC: &[u8; 8] = b"CXVIDX01";
+
+/// The position of a sentence in a treebank file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SentenceOffset {
+    /// The byte offset of the first line of the sentence.
+    pub offset: u64,
+
+    pub n_tokens: usize,
+}
+
+/// Where a tree of a lazily-loaded treebank is stored.
+#[derive(Clone, Copy)]
+enum Tree {
+    /// The sentence in the treebank file.
+    Offset(SentenceOffset),
+
+    /// The index of a tree that was added or edited in this session.
+    Changed(usize),
+}
+
+/// Where the sentences of a lazily-loaded treebank are read from.
+enum Backing {
+    /// The treebank file, which is reopened for every tree.
+    File(PathBuf),
+
+    /// A memory map of the treebank file.
+    Mmap(Arc<Mmap>),
+}
+
+/// A treebank that is read from its file on demand.
+///
+/// Only the byte offsets of the sentences are kept in memory, trees are
+/// parsed when they are requested. Trees that are added or edited are
+/// kept in memory, since the file is never written.
+pub struct LazyTreebank {
+    backing: Backing,
+    format: InputFormat,
+    trees: Vec<Tree>,
+    changed: SentenceArena,
+
+    /// The last error of reading a tree, until it is taken.
+    error: Mutex<Option<String>>,
+}
+
+impl LazyTreebank {
+    pub fn new<P>(path: P, format: InputFormat) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        LazyTreebank::with_backing(Backing::File(path.into()), format)
+    }
+
+    /// Create a treebank that parses trees from a memory map of the
+    /// treebank file.
+    pub fn mmap(map: Arc<Mmap>, format: InputFormat) -> Self {
+        LazyTreebank::with_backing(Backing::Mmap(map), format)
+    }
+
+    fn with_backing(backing: Backing, format: InputFormat) -> Self {
+        LazyTreebank {
+            backing,
+            format,
+            trees: Vec::new(),
+            changed: SentenceArena::new(),
+            error: Mutex::new(None),
+        }
+    }
+
+    pub fn push(&mut self, graph: &DependencyGraph) {
+        self.trees.push(Tree::Changed(self.changed.len()));
+        self.changed.push(graph);
+    }
+
+    /// Add sentences of the treebank file.
+    pub fn push_offsets(&mut self, offsets: &[SentenceOffset]) {
+        self.trees.extend(offsets.iter().cloned().map(Tree::Offset));
+    }
+
+    /// Replace a tree, for instance after it was edited.
+    pub fn replace(&mut self, idx: usize, graph: &DependencyGraph) {
+        match self.trees[idx] {
+            Tree::Offset(_) => {
+                self.trees[idx] = Tree::Changed(self.changed.len());
+                self.changed.push(graph);
+            }
+            Tree::Changed(changed) => self.changed.replace(changed, graph),
+        }
+    }
+
+    /// Take the last error of reading a tree from the treebank file.
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().unwrap().take()
+    }
+
+    fn read(&self, offset: u64) -> Result<DependencyGraph, ViewerError> {
+        let block = match self.backing {
+            Backing::File(ref path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                SentenceBlocks::new(BufReader::new(file)).next()
+            }
+            Backing::Mmap(ref map) => map
+                .get(offset as usize..)
+                .and_then(|data| SentenceBlocks::new(data).next()),
+        };
+        let (_, block) = block.ok_or(ViewerError::NoSentenceAt(offset))??;
+
+        parse_block(&block, self.format)
+    }
+}
+
+impl TreebankStorage for LazyTreebank {
+    fn graph(&self, idx: usize) -> Option<DependencyGraph> {
+        match *self.trees.get(idx)? {
+            Tree::Offset(offset) => match self.read(offset.offset) {
+                Ok(graph) => Some(graph),
+                Err(err) => {
+                    *self.error.lock().unwrap() =
+                        Some(format!("Cannot read tree {}: {}", idx + 1, err));
+                    None
+                }
+            },
+            Tree::Changed(changed) => self.changed.graph(changed),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    fn n_tokens(&self, idx: usize) -> Option<usize> {
+        match *self.trees.get(idx)? {
+            Tree::Offset(offset) => Some(offset.n_tokens),
+            Tree::Changed(changed) => self.changed.n_tokens(changed),
+        }
+    }
+}
+
+/// Iterator over the sentences of a treebank, as their byte offsets
+/// and lines. Sentences are separated by empty lines.
+pub struct SentenceBlocks<R> {
+    read: R,
+    offset: u64,
+}
+
+impl<R: BufRead> SentenceBlocks<R> {
+    pub fn new(read: R) -> Self {
+        SentenceBlocks { read, offset: 0 }
+    }
+}
+
+impl<R: BufRead> Iterator for SentenceBlocks<R> {
+    type Item = io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        let mut block = String::new();
+        let mut start = self.offset;
+
+        loop {
+            line.clear();
+            let n = match self.read.read_line(&mut line) {
+                Ok(n) => n,
+                Err(err) => return Some(Err(err)),
+            };
+            self.offset += n as u64;
+
+            if n == 0 {
+                break;
+            }
+
+            if line.trim().is_empty() {
+                if block.is_empty() {
+                    start = self.offset;
+                    continue;
+                }
+
+                break;
+            }
+
+            block.push_str(&line);
+        }
+
+        if block.is_empty() {
+            None
+        } else {
+            Some(Ok((start, block)))
+        }
+    }
+}
+
+/// Count the tokens of a sentence without parsing it. Comments,
+/// multiword tokens and empty nodes are not counted. Stanford
+/// dependencies do not have a line per token, so they are parsed.
+pub fn count_tokens(block: &str, format: InputFormat) -> usize {
+    if format == InputFormat::Stanford {
+        return parse_block(block, format)
+            .map(|graph| graph.0.node_count())
+            .unwrap_or(0);
+    }
+
+    block
+        .lines()
+        .filter(|line| {
+            line.split('\t')
+                .next()
+                .map(|id| id.parse::<usize>().is_ok())
+                .unwrap_or(false)
+        })
+        .count()
+}
+
+/// Parse the lines of a single sentence.
+pub fn parse_block(block: &str, format: InputFormat) -> Result<DependencyGraph, ViewerError> {
+    read_graphs(block.as_bytes(), format)
+        .next()
+        .unwrap_or(Err(ViewerError::EmptySentence))
+}
+
+/// Memory-map a treebank file.
+///
+/// The treebank must not be modified while it is mapped, since the
+/// trees that are read from the map would change under our feet.
+pub fn map_treebank(path: &Path) -> Result<Mmap, ViewerError> {
+    let file = File::open(path).context("Cannot open treebank")?;
+
+    // Safe as long as the file is not modified, see above.
+    let map = unsafe { Mmap::map(&file) }.context("Cannot memory-map treebank")?;
+
+    Ok(map)
+}
+
+/// The path of the offsets file of a treebank.
+pub fn offsets_path(treebank: &Path) -> PathBuf {
+    let mut path = treebank.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Read the sentence offsets of a treebank from `TREEBANK.idx`. Returns
+/// `None` when there is no offsets file or it is out of date.
+pub fn read_offsets(treebank: &Path) -> Result<Option<Vec<SentenceOffset>>, ViewerError> {
+    let path = offsets_path(treebank);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut read = BufReader::new(File::open(&path).context("Cannot open offsets file")?);
+
+    let mut magic = [0; 8];
+    read.read_exact(&mut magic)?;
+    if &magic != OFFSETS_MAGIC {
+        return Ok(None);
+    }
+
+    let source = Source {
+        len: read_u64(&mut read)?,
+        modified: read_u64(&mut read)?,
+    };
+    if source != Source::of(treebank)? {
+        return Ok(None);
+    }
+
+    // Every sentence takes at least a byte, larger counts are corrupt.
+    let n_sentences = read_u64(&mut read)?;
+    if n_sentences > source.len {
+        return Ok(None);
+    }
+
+    let n_sentences = n_sentences as usize;
+    let mut offsets = Vec::with_capacity(n_sentences);
+    for _ in 0..n_sentences {
+        offsets.push(SentenceOffset {
+            offset: read_u64(&mut read)?,
+            n_tokens: read_u64(&mut read)? as usize,
+        });
+    }
+
+    Ok(Some(offsets))
+}
+
+/// Write the sentence offsets of a treebank to `TREEBANK.idx`, so that
+/// the treebank can be reopened without scanning it.
+pub fn write_offsets(treebank: &Path, offsets: &[SentenceOffset]) -> Result<(), ViewerError> {
+    let source = Source::of(treebank)?;
+    let mut write =
+        BufWriter::new(File::create(offsets_path(treebank)).context("Cannot create offsets file")?);
+
+    write.write_all(OFFSETS_MAGIC)?;
+    write.write_all(&source.len.to_le_bytes())?;
+    write.write_all(&source.modified.to_le_bytes())?;
+    write.write_all(&(offsets.len() as u64).to_le_bytes())?;
+    for offset in offsets {
+        write.write_all(&offset.offset.to_le_bytes())?;
+        write.write_all(&(offset.n_tokens as u64).to_le_bytes())?;
+    }
+
+    write.flush()?;
+
+    Ok(())
+}
+
+fn read_u64<R>(read: &mut R) -> io::Result<u64>
+where
+    R: Read,
+{
+    let mut buf = [0; 8];
+    read.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::{env, process};
+
+    use super::{
+        count_tokens, map_treebank, offsets_path, read_offsets, write_offsets, LazyTreebank,
+        SentenceBlocks, SentenceOffset, OFFSETS_MAGIC,
+    };
+    use arena::SentenceArena;
+    use input::{read_graphs, InputFormat};
+    use search::{MatchOptions, Query};
+    use storage::TreebankStorage;
+
+    static TREEBANK: &str = "1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\n\
+                             2\tHaus\tHaus\tNN\tNN\t_\t0\tROOT\t_\t_\n\
+                             \n\
+                             \n\
+                             1\tEr\ter\tPPER\tPPER\t_\t2\tSUBJ\t_\t_\n\
+                             2\tging\tgehen\tVVFIN\tVVFIN\t_\t0\tROOT\t_\t_\n\
+                             3\tnach\tnach\tAPPR\tAPPR\t_\t2\tPP\t_\t_\n\
+                             4\tHaus\t_\tNN\tNN\t_\t3\tPN\t_\t_\n";
+
+    fn write_treebank(name: &str) -> (PathBuf, Vec<SentenceOffset>) {
+        let path = env::temp_dir().join(format!("conllx-view-{}-{}.conll", name, process::id()));
+        fs::write(&path, TREEBANK).unwrap();
+
+        let offsets = SentenceBlocks::new(TREEBANK.as_bytes())
+            .map(|block| {
+                let (offset, block) = block.unwrap();
+                SentenceOffset {
+                    offset,
+                    n_tokens: count_tokens(&block, InputFormat::Conllx),
+                }
+            })
+            .collect();
+
+        (path, offsets)
+    }
+
+    #[test]
+    fn offsets_round_trip() {
+        let (treebank, offsets) = write_treebank("offsets");
+        assert_eq!(
+            offsets,
+            vec![
+                SentenceOffset {
+                    offset: 0,
+                    n_tokens: 2,
+                },
+                SentenceOffset {
+                    offset: 63,
+                    n_tokens: 4,
+                },
+            ]
+        );
+
+        assert_eq!(read_offsets(&treebank).unwrap(), None);
+        write_offsets(&treebank, &offsets).unwrap();
+        assert_eq!(read_offsets(&treebank).unwrap(), Some(offsets.clone()));
+
+        // Offsets files of another layout are not read.
+        let mut data = fs::read(offsets_path(&treebank)).unwrap();
+        data[..OFFSETS_MAGIC.len()].copy_from_slice(b"CXVIDX00");
+        fs::write(offsets_path(&treebank), &data).unwrap();
+        assert_eq!(read_offsets(&treebank).unwrap(), None);
+
+        // Offsets are out of date when the treebank changes.
+        write_offsets(&treebank, &offsets).unwrap();
+        fs::write(&treebank, &TREEBANK[..63]).unwrap();
+        assert_eq!(read_offsets(&treebank).unwrap(), None);
+
+        fs::remove_file(offsets_path(&treebank)).unwrap();
+        fs::remove_file(&treebank).unwrap();
+    }
+
+    #[test]
+    fn lazy_storage_agrees_with_the_arena() {
+        let (treebank, offsets) = write_treebank("lazy");
+
+        let mut arena = SentenceArena::new();
+        for graph in read_graphs(TREEBANK.as_bytes(), InputFormat::Conllx) {
+            arena.push(&graph.unwrap());
+        }
+
+        let mut file = LazyTreebank::new(&treebank, InputFormat::Conllx);
+        file.push_offsets(&offsets);
+        let map = Arc::new(map_treebank(&treebank).unwrap());
+        let mut mapped = LazyTreebank::mmap(map, InputFormat::Conllx);
+        mapped.push_offsets(&offsets);
+
+        // Edited trees are kept in memory.
+        let mut edited = arena.graph(0).unwrap();
+        edited.edit_token(0, |token| {
+            token.set_head_rel(Some("NK"));
+        });
+        arena.replace(0, &edited);
+        file.replace(0, &edited);
+        mapped.replace(0, &edited);
+
+        let query = "Haus"
+            .parse::<Query>()
+            .unwrap()
+            .prepare(MatchOptions::default());
+        for lazy in &[&file, &mapped] {
+            assert_eq!(lazy.len(), arena.len());
+            for idx in 0..arena.len() {
+                assert_eq!(lazy.sentence(idx), arena.sentence(idx));
+                assert_eq!(lazy.n_tokens(idx), arena.n_tokens(idx));
+                assert_eq!(lazy.lemmas(idx), arena.lemmas(idx));
+                assert_eq!(lazy.find(idx, &query), arena.find(idx, &query));
+            }
+            assert!(lazy.graph(arena.len()).is_none());
+        }
+
+        fs::remove_file(&treebank).unwrap();
+    }
+}
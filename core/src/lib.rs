@@ -0,0 +1,97 @@
+//! The treebank model, readers and rendering of the conllx-view viewer,
+//! without GTK.
+//!
+//! Trees are read with `input::read_graphs` and rendered with the `Dot`,
+//! `Tikz` and `Svg` traits of `graph`, using `graph::RenderOptions`. The
+//! `model` module holds the state of a browsed treebank for frontends;
+//! they implement `mainloop::MainContext` to receive the trees that are
+//! read in the background. With the `rsvg` feature, trees can also be
+//! rendered as librsvg handles.
+
+extern crate bzip2;
+extern crate conllx;
+extern crate enum_map;
+#[macro_use]
+extern crate failure;
+extern crate flate2;
+extern crate itertools;
+#[macro_use]
+extern crate lazy_static;
+extern crate libc;
+extern crate memmap;
+extern crate petgraph;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+extern crate rand;
+extern crate rayon;
+extern crate reqwest;
+#[cfg(feature = "rsvg")]
+extern crate rsvg;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate stdinout;
+extern crate xz2;
+#[cfg(feature = "zmq")]
+extern crate zmq;
+
+pub mod alert;
+pub mod arena;
+pub mod cache;
+pub mod compress;
+pub mod config;
+pub mod conll2009;
+pub mod conllu;
+pub mod consistency;
+pub mod detokenize;
+pub mod diff;
+pub mod dirs;
+pub mod download;
+pub mod edit;
+// The failure derive of the error type defines impls in a function body.
+#[allow(non_local_definitions)]
+pub mod error;
+pub mod exercise;
+pub mod export;
+pub mod features;
+pub mod ffi;
+pub mod git;
+pub mod graph;
+pub mod hash;
+pub mod index;
+pub mod input;
+pub mod layer;
+pub mod layout;
+pub mod lazy;
+pub mod library;
+pub mod loader;
+#[macro_use]
+pub mod macros;
+pub mod mainloop;
+pub mod model;
+pub mod notes;
+pub mod permalink;
+pub mod profile;
+pub mod quiz;
+pub mod recent;
+pub mod recorder;
+pub mod render;
+pub mod report;
+pub mod roundtrip;
+pub mod search;
+pub mod server;
+pub mod similarity;
+pub mod stanford;
+pub mod stats;
+pub mod storage;
+pub mod style;
+pub mod subtrees;
+pub mod symbol;
+pub mod theme;
+pub mod tui;
+pub mod validate;
+pub mod versions;
+pub mod vim;
@@ -0,0 +1,129 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use conllx::{ReadSentence, Reader, Sentence, WriteSentence, Writer};
+use serde_json;
+
+use dirs::data_dir;
+use error::{ResultExt, ViewerError};
+
+const SENTENCES_FILE: &str = "library.conll";
+const METADATA_FILE: &str = "library.json";
+
+/// Metadata of an example in the construction library.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LibraryEntry {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+/// A construction library: a collection of named and tagged example
+/// sentences.
+///
+/// The library is stored in a directory as a CoNLL-X file with the
+/// example sentences and a JSON file with the metadata of each example,
+/// in the same order.
+pub struct Library {
+    dir: PathBuf,
+    entries: Vec<LibraryEntry>,
+    sentences: Vec<Sentence>,
+}
+
+impl Library {
+    /// Open the library in the given directory. An empty library is
+    /// returned when the directory does not contain a library yet.
+    pub fn open<P>(dir: P) -> Result<Self, ViewerError>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+
+        let metadata_path = dir.join(METADATA_FILE);
+        if !metadata_path.exists() {
+            return Ok(Library {
+                dir,
+                entries: Vec::new(),
+                sentences: Vec::new(),
+            });
+        }
+
+        let entries: Vec<LibraryEntry> = serde_json::from_reader(BufReader::new(
+            File::open(&metadata_path).context("Cannot open library metadata")?,
+        ))
+        .context("Cannot read library metadata")?;
+
+        let reader = Reader::new(BufReader::new(
+            File::open(dir.join(SENTENCES_FILE)).context("Cannot open library sentences")?,
+        ));
+        let sentences = reader.sentences().collect::<Result<Vec<_>, _>>()?;
+
+        if entries.len() != sentences.len() {
+            return Err(ViewerError::InconsistentLibrary(dir.display().to_string()));
+        }
+
+        Ok(Library {
+            dir,
+            entries,
+            sentences,
+        })
+    }
+
+    /// Add an example to the library and write the library to disk.
+    pub fn add(&mut self, entry: LibraryEntry, sentence: Sentence) -> Result<(), ViewerError> {
+        self.entries.push(entry);
+        self.sentences.push(sentence);
+        self.save()
+    }
+
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    /// Remove an example from the library and write the library to disk.
+    pub fn remove(&mut self, idx: usize) -> Result<(), ViewerError> {
+        if idx < self.entries.len() {
+            self.entries.remove(idx);
+            self.sentences.remove(idx);
+        }
+
+        self.save()
+    }
+
+    pub fn sentence(&self, idx: usize) -> Option<&Sentence> {
+        self.sentences.get(idx)
+    }
+
+    fn save(&self) -> Result<(), ViewerError> {
+        fs::create_dir_all(&self.dir).context("Cannot create library directory")?;
+
+        let mut writer = Writer::new(BufWriter::new(
+            File::create(self.dir.join(SENTENCES_FILE))
+                .context("Cannot create library sentences")?,
+        ));
+        for sentence in &self.sentences {
+            writer.write_sentence(sentence)?;
+        }
+
+        let metadata_writer = BufWriter::new(
+            File::create(self.dir.join(METADATA_FILE)).context("Cannot create library metadata")?,
+        );
+        serde_json::to_writer_pretty(metadata_writer, &self.entries)?;
+
+        Ok(())
+    }
+}
+
+/// Parse a list of tags, separated by commas.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// The default library directory.
+pub fn default_library_dir() -> Option<PathBuf> {
+    Some(data_dir()?.join("library"))
+}
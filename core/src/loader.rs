@@ -0,0 +1,1101 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+#[cfg(feature = "zmq")]
+use std::io::Cursor;
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use conllx::Sentence;
+use memmap::Mmap;
+use stdinout::Input;
+#[cfg(feature = "zmq")]
+use zmq;
+
+use compress::{decompress, Compression};
+use download::{download, treebank_url, Download};
+use error::{ResultExt, ViewerError};
+use git::GitFile;
+use graph::DependencyGraph;
+use index::SearchIndex;
+use input::{detect_format, parse_blocks, unsupported_columns, GraphBatches, InputFormat};
+use lazy::{
+    count_tokens, map_treebank, read_offsets, write_offsets, LazyTreebank, SentenceBlocks,
+    SentenceOffset,
+};
+use mainloop::{self, MainContext, Sender};
+use model::{StatefulTreebankModel, TreebankModel};
+use notes::{read_notes, SentenceNotes};
+use recent::RecentFiles;
+
+/// Number of sentences that are parsed in parallel and sent to the
+/// model at a time.
+const BATCH_SIZE: usize = 1000;
+
+/// Time between checks for connections to the socket that is listened
+/// on, and for canceled reading.
+const ACCEPT_INTERVAL_MS: u64 = 100;
+
+/// Number of attempts to bind the socket that is listened on, while it
+/// is still in use by the previous treebank.
+const BIND_ATTEMPTS: usize = 20;
+
+/// A file of the treebank that is viewed, for saving changes.
+pub struct TreebankFile {
+    pub path: PathBuf,
+    pub format: InputFormat,
+    pub compression: Option<Compression>,
+    pub git: Option<GitFile>,
+
+    /// Template for review links to the lines of a tree.
+    pub review_url: Option<String>,
+
+    /// Save unchanged sentences exactly as they were read.
+    pub strict: bool,
+
+    /// Keep the notes of sentences in `# note` comments, rather than in
+    /// `TREEBANK.notes.json`.
+    pub note_comments: bool,
+}
+
+/// A subscription to the sentences of a ZeroMQ publisher.
+#[cfg(feature = "zmq")]
+#[derive(Clone, Debug)]
+pub struct Subscription {
+    /// Endpoint of the publisher, e.g. `tcp://localhost:5556`.
+    pub endpoint: String,
+
+    /// Topic of the messages, all messages are received when it is empty.
+    pub topic: String,
+}
+
+/// How treebanks are read.
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    /// The format of treebanks, detected when `None`.
+    pub format: Option<InputFormat>,
+
+    /// Search with an index, stored as `TREEBANK.index`.
+    pub index: bool,
+
+    /// Read trees from the treebank file on demand.
+    pub lazy: bool,
+
+    /// Unix socket to listen on. The trees that clients send are added
+    /// to the treebank.
+    pub listen: Option<PathBuf>,
+
+    /// Read trees from a memory map of the treebank file, implies `lazy`.
+    pub mmap: bool,
+
+    /// Keep the notes of sentences in `# note` comments of the treebank.
+    pub note_comments: bool,
+
+    /// Store the sentence offsets of lazily-loaded treebanks as
+    /// `TREEBANK.idx`.
+    pub offsets: bool,
+
+    /// Template for review links to the lines of a tree.
+    pub review_url: Option<String>,
+
+    /// Save unchanged sentences exactly as they were read.
+    pub strict: bool,
+
+    /// ZeroMQ publisher to subscribe to. The sentences that it publishes
+    /// are added to the treebank.
+    #[cfg(feature = "zmq")]
+    pub subscribe: Option<Subscription>,
+}
+
+impl LoadOptions {
+    /// Whether trees arrive from a socket or a subscription, besides
+    /// the treebank files.
+    pub fn has_live_sources(&self) -> bool {
+        live_sources(self) > 0
+    }
+}
+
+/// Messages from the thread that reads the treebank.
+enum TreebankMessage {
+    /// Progress of downloading the treebank, `None` when the download
+    /// is finished.
+    Download(Option<Download>),
+
+    /// The treebank could not be opened or read.
+    Error(String),
+
+    /// A problem that does not stop reading the treebank, such as a
+    /// sidecar file that cannot be read.
+    Warning(String),
+
+    /// The trees that follow are read from this file, `None` for trees
+    /// that are not read from a treebank file.
+    File(Option<TreebankFile>),
+
+    /// The treebank is read lazily from this file, or from its memory
+    /// map.
+    Lazy(PathBuf, InputFormat, Option<Arc<Mmap>>),
+
+    /// Sentences of the treebank files at git HEAD.
+    Revision(Vec<Sentence>),
+
+    /// Notes of the trees of the current file.
+    Notes(SentenceNotes),
+
+    /// Trees, in treebank order.
+    Graphs(Vec<DependencyGraph>),
+
+    Index(SearchIndex),
+
+    /// Offsets of sentences of a lazily-loaded treebank.
+    Offsets(Vec<SentenceOffset>),
+}
+
+/// Sends messages from the reader thread, until reading is canceled.
+struct TreebankSender {
+    tx: Sender<TreebankMessage>,
+    canceled: Arc<AtomicBool>,
+}
+
+impl TreebankSender {
+    fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::SeqCst)
+    }
+
+    fn send(&self, msg: TreebankMessage) -> Result<(), ViewerError> {
+        if self.is_canceled() {
+            return Err(ViewerError::Canceled);
+        }
+
+        self.tx.send(msg).map_err(|_| ViewerError::Canceled)
+    }
+}
+
+/// A treebank that is read on a separate thread. Reading is canceled
+/// when the loader is dropped.
+pub struct TreebankLoader {
+    canceled: Arc<AtomicBool>,
+}
+
+impl Drop for TreebankLoader {
+    fn drop(&mut self) {
+        self.canceled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The treebank that is viewed. Another treebank can be opened while
+/// the viewer runs.
+pub struct TreebankOpener {
+    options: LoadOptions,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    loader: RefCell<Option<TreebankLoader>>,
+    paths: RefCell<Vec<PathBuf>>,
+    recent: RefCell<RecentFiles>,
+
+    /// The main loop that receives the trees from the reader thread.
+    context: Rc<dyn MainContext>,
+}
+
+impl TreebankOpener {
+    pub fn new(
+        options: LoadOptions,
+        treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+        recent: RecentFiles,
+        context: Rc<dyn MainContext>,
+    ) -> Self {
+        TreebankOpener {
+            options,
+            treebank_model,
+            context,
+            loader: RefCell::new(None),
+            paths: RefCell::new(Vec::new()),
+            recent: RefCell::new(recent),
+        }
+    }
+
+    /// Open a treebank, replacing the treebank that is viewed. Several
+    /// files are concatenated into one treebank, a directory stands for
+    /// the treebank files in the directory and `http(s)://` URLs are
+    /// downloaded. Reads from the standard input when there are no paths.
+    ///
+    /// The files are opened on the reader thread, errors are reported
+    /// with `StatefulTreebankModel::read_error` and problems that do not
+    /// stop reading with `StatefulTreebankModel::read_warning`. Treebanks
+    /// that are opened from files are added to the recent files.
+    pub fn open(&self, paths: Vec<PathBuf>) {
+        let loader = load_treebank(
+            paths.clone(),
+            &self.options,
+            &self.treebank_model,
+            &*self.context,
+        );
+
+        // Replacing the loader cancels reading the previous treebank.
+        *self.loader.borrow_mut() = Some(loader);
+        *self.paths.borrow_mut() = paths.clone();
+
+        if !paths.is_empty() {
+            if let Err(err) = self.recent.borrow_mut().add(&paths) {
+                self.treebank_model
+                    .borrow_mut()
+                    .push_read_warning(format!("Cannot save recent files: {}", err));
+            }
+        }
+    }
+
+    pub fn options(&self) -> &LoadOptions {
+        &self.options
+    }
+
+    /// Get the paths of the treebank that is viewed, as they were given.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.paths.borrow().clone()
+    }
+
+    /// Get the treebanks that were opened recently.
+    pub fn recent(&self) -> Vec<Vec<PathBuf>> {
+        self.recent.borrow().treebanks().to_vec()
+    }
+}
+
+/// Get the sentences of the treebank files at git HEAD, in treebank
+/// order. Returns `None` unless all files are tracked in git.
+pub fn head_revision<'a, I>(files: I) -> Result<Option<Vec<Sentence>>, ViewerError>
+where
+    I: IntoIterator<Item = &'a TreebankFile>,
+{
+    let mut sentences = Vec::new();
+    let mut n_files = 0;
+
+    for file in files {
+        let git = match file.git {
+            Some(ref git) => git,
+            None => return Ok(None),
+        };
+
+        sentences.extend(git.head_sentences(file.format)?);
+        n_files += 1;
+    }
+
+    if n_files == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(sentences))
+}
+
+/// Open the treebank files, replacing the trees of the model. The files
+/// are opened and read on a separate thread and the trees are added to
+/// the model on the main thread, whose main loop is `context`. Reads from
+/// the standard input when there are no paths.
+fn load_treebank(
+    paths: Vec<PathBuf>,
+    options: &LoadOptions,
+    treebank_model: &Rc<RefCell<StatefulTreebankModel>>,
+    context: &dyn MainContext,
+) -> TreebankLoader {
+    treebank_model.borrow_mut().reset(TreebankModel::new());
+
+    let canceled = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mainloop::channel();
+    let tx = TreebankSender {
+        tx,
+        canceled: canceled.clone(),
+    };
+
+    let mut options = options.clone();
+    thread::spawn(move || {
+        let (sources, map) = match open_sources(paths, &mut options, &tx) {
+            Ok(sources) => sources,
+            Err(err) => {
+                let _ = tx.send(TreebankMessage::Error(format!(
+                    "Cannot open treebank: {}",
+                    err
+                )));
+                return;
+            }
+        };
+
+        for (source, file) in sources {
+            if !source.is_stdin() && tx.send(TreebankMessage::File(file)).is_err() {
+                return;
+            }
+
+            if let Some(path) = source.path.as_ref().filter(|path| path.is_file()) {
+                match read_notes(path, options.note_comments) {
+                    Ok(ref notes) if notes.is_empty() => (),
+                    Ok(notes) => {
+                        if tx.send(TreebankMessage::Notes(notes)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) => {
+                        let warning = format!("Cannot read notes of {}: {}", path.display(), err);
+                        if tx.send(TreebankMessage::Warning(warning)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            // A file that cannot be read does not prevent reading the
+            // other files.
+            let description = source.name();
+            let downloaded = source.url().is_some();
+            if let Err(err) = read_trees(source, map.clone(), &options, &tx) {
+                if tx.is_canceled() {
+                    return;
+                }
+
+                let error = format!("Cannot read {}: {}", description, err);
+                if tx.send(TreebankMessage::Error(error)).is_err() {
+                    return;
+                }
+            }
+
+            if downloaded && tx.send(TreebankMessage::Download(None)).is_err() {
+                return;
+            }
+        }
+    });
+
+    let receiver_canceled = canceled.clone();
+    let treebank_model = treebank_model.clone();
+    rx.attach(context, move |msg| {
+        // Drop the messages that were sent before reading was canceled.
+        if receiver_canceled.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        match msg {
+            TreebankMessage::Download(download) => treebank_model.set_download(download),
+            TreebankMessage::Error(error) => treebank_model.push_read_error(error),
+            TreebankMessage::Warning(warning) => treebank_model.push_read_warning(warning),
+            TreebankMessage::File(file) => treebank_model.push_file(file.map(Rc::new)),
+            TreebankMessage::Graphs(graphs) => treebank_model.extend(graphs),
+            TreebankMessage::Index(index) => treebank_model.set_index(Some(index)),
+            TreebankMessage::Lazy(path, format, map) => {
+                let lazy = match map {
+                    Some(map) => LazyTreebank::mmap(map, format),
+                    None => LazyTreebank::new(path, format),
+                };
+                treebank_model.reset(TreebankModel::lazy(lazy));
+            }
+            TreebankMessage::Notes(notes) => treebank_model.push_notes(notes),
+            TreebankMessage::Offsets(offsets) => treebank_model.push_offsets(&offsets),
+            TreebankMessage::Revision(sentences) => treebank_model.set_revision(Some(sentences)),
+        }
+    });
+
+    TreebankLoader { canceled }
+}
+
+/// A source of a treebank, with the file that its trees are saved to.
+type OpenedSource = (TreebankSource, Option<TreebankFile>);
+
+/// Open the sources of a treebank on the reader thread, with the files
+/// that their trees are saved to. Directories are replaced by the
+/// treebank files in them. The model is switched to lazy loading and
+/// gets the git HEAD revision of the files, when applicable.
+fn open_sources(
+    paths: Vec<PathBuf>,
+    options: &mut LoadOptions,
+    tx: &TreebankSender,
+) -> Result<(Vec<OpenedSource>, Option<Arc<Mmap>>), ViewerError> {
+    let mut files = Vec::new();
+    for path in paths {
+        if treebank_url(&path).is_none() && path.is_dir() {
+            files.extend(treebank_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    if files.len() + live_sources(options) > 1 {
+        if options.lazy || options.mmap {
+            return Err(ViewerError::RequiresSingleFile("lazy loading".to_owned()));
+        }
+
+        if options.index {
+            tx.send(TreebankMessage::Warning(
+                "Search indexes are only used for single treebank files".to_owned(),
+            ))?;
+            options.index = false;
+        }
+    }
+
+    // Treebank files may use different formats, they are read into the
+    // same representation.
+    let inputs = if files.is_empty() && live_sources(options) == 0 {
+        vec![None]
+    } else {
+        files.into_iter().map(Some).collect()
+    };
+    let mut sources = Vec::new();
+    for path in inputs {
+        sources.push(TreebankSource::open(path, options.format, tx)?);
+    }
+    if let Some(ref path) = options.listen {
+        sources.push(TreebankSource {
+            input: SourceInput::Listen(path.clone()),
+            path: None,
+            format: options.format,
+            compression: None,
+        });
+    }
+    #[cfg(feature = "zmq")]
+    {
+        if let Some(ref subscription) = options.subscribe {
+            sources.push(TreebankSource {
+                input: SourceInput::Subscribe(subscription.clone()),
+                path: None,
+                format: options.format,
+                compression: None,
+            });
+        }
+    }
+
+    // Lazily-loaded trees are read from the uncompressed treebank file.
+    let lazy = match sources[0] {
+        _ if !options.lazy && !options.mmap => None,
+        TreebankSource {
+            compression: Some(_),
+            ..
+        } => return Err(ViewerError::RequiresUncompressed("lazy loading".to_owned())),
+        TreebankSource {
+            format: Some(format),
+            path: Some(ref path),
+            ..
+        } => Some((path.clone(), format)),
+        _ => return Err(ViewerError::RequiresInputFile("lazy loading".to_owned())),
+    };
+    let map = match lazy {
+        Some((ref path, _)) if options.mmap => Some(Arc::new(map_treebank(path)?)),
+        _ => None,
+    };
+    if let Some((path, format)) = lazy {
+        tx.send(TreebankMessage::Lazy(path, format, map.clone()))?;
+    }
+
+    // Trees that are downloaded are not read from a file.
+    let files: Vec<_> = sources
+        .iter()
+        .map(|source| {
+            let path = source.path.as_ref()?;
+            Some(TreebankFile {
+                path: path.clone(),
+                format: source.format?,
+                compression: source.compression,
+                git: GitFile::open(path),
+                review_url: options.review_url.clone(),
+                strict: options.strict,
+                note_comments: options.note_comments,
+            })
+        })
+        .collect();
+
+    let local: Option<Vec<_>> = files.iter().map(Option::as_ref).collect();
+    match local.map(head_revision) {
+        Some(Ok(Some(sentences))) => tx.send(TreebankMessage::Revision(sentences))?,
+        Some(Ok(None)) | None => (),
+        Some(Err(err)) => {
+            let error = format!("Cannot read treebank from git HEAD: {}", err);
+            tx.send(TreebankMessage::Error(error))?;
+        }
+    }
+
+    Ok((sources.into_iter().zip(files).collect(), map))
+}
+
+/// A treebank file, URL or the standard input, that is opened and read
+/// on the reader thread.
+struct TreebankSource {
+    input: SourceInput,
+    path: Option<PathBuf>,
+
+    /// The format, `None` when it is detected on the reader thread.
+    format: Option<InputFormat>,
+
+    compression: Option<Compression>,
+}
+
+/// Where a treebank is read from.
+enum SourceInput {
+    /// A file or the standard input.
+    Input(Input),
+
+    /// A Unix socket that is listened on, for clients that send trees.
+    Listen(PathBuf),
+
+    /// A named pipe or another file that is not a regular file, which is
+    /// opened when its trees are read.
+    Stream(PathBuf),
+
+    /// A ZeroMQ publisher that is subscribed to.
+    #[cfg(feature = "zmq")]
+    Subscribe(Subscription),
+
+    /// A URL, which is downloaded when its trees are read.
+    Url(String),
+}
+
+impl TreebankSource {
+    /// Open a treebank. The format of treebank files is detected
+    /// immediately. The standard input and URLs are not accessed until
+    /// their trees are read, since the standard input can only be read
+    /// once and may be a pipe that is not written to yet.
+    fn open(
+        path: Option<PathBuf>,
+        format: Option<InputFormat>,
+        tx: &TreebankSender,
+    ) -> Result<Self, ViewerError> {
+        if let Some(url) = path.as_ref().and_then(|path| treebank_url(path)) {
+            return Ok(TreebankSource {
+                input: SourceInput::Url(url.to_owned()),
+                path: None,
+                format,
+                compression: None,
+            });
+        }
+
+        // Opening a pipe blocks until it has a writer, and its data can
+        // only be read once. Like the standard input, pipes are neither
+        // opened nor probed until they are read.
+        if let Some(path) = path.clone().filter(|path| is_stream(path)) {
+            return Ok(TreebankSource {
+                input: SourceInput::Stream(path),
+                path: None,
+                format,
+                compression: None,
+            });
+        }
+
+        let input = Input::from(path.clone());
+
+        let (compression, format) = match path {
+            Some(ref path) => {
+                let mut read = input.buf_read()?;
+                let compression = Compression::detect(&mut read)?;
+                let name = source_name(Some(path));
+                let format = detect_file_format(&mut decompress(read)?, &name, format, tx)?;
+                (compression, Some(format))
+            }
+            None => (None, format),
+        };
+
+        Ok(TreebankSource {
+            input: SourceInput::Input(input),
+            path,
+            format,
+            compression,
+        })
+    }
+
+    fn is_stdin(&self) -> bool {
+        match self.input {
+            SourceInput::Input(_) => self.path.is_none(),
+            SourceInput::Listen(_) | SourceInput::Stream(_) | SourceInput::Url(_) => false,
+            #[cfg(feature = "zmq")]
+            SourceInput::Subscribe(_) => false,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self.input {
+            SourceInput::Input(_) => source_name(self.path.as_ref()),
+            SourceInput::Listen(ref path) | SourceInput::Stream(ref path) => {
+                path.display().to_string()
+            }
+            #[cfg(feature = "zmq")]
+            SourceInput::Subscribe(ref subscription) => subscription.endpoint.clone(),
+            SourceInput::Url(ref url) => url.to_owned(),
+        }
+    }
+
+    fn url(&self) -> Option<&str> {
+        match self.input {
+            SourceInput::Input(_) | SourceInput::Listen(_) | SourceInput::Stream(_) => None,
+            #[cfg(feature = "zmq")]
+            SourceInput::Subscribe(_) => None,
+            SourceInput::Url(ref url) => Some(url),
+        }
+    }
+}
+
+/// The number of sources that trees keep arriving from, besides the
+/// treebank files.
+fn live_sources(options: &LoadOptions) -> usize {
+    #[cfg(feature = "zmq")]
+    let subscriptions = options.subscribe.iter().count();
+    #[cfg(not(feature = "zmq"))]
+    let subscriptions = 0;
+
+    options.listen.iter().count() + subscriptions
+}
+
+/// Check whether a path is a named pipe.
+fn is_fifo(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+/// Check whether a path is a named pipe, socket or device, which is read
+/// as a stream.
+fn is_stream(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| !metadata.is_file() && !metadata.is_dir())
+        .unwrap_or(false)
+}
+
+fn source_name(path: Option<&PathBuf>) -> String {
+    path.map(|path| path.display().to_string())
+        .unwrap_or_else(|| "stdin".to_owned())
+}
+
+/// Get the format of a treebank, detecting it when no format is given.
+/// Warns about columns that cannot be represented.
+fn detect_file_format<R>(
+    read: &mut R,
+    name: &str,
+    format: Option<InputFormat>,
+    tx: &TreebankSender,
+) -> Result<InputFormat, ViewerError>
+where
+    R: BufRead,
+{
+    let sample = String::from_utf8_lossy(read.fill_buf()?).into_owned();
+    let format = format.unwrap_or_else(|| detect_format(&sample));
+
+    let columns = unsupported_columns(&sample, format);
+    if !columns.is_empty() {
+        tx.send(TreebankMessage::Warning(format!(
+            "{}: ignoring {}",
+            name,
+            columns.join(", ")
+        )))?;
+    }
+
+    Ok(format)
+}
+
+/// Get the treebank files in a directory, sorted by name. Hidden files
+/// and the index and offsets files of treebanks are skipped.
+fn treebank_files(dir: &Path) -> Result<Vec<PathBuf>, ViewerError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        let sidecar = path
+            .extension()
+            .is_some_and(|ext| ext == "idx" || ext == "index");
+        if path.is_file() && !hidden && !sidecar {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Read the trees of a treebank and send them to the model.
+fn read_trees(
+    source: TreebankSource,
+    map: Option<Arc<Mmap>>,
+    options: &LoadOptions,
+    tx: &TreebankSender,
+) -> Result<(), ViewerError> {
+    let mut read = match source.input {
+        SourceInput::Input(ref input) => decompress(input.buf_read()?)?,
+        SourceInput::Listen(ref path) => return listen(path, source.format, tx),
+        SourceInput::Stream(ref path) if is_fifo(path) => {
+            return read_fifo(path, source.format, tx)
+        }
+        SourceInput::Stream(ref path) => decompress(BufReader::new(File::open(path)?))?,
+        #[cfg(feature = "zmq")]
+        SourceInput::Subscribe(ref subscription) => {
+            return subscribe(subscription, source.format, tx)
+        }
+        SourceInput::Url(ref url) => {
+            let download = download(url, |download| {
+                // Reading stops at the next batch of trees when canceled.
+                let _ = tx.send(TreebankMessage::Download(Some(download.clone())));
+            })?;
+            decompress(BufReader::new(download))?
+        }
+    };
+    let format = match source.format {
+        Some(format) => format,
+        None => detect_file_format(&mut read, &source.name(), None, tx)?,
+    };
+    // Streams have no path, so that their trees are sent as they
+    // arrive.
+    let path = source.path;
+    let index_path = path.clone().filter(|_| options.index);
+
+    // Build the index while reading, when there is none yet.
+    let mut index = None;
+    if let Some(ref path) = index_path {
+        match SearchIndex::open(path) {
+            Ok(Some(existing)) => tx.send(TreebankMessage::Index(existing))?,
+            Ok(None) => index = Some(SearchIndex::new()),
+            Err(err) => {
+                tx.send(TreebankMessage::Warning(format!(
+                    "Cannot open search index: {}",
+                    err
+                )))?;
+                index = Some(SearchIndex::new());
+            }
+        }
+    }
+
+    if options.lazy || options.mmap {
+        let path = path.ok_or_else(|| ViewerError::RequiresInputFile("lazy loading".to_owned()))?;
+
+        // Reopen the treebank from its offsets file, unless it has to be
+        // read anyway to build the search index.
+        let stored = if options.offsets && index.is_none() {
+            match read_offsets(&path) {
+                Ok(offsets) => offsets,
+                Err(err) => {
+                    tx.send(TreebankMessage::Warning(format!(
+                        "Cannot read sentence offsets: {}",
+                        err
+                    )))?;
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        match stored {
+            Some(offsets) => tx.send(TreebankMessage::Offsets(offsets))?,
+            None => {
+                let offsets = match map {
+                    Some(ref map) => scan_treebank(&map[..], format, index.as_mut(), tx)?,
+                    None => scan_treebank(read, format, index.as_mut(), tx)?,
+                };
+                if options.offsets {
+                    if let Err(err) = write_offsets(&path, &offsets) {
+                        tx.send(TreebankMessage::Warning(format!(
+                            "Cannot save sentence offsets: {}",
+                            err
+                        )))?;
+                    }
+                }
+            }
+        }
+    } else if path.is_none() {
+        read_stream(read, format, tx)?;
+    } else {
+        // Sentences are parsed in parallel, batches are sent in treebank
+        // order.
+        for graphs in GraphBatches::new(read, format, BATCH_SIZE) {
+            let graphs = graphs?;
+
+            if let Some(ref mut index) = index {
+                for graph in &graphs {
+                    index.add(graph);
+                }
+            }
+
+            tx.send(TreebankMessage::Graphs(graphs))?;
+        }
+    }
+
+    if let (Some(path), Some(mut index)) = (index_path, index) {
+        if let Err(err) = index.save(&path) {
+            tx.send(TreebankMessage::Warning(format!(
+                "Cannot save search index: {}",
+                err
+            )))?;
+        }
+
+        tx.send(TreebankMessage::Index(index))?;
+    }
+
+    Ok(())
+}
+
+/// Read trees from a named pipe. The pipe is opened again when its
+/// writer closes it, so that writers can come and go.
+fn read_fifo(
+    path: &Path,
+    format: Option<InputFormat>,
+    tx: &TreebankSender,
+) -> Result<(), ViewerError> {
+    let name = path.display().to_string();
+
+    while !tx.is_canceled() {
+        let mut read = decompress(BufReader::new(File::open(path)?))?;
+        let format = match format {
+            Some(format) => format,
+            None => detect_file_format(&mut read, &name, None, tx)?,
+        };
+        read_stream(read, format, tx)?;
+    }
+
+    Ok(())
+}
+
+/// Read trees from the clients of a Unix socket, one client at a time.
+/// A client can send any number of sentences before it disconnects. A
+/// client that sends invalid sentences does not stop listening.
+fn listen(
+    path: &Path,
+    format: Option<InputFormat>,
+    tx: &TreebankSender,
+) -> Result<(), ViewerError> {
+    let listener = bind_socket(path).context("Cannot bind socket")?;
+
+    // Accepting does not block, so that canceling stops listening.
+    listener.set_nonblocking(true)?;
+
+    let name = path.display().to_string();
+    while !tx.is_canceled() {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(ACCEPT_INTERVAL_MS));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        stream.set_nonblocking(false)?;
+
+        let result = decompress(BufReader::new(stream))
+            .map_err(ViewerError::from)
+            .and_then(|mut read| {
+                let format = match format {
+                    Some(format) => format,
+                    None => detect_file_format(&mut read, &name, None, tx)?,
+                };
+                read_stream(read, format, tx)
+            });
+        if let Err(err) = result {
+            if tx.is_canceled() {
+                break;
+            }
+
+            tx.send(TreebankMessage::Warning(format!(
+                "Cannot read trees from client of {}: {}",
+                name, err
+            )))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the trees of the messages of a ZeroMQ publisher. A message
+/// holds one or more sentences in its last part. The topic is removed
+/// from messages that consist of a single part.
+#[cfg(feature = "zmq")]
+fn subscribe(
+    subscription: &Subscription,
+    format: Option<InputFormat>,
+    tx: &TreebankSender,
+) -> Result<(), ViewerError> {
+    let context = zmq::Context::new();
+    let socket = context.socket(zmq::SUB)?;
+    socket
+        .connect(&subscription.endpoint)
+        .context("Cannot connect to publisher")?;
+    socket.set_subscribe(subscription.topic.as_bytes())?;
+
+    // Receiving times out, so that canceling stops the subscription.
+    socket.set_rcvtimeo(ACCEPT_INTERVAL_MS as i32)?;
+
+    while !tx.is_canceled() {
+        let mut parts = match socket.recv_multipart(0) {
+            Ok(parts) => parts,
+            Err(zmq::Error::EAGAIN) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut data = match parts.pop() {
+            Some(data) => data,
+            None => continue,
+        };
+        if parts.is_empty() {
+            data.drain(..subscription.topic.len());
+        }
+
+        let mut read = Cursor::new(data);
+        let result = detect_file_format(&mut read, &subscription.endpoint, format, tx)
+            .and_then(|format| read_stream(read, format, tx));
+        if let Err(err) = result {
+            if tx.is_canceled() {
+                break;
+            }
+
+            tx.send(TreebankMessage::Warning(format!(
+                "Cannot read trees from {}: {}",
+                subscription.endpoint, err
+            )))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Bind a Unix socket. A socket that nobody listens on, left over from a
+/// viewer that exited, is replaced. When another treebank is opened, the
+/// socket of the previous treebank is closed shortly.
+fn bind_socket(path: &Path) -> io::Result<UnixListener> {
+    for _ in 1..BIND_ATTEMPTS {
+        match UnixListener::bind(path) {
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => (),
+            result => return result,
+        }
+
+        let socket = fs::metadata(path)
+            .map(|metadata| metadata.file_type().is_socket())
+            .unwrap_or(false);
+        if socket && UnixStream::connect(path).is_err() {
+            fs::remove_file(path)?;
+        } else {
+            thread::sleep(Duration::from_millis(ACCEPT_INTERVAL_MS));
+        }
+    }
+
+    UnixListener::bind(path)
+}
+
+/// Read trees from a stream that can stall, such as a pipe that a parser
+/// writes to. Trees are sent as soon as they are read: the sentences are
+/// read on this thread, and parsed and sent on another thread in batches
+/// of the sentences that have arrived.
+fn read_stream<R>(read: R, format: InputFormat, tx: &TreebankSender) -> Result<(), ViewerError>
+where
+    R: BufRead,
+{
+    let (block_tx, block_rx) = channel();
+
+    thread::scope(|scope| {
+        let parser = scope.spawn(move || -> Result<(), ViewerError> {
+            while let Ok(block) = block_rx.recv() {
+                let mut blocks = vec![block];
+                blocks.extend(block_rx.try_iter().take(BATCH_SIZE - 1));
+                tx.send(TreebankMessage::Graphs(parse_blocks(&blocks, format)?))?;
+            }
+
+            Ok(())
+        });
+
+        let mut result = Ok(());
+        for block in SentenceBlocks::new(read) {
+            match block {
+                // The parser thread stops when reading is canceled.
+                Ok((_, block)) => {
+                    if block_tx.send(block).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    result = Err(err.into());
+                    break;
+                }
+            }
+        }
+
+        drop(block_tx);
+        let parsed = parser.join().expect("Parser thread panicked");
+
+        result.and(parsed)
+    })
+}
+
+/// Record the offsets of the sentences of a treebank, adding them to the
+/// index when one is built. The offsets are sent to the model in batches.
+fn scan_treebank<R>(
+    read: R,
+    format: InputFormat,
+    mut index: Option<&mut SearchIndex>,
+    tx: &TreebankSender,
+) -> Result<Vec<SentenceOffset>, ViewerError>
+where
+    R: BufRead,
+{
+    let mut offsets = Vec::new();
+    let mut blocks = SentenceBlocks::new(read);
+
+    loop {
+        let batch = blocks
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<Result<Vec<_>, _>>()?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let (starts, blocks): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+
+        // Trees are only parsed to build the index.
+        if let Some(ref mut index) = index {
+            for graph in parse_blocks(&blocks, format)? {
+                index.add(&graph);
+            }
+        }
+
+        let n_sent = offsets.len();
+        offsets.extend(
+            starts
+                .into_iter()
+                .zip(&blocks)
+                .map(|(offset, block)| SentenceOffset {
+                    offset,
+                    n_tokens: count_tokens(block, format),
+                }),
+        );
+
+        tx.send(TreebankMessage::Offsets(offsets[n_sent..].to_vec()))?;
+    }
+
+    Ok(offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{load_treebank, LoadOptions};
+    use mainloop::PollingContext;
+    use model::StatefulTreebankModel;
+
+    #[test]
+    fn open_errors_are_reported_by_the_model() {
+        let treebank_model = Rc::new(RefCell::new(StatefulTreebankModel::new()));
+        let paths = vec![PathBuf::from("/nonexistent/treebank.conll")];
+        let context = PollingContext::new();
+        let _loader = load_treebank(paths, &LoadOptions::default(), &treebank_model, &context);
+
+        // The file is opened on the reader thread.
+        while context.poll() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let treebank_model = treebank_model.borrow();
+        assert!(treebank_model
+            .read_error()
+            .unwrap()
+            .starts_with("Cannot open treebank"));
+        assert!(treebank_model.is_empty());
+    }
+}
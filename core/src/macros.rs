@@ -16,6 +16,8 @@ macro_rules! clone {
     );
 }
 
+// The statement needs its own block, which rustfmt would remove.
+#[rustfmt::skip]
 #[macro_export]
 macro_rules! ok_or {
     ($expr:expr, $stmt:stmt) => (match $expr {
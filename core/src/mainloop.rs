@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::sync::mpsc::{self, SendError, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of messages that are handled per dispatch, so that the
+/// interface stays responsive while a large treebank is read.
+const MAX_MESSAGES_PER_DISPATCH: usize = 1000;
+
+/// Wakes the main loop of a frontend from any thread, so that it
+/// dispatches the messages of a receiver.
+pub type Waker = Box<dyn Fn() + Send + Sync>;
+
+/// The main loop of a frontend, which handles the messages of channels
+/// on the main thread.
+pub trait MainContext {
+    /// Attach a receiver to the main loop. The main loop calls
+    /// `dispatch` after the returned waker was called, until `dispatch`
+    /// returns `false`.
+    fn attach(&self, dispatch: Box<dyn FnMut() -> bool>) -> Waker;
+}
+
+/// The waker of the main loop that a receiver is attached to, `None`
+/// until the receiver is attached.
+type WakerSlot = Arc<Mutex<Option<Waker>>>;
+
+fn wake(waker: &WakerSlot) {
+    if let Some(ref wake) = *waker.lock().unwrap() {
+        wake();
+    }
+}
+
+/// Create a channel whose messages are received on the main thread of a
+/// frontend. Sending a message wakes the main loop that the receiver is
+/// attached to.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::channel();
+    let waker = Arc::new(Mutex::new(None));
+    let sender = Sender {
+        tx: Some(tx),
+        waker: waker.clone(),
+    };
+
+    (sender, Receiver { rx, waker })
+}
+
+pub struct Sender<T> {
+    /// Only taken when the sender is dropped.
+    tx: Option<mpsc::Sender<T>>,
+    waker: WakerSlot,
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        match self.tx {
+            Some(ref tx) => tx.send(msg)?,
+            None => unreachable!(),
+        }
+        wake(&self.waker);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender {
+            tx: self.tx.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // The receiver is detached when it notices that all senders are
+        // dropped, so it should only be woken afterwards.
+        self.tx.take();
+        wake(&self.waker);
+    }
+}
+
+pub struct Receiver<T> {
+    rx: mpsc::Receiver<T>,
+    waker: WakerSlot,
+}
+
+/// The state of a receiver after handling its messages.
+enum Received {
+    /// All messages were handled.
+    All,
+
+    /// Messages are left to be handled in the next dispatch.
+    Pending,
+
+    /// All senders are dropped.
+    Disconnected,
+}
+
+impl<T: 'static> Receiver<T> {
+    /// Attach the receiver to the main loop of a frontend. `receive` is
+    /// called on the main thread for every message, until all senders
+    /// are dropped.
+    pub fn attach<F>(self, context: &dyn MainContext, mut receive: F)
+    where
+        F: FnMut(T) + 'static,
+    {
+        let waker = self.waker.clone();
+        let dispatch = move || match self.receive_messages(&mut receive) {
+            Received::All => true,
+            Received::Pending => {
+                wake(&self.waker);
+                true
+            }
+            Received::Disconnected => false,
+        };
+        *waker.lock().unwrap() = Some(context.attach(Box::new(dispatch)));
+
+        // Messages may have been sent before the receiver was attached.
+        wake(&waker);
+    }
+
+    fn receive_messages<F>(&self, receive: &mut F) -> Received
+    where
+        F: FnMut(T),
+    {
+        for _ in 0..MAX_MESSAGES_PER_DISPATCH {
+            match self.rx.try_recv() {
+                Ok(msg) => receive(msg),
+                Err(TryRecvError::Empty) => return Received::All,
+                Err(TryRecvError::Disconnected) => return Received::Disconnected,
+            }
+        }
+
+        Received::Pending
+    }
+}
+
+/// A main loop that dispatches all attached receivers when it is polled,
+/// for frontends that poll once per frame.
+#[derive(Default)]
+pub struct PollingContext {
+    receivers: RefCell<Vec<Box<dyn FnMut() -> bool>>>,
+}
+
+impl PollingContext {
+    pub fn new() -> Self {
+        PollingContext::default()
+    }
+
+    /// Handle the pending messages of the attached receivers. Returns
+    /// whether receivers are still attached.
+    pub fn poll(&self) -> bool {
+        // Receivers can be attached while messages are handled.
+        let mut receivers = self.receivers.replace(Vec::new());
+        receivers.retain_mut(|dispatch| dispatch());
+
+        let mut attached = self.receivers.borrow_mut();
+        receivers.append(&mut attached);
+        *attached = receivers;
+        !attached.is_empty()
+    }
+}
+
+impl MainContext for PollingContext {
+    fn attach(&self, dispatch: Box<dyn FnMut() -> bool>) -> Waker {
+        self.receivers.borrow_mut().push(dispatch);
+
+        // All receivers are dispatched when the context is polled.
+        Box::new(|| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::{channel, PollingContext};
+
+    #[test]
+    fn receivers_are_detached_when_senders_are_dropped() {
+        let context = PollingContext::new();
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let receiver_received = received.clone();
+        rx.attach(&context, move |msg| {
+            receiver_received.borrow_mut().push(msg)
+        });
+
+        thread::spawn(move || {
+            for msg in 2..2000 {
+                tx.send(msg).unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+
+        while context.poll() {}
+        assert_eq!(*received.borrow(), (1..2000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,2181 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use conllx::{Sentence, Token};
+use enum_map::EnumMap;
+use petgraph::graph::NodeIndex;
+use rayon::prelude::*;
+
+use alert::Alert;
+use arena::SentenceArena;
+use cache::LruCache;
+use consistency::{find_inconsistencies, Inconsistency};
+use download::Download;
+use error::ViewerError;
+use features::{count_features, feature_instances, FeatureCell, FeatureTable};
+
+use graph::{DependencyGraph, DependencyPath, RenderOptions};
+use hash::{ContentHash, SentenceHash};
+use index::SearchIndex;
+use lazy::{LazyTreebank, SentenceOffset};
+use loader::TreebankFile;
+use notes::SentenceNotes;
+use quiz::Quiz;
+use search::{rank, Match, MatchOptions, Query, Ranking};
+use similarity::{nearest, Similarity};
+use stats::{LengthRange, SentenceSummary};
+use storage::TreebankStorage;
+use style::{StyleCache, TreeStyle};
+use subtrees::{mine_subtrees, pattern_instances, SubtreePattern};
+
+/// Changes of a `StatefulTreebankModel` that callbacks are connected to
+/// with `connect_update`.
+#[derive(Clone, Copy, EnumMap, Eq, PartialEq)]
+pub enum ModelUpdate {
+    /// Trees that were added are flagged by alerts.
+    Alert,
+
+    /// Any of the other changes.
+    Any,
+
+    /// The progress of downloading the treebank.
+    Download,
+    MatchSelection,
+
+    /// Notes of trees were changed.
+    Notes,
+    Quiz,
+
+    /// The treebank could not be opened or read.
+    ReadError,
+
+    /// A problem that did not stop reading the treebank.
+    ReadWarning,
+
+    /// The matches of a search, or of the similar trees and patterns
+    /// that were looked for.
+    Search,
+    TokenSelection,
+
+    /// The current tree was edited, or should be shown again.
+    TreeEdit,
+    TreeSelection,
+
+    /// Trees were added or removed.
+    TreebankLen,
+}
+
+/// A tree to move to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Seek {
+    First,
+    Last,
+
+    /// The tree with the given index.
+    Absolute(usize),
+
+    /// The tree that is the given number of trees ahead, or back when
+    /// the number is negative.
+    Relative(isize),
+}
+
+type ModelCallback = Box<dyn Fn(&StatefulTreebankModel) + 'static>;
+
+/// A treebank with the state of browsing it: the current tree and token,
+/// search matches, edits, notes and marks.
+///
+/// Frontends change the state with the methods of the model and follow
+/// it with the callbacks of `connect_update`, which get the model after
+/// the change. The GTK viewer shares the model as an
+/// `Rc<RefCell<StatefulTreebankModel>>` between its widgets.
+pub struct StatefulTreebankModel {
+    inner: TreebankModel,
+    idx: usize,
+    token: Option<usize>,
+
+    /// The token that a dependency path starts at, the path ends at the
+    /// selected token.
+    path_start: Option<usize>,
+
+    /// Tokens of the current tree whose subtrees are collapsed.
+    collapsed: Vec<usize>,
+
+    quiz: Option<Quiz>,
+    originals: BTreeMap<usize, Sentence>,
+    show_original: bool,
+    show_morphology: bool,
+    revision: Option<Vec<Sentence>>,
+    show_revision: bool,
+    matches: Vec<Match>,
+    current_match: Option<usize>,
+    ranking: Ranking,
+    index: Option<SearchIndex>,
+    callbacks: EnumMap<ModelUpdate, Vec<ModelCallback>>,
+
+    /// The files that the trees were read from, by their first tree.
+    /// Trees that were downloaded have no file.
+    files: Vec<(usize, Option<Rc<TreebankFile>>)>,
+
+    /// The styles of the trees in the files, from their style comments.
+    styles: RefCell<StyleCache>,
+
+    /// Progress of downloading the treebank.
+    download: Option<Download>,
+
+    /// The last error of opening or reading the treebank.
+    read_error: Option<String>,
+
+    /// The last problem that did not stop reading the treebank.
+    read_warning: Option<String>,
+
+    /// Select the last tree when trees are added.
+    follow: bool,
+
+    /// The tree to select as soon as it is read.
+    start: Option<usize>,
+
+    /// Only browse trees with lengths in this range.
+    length_filter: Option<LengthRange>,
+
+    /// The interval at which the slideshow advances, `None` when there
+    /// is no slideshow.
+    slideshow: Option<Duration>,
+
+    /// Trees that are marked for review.
+    marked: BTreeSet<usize>,
+
+    /// Free-text notes of trees, e.g. for adjudication.
+    notes: BTreeMap<usize, String>,
+
+    /// Trees that were removed. They are skipped while browsing and
+    /// searching, and left out when the treebank is saved.
+    removed: BTreeSet<usize>,
+
+    /// The removed trees by removal, for undoing the last removal.
+    removals: Vec<Vec<usize>>,
+
+    /// The previous versions of edited trees by edit, for undoing the
+    /// last edit. Edits that are grouped, such as the edits of a macro,
+    /// are undone together.
+    edits: Vec<Vec<(usize, Arc<DependencyGraph>)>>,
+    group_edits: bool,
+
+    /// Number of trees that a page jump moves.
+    page_stride: usize,
+
+    /// Number of most recent trees that are kept, `None` to keep all
+    /// trees.
+    capacity: Option<usize>,
+
+    /// Rules that flag the trees that are added.
+    alerts: Vec<Alert>,
+
+    /// The alerts that flag trees, by tree.
+    alerted: BTreeMap<usize, Vec<usize>>,
+
+    /// The number of trees that each alert flagged, including trees that
+    /// were dropped.
+    alert_counts: Vec<usize>,
+
+    /// The (tree, alert) pairs of the trees that were flagged when trees
+    /// were last added.
+    new_alerts: Vec<(usize, usize)>,
+}
+
+impl Default for StatefulTreebankModel {
+    fn default() -> Self {
+        StatefulTreebankModel::new()
+    }
+}
+
+impl StatefulTreebankModel {
+    /// Create a model without trees. Trees are added with `push` and
+    /// `extend`, or by `loader::TreebankOpener`.
+    pub fn new() -> Self {
+        StatefulTreebankModel::with_model(TreebankModel::new())
+    }
+
+    /// Create a model with the given trees.
+    #[allow(dead_code)]
+    pub fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = DependencyGraph>,
+    {
+        StatefulTreebankModel::with_model(TreebankModel::from_iter(iter))
+    }
+
+    fn with_model(inner: TreebankModel) -> Self {
+        StatefulTreebankModel {
+            inner,
+            idx: 0,
+            token: None,
+            path_start: None,
+            collapsed: Vec::new(),
+            quiz: None,
+            originals: BTreeMap::new(),
+            show_original: false,
+            show_morphology: false,
+            revision: None,
+            show_revision: false,
+            matches: Vec::new(),
+            current_match: None,
+            ranking: Ranking::Document,
+            index: None,
+            callbacks: EnumMap::new(),
+            files: Vec::new(),
+            download: None,
+            read_error: None,
+            read_warning: None,
+            styles: RefCell::new(StyleCache::new()),
+            follow: false,
+            start: None,
+            length_filter: None,
+            slideshow: None,
+            marked: BTreeSet::new(),
+            notes: BTreeMap::new(),
+            removed: BTreeSet::new(),
+            removals: Vec::new(),
+            edits: Vec::new(),
+            group_edits: false,
+            page_stride: DEFAULT_PAGE_STRIDE,
+            capacity: None,
+            alerts: Vec::new(),
+            alerted: BTreeMap::new(),
+            alert_counts: Vec::new(),
+            new_alerts: Vec::new(),
+        }
+    }
+
+    fn callbacks(&mut self, update: ModelUpdate) {
+        for callback in &self.callbacks[update] {
+            (*callback)(self)
+        }
+
+        for callback in &self.callbacks[ModelUpdate::Any] {
+            (*callback)(self)
+        }
+    }
+
+    /// Call `callback` with the model after every change of the kind
+    /// `update`. Callbacks cannot change the model, since it is borrowed
+    /// while they are called.
+    pub fn connect_update<F>(&mut self, update: ModelUpdate, callback: F)
+    where
+        F: 'static + Fn(&StatefulTreebankModel),
+    {
+        self.callbacks[update].push(Box::new(callback));
+    }
+
+    /// Iterate over the trees that were edited in this session, with
+    /// their original and current versions.
+    pub fn edited<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (usize, &'a Sentence, Arc<DependencyGraph>)> + 'a {
+        self.originals
+            .iter()
+            .filter_map(move |(&idx, original)| Some((idx, original, self.inner.graph(idx)?)))
+    }
+
+    /// Return the progress of downloading the treebank, `None` when the
+    /// treebank is not being downloaded.
+    pub fn download(&self) -> Option<&Download> {
+        self.download.as_ref()
+    }
+
+    /// Get the last error of opening or reading the treebank.
+    pub fn read_error(&self) -> Option<&str> {
+        self.read_error.as_deref()
+    }
+
+    /// Get the last problem that did not stop reading the treebank.
+    pub fn read_warning(&self) -> Option<&str> {
+        self.read_warning.as_deref()
+    }
+
+    /// Take the last error of reading a tree of a lazily-loaded treebank.
+    pub fn take_tree_error(&self) -> Option<String> {
+        self.inner.take_tree_error()
+    }
+
+    /// Get the file that a tree was read from, with the index of the
+    /// tree in that file. Returns `None` for treebanks that were read
+    /// from the standard input or downloaded.
+    pub fn file(&self, idx: usize) -> Option<(&Rc<TreebankFile>, usize)> {
+        let pos = match self.files.binary_search_by_key(&idx, |&(first, _)| first) {
+            Ok(pos) => pos,
+            Err(0) => return None,
+            Err(pos) => pos - 1,
+        };
+
+        let (first, ref file) = self.files[pos];
+        Some((file.as_ref()?, idx - first))
+    }
+
+    /// Get the style of the current tree, from the style comments in its
+    /// treebank file. Trees that were not read from a file have the
+    /// default style.
+    pub fn tree_style(&self) -> Result<TreeStyle, ViewerError> {
+        match self.file(self.idx) {
+            Some((file, idx)) => self.styles.borrow_mut().style(&file.path, idx),
+            None => Ok(TreeStyle::default()),
+        }
+    }
+
+    /// Get the files that the trees were read from, with their trees.
+    /// Trees that were downloaded have no file.
+    pub fn files(&self) -> Vec<(Option<&Rc<TreebankFile>>, Range<usize>)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(pos, &(first, ref file))| {
+                let end = self
+                    .files
+                    .get(pos + 1)
+                    .map(|&(next, _)| next)
+                    .unwrap_or_else(|| self.len());
+                (file.as_ref(), first..end)
+            })
+            .collect()
+    }
+
+    fn edit_token<F>(&mut self, token: usize, edit: F) -> Result<(), ViewerError>
+    where
+        F: FnOnce(&mut Token),
+    {
+        let idx = self.idx;
+        let graph = self.inner.graph(idx).ok_or(ViewerError::NoGraphSelected)?;
+
+        self.originals
+            .entry(idx)
+            .or_insert_with(|| graph.to_sentence());
+
+        if !self.group_edits || self.edits.is_empty() {
+            self.edits.push(Vec::new());
+        }
+        if let Some(edit) = self.edits.last_mut() {
+            edit.push((idx, graph.clone()));
+        }
+
+        let mut graph = (*graph).clone();
+        graph.edit_token(token, edit);
+        self.inner.replace(idx, graph);
+
+        self.callbacks(ModelUpdate::TreeEdit);
+
+        Ok(())
+    }
+
+    /// Return the current dependency graph. Returns `None` when the
+    /// treebank is currently empty.
+    pub fn graph(&self) -> Option<Arc<DependencyGraph>> {
+        self.inner.graph(self.idx)
+    }
+
+    /// Return the tree that is shown: the version of the current tree in
+    /// the revision when the revision is shown, and otherwise the current
+    /// dependency graph.
+    pub fn shown_graph(&self) -> Option<Arc<DependencyGraph>> {
+        match self.revision().filter(|_| self.show_revision) {
+            Some(sentence) => Some(Arc::new(DependencyGraph::from(sentence.clone()))),
+            None => self.graph(),
+        }
+    }
+
+    /// Render options of the current tree: `options` with the selected
+    /// token, the dependency path, the quiz answers and the versions that
+    /// the tree is compared with.
+    pub fn render_options(&self, options: &RenderOptions) -> RenderOptions {
+        let mut options = options.clone();
+        options.selection = self.selected_token();
+        options.path = self.dependency_path();
+        options.quiz = self.quiz().map(|quiz| quiz.answers(self.idx));
+        if self.show_original {
+            options.original = self.original().or_else(|| self.revision()).cloned();
+        }
+        if self.show_morphology {
+            options.morphology = self.original().or_else(|| self.revision()).cloned();
+        }
+
+        options
+    }
+
+    /// Return the content hash of the tree at an index.
+    pub fn content_hash(&self, idx: usize) -> Option<ContentHash> {
+        Some(self.inner.graph(idx)?.content_hash())
+    }
+
+    /// Return the dependency graph at an index.
+    pub fn graph_at(&self, idx: usize) -> Option<Arc<DependencyGraph>> {
+        self.inner.graph(idx)
+    }
+
+    /// Guess the annotation of the selected token in the current quiz.
+    /// If no token is selected, the guess is for the first token that
+    /// was not answered yet. Afterwards, the next unanswered token is
+    /// selected.
+    ///
+    /// Returns whether the guess was correct, `None` if there is no quiz
+    /// or the token was already answered.
+    pub fn guess(&mut self, guess: &str) -> Option<bool> {
+        let idx = self.idx;
+        let correct = {
+            let graph = self.inner.graph(idx)?;
+            let quiz = self.quiz.as_mut()?;
+            let token = match self.token {
+                Some(token) => token,
+                None => quiz.next_hidden(idx, &graph, None)?,
+            };
+
+            self.token = Some(token);
+            quiz.guess(idx, token, &graph.0[NodeIndex::new(token)].token, guess)
+        };
+
+        self.select_next_hidden();
+
+        correct
+    }
+
+    /// Return the originally-loaded version of the current tree, `None`
+    /// if the tree was not edited.
+    pub fn original(&self) -> Option<&Sentence> {
+        self.originals.get(&self.idx)
+    }
+
+    /// Check whether a tree differs from its version in the revision.
+    /// Returns `None` when no revision is set.
+    pub fn is_changed_since_revision(&self, idx: usize) -> Option<bool> {
+        let revision = self.revision.as_ref()?;
+        let graph = self.inner.graph(idx)?;
+
+        Some(match revision.get(idx) {
+            Some(sentence) => sentence.content_hash() != graph.content_hash(),
+            None => true,
+        })
+    }
+
+    /// Check whether a tree differs from the version that was loaded.
+    /// Trees that were edited back to their loaded version are not
+    /// changed.
+    pub fn is_edited(&self, idx: usize) -> bool {
+        match (self.originals.get(&idx), self.inner.graph(idx)) {
+            (Some(original), Some(graph)) => original.content_hash() != graph.content_hash(),
+            _ => false,
+        }
+    }
+
+    /// Return the number of trees that differ from their version in the
+    /// revision. Returns `None` when no revision is set.
+    pub fn n_changed_since_revision(&self) -> Option<usize> {
+        self.revision.as_ref()?;
+
+        Some(
+            (0..self.len())
+                .filter(|&idx| self.is_changed_since_revision(idx) == Some(true))
+                .count(),
+        )
+    }
+
+    /// The index of the current tree.
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Whether a tree is marked for review.
+    pub fn is_marked(&self, idx: usize) -> bool {
+        self.marked.contains(&idx)
+    }
+
+    pub fn has_note(&self, idx: usize) -> bool {
+        self.notes.contains_key(&idx)
+    }
+
+    /// Whether a tree was removed. Removed trees keep their index until
+    /// the treebank is saved.
+    pub fn is_removed(&self, idx: usize) -> bool {
+        self.removed.contains(&idx)
+    }
+
+    /// Whether a tree is browsed: it is not removed and its length is in
+    /// the range of the length filter.
+    fn is_browsed(&self, idx: usize) -> bool {
+        !self.is_removed(idx) && self.in_length_range(idx)
+    }
+
+    fn in_length_range(&self, idx: usize) -> bool {
+        match self.length_filter {
+            Some(range) => self
+                .inner
+                .n_tokens(idx)
+                .is_some_and(|len| range.contains(len)),
+            None => true,
+        }
+    }
+
+    /// The number of trees, including removed trees.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Select the next match. In document order, this is the first
+    /// match after the selected token, wrapping around to the start of
+    /// the treebank. Otherwise, it is the match after the selected match.
+    pub fn next_match(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        let next = if self.ranking == Ranking::Document {
+            let position = Match {
+                sentence: self.idx,
+                token: self.token.unwrap_or(0),
+            };
+            match self.token {
+                Some(_) => self.matches.iter().position(|&m| m > position),
+                None => self.matches.iter().position(|&m| m >= position),
+            }
+        } else {
+            self.current_match.map(|current| current + 1)
+        };
+
+        self.select_match(next.filter(|&next| next < self.matches.len()).unwrap_or(0));
+    }
+
+    /// Select the next token of the current graph. Selects the first
+    /// token when no token is selected.
+    pub fn next_token(&mut self) {
+        let token = self.token.map(|token| token + 1).unwrap_or(0);
+        self.select_token(Some(token));
+    }
+
+    /// Select the previous token of the current graph. Selects the last
+    /// token when no token is selected.
+    pub fn previous_token(&mut self) {
+        let n_tokens = ok_or!(self.graph(), return).0.node_count();
+        let token = match self.token {
+            Some(0) => 0,
+            Some(token) => token - 1,
+            None => n_tokens.saturating_sub(1),
+        };
+        self.select_token(Some(token));
+    }
+
+    /// The quiz of the treebank, `None` when the treebank is browsed
+    /// without a quiz.
+    pub fn quiz(&self) -> Option<&Quiz> {
+        self.quiz.as_ref()
+    }
+
+    /// Reveal the annotation of the selected token in the current quiz.
+    pub fn reveal(&mut self) {
+        let idx = self.idx;
+        let token = ok_or!(self.token, return);
+        ok_or!(self.quiz.as_mut(), return).reveal(idx, token);
+
+        self.select_next_hidden();
+    }
+
+    /// Add a tree. The first tree that is added becomes the current
+    /// tree.
+    pub fn push(&mut self, graph: DependencyGraph) {
+        let first = self.is_empty();
+
+        self.new_alerts.clear();
+        let idx = self.len();
+        self.flag(idx, &graph);
+        self.inner.push(graph);
+        let trimmed = self.trim();
+
+        self.callbacks(ModelUpdate::TreebankLen);
+
+        if !self.select_start() && (first || trimmed) {
+            self.callbacks(ModelUpdate::TreeSelection);
+        }
+
+        if !self.new_alerts.is_empty() {
+            self.callbacks(ModelUpdate::Alert);
+        }
+    }
+
+    /// Replace the treebank, for instance when another file is opened.
+    /// The state of the previous treebank is cleared and the quiz is
+    /// restarted.
+    pub fn reset(&mut self, inner: TreebankModel) {
+        let callbacks = mem::replace(&mut self.callbacks, EnumMap::new());
+        let quiz = self.quiz.as_ref().map(|quiz| Quiz::new(quiz.mode()));
+        let follow = self.follow;
+        let length_filter = self.length_filter;
+        let slideshow = self.slideshow;
+        let capacity = self.capacity;
+        let alerts = mem::take(&mut self.alerts);
+
+        *self = StatefulTreebankModel::with_model(inner);
+        self.callbacks = callbacks;
+        self.quiz = quiz;
+        self.follow = follow;
+        self.length_filter = length_filter;
+        self.slideshow = slideshow;
+        self.capacity = capacity;
+        self.set_alerts(alerts);
+
+        self.callbacks(ModelUpdate::TreebankLen);
+        self.callbacks(ModelUpdate::TreeSelection);
+        self.callbacks(ModelUpdate::Search);
+        self.callbacks(ModelUpdate::Quiz);
+    }
+
+    /// Mark trees for review, or clear their marks when all of them are
+    /// marked already.
+    pub fn toggle_marks(&mut self, indices: &[usize]) {
+        if indices.iter().all(|idx| self.marked.contains(idx)) {
+            for idx in indices {
+                self.marked.remove(idx);
+            }
+        } else {
+            self.marked.extend(indices.iter().cloned());
+        }
+
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    pub fn has_notes(&self) -> bool {
+        !self.notes.is_empty()
+    }
+
+    /// Get the note of a tree.
+    pub fn note(&self, idx: usize) -> Option<&str> {
+        self.notes.get(&idx).map(String::as_str)
+    }
+
+    /// Set the note of a tree. An empty note removes the note.
+    pub fn set_note(&mut self, idx: usize, note: &str) {
+        if note.trim().is_empty() {
+            self.notes.remove(&idx);
+        } else {
+            self.notes.insert(idx, note.to_owned());
+        }
+
+        self.callbacks(ModelUpdate::Notes);
+    }
+
+    /// Get the notes of the trees in a range, by their index in the
+    /// range.
+    pub fn range_notes(&self, trees: Range<usize>) -> SentenceNotes {
+        let first = trees.start;
+        self.notes
+            .range(trees)
+            .map(|(&idx, note)| (idx - first, note.clone()))
+            .collect()
+    }
+
+    /// Add the notes of the file that trees are read from, by the index
+    /// of the tree in the file.
+    pub fn push_notes(&mut self, notes: SentenceNotes) {
+        let first = self.files.last().map(|&(first, _)| first).unwrap_or(0);
+        self.notes
+            .extend(notes.into_iter().map(|(idx, note)| (first + idx, note)));
+
+        self.callbacks(ModelUpdate::Notes);
+    }
+
+    /// Remove trees from the treebank. The trees are kept in the trash
+    /// until the session ends, so that they can be restored. When the
+    /// current tree is removed, the next tree that is kept is selected.
+    pub fn remove(&mut self, indices: &[usize]) {
+        let removal: Vec<_> = indices
+            .iter()
+            .cloned()
+            .filter(|&idx| idx < self.len() && !self.is_removed(idx))
+            .collect();
+        if removal.is_empty() {
+            return;
+        }
+
+        self.removed.extend(removal.iter().cloned());
+        self.removals.push(removal);
+
+        let removed = &self.removed;
+        self.matches.retain(|m| !removed.contains(&m.sentence));
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+
+        if self.is_removed(self.idx) {
+            let next = (self.idx..self.len())
+                .chain((0..self.idx).rev())
+                .find(|&idx| !self.is_removed(idx));
+            self.set_idx(next.unwrap_or(self.len()));
+        } else {
+            self.callbacks(ModelUpdate::TreeSelection);
+        }
+    }
+
+    /// Return the removed trees, the most recently removed first.
+    pub fn removed(&self) -> Vec<usize> {
+        self.removals
+            .iter()
+            .rev()
+            .flat_map(|removal| removal.iter().cloned())
+            .collect()
+    }
+
+    /// Group the edits until `end_edits`, so that they are undone
+    /// together.
+    pub fn begin_edits(&mut self) {
+        self.edits.push(Vec::new());
+        self.group_edits = true;
+    }
+
+    /// Stop grouping the edits of `begin_edits`.
+    pub fn end_edits(&mut self) {
+        self.group_edits = false;
+        if self.edits.last().is_some_and(Vec::is_empty) {
+            self.edits.pop();
+        }
+    }
+
+    /// Undo the last edit, or the last group of edits, and select the
+    /// first tree that was changed back.
+    pub fn undo_edit(&mut self) {
+        let edit = ok_or!(self.edits.pop(), return);
+        let first = edit.iter().map(|&(idx, _)| idx).min();
+
+        for (idx, graph) in edit.into_iter().rev() {
+            self.inner.replace(idx, (*graph).clone());
+        }
+
+        match first {
+            Some(first) if first != self.idx => self.set_idx(first),
+            _ => self.callbacks(ModelUpdate::TreeEdit),
+        }
+    }
+
+    /// Restore removed trees from the trash.
+    pub fn restore(&mut self, indices: &[usize]) {
+        for idx in indices {
+            self.removed.remove(idx);
+        }
+
+        let removed = &self.removed;
+        for removal in &mut self.removals {
+            removal.retain(|idx| removed.contains(idx));
+        }
+        self.removals.retain(|removal| !removal.is_empty());
+
+        self.callbacks(ModelUpdate::TreeSelection);
+    }
+
+    /// Restore the trees of the last removal and select the first of
+    /// them.
+    pub fn undo_removal(&mut self) {
+        let removal = ok_or!(self.removals.last().cloned(), return);
+        self.restore(&removal);
+
+        if let Some(&first) = removal.iter().min() {
+            self.set_idx(first);
+        }
+    }
+
+    /// Add several trees, notifying the listeners once.
+    pub fn extend<I>(&mut self, graphs: I)
+    where
+        I: IntoIterator<Item = DependencyGraph>,
+    {
+        let first = self.is_empty();
+
+        self.new_alerts.clear();
+        for graph in graphs {
+            let idx = self.len();
+            self.flag(idx, &graph);
+            self.inner.push(graph);
+        }
+        let trimmed = self.trim();
+
+        self.callbacks(ModelUpdate::TreebankLen);
+
+        if !self.select_start() {
+            if self.follow {
+                self.seek(Seek::Last);
+            } else if !self.is_empty() && !self.in_length_range(self.idx) {
+                // The filter was set before matching trees were read.
+                self.seek(Seek::First);
+            } else if (first || trimmed) && !self.is_empty() {
+                self.callbacks(ModelUpdate::TreeSelection);
+            }
+        }
+
+        if !self.new_alerts.is_empty() {
+            self.callbacks(ModelUpdate::Alert);
+        }
+    }
+
+    /// Return the alerts that flag trees.
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    /// Return the number of trees that each alert flagged, including
+    /// trees that were dropped from the treebank.
+    pub fn alert_counts(&self) -> &[usize] {
+        &self.alert_counts
+    }
+
+    /// Return the indices of the alerts that flag a tree.
+    pub fn tree_alerts(&self, idx: usize) -> &[usize] {
+        self.alerted.get(&idx).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Return the (tree, alert) pairs of the trees that were flagged
+    /// when trees were last added.
+    pub fn new_alerts(&self) -> &[(usize, usize)] {
+        &self.new_alerts
+    }
+
+    /// Flag trees with alerts. The trees of the treebank are flagged
+    /// immediately, trees that are added later when they are added.
+    pub fn set_alerts(&mut self, alerts: Vec<Alert>) {
+        self.alert_counts = vec![0; alerts.len()];
+        self.alerts = alerts;
+        self.alerted.clear();
+
+        for idx in 0..self.len() {
+            let graph = ok_or!(self.inner.graph(idx), continue);
+            self.flag(idx, &graph);
+        }
+        self.new_alerts.clear();
+
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    fn flag(&mut self, idx: usize, graph: &DependencyGraph) {
+        let flagged: Vec<_> = self
+            .alerts
+            .iter()
+            .enumerate()
+            .filter(|(_, alert)| alert.matches(graph))
+            .map(|(alert, _)| alert)
+            .collect();
+        if flagged.is_empty() {
+            return;
+        }
+
+        for &alert in &flagged {
+            self.alert_counts[alert] += 1;
+            self.new_alerts.push((idx, alert));
+        }
+        self.alerted.insert(idx, flagged);
+    }
+
+    /// The number of most recent trees that are kept, `None` when all
+    /// trees are kept.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Keep only the most recent trees, like a ring buffer, so that a
+    /// treebank that is read indefinitely does not exhaust memory. The
+    /// oldest trees are dropped when trees are added. Lazily-loaded
+    /// treebanks are not trimmed.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity.map(|capacity| capacity.max(1));
+
+        if self.trim() {
+            self.callbacks(ModelUpdate::TreebankLen);
+            self.callbacks(ModelUpdate::TreeSelection);
+        }
+    }
+
+    /// Drop the oldest trees that exceed the capacity. The indices of the
+    /// state of the remaining trees are shifted. Returns whether trees
+    /// were dropped.
+    fn trim(&mut self) -> bool {
+        let n = match self.capacity {
+            Some(capacity) if self.len() > capacity && !self.inner.is_lazy() => {
+                self.len() - capacity
+            }
+            _ => return false,
+        };
+
+        self.inner.remove_front(n);
+
+        let shift = |idx: usize| idx.checked_sub(n);
+        if self.idx < n {
+            self.token = None;
+        }
+        self.idx = self.idx.saturating_sub(n);
+
+        self.originals = mem::take(&mut self.originals)
+            .into_iter()
+            .filter_map(|(idx, sentence)| Some((shift(idx)?, sentence)))
+            .collect();
+        self.marked = self.marked.iter().filter_map(|&idx| shift(idx)).collect();
+        self.notes = mem::take(&mut self.notes)
+            .into_iter()
+            .filter_map(|(idx, note)| Some((shift(idx)?, note)))
+            .collect();
+        self.alerted = mem::take(&mut self.alerted)
+            .into_iter()
+            .filter_map(|(idx, alerts)| Some((shift(idx)?, alerts)))
+            .collect();
+        self.new_alerts = self
+            .new_alerts
+            .iter()
+            .filter_map(|&(idx, alert)| Some((shift(idx)?, alert)))
+            .collect();
+        self.removed = self.removed.iter().filter_map(|&idx| shift(idx)).collect();
+        for removal in &mut self.removals {
+            *removal = removal.iter().filter_map(|&idx| shift(idx)).collect();
+        }
+        self.removals.retain(|removal| !removal.is_empty());
+        for edit in &mut self.edits {
+            *edit = edit
+                .drain(..)
+                .filter_map(|(idx, graph)| Some((shift(idx)?, graph)))
+                .collect();
+        }
+        self.edits.retain(|edit| !edit.is_empty());
+
+        if let Some(ref mut revision) = self.revision {
+            let n = n.min(revision.len());
+            revision.drain(..n);
+        }
+        if let Some(ref mut quiz) = self.quiz {
+            quiz.remove_front(n);
+        }
+
+        // A file starts at the first remaining tree when its first trees
+        // were dropped.
+        let firsts: Vec<_> = self.files.iter().map(|&(first, _)| first).collect();
+        let files = mem::take(&mut self.files);
+        self.files = files
+            .into_iter()
+            .enumerate()
+            .filter(|&(pos, _)| firsts.get(pos + 1).is_none_or(|&next| next > n))
+            .map(|(_, (first, file))| (first.saturating_sub(n), file))
+            .collect();
+
+        // The index refers to the dropped trees.
+        self.index = None;
+
+        let had_matches = !self.matches.is_empty();
+        self.matches = self
+            .matches
+            .iter()
+            .filter_map(|m| {
+                Some(Match {
+                    sentence: shift(m.sentence)?,
+                    token: m.token,
+                })
+            })
+            .collect();
+        self.current_match = None;
+        if had_matches {
+            self.callbacks(ModelUpdate::Search);
+        }
+
+        true
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    /// Follow the treebank as it is read, selecting the newest tree
+    /// whenever trees are added.
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+
+        if follow {
+            self.seek(Seek::Last);
+        }
+    }
+
+    /// Select the first tree with the largest value of a summary metric,
+    /// such as the longest tree. Returns whether a tree was selected.
+    pub fn seek_max<F>(&mut self, metric: F) -> bool
+    where
+        F: Fn(&SentenceSummary) -> usize,
+    {
+        // The maximum of the reversed trees is the first tree on ties.
+        let target = (0..self.len())
+            .rev()
+            .filter(|&idx| self.is_browsed(idx))
+            .filter_map(|idx| Some((idx, metric(&self.inner.summary(idx)?))))
+            .max_by_key(|&(_, value)| value);
+
+        match target {
+            Some((idx, _)) => {
+                self.set_idx(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Select the next tree with the relation `rel`. Returns whether
+    /// there is such a tree.
+    pub fn seek_relation(&mut self, rel: &str) -> bool {
+        let target = (self.idx + 1..self.len())
+            .filter(|&idx| self.is_browsed(idx))
+            .find(|&idx| {
+                self.inner
+                    .summary(idx)
+                    .is_some_and(|summary| summary.has_relation(rel))
+            });
+
+        match target {
+            Some(idx) => {
+                self.set_idx(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn length_filter(&self) -> Option<LengthRange> {
+        self.length_filter
+    }
+
+    /// Only browse the trees with lengths in `range`, `None` browses all
+    /// trees. When the current tree is not in the range, the next tree in
+    /// the range is selected.
+    pub fn set_length_filter(&mut self, range: Option<LengthRange>) {
+        self.length_filter = range;
+
+        let moved = !self.is_empty()
+            && !self.in_length_range(self.idx)
+            && (self.seek(Seek::Relative(1)) || self.seek(Seek::Relative(-1)));
+        if !moved {
+            self.callbacks(ModelUpdate::TreeSelection);
+        }
+    }
+
+    /// Select the tree `idx`, or otherwise as soon as it is read.
+    pub fn set_start(&mut self, idx: usize) {
+        self.start = Some(idx);
+        self.select_start();
+    }
+
+    /// Select the start tree once it is read. Returns whether it was
+    /// selected.
+    fn select_start(&mut self) -> bool {
+        match self.start {
+            Some(start) if start < self.len() => {
+                self.start = None;
+                self.set_idx(start);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn slideshow(&self) -> Option<Duration> {
+        self.slideshow
+    }
+
+    /// Advance to the next tree every `interval`, `None` stops the
+    /// slideshow. The viewer does the advancing.
+    pub fn set_slideshow(&mut self, interval: Option<Duration>) {
+        self.slideshow = interval;
+    }
+
+    /// Start a file of the treebank. The trees that are added afterwards
+    /// belong to this file, `None` for trees that are downloaded.
+    pub fn push_file(&mut self, file: Option<Rc<TreebankFile>>) {
+        let first = self.len();
+
+        // A file without trees is replaced by the next file.
+        if self.files.last().is_some_and(|&(last, _)| last == first) {
+            self.files.pop();
+        }
+
+        self.files.push((first, file));
+    }
+
+    /// Add sentences of a lazily-loaded treebank by their offsets.
+    pub fn push_offsets(&mut self, offsets: &[SentenceOffset]) {
+        let first = self.is_empty();
+
+        self.inner.push_offsets(offsets);
+
+        self.callbacks(ModelUpdate::TreebankLen);
+
+        if first && !self.is_empty() {
+            self.callbacks(ModelUpdate::TreeSelection);
+        }
+    }
+
+    /// Report an error of opening or reading the treebank.
+    pub fn push_read_error(&mut self, error: String) {
+        self.read_error = Some(error);
+        self.callbacks(ModelUpdate::ReadError);
+    }
+
+    /// Report a problem that does not stop reading the treebank.
+    pub fn push_read_warning(&mut self, warning: String) {
+        self.read_warning = Some(warning);
+        self.callbacks(ModelUpdate::ReadWarning);
+    }
+
+    /// Update the progress of downloading the treebank.
+    pub fn set_download(&mut self, download: Option<Download>) {
+        self.download = download;
+        self.callbacks(ModelUpdate::Download);
+    }
+
+    /// Attach the selected token to the token with the given (1-based)
+    /// position, `0` attaches the token to the root.
+    pub fn set_head(&mut self, head: usize) -> Result<(), ViewerError> {
+        let token = self.token.ok_or(ViewerError::NoTokenSelected)?;
+        let n_tokens = self
+            .graph()
+            .ok_or(ViewerError::NoGraphSelected)?
+            .0
+            .node_count();
+
+        if head > n_tokens || head == token + 1 {
+            return Err(ViewerError::InvalidHead(head));
+        }
+
+        self.edit_token(token, |token| token.set_head(Some(head)))
+    }
+
+    /// Change the dependency relation of the selected token.
+    pub fn set_relation(&mut self, rel: &str) -> Result<(), ViewerError> {
+        let token = self.token.ok_or(ViewerError::NoTokenSelected)?;
+
+        if rel.is_empty() {
+            return Err(ViewerError::EmptyRelation);
+        }
+
+        self.edit_token(token, |token| token.set_head_rel(Some(rel)))
+    }
+
+    /// Change the lemma of the selected token. An empty lemma clears
+    /// the lemma.
+    pub fn set_lemma(&mut self, lemma: &str) -> Result<(), ViewerError> {
+        let token = self.token.ok_or(ViewerError::NoTokenSelected)?;
+        self.edit_token(token, |token| token.set_lemma(non_empty(lemma)))
+    }
+
+    /// Change the universal part-of-speech tag of the selected token.
+    pub fn set_upos(&mut self, upos: &str) -> Result<(), ViewerError> {
+        let token = self.token.ok_or(ViewerError::NoTokenSelected)?;
+        self.edit_token(token, |token| token.set_cpos(non_empty(upos)))
+    }
+
+    /// Change the language-specific part-of-speech tag of the selected
+    /// token.
+    pub fn set_xpos(&mut self, xpos: &str) -> Result<(), ViewerError> {
+        let token = self.token.ok_or(ViewerError::NoTokenSelected)?;
+        self.edit_token(token, |token| token.set_pos(non_empty(xpos)))
+    }
+
+    /// Show the current tree again, e.g. after the render settings
+    /// changed.
+    pub fn redraw(&mut self) {
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    /// Show the original version of edited trees as an overlay.
+    pub fn set_show_original(&mut self, show: bool) {
+        self.show_original = show;
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    pub fn show_original(&self) -> bool {
+        self.show_original
+    }
+
+    /// Mark the tokens whose lemma, part-of-speech tag or features differ
+    /// from the original version or the revision of the tree.
+    pub fn set_show_morphology(&mut self, show: bool) {
+        self.show_morphology = show;
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    pub fn show_morphology(&self) -> bool {
+        self.show_morphology
+    }
+
+    /// Return the version of the current tree in the revision, `None` if
+    /// no revision is set or the tree is not in the revision.
+    pub fn revision(&self) -> Option<&Sentence> {
+        self.revision.as_ref()?.get(self.idx)
+    }
+
+    /// Set a previous version of the treebank, such as the version that
+    /// was last committed, to compare the trees against.
+    pub fn set_revision(&mut self, revision: Option<Vec<Sentence>>) {
+        self.revision = revision;
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    /// Show the version of the current tree in the revision instead of
+    /// the tree itself.
+    pub fn set_show_revision(&mut self, show: bool) {
+        self.show_revision = show;
+        self.callbacks(ModelUpdate::TreeEdit);
+    }
+
+    pub fn show_revision(&self) -> bool {
+        self.show_revision
+    }
+
+    /// Return the sentences of all trees.
+    pub fn sentences(&self) -> Vec<Sentence> {
+        let storage = self.inner.storage();
+        (0..storage.len())
+            .filter_map(|idx| storage.sentence(idx))
+            .collect()
+    }
+
+    /// Use a search index. Only the sentences that the index reports as
+    /// candidates are searched, besides the trees that were edited or
+    /// that are not indexed.
+    pub fn set_index(&mut self, index: Option<SearchIndex>) {
+        self.index = index;
+    }
+
+    /// Search all trees for tokens that match a query.
+    pub fn search(&mut self, query: &Query, options: MatchOptions) {
+        let candidates = self.index.as_ref().and_then(|index| {
+            let mut candidates = index.candidates(query, options)?;
+            candidates.extend(self.originals.keys().cloned());
+            candidates.extend(index.len()..self.inner.len());
+            candidates.sort();
+            candidates.dedup();
+            Some(candidates)
+        });
+        let mut sentences = candidates.unwrap_or_else(|| (0..self.inner.len()).collect());
+        sentences.retain(|idx| !self.removed.contains(idx));
+
+        // Trees are searched in parallel, the matches of each tree are
+        // concatenated in treebank order.
+        let query = query.prepare(options);
+        let storage = self.inner.storage();
+        let matches: Vec<Vec<Match>> = sentences
+            .into_par_iter()
+            .filter_map(|sentence| Some((sentence, storage.find(sentence, &query)?)))
+            .map(|(sentence, tokens)| {
+                tokens
+                    .into_iter()
+                    .map(|token| Match { sentence, token })
+                    .collect()
+            })
+            .collect();
+        self.matches = matches.into_iter().flatten().collect();
+        rank(
+            &mut self.matches,
+            self.ranking,
+            self.inner.storage(),
+            self.revision.as_deref(),
+        );
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// Find the `n` trees that are most similar to the current tree. The
+    /// trees replace the matches of the last search, the most similar
+    /// tree first, with their roots as matching tokens.
+    pub fn find_similar(&mut self, similarity: &dyn Similarity, n: usize) {
+        let graph = ok_or!(self.graph(), return);
+        let candidates = (0..self.inner.len())
+            .filter(|&idx| idx != self.idx && !self.removed.contains(&idx))
+            .collect();
+
+        let storage = self.inner.storage();
+        self.matches = nearest(storage, candidates, &graph, similarity, n)
+            .into_iter()
+            .map(|(sentence, _)| {
+                let token = storage
+                    .graph(sentence)
+                    .and_then(|graph| {
+                        graph
+                            .0
+                            .node_indices()
+                            .position(|idx| graph.0[idx].token.head() == Some(0))
+                    })
+                    .unwrap_or(0);
+                Match { sentence, token }
+            })
+            .collect();
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// Count the subtree patterns of up to `max_depth` arcs that occur at
+    /// least `min_count` times. Removed trees are not counted.
+    pub fn subtree_patterns(&self, max_depth: usize, min_count: usize) -> Vec<SubtreePattern> {
+        mine_subtrees(self.inner.storage(), self.unremoved(), max_depth, min_count)
+    }
+
+    /// Find the tokens that head a subtree pattern. The tokens replace the
+    /// matches of the last search.
+    pub fn find_subtrees(&mut self, pattern: &SubtreePattern) {
+        self.matches = pattern_instances(self.inner.storage(), self.unremoved(), pattern);
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// Find the tokens with the same form and `window` words of context
+    /// that are annotated differently. Removed trees are not checked.
+    pub fn inconsistencies(&self, window: usize) -> Vec<Inconsistency> {
+        find_inconsistencies(self.inner.storage(), self.unremoved(), window)
+    }
+
+    /// Show the tokens of an inconsistency, or of one of its variants.
+    /// The tokens replace the matches of the last search.
+    pub fn find_inconsistency(&mut self, inconsistency: &Inconsistency, variant: Option<usize>) {
+        self.matches = match variant.and_then(|variant| inconsistency.variants.get(variant)) {
+            Some(variant) => variant.matches.clone(),
+            None => inconsistency.matches(),
+        };
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// Count the feature values by universal part-of-speech tag in the
+    /// trees that were not removed.
+    pub fn feature_table(&self) -> FeatureTable {
+        count_features(self.inner.storage(), self.unremoved())
+    }
+
+    /// Find the tokens with the feature value and tag of a cell. The
+    /// tokens replace the matches of the last search.
+    pub fn find_feature(&mut self, cell: &FeatureCell) {
+        self.matches = feature_instances(self.inner.storage(), self.unremoved(), cell);
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// The indices of the trees that were not removed.
+    fn unremoved(&self) -> Vec<usize> {
+        (0..self.inner.len())
+            .filter(|idx| !self.removed.contains(idx))
+            .collect()
+    }
+
+    /// Order the matches of the last search.
+    pub fn rank_matches(&mut self, ranking: Ranking) {
+        rank(
+            &mut self.matches,
+            ranking,
+            self.inner.storage(),
+            self.revision.as_deref(),
+        );
+        self.ranking = ranking;
+        self.current_match = None;
+
+        self.callbacks(ModelUpdate::Search);
+    }
+
+    /// Select a match of the last search by its index.
+    pub fn select_match(&mut self, idx: usize) {
+        let m = ok_or!(self.matches.get(idx).cloned(), return);
+
+        self.current_match = Some(idx);
+        if m.sentence != self.idx {
+            self.set_idx(m.sentence);
+        }
+        self.select_token(Some(m.token));
+
+        self.callbacks(ModelUpdate::MatchSelection);
+    }
+
+    /// Return the matches of the last search.
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    /// Return the index of the selected match.
+    pub fn current_match(&self) -> Option<usize> {
+        self.current_match
+    }
+
+    /// Select the tree with the given index. Indices outside the
+    /// treebank are ignored.
+    pub fn select_tree(&mut self, idx: usize) {
+        self.set_idx(idx);
+    }
+
+    /// Select a token of the current graph by its offset, or clear the
+    /// selection with `None`. Offsets outside the graph are ignored.
+    pub fn select_token(&mut self, token: Option<usize>) {
+        let n_tokens = ok_or!(self.graph(), return).0.node_count();
+
+        match token {
+            Some(token) if token >= n_tokens => return,
+            _ => self.token = token,
+        }
+
+        self.callbacks(ModelUpdate::TokenSelection);
+    }
+
+    fn select_next_hidden(&mut self) {
+        let next = match (self.inner.graph(self.idx), self.quiz.as_ref()) {
+            (Some(graph), Some(quiz)) => quiz.next_hidden(self.idx, &graph, self.token),
+            _ => None,
+        };
+
+        if next.is_some() {
+            self.token = next;
+        }
+
+        self.callbacks(ModelUpdate::Quiz);
+    }
+
+    /// The number of trees that a page jump moves.
+    pub fn page_stride(&self) -> usize {
+        self.page_stride
+    }
+
+    /// Set the number of trees that a page jump moves, at least one.
+    pub fn set_page_stride(&mut self, stride: usize) {
+        self.page_stride = stride.max(1);
+    }
+
+    /// Move to another tree. Removed trees are skipped, except when a tree
+    /// is selected by its index, and moves past the first or last tree
+    /// stop there. Returns whether another tree is selected, so that
+    /// reaching the bounds of the treebank can be signaled.
+    pub fn seek(&mut self, seek: Seek) -> bool {
+        let previous = self.idx;
+
+        let target = {
+            let kept = |idx: &usize| self.is_browsed(*idx);
+            match seek {
+                Seek::First => (0..self.len()).find(kept),
+                Seek::Last => (0..self.len()).rev().find(kept),
+                Seek::Absolute(idx) => Some(idx.min(self.len().saturating_sub(1))),
+                Seek::Relative(n) if n >= 0 => (previous + 1..self.len())
+                    .filter(kept)
+                    .take(n as usize)
+                    .last(),
+                Seek::Relative(n) => (0..previous)
+                    .rev()
+                    .filter(kept)
+                    .take(n.unsigned_abs())
+                    .last(),
+            }
+        };
+
+        self.set_idx(target.unwrap_or(previous));
+
+        self.idx != previous
+    }
+
+    /// Start a quiz on the treebank, or stop it with `None`.
+    pub fn set_quiz(&mut self, quiz: Option<Quiz>) {
+        self.quiz = quiz;
+        self.callbacks(ModelUpdate::Quiz);
+    }
+
+    /// Return the offset of the selected token of the current graph.
+    pub fn selected_token(&self) -> Option<usize> {
+        self.token
+    }
+
+    /// The token that the dependency path starts at, if any.
+    pub fn path_start(&self) -> Option<usize> {
+        self.path_start
+    }
+
+    /// Start a dependency path at a token of the current graph, or
+    /// clear the path with `None`.
+    pub fn set_path_start(&mut self, token: Option<usize>) {
+        self.path_start = token;
+        self.callbacks(ModelUpdate::TokenSelection);
+    }
+
+    /// The dependency path from the start of the path to the selected
+    /// token.
+    pub fn dependency_path(&self) -> Option<DependencyPath> {
+        match (self.path_start, self.token) {
+            (Some(start), Some(token)) if start != token => self.graph()?.path(start, token),
+            _ => None,
+        }
+    }
+
+    /// The tokens of the current tree whose subtrees are collapsed.
+    pub fn collapsed(&self) -> &[usize] {
+        &self.collapsed
+    }
+
+    pub fn is_collapsed(&self, token: usize) -> bool {
+        self.collapsed.contains(&token)
+    }
+
+    /// Collapse the subtree of a token of the current tree into a single
+    /// placeholder, or expand it when it is collapsed.
+    pub fn toggle_collapsed(&mut self, token: usize) {
+        match self
+            .collapsed
+            .iter()
+            .position(|&collapsed| collapsed == token)
+        {
+            Some(idx) => {
+                self.collapsed.remove(idx);
+            }
+            None => self.collapsed.push(token),
+        }
+
+        self.callbacks(ModelUpdate::TokenSelection);
+    }
+
+    fn set_idx(&mut self, idx: usize) {
+        if idx < self.len() {
+            self.idx = idx;
+            self.token = None;
+            self.path_start = None;
+            self.collapsed.clear();
+        }
+
+        self.callbacks(ModelUpdate::TreeSelection);
+    }
+}
+
+/// A field value, where an empty value is an absent field.
+fn non_empty(value: &str) -> Option<&str> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Default number of trees that a page jump moves.
+pub const DEFAULT_PAGE_STRIDE: usize = 10;
+
+/// Number of parsed trees that are kept around. For lazily-loaded
+/// treebanks, these are the only trees in memory.
+const GRAPH_CACHE_SIZE: usize = 16;
+
+/// How the trees of a treebank are stored.
+enum Storage {
+    Arena(SentenceArena),
+    Lazy(LazyTreebank),
+}
+
+pub struct TreebankModel {
+    storage: Storage,
+    graphs: RefCell<LruCache<usize, Arc<DependencyGraph>>>,
+
+    /// Summaries of the trees. Trees of lazily-loaded treebanks are not
+    /// summarized as they are read, but when a summary is first needed.
+    summaries: RefCell<Vec<Option<SentenceSummary>>>,
+}
+
+impl Default for TreebankModel {
+    fn default() -> Self {
+        TreebankModel::new()
+    }
+}
+
+impl TreebankModel {
+    pub fn new() -> Self {
+        TreebankModel::with_storage(Storage::Arena(SentenceArena::new()))
+    }
+
+    pub fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = DependencyGraph>,
+    {
+        let mut model = TreebankModel::new();
+        for graph in iter {
+            model.push(graph);
+        }
+
+        model
+    }
+
+    /// Create a model that reads the trees from the treebank file on
+    /// demand.
+    pub fn lazy(treebank: LazyTreebank) -> Self {
+        TreebankModel::with_storage(Storage::Lazy(treebank))
+    }
+
+    fn with_storage(storage: Storage) -> Self {
+        TreebankModel {
+            storage,
+            graphs: RefCell::new(LruCache::new(GRAPH_CACHE_SIZE)),
+            summaries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Get the tree storage, for operations on all trees.
+    pub fn storage(&self) -> &dyn TreebankStorage {
+        match self.storage {
+            Storage::Arena(ref arena) => arena,
+            Storage::Lazy(ref lazy) => lazy,
+        }
+    }
+
+    /// Get a graph. Graphs are parsed on demand, recently used graphs
+    /// are cached.
+    pub fn graph(&self, idx: usize) -> Option<Arc<DependencyGraph>> {
+        let mut graphs = self.graphs.borrow_mut();
+        if let Some(graph) = graphs.get(&idx) {
+            return Some(graph.clone());
+        }
+
+        let graph = Arc::new(self.storage().graph(idx)?);
+        graphs.insert(idx, graph.clone());
+        Some(graph)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage().is_empty()
+    }
+
+    /// Take the last error of reading a tree from the treebank file.
+    pub fn take_tree_error(&self) -> Option<String> {
+        match self.storage {
+            Storage::Arena(_) => None,
+            Storage::Lazy(ref lazy) => lazy.take_error(),
+        }
+    }
+
+    /// Whether the trees are read from the treebank file on demand.
+    pub fn is_lazy(&self) -> bool {
+        match self.storage {
+            Storage::Arena(_) => false,
+            Storage::Lazy(_) => true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage().len()
+    }
+
+    pub fn push(&mut self, graph: DependencyGraph) {
+        let idx = self.len();
+        match self.storage {
+            Storage::Arena(ref mut arena) => arena.push(&graph),
+            Storage::Lazy(ref mut lazy) => lazy.push(&graph),
+        }
+        self.set_summary(idx, SentenceSummary::new(&graph));
+    }
+
+    /// Add sentences of a lazily-loaded treebank by their offsets.
+    /// Has no effect when the treebank is not loaded lazily.
+    pub fn push_offsets(&mut self, offsets: &[SentenceOffset]) {
+        if let Storage::Lazy(ref mut lazy) = self.storage {
+            lazy.push_offsets(offsets);
+        }
+    }
+
+    /// Remove the first `n` trees. Has no effect when the treebank is
+    /// loaded lazily.
+    pub fn remove_front(&mut self, n: usize) {
+        if let Storage::Arena(ref mut arena) = self.storage {
+            arena.remove_front(n);
+            *self.graphs.borrow_mut() = LruCache::new(GRAPH_CACHE_SIZE);
+            let summaries = self.summaries.get_mut();
+            let n = n.min(summaries.len());
+            summaries.drain(..n);
+        }
+    }
+
+    /// Replace a graph, for instance after it was edited.
+    pub fn replace(&mut self, idx: usize, graph: DependencyGraph) {
+        match self.storage {
+            Storage::Arena(ref mut arena) => arena.replace(idx, &graph),
+            Storage::Lazy(ref mut lazy) => lazy.replace(idx, &graph),
+        }
+        self.set_summary(idx, SentenceSummary::new(&graph));
+        self.graphs.borrow_mut().insert(idx, Arc::new(graph));
+    }
+
+    /// Get the number of tokens of a tree. Trees of lazily-loaded
+    /// treebanks are not parsed, their offsets have the number.
+    pub fn n_tokens(&self, idx: usize) -> Option<usize> {
+        match self.summaries.borrow().get(idx) {
+            Some(Some(summary)) => Some(summary.len),
+            _ => self.storage().n_tokens(idx),
+        }
+    }
+
+    /// Get the summary of a tree. Trees that were not summarized are
+    /// parsed once, their summary is kept.
+    pub fn summary(&self, idx: usize) -> Option<SentenceSummary> {
+        if let Some(Some(summary)) = self.summaries.borrow().get(idx) {
+            return Some(summary.clone());
+        }
+
+        let summary = SentenceSummary::new(&self.storage().graph(idx)?);
+        self.set_summary(idx, summary.clone());
+        Some(summary)
+    }
+
+    fn set_summary(&self, idx: usize, summary: SentenceSummary) {
+        let mut summaries = self.summaries.borrow_mut();
+        if summaries.len() <= idx {
+            summaries.resize(idx + 1, None);
+        }
+        summaries[idx] = Some(summary);
+    }
+}
+
+impl From<Vec<DependencyGraph>> for TreebankModel {
+    fn from(vec: Vec<DependencyGraph>) -> Self {
+        TreebankModel::from_iter(vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::rc::Rc;
+
+    use conllx::{Features, Token};
+    use petgraph::graph::NodeIndex;
+
+    use super::{Seek, StatefulTreebankModel, TreebankModel};
+    use graph::{DependencyGraph, RenderOptions};
+    use input::InputFormat;
+    use lazy::{count_tokens, LazyTreebank, SentenceBlocks, SentenceOffset};
+    use loader::TreebankFile;
+    use search::Match;
+
+    fn model(len: usize) -> StatefulTreebankModel {
+        StatefulTreebankModel::from_iter(
+            (0..len).map(|idx| DependencyGraph::from(vec![Token::new(idx.to_string())])),
+        )
+    }
+
+    #[test]
+    fn seek_saturates_at_bounds() {
+        let mut model = model(3);
+
+        assert!(!model.seek(Seek::Relative(-1)));
+        assert_eq!(model.idx(), 0);
+
+        assert!(model.seek(Seek::Relative(5)));
+        assert_eq!(model.idx(), 2);
+        assert!(!model.seek(Seek::Relative(1)));
+        assert!(!model.seek(Seek::Absolute(10)));
+
+        assert!(model.seek(Seek::First));
+        assert_eq!(model.idx(), 0);
+        assert!(model.seek(Seek::Last));
+        assert_eq!(model.idx(), 2);
+    }
+
+    #[test]
+    fn seek_by_summaries() {
+        let graph = |tokens: &[(&str, usize, &str)]| {
+            DependencyGraph::from(
+                tokens
+                    .iter()
+                    .map(|&(form, head, rel)| {
+                        let mut token = Token::new(form);
+                        token.set_head(Some(head));
+                        token.set_head_rel(Some(rel));
+                        token
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let mut model = StatefulTreebankModel::from_iter(vec![
+            graph(&[("a", 0, "root")]),
+            graph(&[("b", 2, "det"), ("c", 0, "root")]),
+            graph(&[("d", 0, "root"), ("e", 1, "obj")]),
+            graph(&[("f", 2, "det"), ("g", 3, "nsubj"), ("h", 0, "root")]),
+        ]);
+
+        assert!(model.seek_max(|summary| summary.len));
+        assert_eq!(model.idx(), 3);
+        model.remove(&[3]);
+        assert!(model.seek_max(|summary| summary.depth));
+        assert_eq!(model.idx(), 1);
+
+        model.seek(Seek::First);
+        assert!(model.seek_relation("obj"));
+        assert_eq!(model.idx(), 2);
+        assert!(!model.seek_relation("det"));
+
+        // Summaries follow edits.
+        model.select_token(Some(1));
+        model.set_relation("nmod").unwrap();
+        model.seek(Seek::First);
+        assert!(!model.seek_relation("obj"));
+        assert!(model.seek_relation("nmod"));
+        assert_eq!(model.idx(), 2);
+    }
+
+    #[test]
+    fn length_filter_restricts_browsing() {
+        let tokens = |len: usize| {
+            DependencyGraph::from(
+                (0..len)
+                    .map(|idx| Token::new(idx.to_string()))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let mut model =
+            StatefulTreebankModel::from_iter(vec![tokens(3), tokens(8), tokens(1), tokens(5)]);
+        model.seek(Seek::Absolute(2));
+
+        model.set_length_filter(Some("4-8".parse().unwrap()));
+        assert_eq!(model.idx(), 3);
+        assert!(model.seek(Seek::First));
+        assert_eq!(model.idx(), 1);
+        assert!(!model.seek(Seek::Relative(-1)));
+        assert!(model.seek(Seek::Relative(1)));
+        assert_eq!(model.idx(), 3);
+
+        model.extend(vec![tokens(2), tokens(4)]);
+        assert!(model.seek(Seek::Last));
+        assert_eq!(model.idx(), 5);
+
+        model.set_length_filter(None);
+        assert!(model.seek(Seek::Relative(-1)));
+        assert_eq!(model.idx(), 4);
+    }
+
+    #[test]
+    fn length_filter_uses_offsets_of_lazy_trees() {
+        // The trees are never read, since the treebank does not exist.
+        let path = env::temp_dir().join(format!("conllx-view-lazy-length-{}.conll", process::id()));
+        let offsets: Vec<_> = [3, 8, 1, 5]
+            .iter()
+            .enumerate()
+            .map(|(idx, &n_tokens)| SentenceOffset {
+                offset: idx as u64 * 100,
+                n_tokens,
+            })
+            .collect();
+        let mut lazy = LazyTreebank::new(&path, InputFormat::Conllx);
+        lazy.push_offsets(&offsets);
+        let mut model = StatefulTreebankModel::new();
+        model.reset(TreebankModel::lazy(lazy));
+
+        model.set_length_filter(Some("4-8".parse().unwrap()));
+        assert_eq!(model.idx(), 1);
+        assert!(model.seek(Seek::Relative(1)));
+        assert_eq!(model.idx(), 3);
+        assert!(!model.seek(Seek::Relative(1)));
+    }
+
+    #[test]
+    fn summaries_of_lazy_trees_are_kept() {
+        let path = env::temp_dir().join(format!(
+            "conllx-view-lazy-summaries-{}.conll",
+            process::id()
+        ));
+        let treebank = "1\ta\t_\t_\t_\t_\t0\troot\t_\t_\n\n\
+                        1\tb\t_\t_\t_\t_\t2\tdet\t_\t_\n\
+                        2\tc\t_\t_\t_\t_\t0\troot\t_\t_\n\n\
+                        1\td\t_\t_\t_\t_\t0\troot\t_\t_\n\
+                        2\te\t_\t_\t_\t_\t1\tobj\t_\t_\n";
+        fs::write(&path, treebank).unwrap();
+
+        let offsets: Vec<_> = SentenceBlocks::new(treebank.as_bytes())
+            .map(|block| {
+                let (offset, block) = block.unwrap();
+                SentenceOffset {
+                    offset,
+                    n_tokens: count_tokens(&block, InputFormat::Conllx),
+                }
+            })
+            .collect();
+        let mut lazy = LazyTreebank::new(&path, InputFormat::Conllx);
+        lazy.push_offsets(&offsets);
+        let mut model = StatefulTreebankModel::new();
+        model.reset(TreebankModel::lazy(lazy));
+
+        assert!(model.seek_max(|summary| summary.depth));
+        assert_eq!(model.idx(), 1);
+
+        // Seeking again uses the summaries, the trees are not read.
+        fs::remove_file(&path).unwrap();
+        assert!(model.seek_max(|summary| summary.len));
+        assert_eq!(model.idx(), 1);
+        assert!(model.seek_relation("obj"));
+        assert_eq!(model.idx(), 2);
+    }
+
+    #[test]
+    fn errors_of_reading_lazy_trees_are_kept() {
+        let path = env::temp_dir().join(format!("conllx-view-lazy-error-{}.conll", process::id()));
+        let mut lazy = LazyTreebank::new(&path, InputFormat::Conllx);
+        lazy.push_offsets(&[SentenceOffset {
+            offset: 0,
+            n_tokens: 1,
+        }]);
+        let mut model = StatefulTreebankModel::new();
+        model.reset(TreebankModel::lazy(lazy));
+
+        assert!(model.graph().is_none());
+        assert!(model
+            .take_tree_error()
+            .unwrap()
+            .starts_with("Cannot read tree 1"));
+        assert_eq!(model.take_tree_error(), None);
+    }
+
+    #[test]
+    fn path_ends_at_selected_token() {
+        let tokens = [("dog", 2), ("barks", 0), ("loudly", 2)]
+            .iter()
+            .map(|&(form, head)| {
+                let mut token = Token::new(form);
+                token.set_head(Some(head));
+                token.set_head_rel(Some("dep"));
+                token
+            });
+        let mut model = StatefulTreebankModel::from_iter(vec![
+            DependencyGraph::from(tokens.collect::<Vec<_>>()),
+            DependencyGraph::from(vec![Token::new("2")]),
+        ]);
+
+        model.select_token(Some(0));
+        model.set_path_start(Some(0));
+        assert!(model.dependency_path().is_none());
+        model.select_token(Some(2));
+        assert_eq!(
+            model.dependency_path().map(|path| path.tokens),
+            Some(vec![0, 1, 2])
+        );
+
+        model.seek(Seek::Relative(1));
+        assert_eq!(model.path_start(), None);
+    }
+
+    #[test]
+    fn render_options_follow_the_selection() {
+        let mut model = model(2);
+        model.seek(Seek::First);
+        model.select_token(Some(0));
+        model.set_relation("obj").unwrap();
+
+        let options = RenderOptions {
+            root: true,
+            ..RenderOptions::default()
+        };
+        let rendered = model.render_options(&options);
+        assert!(rendered.root);
+        assert_eq!(rendered.selection, Some(0));
+        assert_eq!(rendered.original, None);
+
+        model.set_show_original(true);
+        let original = model.render_options(&options).original.unwrap();
+        assert_eq!(original[0].head_rel(), None);
+        assert_eq!(
+            model.shown_graph().unwrap().0[NodeIndex::new(0)]
+                .token
+                .head_rel(),
+            Some("obj")
+        );
+    }
+
+    #[test]
+    fn tree_styles_are_read_from_the_file() {
+        let path = env::temp_dir().join(format!("conllx-view-style-{}.conll", process::id()));
+        fs::write(
+            &path,
+            "1\ta\t_\t_\t_\t_\t0\tROOT\t_\t_\n\n\
+             # conllx-view-style: root\n\
+             1\tb\t_\t_\t_\t_\t0\tROOT\t_\t_\n",
+        )
+        .unwrap();
+
+        let mut model = StatefulTreebankModel::new();
+        model.push_file(Some(Rc::new(TreebankFile {
+            path: path.clone(),
+            format: InputFormat::Conllx,
+            compression: None,
+            git: None,
+            review_url: None,
+            strict: false,
+            note_comments: false,
+        })));
+        model.extend(vec![
+            DependencyGraph::from(vec![Token::new("a")]),
+            DependencyGraph::from(vec![Token::new("b")]),
+        ]);
+
+        model.seek(Seek::First);
+        assert_eq!(model.tree_style().unwrap().root, None);
+        model.seek(Seek::Last);
+        assert_eq!(model.tree_style().unwrap().root, Some(true));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn start_tree_is_selected_when_read() {
+        let mut model = model(2);
+        model.set_start(3);
+        assert_eq!(model.idx(), 0);
+
+        model.push(DependencyGraph::from(vec![Token::new("2")]));
+        assert_eq!(model.idx(), 0);
+        model.extend(vec![
+            DependencyGraph::from(vec![Token::new("3")]),
+            DependencyGraph::from(vec![Token::new("4")]),
+        ]);
+        assert_eq!(model.idx(), 3);
+
+        model.seek(Seek::First);
+        model.push(DependencyGraph::from(vec![Token::new("5")]));
+        assert_eq!(model.idx(), 0);
+    }
+
+    #[test]
+    fn seek_skips_removed_trees() {
+        let mut model = model(4);
+        model.remove(&[1, 3]);
+        model.seek(Seek::First);
+
+        assert!(model.seek(Seek::Relative(1)));
+        assert_eq!(model.idx(), 2);
+        assert!(!model.seek(Seek::Last));
+        assert!(model.seek(Seek::Relative(-1)));
+        assert_eq!(model.idx(), 0);
+    }
+
+    #[test]
+    fn capacity_keeps_the_newest_trees() {
+        let mut model = model(3);
+        model.toggle_marks(&[0, 2]);
+        model.seek(Seek::Last);
+        model.set_capacity(Some(4));
+
+        model.extend((3..6).map(|idx| DependencyGraph::from(vec![Token::new(idx.to_string())])));
+
+        assert_eq!(model.len(), 4);
+        assert_eq!(
+            model.graph_at(0).unwrap().0[NodeIndex::new(0)].token.form(),
+            "2"
+        );
+        assert_eq!(model.idx(), 0);
+        assert!(model.is_marked(0));
+        assert!(!model.is_marked(2));
+    }
+
+    #[test]
+    fn notes_are_kept_by_file() {
+        let mut model = model(2);
+        model.push_file(None);
+        model.extend((2..4).map(|idx| DependencyGraph::from(vec![Token::new(idx.to_string())])));
+        model.push_notes(vec![(1, "check".to_owned())].into_iter().collect());
+        model.set_note(0, "first");
+        model.set_note(1, " ");
+
+        assert_eq!(model.note(3), Some("check"));
+        assert!(!model.has_note(1));
+        assert_eq!(
+            model.range_notes(2..4).into_iter().collect::<Vec<_>>(),
+            vec![(1, "check".to_owned())]
+        );
+    }
+
+    #[test]
+    fn undo_grouped_edits() {
+        let mut model = model(3);
+        let relation = |model: &StatefulTreebankModel, idx: usize| {
+            model.graph_at(idx).unwrap().0[NodeIndex::new(0)]
+                .token
+                .head_rel()
+                .map(str::to_owned)
+        };
+
+        model.seek(Seek::First);
+        model.select_token(Some(0));
+        model.set_relation("a").unwrap();
+
+        model.begin_edits();
+        model.set_relation("b").unwrap();
+        model.seek(Seek::Relative(1));
+        model.select_token(Some(0));
+        model.set_relation("c").unwrap();
+        model.end_edits();
+
+        model.undo_edit();
+        assert_eq!(model.idx(), 0);
+        assert_eq!(relation(&model, 0).as_deref(), Some("a"));
+        assert_eq!(relation(&model, 1), None);
+
+        model.undo_edit();
+        assert_eq!(relation(&model, 0), None);
+        assert!(!model.is_edited(0));
+    }
+
+    #[test]
+    fn tags_of_selected_token_are_edited() {
+        let mut model = model(1);
+        model.seek(Seek::First);
+        assert!(model.set_lemma("a").is_err());
+
+        model.select_token(Some(0));
+        model.set_lemma("dog").unwrap();
+        model.set_upos("NOUN").unwrap();
+        model.set_xpos("NN").unwrap();
+        model.set_lemma("").unwrap();
+
+        let graph = model.graph().unwrap();
+        let token = &graph.0[NodeIndex::new(0)].token;
+        assert_eq!(token.lemma(), None);
+        assert_eq!(token.cpos(), Some("NOUN"));
+        assert_eq!(token.pos(), Some("NN"));
+        assert!(model.is_edited(0));
+
+        model.undo_edit();
+        assert_eq!(
+            model.graph().unwrap().0[NodeIndex::new(0)].token.lemma(),
+            Some("dog")
+        );
+    }
+
+    #[test]
+    fn feature_matches_skip_removed_trees() {
+        let graph = |upos: &str| {
+            let mut token = Token::new("de");
+            token.set_cpos(Some(upos));
+            token.set_features(Some(Features::from_string("Definite=Def")));
+            DependencyGraph::from(vec![token])
+        };
+        let mut model =
+            StatefulTreebankModel::from_iter(vec![graph("DET"), graph("ADJ"), graph("ADJ")]);
+        model.remove(&[1]);
+
+        let cells = model.feature_table().cells();
+        assert_eq!(cells.len(), 2);
+        assert_eq!((cells[0].upos.as_str(), cells[0].count), ("ADJ", 1));
+
+        model.find_feature(&cells[0]);
+        assert_eq!(
+            model.matches(),
+            [Match {
+                sentence: 2,
+                token: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn seek_in_empty_treebank() {
+        let mut model = model(0);
+
+        assert!(!model.seek(Seek::First));
+        assert!(!model.seek(Seek::Relative(-1)));
+        assert!(!model.seek(Seek::Absolute(3)));
+    }
+}
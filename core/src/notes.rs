@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use compress::decompress;
+use error::{ResultExt, ViewerError};
+use lazy::SentenceBlocks;
+
+/// The key of the comments with notes, as in `# note = Check the PP.`
+/// A note with several lines has a comment per line.
+const NOTE_KEY: &str = "note";
+
+/// Notes of the sentences of a treebank file, by the index of the
+/// sentence in the file.
+pub type SentenceNotes = BTreeMap<usize, String>;
+
+/// A note in a notes file.
+#[derive(Debug, Deserialize, Serialize)]
+struct NoteEntry {
+    /// The 1-based number of the sentence in the treebank file.
+    sentence: usize,
+    note: String,
+}
+
+/// The path of the notes file of a treebank, `TREEBANK.notes.json`.
+pub fn notes_path(treebank: &Path) -> PathBuf {
+    let mut path = treebank.as_os_str().to_owned();
+    path.push(".notes.json");
+    PathBuf::from(path)
+}
+
+/// Read the notes of a treebank from its notes file. Without a notes
+/// file, the notes are read from the `# note` comments of the treebank
+/// when `comments` is true, and there are no notes otherwise.
+pub fn read_notes(treebank: &Path, comments: bool) -> Result<SentenceNotes, ViewerError> {
+    let path = notes_path(treebank);
+    if !path.exists() {
+        if !comments {
+            return Ok(SentenceNotes::new());
+        }
+
+        let read = decompress(BufReader::new(File::open(treebank)?))?;
+        return read_comment_notes(read);
+    }
+
+    let entries: Vec<NoteEntry> = serde_json::from_reader(BufReader::new(
+        File::open(&path).context("Cannot open notes")?,
+    ))
+    .context("Cannot read notes")?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.sentence > 0)
+        .map(|entry| (entry.sentence - 1, entry.note))
+        .collect())
+}
+
+/// Write the notes of a treebank to its notes file. The notes file is
+/// removed when there are no notes.
+pub fn write_notes(treebank: &Path, notes: &SentenceNotes) -> Result<(), ViewerError> {
+    let path = notes_path(treebank);
+    if notes.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).context("Cannot remove notes")?;
+        }
+
+        return Ok(());
+    }
+
+    let entries: Vec<_> = notes
+        .iter()
+        .map(|(&idx, note)| NoteEntry {
+            sentence: idx + 1,
+            note: note.clone(),
+        })
+        .collect();
+
+    let writer = BufWriter::new(File::create(&path).context("Cannot create notes")?);
+    serde_json::to_writer_pretty(writer, &entries)?;
+
+    Ok(())
+}
+
+/// Get the value of a `# note = ...` comment line.
+fn note_line(line: &str) -> Option<&str> {
+    let mut parts = line.strip_prefix('#')?.splitn(2, '=');
+    if parts.next()?.trim() != NOTE_KEY {
+        return None;
+    }
+
+    Some(parts.next()?.trim())
+}
+
+/// Get the note of a sentence from its comments.
+pub fn comment_note(block: &str) -> Option<String> {
+    let lines: Vec<_> = block
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .filter_map(note_line)
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Read the notes of a treebank from the comments of its sentences.
+pub fn read_comment_notes<R>(read: R) -> Result<SentenceNotes, ViewerError>
+where
+    R: BufRead,
+{
+    let mut notes = SentenceNotes::new();
+
+    for (idx, block) in SentenceBlocks::new(read).enumerate() {
+        if let Some(note) = comment_note(&block?.1) {
+            notes.insert(idx, note);
+        }
+    }
+
+    Ok(notes)
+}
+
+/// Replace the note comments of a sentence. The new comments follow the
+/// other comments. The text is kept as it is when the note is unchanged.
+pub fn replace_note_comments(text: &str, note: Option<&str>) -> String {
+    if comment_note(text).as_deref() == note {
+        return text.to_owned();
+    }
+
+    let mut replaced = String::new();
+    let mut lines = text.split_inclusive('\n').peekable();
+
+    while let Some(line) = lines.next_if(|line| line.starts_with('#')) {
+        if note_line(line.trim_end()).is_none() {
+            replaced.push_str(line);
+        }
+    }
+
+    for line in note.iter().flat_map(|note| note.lines()) {
+        replaced.push_str(&format!("# {} = {}\n", NOTE_KEY, line.trim()));
+    }
+
+    replaced.extend(lines);
+
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comment_note, read_comment_notes, replace_note_comments};
+
+    const SENTENCE: &str = "# sent_id = 1\n\
+                            # note = Attach the PP\n\
+                            # note = to the verb?\n\
+                            # text = Look.\n\
+                            1\tLook\t_\t_\t_\t_\t0\troot\t_\t_\n";
+
+    #[test]
+    fn notes_are_read_from_comments() {
+        assert_eq!(
+            comment_note(SENTENCE),
+            Some("Attach the PP\nto the verb?".to_owned())
+        );
+
+        let text = format!("{}\n1\tOk\t_\t_\t_\t_\t0\troot\t_\t_\n", SENTENCE);
+        let notes = read_comment_notes(text.as_bytes()).unwrap();
+        assert_eq!(notes.keys().cloned().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn note_comments_are_replaced() {
+        assert_eq!(
+            replace_note_comments(SENTENCE, Some("Attach the PP\nto the verb?")),
+            SENTENCE
+        );
+        assert_eq!(
+            replace_note_comments(SENTENCE, Some("Fine")),
+            "# sent_id = 1\n\
+             # text = Look.\n\
+             # note = Fine\n\
+             1\tLook\t_\t_\t_\t_\t0\troot\t_\t_\n"
+        );
+        assert_eq!(
+            replace_note_comments(SENTENCE, None),
+            "# sent_id = 1\n\
+             # text = Look.\n\
+             1\tLook\t_\t_\t_\t_\t0\troot\t_\t_\n"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::BufRead;
+
+use error::ViewerError;
+
+/// Find the lines of the sentences in a treebank, as 1-based, inclusive
+/// (first, last) pairs. Comment lines belong to the sentence that follows
+/// them.
+pub fn sentence_lines<R>(read: R) -> Result<Vec<(usize, usize)>, ViewerError>
+where
+    R: BufRead,
+{
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut last = 0;
+
+    for (idx, line) in read.lines().enumerate() {
+        let line_no = idx + 1;
+
+        if line?.trim().is_empty() {
+            if let Some(start) = start.take() {
+                ranges.push((start, last));
+            }
+        } else {
+            start = start.or(Some(line_no));
+            last = line_no;
+        }
+    }
+
+    // The last sentence may not be followed by an empty line.
+    if let Some(start) = start {
+        ranges.push((start, last));
+    }
+
+    Ok(ranges)
+}
+
+/// Create a review link to lines of a file. The template can contain
+/// the `{path}`, `{start}` and `{end}` placeholders, for instance
+/// `https://example.org/treebank/blob/master/{path}#L{start}-L{end}`.
+/// Without a template, a `path:start-end` reference is created.
+pub fn review_link(template: Option<&str>, path: &str, (start, end): (usize, usize)) -> String {
+    match template {
+        Some(template) => template
+            .replace("{path}", path)
+            .replace("{start}", &start.to_string())
+            .replace("{end}", &end.to_string()),
+        None => format!("{}:{}-{}", path, start, end),
+    }
+}
+
+/// Join arguments into a command line for a POSIX shell. Arguments with
+/// characters that the shell interprets are single-quoted.
+pub fn command_line<S>(args: &[S]) -> String
+where
+    S: AsRef<str>,
+{
+    let quoted: Vec<_> = args
+        .iter()
+        .map(|arg| {
+            let arg = arg.as_ref();
+            let safe = |c: char| c.is_alphanumeric() || "%+,-./:=@_".contains(c);
+            if !arg.is_empty() && arg.chars().all(safe) {
+                arg.to_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect();
+
+    quoted.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::command_line;
+
+    #[test]
+    fn quotes_shell_arguments() {
+        assert_eq!(
+            command_line(&["conllx-view", "--tree", "3", "tree bank.conll"]),
+            "conllx-view --tree 3 'tree bank.conll'"
+        );
+        assert_eq!(
+            command_line(&["--search", "root cpos=VERB", "it's", ""]),
+            "--search 'root cpos=VERB' 'it'\\''s' ''"
+        );
+    }
+}
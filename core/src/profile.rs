@@ -0,0 +1,184 @@
+use std::io::BufRead;
+use std::str::FromStr;
+
+use conllx::Token;
+
+use detokenize::Spacing;
+use error::ViewerError;
+use lazy::SentenceBlocks;
+use theme::ThemeColors;
+
+/// Colors of word forms by their part-of-speech tags.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Word forms are not colored.
+    Plain,
+
+    /// Universal part-of-speech tags (UPOS) in the CPOS layer.
+    Universal,
+
+    /// STTS tags in the POS layer, as used in German treebanks.
+    Stts,
+}
+
+impl Palette {
+    /// Get the Graphviz color of the word form of a token in a theme,
+    /// `None` for the default color.
+    pub fn color(self, token: &Token, colors: &ThemeColors) -> Option<&'static str> {
+        match self {
+            Palette::Plain => None,
+            Palette::Universal => match token.cpos()? {
+                "NOUN" | "PROPN" | "PRON" => Some(colors.noun),
+                "VERB" | "AUX" => Some(colors.verb),
+                "ADJ" | "ADV" => Some(colors.adjective),
+                _ => None,
+            },
+            Palette::Stts => {
+                let pos = token.pos()?;
+                if pos.starts_with('N') || pos.starts_with("PP") {
+                    Some(colors.noun)
+                } else if pos.starts_with('V') {
+                    Some(colors.verb)
+                } else if pos.starts_with("ADJ") || pos == "ADV" {
+                    Some(colors.adjective)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Display settings for the treebanks of a language.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Profile {
+    /// Font of the word forms in trees.
+    pub font: &'static str,
+
+    /// Show sentences and trees from right to left.
+    pub rtl: bool,
+
+    /// Punctuation spacing rules for the text of sentences.
+    pub spacing: Spacing,
+
+    pub palette: Palette,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            font: "Helvetica",
+            rtl: false,
+            spacing: Spacing::Default,
+            palette: Palette::Plain,
+        }
+    }
+}
+
+/// Built-in profiles by ISO 639-1 language code.
+const PROFILES: &[(&str, Profile)] = &[
+    (
+        "ar",
+        Profile {
+            font: "Noto Naskh Arabic",
+            rtl: true,
+            spacing: Spacing::Default,
+            palette: Palette::Universal,
+        },
+    ),
+    (
+        "de",
+        Profile {
+            font: "Helvetica",
+            rtl: false,
+            spacing: Spacing::Default,
+            palette: Palette::Stts,
+        },
+    ),
+    (
+        "en",
+        Profile {
+            font: "Helvetica",
+            rtl: false,
+            spacing: Spacing::Default,
+            palette: Palette::Universal,
+        },
+    ),
+    (
+        "fr",
+        Profile {
+            font: "Helvetica",
+            rtl: false,
+            spacing: Spacing::French,
+            palette: Palette::Universal,
+        },
+    ),
+    (
+        "he",
+        Profile {
+            font: "Noto Sans Hebrew",
+            rtl: true,
+            spacing: Spacing::Default,
+            palette: Palette::Universal,
+        },
+    ),
+    (
+        "ja",
+        Profile {
+            font: "Noto Sans CJK JP",
+            rtl: false,
+            spacing: Spacing::None,
+            palette: Palette::Universal,
+        },
+    ),
+    (
+        "zh",
+        Profile {
+            font: "Noto Sans CJK SC",
+            rtl: false,
+            spacing: Spacing::None,
+            palette: Palette::Universal,
+        },
+    ),
+];
+
+impl FromStr for Profile {
+    type Err = ViewerError;
+
+    /// Get the profile of a language code, such as `de` or `pt-BR`.
+    /// Region and treebank suffixes are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lang = s.split(&['-', '_'][..]).next().unwrap_or(s);
+        if lang == "default" {
+            return Ok(Profile::default());
+        }
+
+        PROFILES
+            .iter()
+            .find(|&&(code, _)| code.eq_ignore_ascii_case(lang))
+            .map(|&(_, profile)| profile)
+            .ok_or_else(|| ViewerError::UnknownLanguage(s.to_owned()))
+    }
+}
+
+/// Get the language of a treebank from `# lang = CODE` metadata in its
+/// first sentence.
+pub fn treebank_language<R>(read: R) -> Option<String>
+where
+    R: BufRead,
+{
+    let (_, block) = SentenceBlocks::new(read).next()?.ok()?;
+
+    block
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line[1..].splitn(2, '=');
+            match (parts.next()?.trim(), parts.next()?.trim()) {
+                ("lang", lang) | ("language", lang) if !lang.is_empty() => Some(lang.to_owned()),
+                _ => None,
+            }
+        })
+        .next()
+}
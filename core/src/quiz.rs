@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use conllx::Token;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+
+/// The part of the annotation that a student has to guess.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QuizMode {
+    /// Guess the dependency relations, the arcs are shown.
+    Labels,
+
+    /// Guess the heads, the arcs are hidden.
+    Heads,
+}
+
+impl FromStr for QuizMode {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "labels" => Ok(QuizMode::Labels),
+            "heads" => Ok(QuizMode::Heads),
+            _ => Err(ViewerError::UnknownQuizMode(s.to_owned())),
+        }
+    }
+}
+
+/// The answers that were given for the tokens of a single tree.
+#[derive(Clone, Debug)]
+pub struct QuizAnswers {
+    pub mode: QuizMode,
+    answers: HashMap<usize, bool>,
+}
+
+impl QuizAnswers {
+    pub fn new(mode: QuizMode) -> Self {
+        QuizAnswers {
+            mode,
+            answers: HashMap::new(),
+        }
+    }
+
+    /// Returns whether the answer for a token was correct, `None` if the
+    /// token was not answered yet.
+    pub fn answer(&self, token: usize) -> Option<bool> {
+        self.answers.get(&token).cloned()
+    }
+}
+
+/// A quiz over the trees of a treebank, keeping track of the score.
+pub struct Quiz {
+    mode: QuizMode,
+    trees: HashMap<usize, QuizAnswers>,
+    correct: usize,
+    answered: usize,
+}
+
+impl Quiz {
+    pub fn new(mode: QuizMode) -> Self {
+        Quiz {
+            mode,
+            trees: HashMap::new(),
+            correct: 0,
+            answered: 0,
+        }
+    }
+
+    pub fn mode(&self) -> QuizMode {
+        self.mode
+    }
+
+    /// Get the answers for a tree.
+    pub fn answers(&self, tree: usize) -> QuizAnswers {
+        self.trees
+            .get(&tree)
+            .cloned()
+            .unwrap_or_else(|| QuizAnswers::new(self.mode))
+    }
+
+    /// Check a guess for a token of a tree. Returns `None` when the token
+    /// was already answered, otherwise whether the guess was correct. The
+    /// answer for the token is revealed.
+    pub fn guess(&mut self, tree: usize, token: usize, gold: &Token, guess: &str) -> Option<bool> {
+        let guess = guess.trim();
+        let correct = match self.mode {
+            QuizMode::Labels => gold
+                .head_rel()
+                .map(|rel| rel.eq_ignore_ascii_case(guess))
+                .unwrap_or(false),
+            QuizMode::Heads => guess.parse::<usize>().ok() == gold.head(),
+        };
+
+        self.record(tree, token, correct)
+    }
+
+    /// Reveal the answer for a token of a tree. The token counts as
+    /// incorrectly answered.
+    pub fn reveal(&mut self, tree: usize, token: usize) {
+        self.record(tree, token, false);
+    }
+
+    /// Find the first token after `token` that was not answered yet,
+    /// wrapping around to the start of the tree.
+    pub fn next_hidden(
+        &self,
+        tree: usize,
+        graph: &DependencyGraph,
+        token: Option<usize>,
+    ) -> Option<usize> {
+        let n_tokens = graph.0.node_count();
+        let start = token.map(|token| token + 1).unwrap_or(0);
+        let answers = self.trees.get(&tree);
+
+        (0..n_tokens)
+            .map(|offset| (start + offset) % n_tokens)
+            .find(|token| answers.and_then(|a| a.answer(*token)).is_none())
+    }
+
+    /// Forget the answers for the first `n` trees, after they were
+    /// removed from the front of the treebank. The score is kept.
+    pub fn remove_front(&mut self, n: usize) {
+        self.trees = self
+            .trees
+            .drain()
+            .filter(|&(tree, _)| tree >= n)
+            .map(|(tree, answers)| (tree - n, answers))
+            .collect();
+    }
+
+    /// The number of correct answers and the number of answers.
+    pub fn score(&self) -> (usize, usize) {
+        (self.correct, self.answered)
+    }
+
+    fn record(&mut self, tree: usize, token: usize, correct: bool) -> Option<bool> {
+        let mode = self.mode;
+        let answers = self
+            .trees
+            .entry(tree)
+            .or_insert_with(|| QuizAnswers::new(mode));
+
+        if answers.answers.contains_key(&token) {
+            return None;
+        }
+
+        answers.answers.insert(token, correct);
+        self.answered += 1;
+        if correct {
+            self.correct += 1;
+        }
+
+        Some(correct)
+    }
+}
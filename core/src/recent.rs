@@ -0,0 +1,77 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use serde_json;
+
+use dirs::data_dir;
+use error::{ResultExt, ViewerError};
+
+const RECENT_FILE: &str = "recent.json";
+
+/// Maximum number of treebanks in the list of recent files.
+const MAX_RECENT: usize = 10;
+
+/// The treebanks that were opened recently, most recent first. A
+/// treebank that consists of several files is one entry.
+#[derive(Default)]
+pub struct RecentFiles {
+    path: Option<PathBuf>,
+    treebanks: Vec<Vec<PathBuf>>,
+}
+
+impl RecentFiles {
+    /// Open the list of recent files. The list is not stored when `path`
+    /// is `None`, and is empty when the file does not exist yet.
+    pub fn open(path: Option<PathBuf>) -> Result<Self, ViewerError> {
+        let treebanks = match path {
+            Some(ref path) if path.exists() => serde_json::from_reader(BufReader::new(
+                File::open(path).context("Cannot open recent files")?,
+            ))
+            .context("Cannot read recent files")?,
+            _ => Vec::new(),
+        };
+
+        Ok(RecentFiles { path, treebanks })
+    }
+
+    /// Add a treebank to the front of the list and write the list to
+    /// disk.
+    pub fn add(&mut self, paths: &[PathBuf]) -> Result<(), ViewerError> {
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+            .collect();
+
+        self.treebanks.retain(|treebank| *treebank != paths);
+        self.treebanks.insert(0, paths);
+        self.treebanks.truncate(MAX_RECENT);
+
+        self.save()
+    }
+
+    pub fn treebanks(&self) -> &[Vec<PathBuf>] {
+        &self.treebanks
+    }
+
+    fn save(&self) -> Result<(), ViewerError> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Cannot create data directory")?;
+        }
+
+        let writer = BufWriter::new(File::create(path).context("Cannot create recent files")?);
+        serde_json::to_writer_pretty(writer, &self.treebanks)?;
+
+        Ok(())
+    }
+}
+
+/// The default location of the list of recent files.
+pub fn default_recent_path() -> Option<PathBuf> {
+    Some(data_dir()?.join(RECENT_FILE))
+}
@@ -0,0 +1,90 @@
+/// A step of a keyboard macro: a window action with its parameter, if
+/// any, e.g. `set-relation` with `nsubj`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroStep {
+    pub action: String,
+    pub parameter: Option<String>,
+}
+
+/// Records the actions that are activated as a keyboard macro.
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    recording: Option<Vec<MacroStep>>,
+    steps: Vec<MacroStep>,
+}
+
+impl MacroRecorder {
+    pub fn new() -> Self {
+        MacroRecorder::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Add a step to the macro that is being recorded. Steps are ignored
+    /// when no macro is recorded.
+    pub fn record(&mut self, step: MacroStep) {
+        if let Some(ref mut recording) = self.recording {
+            recording.push(step);
+        }
+    }
+
+    /// Start recording a new macro.
+    pub fn start(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// The steps of the last recorded macro.
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    /// Stop recording. The recorded macro replaces the previous macro,
+    /// unless no steps were recorded.
+    pub fn stop(&mut self) {
+        match self.recording.take() {
+            Some(steps) if !steps.is_empty() => self.steps = steps,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MacroRecorder, MacroStep};
+
+    fn step(action: &str) -> MacroStep {
+        MacroStep {
+            action: action.to_owned(),
+            parameter: None,
+        }
+    }
+
+    #[test]
+    fn records_steps_while_recording() {
+        let mut recorder = MacroRecorder::new();
+        recorder.record(step("next"));
+        recorder.start();
+        recorder.record(step("next-token"));
+        recorder.record(step("next"));
+        recorder.stop();
+        recorder.record(step("previous"));
+
+        assert_eq!(recorder.steps(), &[step("next-token"), step("next")]);
+    }
+
+    #[test]
+    fn empty_recording_keeps_macro() {
+        let mut recorder = MacroRecorder::new();
+        recorder.start();
+        recorder.record(step("next"));
+        recorder.stop();
+
+        recorder.start();
+        assert!(recorder.is_recording());
+        recorder.stop();
+
+        assert_eq!(recorder.steps(), &[step("next")]);
+    }
+}
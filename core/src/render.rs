@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use error::ViewerError;
+use graph::{DependencyGraph, RenderOptions, Svg};
+
+/// A rendered tree.
+pub struct Rendering {
+    /// The render request that this is the result of.
+    pub generation: usize,
+
+    /// Reset the zoom level when showing the tree.
+    pub reset_zoom: bool,
+
+    pub svg: Result<String, ViewerError>,
+}
+
+struct RenderJob {
+    generation: usize,
+    reset_zoom: bool,
+    graph: Arc<DependencyGraph>,
+    options: RenderOptions,
+}
+
+/// Renders trees to SVG on a pool of worker threads.
+///
+/// Only the most recent render request is relevant: requests that were
+/// superseded before a worker picked them up are skipped, and renderings
+/// of superseded requests are not delivered.
+#[derive(Clone)]
+pub struct Renderer {
+    jobs: Sender<RenderJob>,
+    generation: Arc<AtomicUsize>,
+}
+
+impl Renderer {
+    /// Start a renderer with `n_workers` threads. `deliver` is called on
+    /// the worker thread with every rendering.
+    pub fn new<F>(n_workers: usize, deliver: F) -> Self
+    where
+        F: Fn(Rendering) + Send + Sync + 'static,
+    {
+        let (tx, rx) = channel();
+        let jobs = Arc::new(Mutex::new(rx));
+        let generation = Arc::new(AtomicUsize::new(0));
+        let deliver = Arc::new(deliver);
+
+        for _ in 0..n_workers {
+            let jobs = jobs.clone();
+            let generation = generation.clone();
+            let deliver = deliver.clone();
+
+            thread::spawn(move || {
+                while let Some(job) = next_job(&jobs) {
+                    if job.generation != generation.load(Ordering::SeqCst) {
+                        continue;
+                    }
+
+                    let svg = job.graph.svg(&job.options);
+
+                    if job.generation == generation.load(Ordering::SeqCst) {
+                        deliver(Rendering {
+                            generation: job.generation,
+                            reset_zoom: job.reset_zoom,
+                            svg,
+                        });
+                    }
+                }
+            });
+        }
+
+        Renderer {
+            jobs: tx,
+            generation,
+        }
+    }
+
+    /// Request a rendering of a tree, superseding earlier requests.
+    /// Returns the generation of the request.
+    pub fn render(
+        &self,
+        graph: Arc<DependencyGraph>,
+        options: RenderOptions,
+        reset_zoom: bool,
+    ) -> usize {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        self.jobs
+            .send(RenderJob {
+                generation,
+                reset_zoom,
+                graph,
+                options,
+            })
+            .expect("Render workers stopped");
+
+        generation
+    }
+
+    /// Supersede earlier requests without requesting a new rendering.
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Check whether a rendering is of the most recent request.
+    pub fn is_current(&self, rendering: &Rendering) -> bool {
+        rendering.generation == self.generation.load(Ordering::SeqCst)
+    }
+}
+
+/// Get the next job, skipping over queued jobs to the most recent one.
+/// Returns `None` when the renderer was dropped.
+fn next_job(jobs: &Mutex<Receiver<RenderJob>>) -> Option<RenderJob> {
+    let jobs = jobs.lock().unwrap();
+    let mut job = jobs.recv().ok()?;
+    while let Ok(newer) = jobs.try_recv() {
+        job = newer;
+    }
+
+    Some(job)
+}
@@ -0,0 +1,373 @@
+use std::io::Write;
+
+use conllx::Features;
+use serde_json;
+
+use detokenize::detokenize;
+use error::ViewerError;
+use graph::{DependencyGraph, RenderOptions, Svg};
+
+const REPORT_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+nav a { margin-right: 0.5em; }
+.sentence { border-top: 1px solid #ccc; margin-top: 1em; overflow-x: auto; }
+.sentence h2 { font-size: 1em; }
+.hidden { display: none; }
+";
+
+/// Filters the sentences and the index on the text of the search box.
+const REPORT_SCRIPT: &str = "document.getElementById('search').addEventListener('input', function () {
+  var query = this.value.toLowerCase();
+  document.querySelectorAll('.sentence').forEach(function (sentence) {
+    var hidden = sentence.dataset.text.indexOf(query) < 0;
+    sentence.classList.toggle('hidden', hidden);
+    document.querySelector('nav a[href=\"#' + sentence.id + '\"]').classList.toggle('hidden', hidden);
+  });
+});
+";
+
+/// Write trees as a single self-contained HTML page, with the text and
+/// an inline SVG image of every tree, an index of the sentences and a
+/// search box that filters the sentences on their text. `indices` are
+/// the indices of the trees in the treebank, which number the sentences
+/// and their anchors.
+pub fn write_html_report<W>(
+    mut write: W,
+    title: &str,
+    graphs: &[DependencyGraph],
+    indices: &[usize],
+    options: &RenderOptions,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    write_head(&mut write, title, REPORT_STYLE)?;
+    writeln!(
+        write,
+        "<input id=\"search\" type=\"search\" placeholder=\"Search sentences\">"
+    )?;
+
+    writeln!(write, "<nav>")?;
+    for &idx in indices {
+        writeln!(write, "<a href=\"#s{0}\">{0}</a>", idx + 1)?;
+    }
+    writeln!(write, "</nav>")?;
+
+    for (graph, &idx) in graphs.iter().zip(indices) {
+        let text = detokenize(graph, options.profile.spacing);
+        writeln!(
+            write,
+            "<section class=\"sentence\" id=\"s{}\" data-text=\"{}\">",
+            idx + 1,
+            escape_html(text.to_lowercase())
+        )?;
+        writeln!(
+            write,
+            "<h2><a href=\"#s{0}\">Sentence {0}</a></h2>",
+            idx + 1
+        )?;
+        writeln!(write, "<p>{}</p>", escape_html(&text))?;
+        writeln!(write, "{}", inline_svg(&graph.svg(options)?))?;
+        writeln!(write, "</section>")?;
+    }
+
+    writeln!(write, "<script>\n{}</script>", REPORT_SCRIPT)?;
+    writeln!(write, "</body>\n</html>")?;
+
+    Ok(())
+}
+
+const INTERACTIVE_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+#position { color: #666; }
+#token { min-height: 1.5em; font-family: monospace; white-space: pre; }
+.tree { overflow-x: auto; }
+.tree g.node { cursor: pointer; }
+.tree g.selected text { fill: #c00; font-weight: bold; }
+.hidden { display: none; }
+";
+
+/// Shows one tree at a time. The keys of the viewer browse the trees and
+/// select tokens, and the query box finds the next tree with a token that
+/// matches all terms of the query.
+const INTERACTIVE_SCRIPT: &str = r#"(function () {
+  var LAYERS = ['form', 'lemma', 'cpos', 'pos', 'features', 'head', 'headrel', 'pheadrel'];
+  var trees = document.querySelectorAll('.tree');
+  var tokens = JSON.parse(document.getElementById('tokens').textContent);
+  var position = document.getElementById('position');
+  var info = document.getElementById('token');
+  var query = document.getElementById('query');
+  var current = 0;
+  var selected = null;
+
+  function describe(token) {
+    return LAYERS.filter(function (layer) { return token[layer] !== null; })
+      .map(function (layer) { return layer + ': ' + token[layer]; })
+      .join('\n');
+  }
+
+  function node(offset) {
+    return trees[current].querySelector('g.node[id="n' + offset + '"]');
+  }
+
+  function select(offset) {
+    if (selected !== null && node(selected)) {
+      node(selected).classList.remove('selected');
+    }
+    selected = offset !== null && offset >= 0 && offset < tokens[current].length ? offset : null;
+    if (selected !== null && node(selected)) {
+      node(selected).classList.add('selected');
+    }
+    info.textContent = selected === null ? '' : describe(tokens[current][selected]);
+  }
+
+  function show(idx) {
+    select(null);
+    trees[current].classList.add('hidden');
+    current = Math.max(0, Math.min(trees.length - 1, idx));
+    trees[current].classList.remove('hidden');
+    position.textContent = 'Tree ' + (current + 1) + ' of ' + trees.length;
+    history.replaceState(null, '', '#' + trees[current].id);
+  }
+
+  function find(text) {
+    var terms = text.split(/\s+/).filter(function (term) { return term; }).map(function (term) {
+      var eq = term.indexOf('=');
+      return eq < 0 ? ['form', term] : [term.slice(0, eq), term.slice(eq + 1)];
+    });
+    if (!terms.length) {
+      return;
+    }
+    for (var step = 1; step <= trees.length; step++) {
+      var idx = (current + step) % trees.length;
+      for (var offset = 0; offset < tokens[idx].length; offset++) {
+        var token = tokens[idx][offset];
+        if (terms.every(function (term) { return token[term[0]] === term[1]; })) {
+          show(idx);
+          select(offset);
+          return;
+        }
+      }
+    }
+    position.textContent = 'No tree matches ' + text;
+  }
+
+  trees.forEach(function (tree, idx) {
+    tree.querySelectorAll('g.node').forEach(function (g) {
+      var offset = parseInt(g.id.slice(1), 10);
+      var token = tokens[idx][offset];
+      if (!token) {
+        return;
+      }
+      var title = g.querySelector('title');
+      if (!title) {
+        title = g.insertBefore(document.createElementNS('http://www.w3.org/2000/svg', 'title'), g.firstChild);
+      }
+      title.textContent = describe(token);
+      g.addEventListener('click', function () { select(offset); });
+    });
+  });
+
+  query.addEventListener('keydown', function (event) {
+    if (event.key === 'Enter') {
+      find(query.value);
+    } else if (event.key === 'Escape') {
+      query.blur();
+    }
+  });
+
+  document.addEventListener('keydown', function (event) {
+    if (event.target === query || event.ctrlKey || event.altKey || event.metaKey) {
+      return;
+    }
+    var actions = {
+      'n': function () { show(current + 1); },
+      'p': function () { show(current - 1); },
+      'Home': function () { show(0); },
+      'End': function () { show(trees.length - 1); },
+      'PageDown': function () { show(current + 10); },
+      'PageUp': function () { show(current - 10); },
+      'ArrowRight': function () { select(selected === null ? 0 : selected + 1); },
+      'ArrowLeft': function () { select(selected === null ? tokens[current].length - 1 : selected - 1); },
+      'Escape': function () { select(null); },
+      '/': function () { query.focus(); }
+    };
+    if (actions[event.key]) {
+      event.preventDefault();
+      actions[event.key]();
+    }
+  });
+
+  var start = 0;
+  trees.forEach(function (tree, idx) {
+    if ('#' + tree.id === location.hash) {
+      start = idx;
+    }
+  });
+  show(start);
+})();
+"#;
+
+/// The annotation of a token, as it is shown in tooltips and matched by
+/// queries of interactive reports.
+#[derive(Serialize)]
+struct TokenInfo<'a> {
+    form: &'a str,
+    lemma: Option<&'a str>,
+    cpos: Option<&'a str>,
+    pos: Option<&'a str>,
+    features: Option<&'a str>,
+    head: Option<usize>,
+    headrel: Option<&'a str>,
+    pheadrel: Option<&'a str>,
+}
+
+/// Write trees as a single self-contained HTML page that shows one tree
+/// at a time, like the viewer. `n`, `p`, `Home`, `End`, `PgDn` and `PgUp`
+/// browse the trees, the arrow keys and clicks select tokens and `/`
+/// focuses a query box, that takes the queries of the viewer's search
+/// bar. The annotation of a token is shown as its tooltip. `indices` are
+/// the indices of the trees in the treebank, which are the anchors of the
+/// trees.
+pub fn write_interactive_report<W>(
+    mut write: W,
+    title: &str,
+    graphs: &[DependencyGraph],
+    indices: &[usize],
+    options: &RenderOptions,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    write_head(&mut write, title, INTERACTIVE_STYLE)?;
+    writeln!(
+        write,
+        "<input id=\"query\" type=\"search\" placeholder=\"Query, e.g. lemma=be pos=VBZ (/)\">"
+    )?;
+    writeln!(write, "<p id=\"position\"></p>")?;
+
+    let mut tokens = Vec::new();
+    for (graph, &idx) in graphs.iter().zip(indices) {
+        writeln!(write, "<section class=\"tree hidden\" id=\"s{}\">", idx + 1)?;
+        writeln!(
+            write,
+            "<p>{}</p>",
+            escape_html(detokenize(graph, options.profile.spacing))
+        )?;
+        writeln!(write, "{}", inline_svg(&graph.svg(options)?))?;
+        writeln!(write, "</section>")?;
+
+        let sentence = graph.to_sentence();
+        let sentence_tokens: Vec<_> = sentence
+            .iter()
+            .map(|token| TokenInfo {
+                form: token.form(),
+                lemma: token.lemma(),
+                cpos: token.cpos(),
+                pos: token.pos(),
+                features: token.features().map(Features::as_str),
+                head: token.head(),
+                headrel: token.head_rel(),
+                pheadrel: token.p_head_rel(),
+            })
+            .collect();
+        tokens.push(serde_json::to_string(&sentence_tokens)?);
+    }
+
+    writeln!(write, "<p id=\"token\"></p>")?;
+    // The data cannot end the script element early.
+    writeln!(
+        write,
+        "<script type=\"application/json\" id=\"tokens\">[{}]</script>",
+        tokens.join(",").replace("</", "<\\/")
+    )?;
+    writeln!(write, "<script>\n{}</script>", INTERACTIVE_SCRIPT)?;
+    writeln!(write, "</body>\n</html>")?;
+
+    Ok(())
+}
+
+/// Write the start of a page, up to and including its heading.
+fn write_head<W>(write: &mut W, title: &str, style: &str) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    writeln!(write, "<!DOCTYPE html>")?;
+    writeln!(write, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(write, "<title>{}</title>", escape_html(title))?;
+    writeln!(write, "<style>\n{}</style>\n</head>\n<body>", style)?;
+    writeln!(write, "<h1>{}</h1>", escape_html(title))?;
+
+    Ok(())
+}
+
+/// Escape the special characters of HTML in text and attribute values.
+pub fn escape_html<S>(s: S) -> String
+where
+    S: AsRef<str>,
+{
+    s.as_ref()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Strip the XML declaration and document type of an SVG image, so that
+/// it can be embedded in an HTML page.
+pub fn inline_svg(svg: &str) -> &str {
+    match svg.find("<svg") {
+        Some(start) => &svg[start..],
+        None => svg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::{write_html_report, write_interactive_report};
+    use graph::{DependencyGraph, RenderOptions, TreeRenderer};
+
+    #[test]
+    fn report_has_anchors_text_and_images() {
+        let mut token = Token::new("Dogs&cats");
+        token.set_head(Some(0));
+        token.set_head_rel(Some("root"));
+        let graph = DependencyGraph::from(vec![token]);
+        let options = RenderOptions {
+            renderer: TreeRenderer::Diagram,
+            ..RenderOptions::default()
+        };
+
+        let mut html = Vec::new();
+        write_html_report(&mut html, "Sample <1>", &[graph], &[4], &options).unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        assert!(html.contains("<title>Sample &lt;1&gt;</title>"));
+        assert!(html.contains("<a href=\"#s5\">5</a>"));
+        assert!(html.contains("id=\"s5\" data-text=\"dogs&amp;cats\""));
+        assert!(html.contains("<p>Dogs&amp;cats</p>"));
+        assert!(html.contains("<svg"));
+        assert!(!html.contains("<?xml"));
+    }
+
+    #[test]
+    fn interactive_report_embeds_the_tokens() {
+        let mut token = Token::new("</script>");
+        token.set_lemma(Some("be"));
+        token.set_head(Some(0));
+        token.set_head_rel(Some("root"));
+        let graph = DependencyGraph::from(vec![token]);
+        let options = RenderOptions {
+            renderer: TreeRenderer::Diagram,
+            ..RenderOptions::default()
+        };
+
+        let mut html = Vec::new();
+        write_interactive_report(&mut html, "Sample", &[graph], &[0], &options).unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        assert!(html.contains("<section class=\"tree hidden\" id=\"s1\">"));
+        assert!(html.contains(r#"[[{"form":"<\/script>","lemma":"be","cpos":null,"pos":null,"features":null,"head":0,"headrel":"root","pheadrel":null}]]"#));
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+}
@@ -0,0 +1,400 @@
+use std::io::{Read, Write};
+
+use conllx::{Sentence, Token};
+use petgraph::graph::NodeIndex;
+
+use conllu::{format_numeric_field, format_string_field};
+use error::ViewerError;
+use export::write_graph;
+use graph::DependencyGraph;
+use input::InputFormat;
+use lazy::parse_block;
+use notes::{replace_note_comments, SentenceNotes};
+
+/// The text of a treebank file, as its sentences and the text between
+/// them, for saving without spurious changes.
+///
+/// Sentences that were not changed are written back exactly as they were
+/// read. Of changed sentences, the comments and the lines of unchanged
+/// tokens are kept. Changed tokens only get new FORM to DEPREL columns
+/// (the gold columns in CoNLL-2009), the other columns are kept.
+/// Changed sentences of Stanford dependencies are written anew.
+pub struct TreebankText {
+    format: InputFormat,
+
+    /// The text before every sentence, followed by the text after the
+    /// last sentence.
+    separators: Vec<String>,
+
+    sentences: Vec<String>,
+
+    /// Notes that replace the note comments of the sentences.
+    notes: Option<SentenceNotes>,
+}
+
+impl TreebankText {
+    pub fn read<R>(mut read: R, format: InputFormat) -> Result<Self, ViewerError>
+    where
+        R: Read,
+    {
+        let mut text = String::new();
+        read.read_to_string(&mut text)?;
+
+        let mut separators = vec![String::new()];
+        let mut sentences: Vec<String> = Vec::new();
+        let mut in_sentence = false;
+
+        for line in text.split_inclusive('\n') {
+            if line.trim().is_empty() {
+                if in_sentence {
+                    separators.push(String::new());
+                    in_sentence = false;
+                }
+
+                separators.last_mut().unwrap().push_str(line);
+            } else {
+                if !in_sentence {
+                    sentences.push(String::new());
+                    in_sentence = true;
+                }
+
+                sentences.last_mut().unwrap().push_str(line);
+            }
+        }
+
+        if in_sentence {
+            separators.push(String::new());
+        }
+
+        Ok(TreebankText {
+            format,
+            separators,
+            sentences,
+            notes: None,
+        })
+    }
+
+    /// Write the notes as `# note` comments, replacing the note comments
+    /// that were read. Sentences without a note lose their note comments.
+    pub fn set_notes(&mut self, notes: SentenceNotes) {
+        self.notes = Some(notes);
+    }
+
+    /// Write the treebank with the given sentences, leaving out the
+    /// sentences for which `is_removed` is true. Sentences beyond the
+    /// sentences that were read are added at the end.
+    pub fn write<W, F>(
+        &self,
+        mut write: W,
+        sentences: &[Sentence],
+        is_removed: F,
+    ) -> Result<(), ViewerError>
+    where
+        W: Write,
+        F: Fn(usize) -> bool,
+    {
+        let mut written = false;
+        let mut ends_line = true;
+
+        for (idx, sentence) in sentences.iter().enumerate() {
+            if is_removed(idx) {
+                continue;
+            }
+
+            let text = match self.sentences.get(idx) {
+                Some(text) => {
+                    // The text before the first sentence stays in front.
+                    let separator = if written { idx } else { 0 };
+                    write.write_all(self.separators[separator].as_bytes())?;
+                    self.sentence_text(text, sentence)?
+                }
+                None => {
+                    if written {
+                        if !ends_line {
+                            write.write_all(b"\n")?;
+                        }
+
+                        write.write_all(b"\n")?;
+                    }
+
+                    self.format_graph(String::new(), &DependencyGraph::from(sentence.clone()))?
+                }
+            };
+
+            let text = match self.notes {
+                Some(ref notes) => {
+                    replace_note_comments(&text, notes.get(&idx).map(String::as_str))
+                }
+                None => text,
+            };
+
+            written = true;
+            ends_line = text.ends_with('\n');
+            write.write_all(text.as_bytes())?;
+        }
+
+        let trailing = self
+            .separators
+            .get(sentences.len())
+            .unwrap_or_else(|| self.separators.last().unwrap());
+        write.write_all(trailing.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Get the text of a sentence that was read as `text`.
+    fn sentence_text(&self, text: &str, sentence: &Sentence) -> Result<String, ViewerError> {
+        let comments: String = text
+            .split_inclusive('\n')
+            .filter(|line| line.starts_with('#'))
+            .collect();
+        let block: String = text
+            .split_inclusive('\n')
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+
+        let mut graph = match parse_block(&block, self.format) {
+            Ok(graph) => graph,
+            Err(_) => return self.format_graph(comments, &DependencyGraph::from(sentence.clone())),
+        };
+
+        let tokens = graph.to_sentence();
+        if tokens == *sentence {
+            return Ok(text.to_owned());
+        }
+
+        if tokens.len() != sentence.len() {
+            return self.format_graph(comments, &DependencyGraph::from(sentence.clone()));
+        }
+
+        if self.format != InputFormat::Stanford {
+            return Ok(replace_token_lines(text, &tokens, sentence, self.format));
+        }
+
+        // Keep the enhanced dependencies.
+        for (offset, token) in sentence.iter().enumerate() {
+            graph.0[NodeIndex::new(offset)].token = token.clone();
+        }
+
+        self.format_graph(comments, &graph)
+    }
+
+    fn format_graph(
+        &self,
+        mut text: String,
+        graph: &DependencyGraph,
+    ) -> Result<String, ViewerError> {
+        let mut data = Vec::new();
+        write_graph(&mut data, graph, self.format)?;
+        text.push_str(&String::from_utf8(data)?);
+
+        Ok(text)
+    }
+}
+
+/// Replace the columns of the tokens that differ between `original` and
+/// `sentence`, which have the same length.
+fn replace_token_lines(
+    text: &str,
+    original: &[Token],
+    sentence: &[Token],
+    format: InputFormat,
+) -> String {
+    let mut tokens = original.iter().zip(sentence);
+    let mut replaced = String::new();
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(&['\r', '\n'][..]);
+        let mut fields: Vec<_> = content.split('\t').map(ToOwned::to_owned).collect();
+
+        // Comments, multiword tokens and empty nodes are kept.
+        if line.starts_with('#') || fields[0].parse::<usize>().is_err() {
+            replaced.push_str(line);
+            continue;
+        }
+
+        let (original, token) = match tokens.next() {
+            Some(tokens) => tokens,
+            None => {
+                replaced.push_str(line);
+                continue;
+            }
+        };
+
+        if original == token {
+            replaced.push_str(line);
+            continue;
+        }
+
+        let features = format_string_field(token.features().map(|features| features.as_str()));
+        let head = format_numeric_field(token.head());
+        let columns = if format == InputFormat::Conll2009 {
+            vec![
+                (1, token.form()),
+                (2, format_string_field(token.lemma())),
+                (4, format_string_field(token.pos().or_else(|| token.cpos()))),
+                (6, features),
+                (8, &head),
+                (10, format_string_field(token.head_rel())),
+            ]
+        } else {
+            vec![
+                (1, token.form()),
+                (2, format_string_field(token.lemma())),
+                (3, format_string_field(token.cpos())),
+                (4, format_string_field(token.pos())),
+                (5, features),
+                (6, &head),
+                (7, format_string_field(token.head_rel())),
+            ]
+        };
+
+        for (column, value) in columns {
+            if fields.len() <= column {
+                fields.resize(column + 1, "_".to_owned());
+            }
+
+            fields[column] = value.to_owned();
+        }
+
+        replaced.push_str(&fields.join("\t"));
+        replaced.push_str(&line[content.len()..]);
+    }
+
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Sentence;
+
+    use input::{read_graphs, InputFormat};
+
+    use super::TreebankText;
+
+    fn sentences(text: &str, format: InputFormat) -> Vec<Sentence> {
+        read_graphs(text.as_bytes(), format)
+            .map(|graph| graph.unwrap().to_sentence())
+            .collect()
+    }
+
+    fn round_trip(text: &str, format: InputFormat, sentences: &[Sentence]) -> String {
+        let mut data = Vec::new();
+        TreebankText::read(text.as_bytes(), format)
+            .unwrap()
+            .write(&mut data, sentences, |_| false)
+            .unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn unchanged_conllx_is_identical() {
+        let text = "\n1\tDas\tdas\tART\tART\t_\t2\tDET\t_\t_\textra\r\n\
+                    2\tHaus\thaus\tN\tNN\t_\t0\tROOT\t_\t_\textra\r\n\
+                    \r\n\n  \n\
+                    1\tGut\tgut\tADJ\tADJD\t_\t0\tROOT\t_\t_";
+        let sentences = sentences(text, InputFormat::Conllx);
+        assert_eq!(sentences.len(), 2);
+
+        assert_eq!(round_trip(text, InputFormat::Conllx, &sentences), text);
+    }
+
+    #[test]
+    fn unchanged_conllu_is_identical() {
+        let text = "# sent_id = 1\n# text = Dámelo.\n\
+                    1-2\tDámelo\t_\t_\t_\t_\t_\t_\t_\tSpaceAfter=No\n\
+                    1\tDá\tdar\tVERB\t_\tMood=Imp\t0\troot\t0:root\t_\n\
+                    2\tmelo\tyo\tPRON\t_\t_\t1\tiobj\t1:iobj\t_\n\
+                    2.1\tlo\tél\tPRON\t_\t_\t_\t_\t1:obj\t_\n\
+                    3\t.\t.\tPUNCT\t_\t_\t1\tpunct\t1:punct\tLen=1\n\n";
+        let sentences = sentences(text, InputFormat::Conllu);
+
+        assert_eq!(round_trip(text, InputFormat::Conllu, &sentences), text);
+    }
+
+    #[test]
+    fn only_changed_token_lines_are_rewritten() {
+        let text = "# sent_id = 1\n\
+                    1\tI\tI\tPRON\tPRP\t_\t2\tnsubj\t2:nsubj\tAlign=0\n\
+                    2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t0:root\tAlign=1\n\n\
+                    # sent_id = 2\n\
+                    1\tHi\thi\tINTJ\tUH\t_\t0\troot\t0:root\t_\n\n";
+        let mut sentences = sentences(text, InputFormat::Conllu);
+        sentences[0][0].set_head_rel(Some("expl"));
+
+        assert_eq!(
+            round_trip(text, InputFormat::Conllu, &sentences),
+            "# sent_id = 1\n\
+             1\tI\tI\tPRON\tPRP\t_\t2\texpl\t2:nsubj\tAlign=0\n\
+             2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t0:root\tAlign=1\n\n\
+             # sent_id = 2\n\
+             1\tHi\thi\tINTJ\tUH\t_\t0\troot\t0:root\t_\n\n"
+        );
+    }
+
+    #[test]
+    fn changed_conll2009_keeps_predicted_columns() {
+        let text = "# doc\n\
+                    1\tGo\tgo\tgo\tVB\tVB\t_\t_\t0\t0\tROOT\tROOT\tY\tgo.01\n\
+                    2\t!\t!\t!\t.\t.\t_\t_\t1\t1\tP\tP\t_\t_\n";
+        let mut sentences = sentences(text, InputFormat::Conll2009);
+        sentences[0][1].set_head_rel(Some("PUNCT"));
+
+        assert_eq!(
+            round_trip(text, InputFormat::Conll2009, &sentences),
+            "# doc\n\
+             1\tGo\tgo\tgo\tVB\tVB\t_\t_\t0\t0\tROOT\tROOT\tY\tgo.01\n\
+             2\t!\t!\t!\t.\t.\t_\t_\t1\t1\tPUNCT\tP\t_\t_\n"
+        );
+    }
+
+    #[test]
+    fn added_sentences_are_appended() {
+        let text = "1\tJa\tja\tITJ\tITJ\t_\t0\tROOT\t_\t_";
+        let mut sentences = sentences(text, InputFormat::Conllx);
+        sentences.push(sentences[0].clone());
+
+        assert_eq!(
+            round_trip(text, InputFormat::Conllx, &sentences),
+            "1\tJa\tja\tITJ\tITJ\t_\t0\tROOT\t_\t_\n\n\
+             1\tJa\tja\tITJ\tITJ\t_\t0\tROOT\t_\t_\n"
+        );
+    }
+
+    #[test]
+    fn removed_sentences_are_left_out() {
+        let text = "1\tA\ta\tN\tN\t_\t0\tROOT\t_\t_\n\n\
+                    1\tB\tb\tN\tN\t_\t0\tROOT\t_\t_\n\n\
+                    1\tC\tc\tN\tN\t_\t0\tROOT\t_\t_\n";
+        let sentences = sentences(text, InputFormat::Conllx);
+
+        let mut data = Vec::new();
+        TreebankText::read(text.as_bytes(), InputFormat::Conllx)
+            .unwrap()
+            .write(&mut data, &sentences, |idx| idx != 1)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "1\tB\tb\tN\tN\t_\t0\tROOT\t_\t_\n"
+        );
+    }
+
+    #[test]
+    fn notes_are_written_as_comments() {
+        let text = "# note = old\n1\tA\ta\tN\tN\t_\t0\tROOT\t_\t_\n\n\
+                    1\tB\tb\tN\tN\t_\t0\tROOT\t_\t_\n";
+        let sentences = sentences(text, InputFormat::Conllu);
+
+        let mut treebank = TreebankText::read(text.as_bytes(), InputFormat::Conllu).unwrap();
+        treebank.set_notes(vec![(1, "new".to_owned())].into_iter().collect());
+        let mut data = Vec::new();
+        treebank.write(&mut data, &sentences, |_| false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "1\tA\ta\tN\tN\t_\t0\tROOT\t_\t_\n\n\
+             # note = new\n1\tB\tb\tN\tN\t_\t0\tROOT\t_\t_\n"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use conllx::Sentence;
+use rayon::prelude::*;
+
+use diff::diff_sentence;
+use error::ViewerError;
+use graph::DependencyGraph;
+use layer::{Layer, LayerValue};
+use storage::TreebankStorage;
+use symbol::Symbol;
+
+/// Options that control how query values are compared to tokens.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MatchOptions {
+    /// Ignore differences in case.
+    pub case_insensitive: bool,
+
+    /// Ignore diacritics, so that e.g. `Kase` matches `Käse`.
+    pub diacritic_insensitive: bool,
+
+    /// Also compare form values to the lemma, so that inflected forms
+    /// are found by searching for their lemma.
+    pub lemma_fallback: bool,
+}
+
+/// A query that matches tokens.
+///
+/// A query consists of whitespace-separated terms, that must all match
+/// the same token. A term is either a word form or a `layer=value` pair,
+/// such as `lemma=gehen pos=VVFIN`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Query {
+    terms: Vec<(Layer, String)>,
+}
+
+impl FromStr for Query {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut terms = Vec::new();
+
+        for term in s.split_whitespace() {
+            let mut parts = term.splitn(2, '=');
+            let (layer, value) = match (parts.next(), parts.next()) {
+                (Some(layer), Some(value)) => (layer.parse()?, value),
+                _ => (Layer::Form, term),
+            };
+
+            if value.is_empty() {
+                return Err(ViewerError::InvalidQuery(s.to_owned()));
+            }
+
+            terms.push((layer, value.to_owned()));
+        }
+
+        if terms.is_empty() {
+            return Err(ViewerError::InvalidQuery(s.to_owned()));
+        }
+
+        Ok(Query { terms })
+    }
+}
+
+impl Query {
+    /// The terms of the query as (layer, value) pairs.
+    pub fn terms(&self) -> &[(Layer, String)] {
+        &self.terms
+    }
+
+    /// Prepare the query for matching tokens with the given options.
+    pub fn prepare(&self, options: MatchOptions) -> PreparedQuery {
+        let exact = !options.case_insensitive && !options.diacritic_insensitive;
+
+        PreparedQuery {
+            terms: self
+                .terms
+                .iter()
+                .map(|&(layer, ref value)| PreparedTerm {
+                    layer,
+                    value: normalize(value, options),
+                    symbol: if exact && layer.is_interned() {
+                        Symbol::lookup(value)
+                    } else {
+                        None
+                    },
+                })
+                .collect(),
+            options,
+        }
+    }
+}
+
+struct PreparedTerm {
+    layer: Layer,
+
+    /// The normalized value.
+    value: String,
+
+    /// The value as a symbol, when values of the layer are interned and
+    /// compared exactly. Values are not interned for the query: a value
+    /// that was never interned is compared as a string, and cannot match
+    /// the interned values of an arena.
+    symbol: Option<Symbol>,
+}
+
+/// A query with its values normalized for the match options. Values of
+/// interned layers are compared as symbols when possible.
+pub struct PreparedQuery {
+    terms: Vec<PreparedTerm>,
+    options: MatchOptions,
+}
+
+impl PreparedQuery {
+    /// Check whether a token matches all terms of the query.
+    pub fn matches<T>(&self, token: &T) -> bool
+    where
+        T: LayerValue,
+    {
+        let options = self.options;
+
+        self.terms.iter().all(|term| {
+            if let Some(symbol) = term.symbol {
+                return token.symbol(term.layer) == Some(symbol);
+            }
+
+            let matches = |layer| {
+                token
+                    .value(layer)
+                    .map(|token_value| normalize(token_value, options) == term.value)
+                    .unwrap_or(false)
+            };
+
+            matches(term.layer)
+                || (term.layer == Layer::Form && options.lemma_fallback && matches(Layer::Lemma))
+        })
+    }
+
+    /// Return the offsets of the tokens of a graph that match the query.
+    pub fn find(&self, graph: &DependencyGraph) -> Vec<usize> {
+        graph
+            .0
+            .node_indices()
+            .map(|idx| &graph.0[idx])
+            .filter(|node| self.matches(&node.token))
+            .map(|node| node.offset)
+            .collect()
+    }
+}
+
+/// A token that matches a query.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Match {
+    pub sentence: usize,
+    pub token: usize,
+}
+
+/// Orders of search results.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ranking {
+    /// Order of the treebank.
+    Document,
+
+    /// Matches in the shortest sentences first.
+    Shortest,
+
+    /// Matches of the least frequent lemmas (or forms, when the lemma is
+    /// absent) first.
+    RarestLemma,
+
+    /// Matches in the sentences with the most changed tokens compared to
+    /// the revision first.
+    Errors,
+}
+
+impl FromStr for Ranking {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "document" => Ok(Ranking::Document),
+            "shortest" => Ok(Ranking::Shortest),
+            "rarest-lemma" => Ok(Ranking::RarestLemma),
+            "errors" => Ok(Ranking::Errors),
+            _ => Err(ViewerError::UnknownRanking(s.to_owned())),
+        }
+    }
+}
+
+/// Order matches by a ranking. Matches that rank equally are kept in the
+/// order of the treebank. Ranking by errors requires the revision that
+/// the trees are compared against.
+pub fn rank(
+    matches: &mut [Match],
+    ranking: Ranking,
+    storage: &dyn TreebankStorage,
+    revision: Option<&[Sentence]>,
+) {
+    matches.sort();
+
+    match ranking {
+        Ranking::Document => (),
+        Ranking::Shortest => matches.sort_by_cached_key(|m| storage.n_tokens(m.sentence)),
+        Ranking::RarestLemma => {
+            let freqs = (0..storage.len())
+                .into_par_iter()
+                .map(|sentence| {
+                    let mut freqs = HashMap::new();
+                    for lemma in storage.lemmas(sentence).unwrap_or_default() {
+                        *freqs.entry(lemma).or_insert(0) += 1;
+                    }
+                    freqs
+                })
+                .reduce(HashMap::new, |mut freqs, other| {
+                    for (lemma, freq) in other {
+                        *freqs.entry(lemma).or_insert(0) += freq;
+                    }
+                    freqs
+                });
+
+            matches.sort_by_cached_key(|m| {
+                storage
+                    .lemmas(m.sentence)
+                    .and_then(|lemmas| freqs.get(lemmas.get(m.token)?).cloned())
+                    .unwrap_or(0)
+            });
+        }
+        Ranking::Errors => {
+            let revision = match revision {
+                Some(revision) => revision,
+                None => return,
+            };
+
+            let mut sentences: Vec<_> = matches.iter().map(|m| m.sentence).collect();
+            sentences.dedup();
+
+            let errors: HashMap<_, _> = sentences
+                .into_par_iter()
+                .map(|idx| {
+                    let n_errors = revision
+                        .get(idx)
+                        .and_then(|sentence| Some((sentence, storage.sentence(idx)?)))
+                        .and_then(|(sentence, current)| {
+                            diff_sentence(idx, sentence, &current).unwrap_or(None)
+                        })
+                        .map(|diff| diff.tokens.len())
+                        .unwrap_or(0);
+                    (idx, n_errors)
+                })
+                .collect();
+
+            matches.sort_by_key(|m| Reverse(errors[&m.sentence]));
+        }
+    }
+}
+
+/// Normalize a value for comparison.
+pub fn normalize(s: &str, options: MatchOptions) -> String {
+    let mut normalized = if options.diacritic_insensitive {
+        s.chars().filter_map(strip_diacritic).collect()
+    } else {
+        s.to_owned()
+    };
+
+    if options.case_insensitive {
+        normalized = normalized.to_lowercase();
+    }
+
+    normalized
+}
+
+/// Replace a letter with diacritics by its base letter. Combining
+/// diacritics are removed. Only Latin letters are supported.
+fn strip_diacritic(c: char) -> Option<char> {
+    const FOLDS: &[(&str, char)] = &[
+        ("ÀÁÂÃÄÅĀĂĄ", 'A'),
+        ("àáâãäåāăą", 'a'),
+        ("ÇĆĈĊČ", 'C'),
+        ("çćĉċč", 'c'),
+        ("ĎĐ", 'D'),
+        ("ďđ", 'd'),
+        ("ÈÉÊËĒĔĖĘĚ", 'E'),
+        ("èéêëēĕėęě", 'e'),
+        ("ĜĞĠĢ", 'G'),
+        ("ĝğġģ", 'g'),
+        ("ĤĦ", 'H'),
+        ("ĥħ", 'h'),
+        ("ÌÍÎÏĨĪĬĮİ", 'I'),
+        ("ìíîïĩīĭįı", 'i'),
+        ("Ĵ", 'J'),
+        ("ĵ", 'j'),
+        ("Ķ", 'K'),
+        ("ķ", 'k'),
+        ("ĹĻĽĿŁ", 'L'),
+        ("ĺļľŀł", 'l'),
+        ("ÑŃŅŇ", 'N'),
+        ("ñńņň", 'n'),
+        ("ÒÓÔÕÖØŌŎŐ", 'O'),
+        ("òóôõöøōŏő", 'o'),
+        ("ŔŖŘ", 'R'),
+        ("ŕŗř", 'r'),
+        ("ŚŜŞŠ", 'S'),
+        ("śŝşš", 's'),
+        ("ŢŤŦ", 'T'),
+        ("ţťŧ", 't'),
+        ("ÙÚÛÜŨŪŬŮŰŲ", 'U'),
+        ("ùúûüũūŭůűų", 'u'),
+        ("Ŵ", 'W'),
+        ("ŵ", 'w'),
+        ("ÝŶŸ", 'Y'),
+        ("ýÿŷ", 'y'),
+        ("ŹŻŽ", 'Z'),
+        ("źżž", 'z'),
+    ];
+
+    // Combining diacritical marks.
+    if ('\u{300}'..='\u{36f}').contains(&c) {
+        return None;
+    }
+
+    Some(
+        FOLDS
+            .iter()
+            .find(|&&(letters, _)| letters.contains(c))
+            .map(|&(_, base)| base)
+            .unwrap_or(c),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::{normalize, MatchOptions, Query};
+    use symbol::Symbol;
+
+    fn token(form: &str, pos: &str) -> Token {
+        let mut token = Token::new(form);
+        token.set_pos(Some(pos));
+        token
+    }
+
+    #[test]
+    fn values_are_normalized_by_the_options() {
+        let case = MatchOptions {
+            case_insensitive: true,
+            ..MatchOptions::default()
+        };
+        let diacritics = MatchOptions {
+            diacritic_insensitive: true,
+            ..MatchOptions::default()
+        };
+        let both = MatchOptions {
+            case_insensitive: true,
+            diacritic_insensitive: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(normalize("Käse", MatchOptions::default()), "Käse");
+        assert_eq!(normalize("Käse", case), "käse");
+        assert_eq!(normalize("Käse", diacritics), "Kase");
+        assert_eq!(normalize("ÉCOLE", both), "ecole");
+    }
+
+    #[test]
+    fn diacritics_are_folded() {
+        let options = MatchOptions {
+            diacritic_insensitive: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(
+            normalize("Ångström Łódź Ŝtŭpo", options),
+            "Angstrom Lodz Stupo"
+        );
+        assert_eq!(normalize("e\u{301}te\u{308}", options), "ete");
+
+        // Letters without a base letter are kept.
+        assert_eq!(normalize("Straße ß Æ Ω", options), "Straße ß Æ Ω");
+    }
+
+    #[test]
+    fn query_values_are_not_interned() {
+        let query: Query = "pos=search-test-never-interned".parse().unwrap();
+        let prepared = query.prepare(MatchOptions::default());
+        assert!(prepared.matches(&token("a", "search-test-never-interned")));
+        assert!(!prepared.matches(&token("a", "search-test-NN")));
+        assert_eq!(Symbol::lookup("search-test-never-interned"), None);
+
+        Symbol::intern("search-test-NN");
+        let prepared = "pos=search-test-NN"
+            .parse::<Query>()
+            .unwrap()
+            .prepare(MatchOptions::default());
+        assert!(prepared.matches(&token("a", "search-test-NN")));
+        assert!(!prepared.matches(&token("a", "search-test-NE")));
+    }
+
+    #[test]
+    fn insensitive_queries_match_folded_values() {
+        let options = MatchOptions {
+            case_insensitive: true,
+            diacritic_insensitive: true,
+            lemma_fallback: false,
+        };
+        let prepared = "kase pos=nn".parse::<Query>().unwrap().prepare(options);
+        assert!(prepared.matches(&token("KÄSE", "NN")));
+        assert!(!prepared.matches(&token("Kasse", "NN")));
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+
+use error::{ResultExt, ViewerError};
+use graph::{DependencyGraph, Diagram, Dot, Png, RenderOptions, Svg, Tikz};
+use input::{read_sentence, InputFormat};
+use style::sentence_options;
+
+/// Image format of rendered trees.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImageFormat {
+    /// A plain-text arc diagram.
+    Diagram,
+    Dot,
+    Png,
+    Svg,
+    Tikz,
+}
+
+impl ImageFormat {
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Diagram => "txt",
+            ImageFormat::Dot => "dot",
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+            ImageFormat::Tikz => "tikz",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "diagram" => Ok(ImageFormat::Diagram),
+            "dot" => Ok(ImageFormat::Dot),
+            "png" => Ok(ImageFormat::Png),
+            "svg" => Ok(ImageFormat::Svg),
+            "tikz" => Ok(ImageFormat::Tikz),
+            _ => Err(ViewerError::UnknownImageFormat(s.to_owned())),
+        }
+    }
+}
+
+/// Render the first sentence of a CoNLL text as an image. The format of
+/// the text is detected when `format` is `None`. The style comments of
+/// the sentence override `options`.
+pub fn render_sentence(
+    text: &str,
+    format: Option<InputFormat>,
+    image_format: ImageFormat,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, ViewerError> {
+    let graph = read_sentence(text, format)?;
+    let options = &sentence_options(text, options)?;
+
+    render_graph(&graph, image_format, options)
+}
+
+/// Render a tree as an image.
+pub fn render_graph(
+    graph: &DependencyGraph,
+    image_format: ImageFormat,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, ViewerError> {
+    match image_format {
+        ImageFormat::Diagram => Ok(format!("{}\n", graph.diagram(options)).into_bytes()),
+        ImageFormat::Dot => Ok(graph.dot(options)?.into_bytes()),
+        ImageFormat::Png => graph.png(options),
+        ImageFormat::Svg => Ok(graph.svg(options)?.into_bytes()),
+        ImageFormat::Tikz => Ok(graph.tikz(options)?.into_bytes()),
+    }
+}
+
+/// Render the sentence that is read from `read` and write the image to
+/// `write`.
+pub fn render_request<R, W>(
+    mut read: R,
+    mut write: W,
+    format: Option<InputFormat>,
+    image_format: ImageFormat,
+    options: &RenderOptions,
+) -> Result<(), ViewerError>
+where
+    R: Read,
+    W: Write,
+{
+    let mut text = String::new();
+    read.read_to_string(&mut text)
+        .context("Cannot read sentence")?;
+
+    let image = render_sentence(&text, format, image_format, options)?;
+    write.write_all(&image).context("Cannot write image")?;
+    write.flush()?;
+
+    Ok(())
+}
+
+/// Serve rendering requests on a Unix socket.
+///
+/// A client sends a sentence, shuts down the writing half of its
+/// connection and then reads the image until the connection is closed.
+/// When a sentence cannot be rendered, the connection is closed without
+/// any data and the error is printed on the standard error.
+pub fn serve(
+    path: &Path,
+    format: Option<InputFormat>,
+    image_format: ImageFormat,
+    options: &RenderOptions,
+) -> Result<(), ViewerError> {
+    let listener = UnixListener::bind(path).context("Cannot bind socket")?;
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Cannot accept connection: {}", err);
+                continue;
+            }
+        };
+
+        let options = options.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_connection(stream, format, image_format, &options) {
+                eprintln!("Cannot render sentence: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    format: Option<InputFormat>,
+    image_format: ImageFormat,
+    options: &RenderOptions,
+) -> Result<(), ViewerError> {
+    let result = render_request(&stream, &stream, format, image_format, options);
+    match stream.shutdown(Shutdown::Both) {
+        // The client may have closed the connection already.
+        Err(ref err) if err.kind() == io::ErrorKind::NotConnected => (),
+        other => other?,
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_sentence, ImageFormat};
+    use graph::RenderOptions;
+    use input::InputFormat;
+
+    #[test]
+    fn sentences_are_rendered_as_dot_and_tikz() {
+        let text = "1\tthe\t_\t_\t_\t_\t2\tdet\t_\t_\n2\tdog\t_\t_\t_\t_\t0\troot\t_\t_\n";
+        assert_eq!("tikz".parse::<ImageFormat>().unwrap(), ImageFormat::Tikz);
+
+        let dot = render_sentence(
+            text,
+            Some(InputFormat::Conllx),
+            "dot".parse().unwrap(),
+            &RenderOptions::default(),
+        )
+        .unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("dog"));
+
+        let tikz = render_sentence(
+            text,
+            Some(InputFormat::Conllx),
+            ImageFormat::Tikz,
+            &RenderOptions::default(),
+        )
+        .unwrap();
+        assert!(String::from_utf8(tikz).unwrap().contains("dog"));
+    }
+}
@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use petgraph::graph::NodeIndex;
+use rayon::prelude::*;
+
+use graph::DependencyGraph;
+use storage::TreebankStorage;
+
+/// A measure of the similarity of two trees, from `0` (nothing in
+/// common) to `1` (identical).
+pub trait Similarity: Sync {
+    fn similarity(&self, a: &DependencyGraph, b: &DependencyGraph) -> f64;
+}
+
+/// Similarity of the words of two trees: the Jaccard index of their
+/// (lowercased) lemmas, using the forms of tokens without a lemma.
+pub struct TokenOverlap;
+
+impl Similarity for TokenOverlap {
+    fn similarity(&self, a: &DependencyGraph, b: &DependencyGraph) -> f64 {
+        let (a, b) = (lemmas(a), lemmas(b));
+        let union = a.union(&b).count();
+        if union == 0 {
+            return 0.0;
+        }
+
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+fn lemmas(graph: &DependencyGraph) -> HashSet<String> {
+    graph
+        .0
+        .node_indices()
+        .map(|idx| {
+            let token = &graph.0[idx].token;
+            token.lemma().unwrap_or_else(|| token.form()).to_lowercase()
+        })
+        .collect()
+}
+
+/// Similarity of the structure of two trees, a tree kernel over their
+/// arcs and chains of two arcs.
+///
+/// An arc is described by the tags of the head and the dependent and
+/// its relation, a chain by the relations of a token and its head. The
+/// similarity is the cosine of the counts of the descriptions, so that
+/// trees with the same constructions are similar regardless of their
+/// words or length.
+pub struct TreeKernel;
+
+impl Similarity for TreeKernel {
+    fn similarity(&self, a: &DependencyGraph, b: &DependencyGraph) -> f64 {
+        let (a, b) = (fragments(a), fragments(b));
+        let norm = |counts: &HashMap<String, usize>| {
+            counts
+                .values()
+                .map(|&count| (count * count) as f64)
+                .sum::<f64>()
+                .sqrt()
+        };
+
+        let norms = norm(&a) * norm(&b);
+        if norms == 0.0 {
+            return 0.0;
+        }
+
+        let dot: usize = a
+            .iter()
+            .filter_map(|(fragment, count)| Some(count * b.get(fragment)?))
+            .sum();
+        dot as f64 / norms
+    }
+}
+
+/// Count the arcs and chains of a tree.
+fn fragments(graph: &DependencyGraph) -> HashMap<String, usize> {
+    let token = |offset: usize| &graph.0[NodeIndex::new(offset)].token;
+    let tag = |offset: usize| {
+        let token = token(offset);
+        token.cpos().or_else(|| token.pos()).unwrap_or("_")
+    };
+    let head = |offset: usize| {
+        token(offset)
+            .head()
+            .filter(|&head| head > 0 && head <= graph.0.node_count())
+            .map(|head| head - 1)
+    };
+
+    let mut counts = HashMap::new();
+    for offset in 0..graph.0.node_count() {
+        let rel = token(offset).head_rel().unwrap_or("_");
+        let arc = match head(offset) {
+            Some(head) => format!("{} {} {}", tag(head), rel, tag(offset)),
+            None => format!("ROOT {} {}", rel, tag(offset)),
+        };
+        *counts.entry(arc).or_insert(0) += 1;
+
+        if let Some(head) = head(offset) {
+            let chain = format!("{} / {}", token(head).head_rel().unwrap_or("_"), rel);
+            *counts.entry(chain).or_insert(0) += 1;
+        }
+    }
+
+    counts
+}
+
+/// Find the `n` trees among `candidates` that are most similar to
+/// `graph`, as (tree, similarity) pairs, the most similar first. Trees
+/// that have nothing in common with `graph` are left out.
+pub fn nearest(
+    storage: &dyn TreebankStorage,
+    candidates: Vec<usize>,
+    graph: &DependencyGraph,
+    similarity: &dyn Similarity,
+    n: usize,
+) -> Vec<(usize, f64)> {
+    let mut neighbors: Vec<_> = candidates
+        .into_par_iter()
+        .filter_map(|idx| {
+            let score = similarity.similarity(graph, &storage.graph(idx)?);
+            if score > 0.0 {
+                Some((idx, score))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    neighbors.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    neighbors.truncate(n);
+
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::{Similarity, TokenOverlap, TreeKernel};
+    use graph::DependencyGraph;
+
+    fn graph(tokens: &[(&str, &str, usize, &str)]) -> DependencyGraph {
+        DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, cpos, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_cpos(Some(cpos));
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn token_overlap_compares_words() {
+        let a = graph(&[
+            ("The", "DET", 2, "det"),
+            ("dog", "NOUN", 3, "nsubj"),
+            ("barks", "VERB", 0, "root"),
+        ]);
+        let b = graph(&[("the", "DET", 2, "det"), ("cat", "NOUN", 0, "root")]);
+
+        assert_eq!(TokenOverlap.similarity(&a, &a), 1.0);
+        assert_eq!(TokenOverlap.similarity(&a, &b), 1.0 / 4.0);
+    }
+
+    #[test]
+    fn tree_kernel_compares_structure() {
+        let a = graph(&[
+            ("The", "DET", 2, "det"),
+            ("dog", "NOUN", 3, "nsubj"),
+            ("barks", "VERB", 0, "root"),
+        ]);
+        let b = graph(&[
+            ("A", "DET", 2, "det"),
+            ("cat", "NOUN", 3, "nsubj"),
+            ("sleeps", "VERB", 0, "root"),
+        ]);
+        let c = graph(&[("Sleep", "VERB", 0, "root"), ("now", "ADV", 1, "advmod")]);
+
+        assert!((TreeKernel.similarity(&a, &b) - 1.0).abs() < 1e-9);
+        assert!(TreeKernel.similarity(&a, &c) < TreeKernel.similarity(&a, &b));
+        assert_eq!(TokenOverlap.similarity(&a, &b), 0.0);
+    }
+}
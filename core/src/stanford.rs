@@ -0,0 +1,205 @@
+use std::io::{BufRead, Write};
+
+use conllx::{Sentence, Token};
+use petgraph::graph::NodeIndex;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+
+/// Highest position of a word. Words are stored by position, so higher
+/// positions are rejected rather than allocated for.
+const MAX_POSITION: usize = 1 << 16;
+
+/// A word of a Stanford dependency, as its form and 1-based position.
+type Word<'a> = (&'a str, usize);
+
+/// Reader for Stanford dependencies, such as `nsubj(saw-2, John-1)`.
+///
+/// When a word has several heads, as in the collapsed and CC-processed
+/// representations, the first dependency is read as the head of the
+/// word and the other dependencies as enhanced dependencies. Copy nodes
+/// (`saw-2'`) are skipped. Words that do not occur in any dependency
+/// get `_` as their form.
+pub struct StanfordReader<R> {
+    read: R,
+}
+
+impl<R: BufRead> StanfordReader<R> {
+    pub fn new(read: R) -> Self {
+        StanfordReader { read }
+    }
+
+    /// Read the next sentence. Returns `None` at the end of the input.
+    pub fn read_graph(&mut self) -> Result<Option<DependencyGraph>, ViewerError> {
+        let mut line = String::new();
+        let mut forms: Vec<Option<String>> = Vec::new();
+        let mut heads: Vec<Vec<(usize, String)>> = Vec::new();
+
+        loop {
+            line.clear();
+
+            if self.read.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let line = line.trim();
+
+            if line.is_empty() {
+                if forms.is_empty() {
+                    continue;
+                }
+
+                break;
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let (rel, head, dependent) = match parse_dependency(line) {
+                Some(Some(dependency)) => dependency,
+                Some(None) => continue,
+                None => return Err(ViewerError::InvalidDependency(line.to_owned())),
+            };
+
+            if dependent.1 == 0 || head.1 > MAX_POSITION || dependent.1 > MAX_POSITION {
+                return Err(ViewerError::InvalidDependency(line.to_owned()));
+            }
+
+            let n_words = head.1.max(dependent.1);
+            if forms.len() < n_words {
+                forms.resize(n_words, None);
+                heads.resize(n_words, Vec::new());
+            }
+
+            for &(form, position) in &[head, dependent] {
+                if position != 0 && forms[position - 1].is_none() {
+                    forms[position - 1] = Some(form.to_owned());
+                }
+            }
+
+            heads[dependent.1 - 1].push((head.1, rel.to_owned()));
+        }
+
+        if forms.is_empty() {
+            return Ok(None);
+        }
+
+        let mut tokens = Sentence::new();
+        for (form, heads) in forms.iter().zip(&heads) {
+            let mut token = Token::new(form.as_deref().unwrap_or("_"));
+            if let Some(&(head, ref rel)) = heads.first() {
+                token.set_head(Some(head));
+                token.set_head_rel(Some(rel.clone()));
+            }
+            tokens.push(token);
+        }
+
+        let mut graph = DependencyGraph::from(tokens);
+        for (offset, heads) in heads.into_iter().enumerate() {
+            graph.0[NodeIndex::new(offset)].enhanced = heads.into_iter().skip(1).collect();
+        }
+
+        Ok(Some(graph))
+    }
+}
+
+impl<R: BufRead> Iterator for StanfordReader<R> {
+    type Item = Result<DependencyGraph, ViewerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_graph() {
+            Ok(None) => None,
+            Ok(Some(graph)) => Some(Ok(graph)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Write a tree as Stanford dependencies, ordered by dependent. Enhanced
+/// dependencies that differ from the head of a word are written after
+/// its head. Words without a head are left out.
+pub fn write_graph<W>(write: &mut W, graph: &DependencyGraph) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    let sentence = graph.to_sentence();
+    let form = |position: usize| match position {
+        0 => "ROOT",
+        position => sentence.get(position - 1).map_or("_", Token::form),
+    };
+
+    for (offset, node) in graph.0.node_indices().map(|idx| &graph.0[idx]).enumerate() {
+        let head = match node.token.head() {
+            Some(head) => (head, node.token.head_rel().unwrap_or("dep")),
+            None => continue,
+        };
+
+        let enhanced = node
+            .enhanced
+            .iter()
+            .map(|&(head, ref rel)| (head, rel.as_str()))
+            .filter(|&dep| dep != head);
+        for (head, rel) in Some(head).into_iter().chain(enhanced) {
+            writeln!(
+                write,
+                "{}({}-{}, {}-{})",
+                rel,
+                form(head),
+                head,
+                node.token.form(),
+                offset + 1
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a dependency such as `nsubj(saw-2, John-1)` into its relation,
+/// head and dependent. Returns `None` when the line is not a dependency
+/// and `Some(None)` for a dependency between copy nodes.
+pub fn parse_dependency(line: &str) -> Option<Option<(&str, Word<'_>, Word<'_>)>> {
+    let open = line.find('(')?;
+    if open == 0 || !line.ends_with(')') {
+        return None;
+    }
+
+    let rel = &line[..open];
+    let args = &line[open + 1..line.len() - 1];
+
+    // Forms may contain ", ", so try every separator.
+    args.match_indices(", ")
+        .filter_map(|(sep, _)| Some((parse_word(&args[..sep])?, parse_word(&args[sep + 2..])?)))
+        .next()
+        .map(|((head, head_copy), (dependent, dependent_copy))| {
+            if head_copy || dependent_copy {
+                None
+            } else {
+                Some((rel, head, dependent))
+            }
+        })
+}
+
+/// Parse a word such as `saw-2`, returning whether it is a copy node.
+fn parse_word(word: &str) -> Option<(Word<'_>, bool)> {
+    let copy = word.ends_with('\'');
+    let word = word.trim_end_matches('\'');
+    let sep = word.rfind('-')?;
+    let position = word[sep + 1..].parse().ok()?;
+
+    Some(((&word[..sep], position), copy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StanfordReader;
+
+    #[test]
+    fn positions_out_of_range_are_rejected() {
+        let text = "nsubj(sleeps-2, John-1)\nroot(ROOT-0, sleeps-99999999999)\n";
+        let mut reader = StanfordReader::new(text.as_bytes());
+
+        assert!(reader.read_graph().is_err());
+    }
+}
@@ -0,0 +1,509 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use petgraph::graph::NodeIndex;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use layer::{Layer, LayerValue};
+use symbol::Symbol;
+
+/// Frequencies of the values of an annotation layer.
+#[derive(Clone, Debug, Default)]
+pub struct Distribution {
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+impl Distribution {
+    pub fn new() -> Self {
+        Distribution::default()
+    }
+
+    pub fn add(&mut self, value: &str) {
+        *self.counts.entry(value.to_owned()).or_insert(0) += 1;
+        self.total += 1;
+    }
+
+    pub fn count(&self, value: &str) -> usize {
+        self.counts.get(value).cloned().unwrap_or(0)
+    }
+
+    /// The relative frequency of a value, `0` when the distribution is
+    /// empty.
+    pub fn frequency(&self, value: &str) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.count(value) as f64 / self.total as f64
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// The number of values that were added.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The distinct values, the most frequent first.
+    pub fn values(&self) -> Vec<&str> {
+        let mut values: Vec<_> = self.counts.keys().map(String::as_str).collect();
+        values.sort_by(|a, b| self.count(b).cmp(&self.count(a)).then(a.cmp(b)));
+        values
+    }
+}
+
+/// Summary statistics of a treebank: its size and the distributions of
+/// annotation layers.
+pub struct TreebankStats {
+    pub sentences: usize,
+    pub tokens: usize,
+    pub distributions: Vec<(Layer, Distribution)>,
+}
+
+impl TreebankStats {
+    /// Collect statistics of the given layers.
+    pub fn new(layers: &[Layer]) -> Self {
+        TreebankStats {
+            sentences: 0,
+            tokens: 0,
+            distributions: layers
+                .iter()
+                .map(|&layer| (layer, Distribution::new()))
+                .collect(),
+        }
+    }
+
+    pub fn add(&mut self, graph: &DependencyGraph) {
+        self.sentences += 1;
+
+        for idx in graph.0.node_indices() {
+            let token = &graph.0[idx].token;
+            self.tokens += 1;
+
+            for &mut (layer, ref mut distribution) in &mut self.distributions {
+                if let Some(value) = token.value(layer) {
+                    distribution.add(value);
+                }
+            }
+        }
+    }
+}
+
+/// Statistics of a single tree.
+#[derive(Clone, Debug, Default)]
+pub struct SentenceStats {
+    /// Number of arcs from the root to the deepest token.
+    pub depth: usize,
+
+    /// The largest distance between a head and its dependent, in tokens.
+    pub max_dependency_length: usize,
+
+    /// Number of arcs that span a token that is not dominated by the
+    /// head of the arc.
+    pub non_projective: usize,
+
+    pub relations: Distribution,
+}
+
+impl SentenceStats {
+    pub fn new(graph: &DependencyGraph) -> Self {
+        let heads = heads(graph);
+
+        let mut stats = SentenceStats {
+            depth: depth(&heads),
+            ..SentenceStats::default()
+        };
+        for (dependent, &head) in heads.iter().enumerate() {
+            if let Some(rel) = graph.0[NodeIndex::new(dependent)].token.head_rel() {
+                stats.relations.add(rel);
+            }
+
+            let head = ok_or!(head, continue);
+            let (start, end) = (head.min(dependent), head.max(dependent));
+            stats.max_dependency_length = stats.max_dependency_length.max(end - start);
+            if (start + 1..end)
+                .any(|between| !ancestors(&heads, between).any(|ancestor| ancestor == head))
+            {
+                stats.non_projective += 1;
+            }
+        }
+
+        stats
+    }
+}
+
+/// A summary of a tree, kept for every tree of a treebank to find trees
+/// by their properties.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SentenceSummary {
+    /// Number of tokens.
+    pub len: usize,
+
+    /// Number of arcs from the root to the deepest token.
+    pub depth: usize,
+
+    /// The relations of the tree, without duplicates.
+    pub relations: Vec<Symbol>,
+}
+
+impl SentenceSummary {
+    pub fn new(graph: &DependencyGraph) -> Self {
+        let mut relations: Vec<_> = graph
+            .0
+            .node_indices()
+            .filter_map(|idx| graph.0[idx].token.head_rel())
+            .map(Symbol::intern)
+            .collect();
+        relations.sort();
+        relations.dedup();
+
+        SentenceSummary {
+            len: graph.0.node_count(),
+            depth: depth(&heads(graph)),
+            relations,
+        }
+    }
+
+    pub fn has_relation(&self, rel: &str) -> bool {
+        self.relations
+            .iter()
+            .any(|relation| relation.as_str() == rel)
+    }
+}
+
+/// A range of sentence lengths in tokens, such as `5-15`. Either bound
+/// can be left out, as in `5-` or `-15`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LengthRange {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+impl LengthRange {
+    pub fn contains(&self, len: usize) -> bool {
+        self.min.is_none_or(|min| len >= min) && self.max.is_none_or(|max| len <= max)
+    }
+}
+
+impl FromStr for LengthRange {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ViewerError::InvalidLengthRange(s.to_owned());
+        let bound = |bound: &str| match bound.trim() {
+            "" => Ok(None),
+            bound => bound.parse().map(Some).map_err(|_| invalid()),
+        };
+
+        let range = match s.find('-') {
+            Some(idx) => LengthRange {
+                min: bound(&s[..idx])?,
+                max: bound(&s[idx + 1..])?,
+            },
+            None => {
+                let len = bound(s)?.ok_or_else(invalid)?;
+                LengthRange {
+                    min: Some(len),
+                    max: Some(len),
+                }
+            }
+        };
+
+        match range {
+            LengthRange {
+                min: Some(min),
+                max: Some(max),
+            } if max < min => Err(invalid()),
+            range => Ok(range),
+        }
+    }
+}
+
+impl fmt::Display for LengthRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => write!(f, "{}", min),
+            (min, max) => {
+                if let Some(min) = min {
+                    write!(f, "{}", min)?;
+                }
+                write!(f, "-")?;
+                if let Some(max) = max {
+                    write!(f, "{}", max)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The 0-based heads of the tokens of a tree, `None` for the root and
+/// invalid heads.
+fn heads(graph: &DependencyGraph) -> Vec<Option<usize>> {
+    let len = graph.0.node_count();
+    graph
+        .0
+        .node_indices()
+        .map(|idx| {
+            graph.0[idx]
+                .token
+                .head()
+                .filter(|&head| head > 0 && head <= len)
+                .map(|head| head - 1)
+        })
+        .collect()
+}
+
+/// Number of arcs from the root to the deepest token.
+fn depth(heads: &[Option<usize>]) -> usize {
+    (0..heads.len())
+        .map(|offset| ancestors(heads, offset).count() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The heads of a token up to the root. Cycles are cut off after
+/// visiting every token.
+fn ancestors<'a>(heads: &'a [Option<usize>], offset: usize) -> impl Iterator<Item = usize> + 'a {
+    let mut head = heads[offset];
+    (0..heads.len()).map_while(move |_| {
+        let ancestor = head?;
+        head = heads[ancestor];
+        Some(ancestor)
+    })
+}
+
+/// A value with its relative frequencies in two distributions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparedValue {
+    pub value: String,
+    pub frequencies: (f64, f64),
+}
+
+impl ComparedValue {
+    /// The change of the frequency relative to the first frequency,
+    /// `None` when the value only occurs in the second distribution.
+    pub fn relative_difference(&self) -> Option<f64> {
+        let (a, b) = self.frequencies;
+        if a == 0.0 {
+            None
+        } else {
+            Some((b - a) / a)
+        }
+    }
+
+    /// Check whether the frequency changed by more than `threshold`,
+    /// relative to the first frequency. Values that only occur in one
+    /// of the distributions always changed.
+    pub fn differs(&self, threshold: f64) -> bool {
+        match self.relative_difference() {
+            Some(difference) => difference.abs() > threshold,
+            None => true,
+        }
+    }
+}
+
+/// Compare two distributions. The values of both distributions are
+/// returned, the most frequent (summed over both) first.
+pub fn compare(a: &Distribution, b: &Distribution) -> Vec<ComparedValue> {
+    let mut values: Vec<_> = a
+        .counts
+        .keys()
+        .chain(
+            b.counts
+                .keys()
+                .filter(|value| !a.counts.contains_key(*value)),
+        )
+        .map(|value| ComparedValue {
+            value: value.clone(),
+            frequencies: (a.frequency(value), b.frequency(value)),
+        })
+        .collect();
+
+    values.sort_by(|x, y| {
+        let sum = |v: &ComparedValue| v.frequencies.0 + v.frequencies.1;
+        sum(y)
+            .partial_cmp(&sum(x))
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| x.value.cmp(&y.value))
+    });
+
+    values
+}
+
+/// Write the statistics of a treebank as tables of counts and relative
+/// frequencies.
+pub fn write_stats<W>(mut writer: W, stats: &TreebankStats) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    writeln!(writer, "{:<20} {:>10}", "sentences", stats.sentences)?;
+    writeln!(writer, "{:<20} {:>10}", "tokens", stats.tokens)?;
+
+    for &(layer, ref distribution) in stats.distributions.iter().filter(|(_, d)| !d.is_empty()) {
+        writeln!(writer)?;
+        writeln!(writer, "{:<20} {:>10} {:>8}", layer, "count", "%")?;
+        for value in distribution.values() {
+            writeln!(
+                writer,
+                "{:<20} {:>10} {:>7.2}%",
+                value,
+                distribution.count(value),
+                distribution.frequency(value) * 100.0
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the statistics of two treebanks side by side, with the change
+/// of the relative frequency of every value. Values that changed by more
+/// than `threshold` are marked with `*`.
+pub fn write_comparison<W>(
+    mut writer: W,
+    a: &TreebankStats,
+    b: &TreebankStats,
+    threshold: f64,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    writeln!(writer, "{:<20} {:>10} {:>10}", "", "A", "B")?;
+    writeln!(
+        writer,
+        "{:<20} {:>10} {:>10}",
+        "sentences", a.sentences, b.sentences
+    )?;
+    writeln!(writer, "{:<20} {:>10} {:>10}", "tokens", a.tokens, b.tokens)?;
+
+    for (&(layer, ref a), (_, b)) in a.distributions.iter().zip(&b.distributions) {
+        if a.is_empty() && b.is_empty() {
+            continue;
+        }
+
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{:<20} {:>10} {:>10} {:>10}",
+            layer, "A %", "B %", "change"
+        )?;
+        for value in compare(a, b) {
+            let change = match value.relative_difference() {
+                Some(difference) => format!("{:+.1}%", difference * 100.0),
+                None => "new".to_owned(),
+            };
+            let marker = if value.differs(threshold) { " *" } else { "" };
+            writeln!(
+                writer,
+                "{:<20} {:>9.2}% {:>9.2}% {:>10}{}",
+                value.value,
+                value.frequencies.0 * 100.0,
+                value.frequencies.1 * 100.0,
+                change,
+                marker
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::{compare, Distribution, LengthRange, SentenceStats};
+    use graph::DependencyGraph;
+
+    fn distribution(values: &[&str]) -> Distribution {
+        let mut distribution = Distribution::new();
+        for value in values {
+            distribution.add(value);
+        }
+
+        distribution
+    }
+
+    #[test]
+    fn compares_relative_frequencies() {
+        let a = distribution(&["NN", "NN", "NN", "VB"]);
+        let b = distribution(&["NN", "VB", "VB", "JJ"]);
+
+        let values = compare(&a, &b);
+        let names: Vec<_> = values.iter().map(|value| value.value.as_str()).collect();
+        assert_eq!(names, ["NN", "VB", "JJ"]);
+
+        assert_eq!(values[0].frequencies, (0.75, 0.25));
+        assert_eq!(values[0].relative_difference(), Some(-2.0 / 3.0));
+        assert_eq!(values[1].relative_difference(), Some(1.0));
+        assert_eq!(values[2].relative_difference(), None);
+    }
+
+    #[test]
+    fn marks_changes_over_threshold() {
+        let a = distribution(&["NN", "NN", "VB", "VB"]);
+        let b = distribution(&["NN", "NN", "NN", "NN", "NN", "VB", "VB", "VB", "VB", "VB"]);
+
+        assert!(compare(&a, &b).iter().all(|value| !value.differs(0.1)));
+        assert!(compare(&b, &a).iter().all(|value| !value.differs(0.1)));
+        assert!(compare(&a, &distribution(&["NN"]))
+            .iter()
+            .all(|value| value.differs(0.5)));
+    }
+
+    #[test]
+    fn sentence_stats() {
+        let tokens = [
+            ("A", 2, "det"),
+            ("hearing", 3, "nsubj"),
+            ("is", 0, "root"),
+            ("scheduled", 3, "xcomp"),
+            ("today", 2, "nmod"),
+        ];
+        let graph = DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let stats = SentenceStats::new(&graph);
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.max_dependency_length, 3);
+        assert_eq!(stats.non_projective, 1);
+        assert_eq!(stats.relations.total(), 5);
+        assert_eq!(stats.relations.count("nsubj"), 1);
+    }
+
+    #[test]
+    fn parses_length_ranges() {
+        let range: LengthRange = "5-15".parse().unwrap();
+        assert!(!range.contains(4));
+        assert!(range.contains(5));
+        assert!(range.contains(15));
+        assert!(!range.contains(16));
+
+        let range: LengthRange = "-15".parse().unwrap();
+        assert!(range.contains(1));
+        assert_eq!(range.to_string(), "-15");
+        assert_eq!("10".parse::<LengthRange>().unwrap().to_string(), "10");
+        assert_eq!(" 5 - ".parse::<LengthRange>().unwrap().to_string(), "5-");
+
+        assert!("15-5".parse::<LengthRange>().is_err());
+        assert!("five".parse::<LengthRange>().is_err());
+        assert!("".parse::<LengthRange>().is_err());
+    }
+}
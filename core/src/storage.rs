@@ -0,0 +1,50 @@
+use std::borrow::Cow;
+
+use conllx::Sentence;
+
+use graph::DependencyGraph;
+use search::PreparedQuery;
+
+/// Read access to the trees of a treebank, independent of how they are
+/// stored. Trees are read from several threads during searches.
+pub trait TreebankStorage: Sync {
+    /// Find the tokens of a tree that match a query.
+    fn find(&self, idx: usize, query: &PreparedQuery) -> Option<Vec<usize>> {
+        self.graph(idx).map(|graph| query.find(&graph))
+    }
+
+    /// Get a tree as a dependency graph.
+    fn graph(&self, idx: usize) -> Option<DependencyGraph>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the lemmas of a tree, using the forms of tokens without
+    /// a lemma.
+    fn lemmas(&self, idx: usize) -> Option<Vec<Cow<'_, str>>> {
+        let graph = self.graph(idx)?;
+        Some(
+            graph
+                .0
+                .node_indices()
+                .map(|node| {
+                    let token = &graph.0[node].token;
+                    Cow::Owned(token.lemma().unwrap_or_else(|| token.form()).to_owned())
+                })
+                .collect(),
+        )
+    }
+
+    fn len(&self) -> usize;
+
+    /// Get the number of tokens of a tree.
+    fn n_tokens(&self, idx: usize) -> Option<usize> {
+        self.graph(idx).map(|graph| graph.0.node_count())
+    }
+
+    /// Get a tree as CoNLL-X tokens.
+    fn sentence(&self, idx: usize) -> Option<Sentence> {
+        self.graph(idx).map(|graph| graph.to_sentence())
+    }
+}
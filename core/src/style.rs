@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use compress::decompress;
+use error::{ResultExt, ViewerError};
+use graph::RenderOptions;
+use layer::{parse_layers, Layer};
+use lazy::SentenceBlocks;
+use profile::Profile;
+
+/// Prefix of the comment lines with the style of a sentence.
+pub const STYLE_COMMENT: &str = "conllx-view-style:";
+
+/// Render settings of a single sentence, from its
+/// `# conllx-view-style:` comments, e.g.
+///
+/// ```text
+/// # conllx-view-style: rows=lemma; root; highlight=2,3; color=4:red
+/// ```
+///
+/// Tokens are numbered from 1, as in the ID column. Settings that are
+/// not given keep their value from the command line or the viewer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStyle {
+    pub basic: Option<bool>,
+    pub enhanced: Option<bool>,
+    pub projective_heads: Option<bool>,
+    pub root: Option<bool>,
+    pub profile: Option<Profile>,
+    pub rtl: Option<bool>,
+    pub rows: Option<Vec<Layer>>,
+
+    /// Offsets of the tokens whose subtrees are collapsed.
+    pub collapsed: Vec<usize>,
+
+    /// Offsets of the tokens that are highlighted.
+    pub highlighted: Vec<usize>,
+
+    /// Colors of word forms as (offset, color) pairs.
+    pub colors: Vec<(usize, String)>,
+}
+
+impl TreeStyle {
+    /// Read the style of a sentence from the style comments of its lines.
+    pub fn from_comments(block: &str) -> Result<Self, ViewerError> {
+        let mut style = TreeStyle::default();
+
+        for line in block.lines().take_while(|line| line.starts_with('#')) {
+            if let Some(settings) = line[1..].trim_start().strip_prefix(STYLE_COMMENT) {
+                style.parse(settings)?;
+            }
+        }
+
+        Ok(style)
+    }
+
+    /// Add the settings of a style comment, which are separated by
+    /// semicolons.
+    fn parse(&mut self, settings: &str) -> Result<(), ViewerError> {
+        for setting in settings.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = setting.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().map(str::trim);
+
+            match (key, value) {
+                ("basic", None) => self.basic = Some(true),
+                ("no-basic", None) => self.basic = Some(false),
+                ("enhanced", None) => self.enhanced = Some(true),
+                ("no-enhanced", None) => self.enhanced = Some(false),
+                ("projective-heads", None) => self.projective_heads = Some(true),
+                ("no-projective-heads", None) => self.projective_heads = Some(false),
+                ("root", None) => self.root = Some(true),
+                ("no-root", None) => self.root = Some(false),
+                ("rtl", None) => self.rtl = Some(true),
+                ("ltr", None) => self.rtl = Some(false),
+                ("lang", Some(lang)) => self.profile = Some(lang.parse()?),
+                ("rows", Some(rows)) => self.rows = Some(parse_layers(rows)?),
+                ("collapse", Some(tokens)) => self.collapsed.extend(parse_tokens(tokens)?),
+                ("highlight", Some(tokens)) => self.highlighted.extend(parse_tokens(tokens)?),
+                ("color", Some(colors)) => {
+                    for color in colors.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                        self.colors.push(parse_color(color)?);
+                    }
+                }
+                _ => return Err(ViewerError::InvalidStyle(setting.to_owned())),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Override render options with the settings of the style.
+    pub fn apply(&self, options: &mut RenderOptions) {
+        if let Some(basic) = self.basic {
+            options.basic = basic;
+        }
+        if let Some(enhanced) = self.enhanced {
+            options.enhanced = enhanced;
+        }
+        if let Some(projective_heads) = self.projective_heads {
+            options.projective_heads = projective_heads;
+        }
+        if let Some(root) = self.root {
+            options.root = root;
+        }
+        if let Some(profile) = self.profile {
+            options.profile = profile;
+        }
+        if let Some(rtl) = self.rtl {
+            options.profile.rtl = rtl;
+        }
+        if let Some(ref rows) = self.rows {
+            options.rows = rows.clone();
+        }
+
+        options.collapsed.extend(&self.collapsed);
+        options.highlighted.extend(&self.highlighted);
+        options.colors.extend(self.colors.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == TreeStyle::default()
+    }
+}
+
+/// Parse a comma-separated list of 1-based token numbers into offsets.
+fn parse_tokens(s: &str) -> Result<Vec<usize>, ViewerError> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| match t.parse::<usize>() {
+            Ok(id) if id > 0 => Ok(id - 1),
+            _ => Err(ViewerError::InvalidStyle(format!("invalid token: {}", t))),
+        })
+        .collect()
+}
+
+/// Parse a `TOKEN:COLOR` pair. Colors are names, such as `red`, or
+/// `#rrggbb` values.
+fn parse_color(s: &str) -> Result<(usize, String), ViewerError> {
+    let mut parts = s.splitn(2, ':');
+    let token = parse_tokens(parts.next().unwrap_or(""))?;
+    let color = parts.next().unwrap_or("").trim();
+
+    let valid = if let Some(hex) = color.strip_prefix('#') {
+        hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        !color.is_empty() && color.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+
+    if token.len() != 1 || !valid {
+        return Err(ViewerError::InvalidStyle(format!("invalid color: {}", s)));
+    }
+
+    Ok((token[0], color.to_owned()))
+}
+
+/// Get the render options of the first sentence of a CoNLL text, with
+/// the overrides of its style comments.
+pub fn sentence_options(text: &str, options: &RenderOptions) -> Result<RenderOptions, ViewerError> {
+    let mut options = options.clone();
+    TreeStyle::from_comments(text.trim_start())?.apply(&mut options);
+    Ok(options)
+}
+
+/// Read the styles of the sentences of a treebank, in treebank order.
+pub fn read_styles<R>(read: R) -> Result<Vec<TreeStyle>, ViewerError>
+where
+    R: BufRead,
+{
+    let mut styles = Vec::new();
+
+    for block in SentenceBlocks::new(read) {
+        let (_, block) = block?;
+        let style = TreeStyle::from_comments(&block)
+            .with_context(|| format!("Cannot read style of sentence {}", styles.len() + 1))?;
+        styles.push(style);
+    }
+
+    Ok(styles)
+}
+
+/// The styles of the sentences of treebank files, which are read again
+/// when a file changes.
+#[derive(Default)]
+pub struct StyleCache {
+    files: HashMap<PathBuf, (SystemTime, Rc<Vec<TreeStyle>>)>,
+}
+
+impl StyleCache {
+    pub fn new() -> Self {
+        StyleCache::default()
+    }
+
+    /// Get the style of the sentence with the given index in a treebank
+    /// file.
+    pub fn style(&mut self, path: &Path, idx: usize) -> Result<TreeStyle, ViewerError> {
+        let modified = fs::metadata(path)?.modified()?;
+
+        let styles = match self.files.get(path) {
+            Some(&(read, ref styles)) if read == modified => styles.clone(),
+            _ => {
+                let read = decompress(BufReader::new(File::open(path)?))?;
+                let styles = Rc::new(read_styles(read)?);
+                self.files
+                    .insert(path.to_owned(), (modified, styles.clone()));
+                styles
+            }
+        };
+
+        Ok(styles.get(idx).cloned().unwrap_or_default())
+    }
+}
+
+/// Toggle tokens in a list of collapsed subtrees: tokens that are
+/// collapsed are expanded, and the other way around.
+pub fn toggle_subtrees(collapsed: &[usize], toggled: &[usize]) -> Vec<usize> {
+    let mut result: Vec<_> = collapsed
+        .iter()
+        .cloned()
+        .filter(|token| !toggled.contains(token))
+        .collect();
+    result.extend(toggled.iter().filter(|token| !collapsed.contains(token)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::RenderOptions;
+    use layer::Layer;
+
+    use super::{read_styles, sentence_options, TreeStyle};
+
+    #[test]
+    fn style_comments_override_options() {
+        let block = "# sent_id = 1\n\
+                     # conllx-view-style: rows=lemma; root\n\
+                     #conllx-view-style: highlight=2,3; color=1:red, 2:#00ff00; collapse=3\n\
+                     1\tdogs\tdog\t_\t_\t_\t0\tROOT\t_\t_\n";
+
+        let style = TreeStyle::from_comments(block).unwrap();
+        let mut options = RenderOptions {
+            root: false,
+            ..RenderOptions::default()
+        };
+        style.apply(&mut options);
+
+        assert!(options.root);
+        assert!(options.basic);
+        assert_eq!(options.rows, vec![Layer::Lemma]);
+        assert_eq!(options.highlighted, vec![1, 2]);
+        assert_eq!(options.collapsed, vec![2]);
+        assert_eq!(
+            options.colors,
+            vec![(0, "red".to_owned()), (1, "#00ff00".to_owned())]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_styles() {
+        for style in &[
+            "zoom=2",
+            "highlight=0",
+            "color=1:\"red\"",
+            "color=red",
+            "root=yes",
+        ] {
+            let block = format!("# conllx-view-style: {}\n", style);
+            assert!(TreeStyle::from_comments(&block).is_err(), "{}", style);
+        }
+    }
+
+    #[test]
+    fn reads_styles_by_sentence() {
+        let text = "# conllx-view-style: root\n\
+                    1\ta\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                    \n\
+                    1\tb\t_\t_\t_\t_\t0\tROOT\t_\t_\n";
+
+        let styles = read_styles(text.as_bytes()).unwrap();
+        assert_eq!(styles.len(), 2);
+        assert_eq!(styles[0].root, Some(true));
+        assert!(styles[1].is_empty());
+    }
+
+    #[test]
+    fn sentence_options_use_first_sentence() {
+        let text = "\n# conllx-view-style: no-basic\n\
+                    1\ta\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                    \n\
+                    # conllx-view-style: root\n\
+                    1\tb\t_\t_\t_\t_\t0\tROOT\t_\t_\n";
+
+        let options = sentence_options(text, &RenderOptions::default()).unwrap();
+        assert!(!options.basic);
+        assert!(!options.root);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use rayon::prelude::*;
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use search::Match;
+use storage::TreebankStorage;
+
+/// A dependency subtree pattern with its frequency.
+///
+/// A pattern is the tag of a token followed by its dependents, as
+/// `(relation pattern)`, down to `depth` arcs below the token. The
+/// dependents are in a canonical order, so that the same construction
+/// has the same pattern regardless of word order, e.g.
+/// `VERB (nsubj NOUN (det DET)) (obj NOUN)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubtreePattern {
+    pub pattern: String,
+
+    /// Number of arcs from the token to its deepest dependents in the
+    /// pattern.
+    pub depth: usize,
+
+    /// Number of tokens that head the pattern.
+    pub count: usize,
+}
+
+/// The tag of a token: its coarse-grained tag, or otherwise its
+/// fine-grained tag.
+fn tag(graph: &DependencyGraph, idx: NodeIndex) -> &str {
+    let token = &graph.0[idx].token;
+    token.cpos().or_else(|| token.pos()).unwrap_or("_")
+}
+
+/// The pattern of the subtree of a token down to `depth` arcs, with the
+/// number of arcs to the deepest dependents of the pattern.
+fn pattern(graph: &DependencyGraph, idx: NodeIndex, depth: usize) -> (String, usize) {
+    if depth == 0 {
+        return (tag(graph, idx).to_owned(), 0);
+    }
+
+    let mut height = 0;
+    let mut dependents: Vec<_> = graph
+        .0
+        .edges_directed(idx, Direction::Outgoing)
+        .map(|edge| {
+            let (dependent, dependent_height) = pattern(graph, edge.target(), depth - 1);
+            height = height.max(dependent_height + 1);
+            format!("({} {})", edge.weight(), dependent)
+        })
+        .collect();
+    dependents.sort();
+
+    let mut pattern = tag(graph, idx).to_owned();
+    for dependent in dependents {
+        pattern.push(' ');
+        pattern.push_str(&dependent);
+    }
+
+    (pattern, height)
+}
+
+/// Get the patterns of the subtrees of a token, of depth 1 up to
+/// `max_depth`. Tokens without dependents have no patterns, and subtrees
+/// that are shallower than `max_depth` only have patterns up to their
+/// depth.
+fn token_patterns(
+    graph: &DependencyGraph,
+    idx: NodeIndex,
+    max_depth: usize,
+) -> Vec<(String, usize)> {
+    let mut patterns = Vec::new();
+    for depth in 1..=max_depth {
+        let (pattern, height) = pattern(graph, idx, depth);
+        if height < depth {
+            break;
+        }
+        patterns.push((pattern, height));
+    }
+
+    patterns
+}
+
+/// Count the subtree patterns of up to `max_depth` arcs in the given
+/// trees. Returns the patterns that occur at least `min_count` times,
+/// the most frequent first.
+pub fn mine_subtrees(
+    storage: &dyn TreebankStorage,
+    sentences: Vec<usize>,
+    max_depth: usize,
+    min_count: usize,
+) -> Vec<SubtreePattern> {
+    let counts = sentences
+        .into_par_iter()
+        .filter_map(|sentence| storage.graph(sentence))
+        .map(|graph| {
+            let mut counts = HashMap::new();
+            for idx in graph.0.node_indices() {
+                for pattern in token_patterns(&graph, idx, max_depth) {
+                    *counts.entry(pattern).or_insert(0) += 1;
+                }
+            }
+            counts
+        })
+        .reduce(HashMap::new, |mut counts, other| {
+            for (pattern, count) in other {
+                *counts.entry(pattern).or_insert(0) += count;
+            }
+            counts
+        });
+
+    let mut patterns: Vec<_> = counts
+        .into_iter()
+        .filter(|&(_, count)| count >= min_count)
+        .map(|((pattern, depth), count)| SubtreePattern {
+            pattern,
+            depth,
+            count,
+        })
+        .collect();
+    patterns.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.pattern.cmp(&b.pattern))
+    });
+
+    patterns
+}
+
+/// Find the tokens that head a subtree pattern, in treebank order.
+pub fn pattern_instances(
+    storage: &dyn TreebankStorage,
+    sentences: Vec<usize>,
+    pattern: &SubtreePattern,
+) -> Vec<Match> {
+    let matches: Vec<Vec<Match>> = sentences
+        .into_par_iter()
+        .filter_map(|sentence| Some((sentence, storage.graph(sentence)?)))
+        .map(|(sentence, graph)| {
+            graph
+                .0
+                .node_indices()
+                .filter(|&idx| {
+                    let (instance, height) = self::pattern(&graph, idx, pattern.depth);
+                    height == pattern.depth && instance == pattern.pattern
+                })
+                .map(|idx| Match {
+                    sentence,
+                    token: idx.index(),
+                })
+                .collect()
+        })
+        .collect();
+
+    matches.into_iter().flatten().collect()
+}
+
+/// Write subtree patterns as a table of counts, depths and patterns.
+pub fn write_subtrees<W>(mut writer: W, patterns: &[SubtreePattern]) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    writeln!(writer, "{:>10} {:>5} pattern", "count", "depth")?;
+    for pattern in patterns {
+        writeln!(
+            writer,
+            "{:>10} {:>5} {}",
+            pattern.count, pattern.depth, pattern.pattern
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+    use petgraph::graph::NodeIndex;
+
+    use super::token_patterns;
+    use graph::DependencyGraph;
+
+    fn graph() -> DependencyGraph {
+        let tokens = [
+            ("the", "DET", 2, "det"),
+            ("dog", "NOUN", 3, "nsubj"),
+            ("chased", "VERB", 0, "root"),
+            ("cats", "NOUN", 3, "obj"),
+        ];
+
+        DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, cpos, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_cpos(Some(cpos));
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn patterns_grow_with_depth() {
+        let graph = graph();
+
+        assert_eq!(
+            token_patterns(&graph, NodeIndex::new(2), 3),
+            vec![
+                ("VERB (nsubj NOUN) (obj NOUN)".to_owned(), 1),
+                ("VERB (nsubj NOUN (det DET)) (obj NOUN)".to_owned(), 2),
+            ]
+        );
+        assert_eq!(
+            token_patterns(&graph, NodeIndex::new(1), 3),
+            vec![("NOUN (det DET)".to_owned(), 1)]
+        );
+        assert!(token_patterns(&graph, NodeIndex::new(0), 3).is_empty());
+    }
+}
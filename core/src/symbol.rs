@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref SYMBOLS: RwLock<SymbolTable> = RwLock::new(SymbolTable::default());
+}
+
+#[derive(Default)]
+struct SymbolTable {
+    ids: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+/// An interned part-of-speech tag or dependency relation.
+///
+/// These values come from small tag sets, so they are interned once for
+/// the whole program and never freed. Open-ended values, such as feature
+/// sets, are stored per treebank instead. Symbols are compared and hashed as
+/// integers. Their order is the order in which they were interned.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Symbol(NonZeroU32);
+
+impl Symbol {
+    /// Get the symbol of a string, interning the string when necessary.
+    pub fn intern(s: &str) -> Self {
+        if let Some(symbol) = Symbol::lookup(s) {
+            return symbol;
+        }
+
+        let mut table = SYMBOLS.write().unwrap();
+
+        // The string may have been interned since the read lock was
+        // released.
+        if let Some(&symbol) = table.ids.get(s) {
+            return symbol;
+        }
+
+        let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+        let symbol =
+            Symbol(NonZeroU32::new(table.strings.len() as u32 + 1).expect("Symbol table overflow"));
+        table.strings.push(s);
+        table.ids.insert(s, symbol);
+
+        symbol
+    }
+
+    /// Get the symbol of a string, `None` if the string was never
+    /// interned.
+    pub fn lookup(s: &str) -> Option<Self> {
+        SYMBOLS.read().unwrap().ids.get(s).cloned()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        SYMBOLS.read().unwrap().strings[self.0.get() as usize - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::Symbol;
+
+    #[test]
+    fn interning_is_idempotent() {
+        let nsubj = Symbol::intern("symbol-test-nsubj");
+        assert_eq!(Symbol::intern("symbol-test-nsubj"), nsubj);
+        assert_eq!(Symbol::intern(&String::from("symbol-test-nsubj")), nsubj);
+        assert_ne!(Symbol::intern("symbol-test-obj"), nsubj);
+
+        // Symbols are ordered by when they were interned.
+        assert!(Symbol::intern("symbol-test-obj") > nsubj);
+    }
+
+    #[test]
+    fn lookups_do_not_intern() {
+        assert_eq!(Symbol::lookup("symbol-test-never-interned"), None);
+        assert_eq!(Symbol::lookup("symbol-test-never-interned"), None);
+
+        let symbol = Symbol::intern("symbol-test-looked-up");
+        assert_eq!(Symbol::lookup("symbol-test-looked-up"), Some(symbol));
+    }
+
+    #[test]
+    fn symbols_resolve_to_their_string() {
+        for s in &["symbol-test-NN", "symbol-test-Case=Nom|Number=Sing", ""] {
+            assert_eq!(Symbol::intern(s).as_str(), *s);
+        }
+    }
+
+    #[test]
+    fn threads_intern_the_same_symbol() {
+        let symbols: Vec<Symbol> = (0..8)
+            .map(|_| thread::spawn(|| Symbol::intern("symbol-test-threads")))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert!(symbols.iter().all(|&symbol| symbol == symbols[0]));
+        assert_eq!(symbols[0].as_str(), "symbol-test-threads");
+    }
+}
@@ -0,0 +1,202 @@
+use std::str::FromStr;
+
+use error::ViewerError;
+
+/// Colors of rendered trees, for the canvas of the viewer and for saved
+/// trees.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+
+    /// Black on white, with saturated colors and thicker arcs.
+    HighContrast,
+}
+
+/// The colors of a theme, as Graphviz color names or `#rrggbb` values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ThemeColors {
+    pub background: &'static str,
+    pub foreground: &'static str,
+
+    /// Arcs of the basic dependencies.
+    pub edge: &'static str,
+
+    /// Width of the arcs, in points.
+    pub pen_width: usize,
+
+    /// Background of the selected token, the tokens of the dependency
+    /// path and highlighted tokens.
+    pub selection: &'static str,
+    pub path: &'static str,
+    pub highlight: &'static str,
+
+    /// Arcs of the dependency path.
+    pub path_edge: &'static str,
+
+    /// Word forms of marked tokens.
+    pub marked: &'static str,
+
+    /// Outline of tokens whose morphology differs from another version.
+    pub outline: &'static str,
+
+    /// Arcs that were answered correctly or incorrectly in a quiz, and
+    /// arcs that were changed in an edit.
+    pub correct: &'static str,
+    pub incorrect: &'static str,
+    pub changed: &'static str,
+
+    /// Dashed arcs of the original tree, projective heads and enhanced
+    /// dependencies.
+    pub original: &'static str,
+    pub projective: &'static str,
+    pub enhanced: &'static str,
+
+    /// Word forms of nouns, verbs and adjectives in colored palettes.
+    pub noun: &'static str,
+    pub verb: &'static str,
+    pub adjective: &'static str,
+}
+
+const LIGHT: ThemeColors = ThemeColors {
+    background: "white",
+    foreground: "black",
+    edge: "#4b0082",
+    pen_width: 1,
+    selection: "lavender",
+    path: "lightgoldenrod1",
+    highlight: "lightblue",
+    path_edge: "darkorange",
+    marked: "firebrick3",
+    outline: "crimson",
+    correct: "forestgreen",
+    incorrect: "firebrick3",
+    changed: "royalblue",
+    original: "gray50",
+    projective: "darkorange3",
+    enhanced: "darkcyan",
+    noun: "navy",
+    verb: "darkgreen",
+    adjective: "darkorange3",
+};
+
+const DARK: ThemeColors = ThemeColors {
+    background: "#1e1e1e",
+    foreground: "#e6e6e6",
+    edge: "#b39ddb",
+    pen_width: 1,
+    selection: "#3d3a5c",
+    path: "#5c4d1a",
+    highlight: "#1f4e5f",
+    path_edge: "#ffa726",
+    marked: "#ff6f61",
+    outline: "#ff5c8a",
+    correct: "#66bb6a",
+    incorrect: "#ef5350",
+    changed: "#64b5f6",
+    original: "#9e9e9e",
+    projective: "#ffb74d",
+    enhanced: "#4dd0e1",
+    noun: "#82aaff",
+    verb: "#a5d6a7",
+    adjective: "#ffcc80",
+};
+
+const HIGH_CONTRAST: ThemeColors = ThemeColors {
+    background: "white",
+    foreground: "black",
+    edge: "black",
+    pen_width: 2,
+    selection: "yellow",
+    path: "#ffd700",
+    highlight: "cyan",
+    path_edge: "#b35900",
+    marked: "#c00000",
+    outline: "#d00000",
+    correct: "#006400",
+    incorrect: "#c00000",
+    changed: "#0000c0",
+    original: "#404040",
+    projective: "#8b4500",
+    enhanced: "#005f5f",
+    noun: "#00008b",
+    verb: "#005000",
+    adjective: "#7a3d00",
+};
+
+impl Theme {
+    pub fn colors(self) -> &'static ThemeColors {
+        match self {
+            Theme::Light => &LIGHT,
+            Theme::Dark => &DARK,
+            Theme::HighContrast => &HIGH_CONTRAST,
+        }
+    }
+
+    /// The background color as RGB components from 0 to 1, for painting
+    /// the canvas around a tree.
+    pub fn background_rgb(self) -> (f64, f64, f64) {
+        match self {
+            Theme::Light | Theme::HighContrast => (1.0, 1.0, 1.0),
+            Theme::Dark => (30.0 / 255.0, 30.0 / 255.0, 30.0 / 255.0),
+        }
+    }
+
+    /// The theme that matches a GTK theme, e.g. `Adwaita-dark` or
+    /// `HighContrast`.
+    pub fn from_gtk_theme(name: &str, prefer_dark: bool) -> Self {
+        let name = name.to_lowercase();
+        if name.contains("highcontrast") || name.contains("high-contrast") {
+            if name.contains("inverse") {
+                Theme::Dark
+            } else {
+                Theme::HighContrast
+            }
+        } else if prefer_dark || name.contains("dark") {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = ViewerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            "high-contrast" => Ok(Theme::HighContrast),
+            _ => Err(ViewerError::UnknownTheme(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Theme;
+
+    #[test]
+    fn themes_follow_gtk_themes() {
+        assert_eq!(Theme::from_gtk_theme("Adwaita", false), Theme::Light);
+        assert_eq!(Theme::from_gtk_theme("Adwaita", true), Theme::Dark);
+        assert_eq!(Theme::from_gtk_theme("Arc-Dark", false), Theme::Dark);
+        assert_eq!(
+            Theme::from_gtk_theme("HighContrast", false),
+            Theme::HighContrast
+        );
+        assert_eq!(
+            Theme::from_gtk_theme("HighContrastInverse", false),
+            Theme::Dark
+        );
+
+        assert_eq!(
+            "high-contrast".parse::<Theme>().unwrap(),
+            Theme::HighContrast
+        );
+        assert!("solarized".parse::<Theme>().is_err());
+    }
+}
@@ -0,0 +1,565 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+use libc;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use detokenize::{detokenize, Spacing};
+use error::ViewerError;
+use graph::DependencyGraph;
+use model::{Seek, StatefulTreebankModel};
+use search::{MatchOptions, Query};
+use vim::{VimCommand, VimKey, VimKeys};
+
+/// Keys that are listed in the status line when no message is shown.
+const HELP: &str = "n/p: tree  \u{2190}/\u{2192}: token  /: search  Enter: next match  q: quit";
+
+/// Terminal size that is used when the size cannot be determined.
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+/// A key that was typed in the terminal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Down,
+    End,
+    Enter,
+    Escape,
+    Home,
+    Left,
+    PageDown,
+    PageUp,
+    Right,
+    Up,
+}
+
+/// Parse the keys in the input of a terminal. Escape sequences of
+/// unsupported keys are skipped.
+pub fn parse_keys(input: &[u8]) -> Vec<Key> {
+    let input = String::from_utf8_lossy(input);
+    let mut chars = input.chars().peekable();
+    let mut keys = Vec::new();
+
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if chars.peek() == Some(&'[') || chars.peek() == Some(&'O') => {
+                chars.next();
+                let mut parameter = String::new();
+                let mut end = None;
+                for c in &mut chars {
+                    match c {
+                        '0'..='9' | ';' => parameter.push(c),
+                        _ => {
+                            end = Some(c);
+                            break;
+                        }
+                    }
+                }
+
+                match (end, parameter.as_str()) {
+                    (Some('A'), _) => Key::Up,
+                    (Some('B'), _) => Key::Down,
+                    (Some('C'), _) => Key::Right,
+                    (Some('D'), _) => Key::Left,
+                    (Some('H'), _) | (Some('~'), "1") | (Some('~'), "7") => Key::Home,
+                    (Some('F'), _) | (Some('~'), "4") | (Some('~'), "8") => Key::End,
+                    (Some('~'), "5") => Key::PageUp,
+                    (Some('~'), "6") => Key::PageDown,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Escape,
+            '\r' | '\n' => Key::Enter,
+            '\x08' | '\x7f' => Key::Backspace,
+            c => Key::Char(c),
+        };
+
+        keys.push(key);
+    }
+
+    keys
+}
+
+/// A line of a tree drawing, with the token that is on the line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreeLine {
+    pub token: usize,
+    pub text: String,
+}
+
+/// Draw a dependency tree with box-drawing characters, one token per
+/// line. Dependents are indented under their head, in sentence order,
+/// with the relation on the branch, e.g. `├─nsubj─ dog NOUN`.
+pub fn tree_lines(graph: &DependencyGraph) -> Vec<TreeLine> {
+    let mut lines = Vec::new();
+    let mut visited = vec![false; graph.0.node_count()];
+
+    // Tokens in a cycle are not reachable from a root, these are drawn
+    // from the first token of the cycle.
+    let roots: Vec<_> = graph
+        .0
+        .node_indices()
+        .filter(|&idx| {
+            graph
+                .0
+                .edges_directed(idx, Direction::Incoming)
+                .next()
+                .is_none()
+        })
+        .collect();
+    for idx in roots.into_iter().chain(graph.0.node_indices()) {
+        if !visited[idx.index()] {
+            visited[idx.index()] = true;
+            lines.push(TreeLine {
+                token: idx.index(),
+                text: label(graph, idx),
+            });
+            draw_dependents(graph, idx, "", &mut visited, &mut lines);
+        }
+    }
+
+    lines
+}
+
+fn draw_dependents(
+    graph: &DependencyGraph,
+    head: NodeIndex,
+    prefix: &str,
+    visited: &mut [bool],
+    lines: &mut Vec<TreeLine>,
+) {
+    let mut dependents: Vec<_> = graph
+        .0
+        .edges_directed(head, Direction::Outgoing)
+        .filter(|edge| !visited[edge.target().index()])
+        .map(|edge| (edge.target(), edge.weight()))
+        .collect();
+    dependents.sort_by_key(|&(idx, _)| idx);
+    for &(idx, _) in &dependents {
+        visited[idx.index()] = true;
+    }
+
+    for (i, &(idx, rel)) in dependents.iter().enumerate() {
+        let last = i + 1 == dependents.len();
+        lines.push(TreeLine {
+            token: idx.index(),
+            text: format!(
+                "{}{}\u{2500}{}\u{2500} {}",
+                prefix,
+                if last { '\u{2514}' } else { '\u{251c}' },
+                rel,
+                label(graph, idx)
+            ),
+        });
+
+        let prefix = format!("{}{}", prefix, if last { "   " } else { "\u{2502}  " });
+        draw_dependents(graph, idx, &prefix, visited, lines);
+    }
+}
+
+/// The form and tag of a token.
+fn label(graph: &DependencyGraph, idx: NodeIndex) -> String {
+    let token = &graph.0[idx].token;
+    format!(
+        "{} {}",
+        token.form(),
+        token.cpos().or_else(|| token.pos()).unwrap_or("_")
+    )
+}
+
+/// Cut off a line at the width of the terminal.
+fn truncate(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_owned();
+    }
+
+    let mut truncated: String = line.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('\u{2026}');
+    truncated
+}
+
+/// The controlling terminal in raw mode. The terminal is restored when
+/// it is dropped.
+struct Terminal {
+    tty: File,
+    original: libc::termios,
+}
+
+impl Terminal {
+    /// Open the controlling terminal, rather than standard input, which
+    /// may be the treebank.
+    fn open() -> Result<Self, ViewerError> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+
+        let original = unsafe {
+            let mut original = mem::zeroed();
+            if libc::tcgetattr(tty.as_raw_fd(), &mut original) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            // Reads time out after 100ms, so that resizes are picked up.
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 1;
+            if libc::tcsetattr(tty.as_raw_fd(), libc::TCSAFLUSH, &raw) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            original
+        };
+
+        let mut terminal = Terminal { tty, original };
+        terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+
+        Ok(terminal)
+    }
+
+    /// Read the keys that were typed, if any.
+    fn keys(&mut self) -> Result<Vec<Key>, ViewerError> {
+        let mut input = [0; 64];
+        match self.tty.read(&mut input) {
+            Ok(n) => Ok(parse_keys(&input[..n])),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The number of columns and rows of the terminal.
+    fn size(&self) -> (usize, usize) {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } != 0
+            || size.ws_col == 0
+        {
+            return DEFAULT_SIZE;
+        }
+
+        (size.ws_col as usize, size.ws_row as usize)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.tty.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        unsafe {
+            libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+/// State of the terminal viewer.
+struct Tui {
+    spacing: Spacing,
+    vim_keys: VimKeys,
+
+    /// The tree and the first tree line that is shown.
+    scroll: (usize, usize),
+
+    /// The query that is typed, when the search prompt is open.
+    prompt: Option<String>,
+    last_search: Option<String>,
+    message: Option<String>,
+    bell: bool,
+}
+
+impl Tui {
+    /// Handle a key. Returns `false` when the viewer should quit.
+    fn key(&mut self, model: &mut StatefulTreebankModel, key: Key) -> bool {
+        if self.prompt.is_some() {
+            self.prompt_key(model, key);
+            return true;
+        }
+
+        if let Key::Char(c) = key {
+            match self.vim_keys.key(c) {
+                VimKey::Command(command) => {
+                    let seek = match command {
+                        VimCommand::Next(n) => Seek::Relative(n as isize),
+                        VimCommand::Previous(n) => Seek::Relative(-(n as isize)),
+                        VimCommand::First => Seek::First,
+                        VimCommand::Last => Seek::Last,
+                        VimCommand::Goto(n) => Seek::Absolute(n - 1),
+                    };
+                    self.seek(model, seek);
+                    return true;
+                }
+                VimKey::Pending => return true,
+                VimKey::Unhandled => (),
+            }
+        }
+
+        self.message = None;
+        let stride = model.page_stride() as isize;
+        match key {
+            Key::Char('q') | Key::Char('\x03') => return false,
+            Key::Char('n') => self.seek(model, Seek::Relative(1)),
+            Key::Char('p') => self.seek(model, Seek::Relative(-1)),
+            Key::Char('/') => self.prompt = Some(self.last_search.clone().unwrap_or_default()),
+            Key::Home => self.seek(model, Seek::First),
+            Key::End => self.seek(model, Seek::Last),
+            Key::PageDown => self.seek(model, Seek::Relative(stride)),
+            Key::PageUp => self.seek(model, Seek::Relative(-stride)),
+            Key::Right => model.next_token(),
+            Key::Left => model.previous_token(),
+            Key::Down => self.scroll.1 += 1,
+            Key::Up => self.scroll.1 = self.scroll.1.saturating_sub(1),
+            Key::Escape => model.select_token(None),
+            Key::Enter => self.next_match(model),
+            _ => (),
+        }
+
+        true
+    }
+
+    /// Handle a key of the search prompt.
+    fn prompt_key(&mut self, model: &mut StatefulTreebankModel, key: Key) {
+        let text = match self.prompt {
+            Some(ref mut text) => text,
+            None => return,
+        };
+
+        match key {
+            Key::Char(c) if !c.is_control() => text.push(c),
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Escape => self.prompt = None,
+            Key::Enter => {
+                let text = text.clone();
+                self.prompt = None;
+                if self.last_search.as_ref() != Some(&text) {
+                    match text.parse::<Query>() {
+                        Ok(query) => model.search(&query, MatchOptions::default()),
+                        Err(err) => {
+                            self.message = Some(format!("Cannot parse query: {}", err));
+                            return;
+                        }
+                    }
+                    self.last_search = Some(text);
+                }
+                self.next_match(model);
+            }
+            _ => (),
+        }
+    }
+
+    fn next_match(&mut self, model: &mut StatefulTreebankModel) {
+        if model.matches().is_empty() {
+            self.message = Some("No matches".to_owned());
+            self.bell = true;
+        } else {
+            model.next_match();
+        }
+    }
+
+    /// Move to another tree, the bell signals that the first or last
+    /// tree was reached.
+    fn seek(&mut self, model: &mut StatefulTreebankModel, seek: Seek) {
+        if !model.seek(seek) {
+            self.bell = true;
+        }
+    }
+
+    /// Draw the current tree, with a header line with the position in the
+    /// treebank and a status line at the bottom.
+    fn draw(
+        &mut self,
+        model: &StatefulTreebankModel,
+        screen: &mut dyn Write,
+        (width, height): (usize, usize),
+    ) -> Result<(), ViewerError> {
+        let mut header = if model.is_empty() {
+            "No trees".to_owned()
+        } else {
+            format!("Tree {} of {}", model.idx() + 1, model.len())
+        };
+        let n_matches = model.matches().len();
+        match model.current_match() {
+            _ if n_matches == 0 => (),
+            Some(current) => header.push_str(&format!(
+                " \u{2014} {} of {} matches",
+                current + 1,
+                n_matches
+            )),
+            None => header.push_str(&format!(" \u{2014} {} matches", n_matches)),
+        }
+
+        let (sentence, lines) = match model.graph() {
+            Some(graph) => (detokenize(&graph, self.spacing), tree_lines(&graph)),
+            None => (String::new(), Vec::new()),
+        };
+
+        // Scroll the selected token into view, the lines of a tree are
+        // scrolled from the top when another tree is shown.
+        let tree_height = height.saturating_sub(4).max(1);
+        if self.scroll.0 != model.idx() {
+            self.scroll = (model.idx(), 0);
+        }
+        let selected = model
+            .selected_token()
+            .and_then(|token| lines.iter().position(|line| line.token == token));
+        if let Some(row) = selected {
+            if row < self.scroll.1 {
+                self.scroll.1 = row;
+            } else if row >= self.scroll.1 + tree_height {
+                self.scroll.1 = row + 1 - tree_height;
+            }
+        }
+        self.scroll.1 = self.scroll.1.min(lines.len().saturating_sub(tree_height));
+
+        let matched: Vec<_> = model
+            .matches()
+            .iter()
+            .filter(|m| m.sentence == model.idx())
+            .map(|m| m.token)
+            .collect();
+
+        write!(
+            screen,
+            "\x1b[H\x1b[7m{:width$}\x1b[0m\r\n",
+            truncate(&header, width),
+            width = width
+        )?;
+        write!(screen, "{}\x1b[K\r\n\x1b[K\r\n", truncate(&sentence, width))?;
+        for (row, line) in lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll.1)
+            .take(tree_height)
+        {
+            let style = if Some(row) == selected {
+                "\x1b[7m"
+            } else if matched.contains(&line.token) {
+                "\x1b[1m"
+            } else {
+                ""
+            };
+            write!(
+                screen,
+                "{}{}\x1b[0m\x1b[K\r\n",
+                style,
+                truncate(&line.text, width)
+            )?;
+        }
+
+        let status = match (&self.prompt, &self.message) {
+            (Some(text), _) => format!("/{}", text),
+            (None, Some(message)) => message.clone(),
+            (None, None) => HELP.to_owned(),
+        };
+        write!(
+            screen,
+            "\x1b[J\x1b[{};1H{}",
+            height,
+            truncate(&status, width)
+        )?;
+        if self.bell {
+            write!(screen, "\x07")?;
+            self.bell = false;
+        }
+
+        screen.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Browse the trees of a model in the terminal, with the navigation and
+/// search keys of the graphical viewer. Returns when `q` is typed.
+pub fn run(model: &mut StatefulTreebankModel, spacing: Spacing) -> Result<(), ViewerError> {
+    let mut terminal = Terminal::open()?;
+    let mut tui = Tui {
+        spacing,
+        vim_keys: VimKeys::new(),
+        scroll: (model.idx(), 0),
+        prompt: None,
+        last_search: None,
+        message: None,
+        bell: false,
+    };
+
+    let mut size = terminal.size();
+    tui.draw(model, &mut terminal.tty, size)?;
+    loop {
+        let keys = terminal.keys()?;
+        for &key in &keys {
+            if !tui.key(model, key) {
+                return Ok(());
+            }
+        }
+
+        if !keys.is_empty() || terminal.size() != size {
+            size = terminal.size();
+            tui.draw(model, &mut terminal.tty, size)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::Token;
+
+    use super::{parse_keys, tree_lines, Key};
+    use graph::DependencyGraph;
+
+    #[test]
+    fn parses_escape_sequences() {
+        assert_eq!(
+            parse_keys(b"n\x1b[C\x1b[5~/\x1b\r\x7f\x1b[99~\x1bOH"),
+            vec![
+                Key::Char('n'),
+                Key::Right,
+                Key::PageUp,
+                Key::Char('/'),
+                Key::Escape,
+                Key::Enter,
+                Key::Backspace,
+                Key::Home,
+            ]
+        );
+    }
+
+    #[test]
+    fn draws_dependents_under_head() {
+        let tokens = [
+            ("the", "DET", 2, "det"),
+            ("dog", "NOUN", 3, "nsubj"),
+            ("chased", "VERB", 0, "root"),
+            ("cats", "NOUN", 3, "obj"),
+        ];
+        let graph = DependencyGraph::from(
+            tokens
+                .iter()
+                .map(|&(form, cpos, head, rel)| {
+                    let mut token = Token::new(form);
+                    token.set_cpos(Some(cpos));
+                    token.set_head(Some(head));
+                    token.set_head_rel(Some(rel));
+                    token
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let lines: Vec<_> = tree_lines(&graph)
+            .into_iter()
+            .map(|line| (line.token, line.text))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (2, "chased VERB".to_owned()),
+                (1, "\u{251c}\u{2500}nsubj\u{2500} dog NOUN".to_owned()),
+                (
+                    0,
+                    "\u{2502}  \u{2514}\u{2500}det\u{2500} the DET".to_owned()
+                ),
+                (3, "\u{2514}\u{2500}obj\u{2500} cats NOUN".to_owned()),
+            ]
+        );
+    }
+}
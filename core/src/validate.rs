@@ -0,0 +1,210 @@
+use std::fmt;
+
+use conllx::Sentence;
+use rayon::prelude::*;
+
+/// A problem in the annotation of a sentence.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IssueKind {
+    /// The token is part of a cycle, reported for the first token of
+    /// the cycle.
+    Cycle,
+
+    /// The relation of the token is missing or empty.
+    EmptyRelation,
+
+    /// The head of the token is not a token of the sentence.
+    HeadOutOfRange(usize),
+
+    /// The head of the token is missing.
+    MissingHead,
+
+    /// The token is attached to the root, while an earlier token also
+    /// is.
+    MultipleRoots,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IssueKind::Cycle => write!(f, "head cycle"),
+            IssueKind::EmptyRelation => write!(f, "empty relation"),
+            IssueKind::HeadOutOfRange(head) => write!(f, "head {} out of range", head),
+            IssueKind::MissingHead => write!(f, "missing head"),
+            IssueKind::MultipleRoots => write!(f, "multiple roots"),
+        }
+    }
+}
+
+/// An issue, located at a token of a sentence.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Issue {
+    pub sentence: usize,
+    pub token: usize,
+    pub kind: IssueKind,
+}
+
+/// Check a sentence for cycles, multiple roots, out-of-range heads and
+/// empty relations.
+pub fn validate_sentence(idx: usize, sentence: &Sentence) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |token, kind| {
+        issues.push(Issue {
+            sentence: idx,
+            token,
+            kind,
+        })
+    };
+
+    let mut has_root = false;
+    for (offset, token) in sentence.iter().enumerate() {
+        match token.head() {
+            Some(0) if has_root => issue(offset, IssueKind::MultipleRoots),
+            Some(0) => has_root = true,
+            Some(head) if head > sentence.len() => issue(offset, IssueKind::HeadOutOfRange(head)),
+            Some(_) => (),
+            None => issue(offset, IssueKind::MissingHead),
+        }
+
+        if token.head_rel().map(str::is_empty).unwrap_or(true) {
+            issue(offset, IssueKind::EmptyRelation);
+        }
+
+        if cycle_start(sentence, offset) == Some(offset) {
+            issue(offset, IssueKind::Cycle);
+        }
+    }
+
+    issues
+}
+
+/// Check all sentences of a treebank. Sentences are checked in
+/// parallel, the issues are in treebank order.
+pub fn validate(sentences: &[Sentence]) -> Vec<Issue> {
+    let issues: Vec<Vec<Issue>> = sentences
+        .par_iter()
+        .enumerate()
+        .map(|(idx, sentence)| validate_sentence(idx, sentence))
+        .collect();
+
+    issues.into_iter().flatten().collect()
+}
+
+/// If the token is part of a cycle, return the offset of the first token
+/// of the cycle.
+fn cycle_start(sentence: &Sentence, offset: usize) -> Option<usize> {
+    let mut cycle = vec![offset];
+    let mut current = offset;
+
+    // A path of more steps than there are tokens must contain a cycle.
+    for _ in 0..sentence.len() {
+        current = match sentence[current].head() {
+            Some(head) if head > 0 && head <= sentence.len() => head - 1,
+            _ => return None,
+        };
+
+        if current == offset {
+            return cycle.into_iter().min();
+        }
+
+        cycle.push(current);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::{ReadSentence, Reader, Sentence};
+
+    use super::{validate, validate_sentence, Issue, IssueKind};
+
+    fn read_sentence(data: &str) -> Sentence {
+        Reader::new(data.as_bytes())
+            .read_sentence()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn kinds(sentence: &Sentence) -> Vec<(usize, IssueKind)> {
+        validate_sentence(0, sentence)
+            .into_iter()
+            .map(|issue| (issue.token, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn valid_trees_have_no_issues() {
+        let sentence = read_sentence(
+            "1\tDas\t_\t_\t_\t_\t2\tDET\t_\t_\n\
+             2\tHaus\t_\t_\t_\t_\t3\tSUBJ\t_\t_\n\
+             3\tsteht\t_\t_\t_\t_\t0\tROOT\t_\t_\n",
+        );
+        assert!(validate_sentence(0, &sentence).is_empty());
+    }
+
+    #[test]
+    fn bad_heads_are_reported() {
+        let sentence = read_sentence(
+            "1\tDas\t_\t_\t_\t_\t5\tDET\t_\t_\n\
+             2\tHaus\t_\t_\t_\t_\t_\tSUBJ\t_\t_\n\
+             3\tsteht\t_\t_\t_\t_\t0\tROOT\t_\t_\n",
+        );
+        assert_eq!(
+            kinds(&sentence),
+            vec![
+                (0, IssueKind::HeadOutOfRange(5)),
+                (1, IssueKind::MissingHead)
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported_once() {
+        let sentence = read_sentence(
+            "1\tDas\t_\t_\t_\t_\t3\tDET\t_\t_\n\
+             2\tHaus\t_\t_\t_\t_\t1\tSUBJ\t_\t_\n\
+             3\tsteht\t_\t_\t_\t_\t2\tROOT\t_\t_\n\
+             4\t.\t_\t_\t_\t_\t0\tPUNCT\t_\t_\n",
+        );
+        assert_eq!(kinds(&sentence), vec![(0, IssueKind::Cycle)]);
+    }
+
+    #[test]
+    fn multiple_roots_are_reported() {
+        let sentence = read_sentence(
+            "1\tJa\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+             2\tnein\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+             3\tdoch\t_\t_\t_\t_\t0\t_\t_\t_\n",
+        );
+        assert_eq!(
+            kinds(&sentence),
+            vec![
+                (1, IssueKind::MultipleRoots),
+                (2, IssueKind::MultipleRoots),
+                (2, IssueKind::EmptyRelation),
+            ]
+        );
+    }
+
+    #[test]
+    fn issues_are_in_treebank_order() {
+        let valid = read_sentence("1\tJa\t_\t_\t_\t_\t0\tROOT\t_\t_\n");
+        let invalid = read_sentence("1\tJa\t_\t_\t_\t_\t2\tROOT\t_\t_\n");
+        assert_eq!(
+            validate(&[valid, invalid.clone(), invalid]),
+            vec![
+                Issue {
+                    sentence: 1,
+                    token: 0,
+                    kind: IssueKind::HeadOutOfRange(2),
+                },
+                Issue {
+                    sentence: 2,
+                    token: 0,
+                    kind: IssueKind::HeadOutOfRange(2),
+                },
+            ]
+        );
+    }
+}
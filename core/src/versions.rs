@@ -0,0 +1,238 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+
+use error::ViewerError;
+use graph::DependencyGraph;
+use hash::{ContentHash, SentenceHash};
+use input::InputFormat;
+use lazy::{parse_block, SentenceBlocks};
+
+/// A tree of a treebank version, with the metadata that it is aligned
+/// by.
+pub struct VersionedTree {
+    /// The `# sent_id` metadata.
+    pub id: Option<String>,
+
+    /// The `# text` metadata, or otherwise the forms of the tokens.
+    pub text: String,
+
+    pub graph: DependencyGraph,
+}
+
+impl VersionedTree {
+    pub fn new(block: &str, graph: DependencyGraph) -> Self {
+        let mut id = None;
+        let mut text = None;
+
+        for line in block.lines().take_while(|line| line.starts_with('#')) {
+            let mut parts = line[1..].splitn(2, '=');
+            match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+                (Some("sent_id"), Some(value)) if !value.is_empty() => id = Some(value.to_owned()),
+                (Some("text"), Some(value)) if !value.is_empty() => text = Some(value.to_owned()),
+                _ => (),
+            }
+        }
+
+        let text = text.unwrap_or_else(|| {
+            let forms: Vec<_> = graph
+                .0
+                .node_indices()
+                .map(|idx| graph.0[idx].token.form())
+                .collect();
+            forms.join(" ")
+        });
+
+        VersionedTree { id, text, graph }
+    }
+
+    /// The sentence identifier, or otherwise the text of the tree.
+    pub fn label(&self) -> &str {
+        self.id.as_ref().unwrap_or(&self.text)
+    }
+}
+
+/// Read the trees of a treebank version.
+pub fn read_versioned<R>(read: R, format: InputFormat) -> Result<Vec<VersionedTree>, ViewerError>
+where
+    R: BufRead,
+{
+    SentenceBlocks::new(read)
+        .map(|block| {
+            let (_, block) = block?;
+            let graph = parse_block(&block, format)?;
+            Ok(VersionedTree::new(&block, graph))
+        })
+        .collect()
+}
+
+/// The differences between two versions of a treebank. Trees are
+/// identified by their indices in the versions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VersionComparison {
+    /// Trees of the new version that are not in the old version.
+    pub added: Vec<usize>,
+
+    /// Trees of the old version that are not in the new version.
+    pub removed: Vec<usize>,
+
+    /// Trees whose annotation changed, as (old, new) pairs.
+    pub changed: Vec<(usize, usize)>,
+
+    /// Number of trees that did not change.
+    pub unchanged: usize,
+}
+
+/// Compare two versions of a treebank. The trees are aligned by their
+/// sentence identifiers, and the remaining trees by their text. Trees
+/// with the same identifier or text are aligned in treebank order.
+pub fn compare_versions(old: &[VersionedTree], new: &[VersionedTree]) -> VersionComparison {
+    let mut alignment = vec![None; new.len()];
+    let mut aligned = vec![false; old.len()];
+
+    align(old, new, &mut alignment, &mut aligned, |tree| {
+        tree.id.as_ref()
+    });
+    align(old, new, &mut alignment, &mut aligned, |tree| {
+        Some(&tree.text)
+    });
+
+    let hashes: Vec<ContentHash> = old.iter().map(|tree| tree.graph.content_hash()).collect();
+
+    let mut comparison = VersionComparison::default();
+    for (new_idx, old_idx) in alignment.into_iter().enumerate() {
+        match old_idx {
+            Some(old_idx) if hashes[old_idx] == new[new_idx].graph.content_hash() => {
+                comparison.unchanged += 1
+            }
+            Some(old_idx) => comparison.changed.push((old_idx, new_idx)),
+            None => comparison.added.push(new_idx),
+        }
+    }
+    comparison.removed = (0..old.len()).filter(|&idx| !aligned[idx]).collect();
+
+    comparison
+}
+
+/// Align the trees that are not aligned yet by a key.
+fn align<'a, F>(
+    old: &'a [VersionedTree],
+    new: &'a [VersionedTree],
+    alignment: &mut [Option<usize>],
+    aligned: &mut [bool],
+    key: F,
+) where
+    F: Fn(&'a VersionedTree) -> Option<&'a String>,
+{
+    let mut by_key: HashMap<&String, VecDeque<usize>> = HashMap::new();
+    for (idx, tree) in old.iter().enumerate().filter(|&(idx, _)| !aligned[idx]) {
+        if let Some(key) = key(tree) {
+            by_key.entry(key).or_default().push_back(idx);
+        }
+    }
+
+    for (new_idx, tree) in new.iter().enumerate() {
+        if alignment[new_idx].is_some() {
+            continue;
+        }
+
+        let old_idx = key(tree)
+            .and_then(|key| by_key.get_mut(key))
+            .and_then(VecDeque::pop_front);
+        if let Some(old_idx) = old_idx {
+            alignment[new_idx] = Some(old_idx);
+            aligned[old_idx] = true;
+        }
+    }
+}
+
+/// Write a report of the differences between two versions: the number
+/// of added, removed, changed and unchanged trees, followed by the added
+/// (`+`), removed (`-`) and changed (`~`) trees with their 1-based
+/// indices.
+pub fn write_report<W>(
+    mut writer: W,
+    old: &[VersionedTree],
+    new: &[VersionedTree],
+    comparison: &VersionComparison,
+) -> Result<(), ViewerError>
+where
+    W: Write,
+{
+    writeln!(writer, "{:<12} {:>8}", "added", comparison.added.len())?;
+    writeln!(writer, "{:<12} {:>8}", "removed", comparison.removed.len())?;
+    writeln!(writer, "{:<12} {:>8}", "changed", comparison.changed.len())?;
+    writeln!(writer, "{:<12} {:>8}", "unchanged", comparison.unchanged)?;
+
+    if !comparison.added.is_empty() {
+        writeln!(writer)?;
+        for &idx in &comparison.added {
+            writeln!(writer, "+ {:>8} {}", idx + 1, new[idx].label())?;
+        }
+    }
+
+    if !comparison.removed.is_empty() {
+        writeln!(writer)?;
+        for &idx in &comparison.removed {
+            writeln!(writer, "- {:>8} {}", idx + 1, old[idx].label())?;
+        }
+    }
+
+    if !comparison.changed.is_empty() {
+        writeln!(writer)?;
+        for &(old_idx, new_idx) in &comparison.changed {
+            writeln!(
+                writer,
+                "~ {:>8} {}",
+                format!("{}:{}", old_idx + 1, new_idx + 1),
+                new[new_idx].label()
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_versions, read_versioned, VersionComparison};
+    use input::InputFormat;
+
+    const OLD: &str = "# sent_id = a\n\
+                       1\tHi\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                       \n\
+                       # sent_id = b\n\
+                       1\tBye\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                       \n\
+                       1\tWell\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                       \n\
+                       # sent_id = c\n\
+                       1\tGone\t_\t_\t_\t_\t0\tROOT\t_\t_\n";
+
+    const NEW: &str = "# sent_id = b\n\
+                       1\tBye\t_\t_\t_\t_\t0\tFRAG\t_\t_\n\
+                       \n\
+                       # sent_id = a\n\
+                       1\tHi\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                       \n\
+                       # sent_id = d\n\
+                       1\tNew\t_\t_\t_\t_\t0\tROOT\t_\t_\n\
+                       \n\
+                       # text = Well\n\
+                       1\tWell\t_\t_\t_\t_\t0\tROOT\t_\t_\n";
+
+    #[test]
+    fn aligns_by_id_and_text() {
+        let old = read_versioned(OLD.as_bytes(), InputFormat::Conllu).unwrap();
+        let new = read_versioned(NEW.as_bytes(), InputFormat::Conllu).unwrap();
+
+        assert_eq!(
+            compare_versions(&old, &new),
+            VersionComparison {
+                added: vec![2],
+                removed: vec![3],
+                changed: vec![(1, 0)],
+                unchanged: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,143 @@
+/// A navigation command of the Vim-style keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VimCommand {
+    /// Move forward by the given number of trees (`j`, `3j`).
+    Next(usize),
+
+    /// Move backward by the given number of trees (`k`, `3k`).
+    Previous(usize),
+
+    /// Go to the first tree (`gg`).
+    First,
+
+    /// Go to the last tree (`G`).
+    Last,
+
+    /// Go to the tree with the given 1-based number (`42G`, `42gg`).
+    Goto(usize),
+}
+
+/// Outcome of feeding a key to a Vim-style key sequence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VimKey {
+    /// The key completes a command.
+    Command(VimCommand),
+
+    /// The key is part of a command that is not complete yet, such as a
+    /// count or the first `g` of `gg`.
+    Pending,
+
+    /// The key is not a Vim-style key and should be handled as usual.
+    Unhandled,
+}
+
+/// State of a Vim-style key sequence: the count prefix and whether the
+/// first `g` of `gg` was typed.
+#[derive(Clone, Debug, Default)]
+pub struct VimKeys {
+    count: Option<usize>,
+    g: bool,
+}
+
+impl VimKeys {
+    pub fn new() -> Self {
+        VimKeys::default()
+    }
+
+    /// Feed a key to the sequence. The sequence is reset when a command
+    /// is complete or when the key is not a Vim-style key.
+    pub fn key(&mut self, key: char) -> VimKey {
+        if self.g && key != 'g' {
+            self.reset();
+            return VimKey::Unhandled;
+        }
+
+        let count = self.count;
+        let command = match key {
+            // A leading zero is not a count, as in Vim.
+            '0'..='9' if key != '0' || count.is_some() => {
+                let digit = key.to_digit(10).unwrap() as usize;
+                self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                return VimKey::Pending;
+            }
+            'g' if !self.g => {
+                self.g = true;
+                return VimKey::Pending;
+            }
+            'g' => count.map(VimCommand::Goto).unwrap_or(VimCommand::First),
+            'G' => count.map(VimCommand::Goto).unwrap_or(VimCommand::Last),
+            'j' => VimCommand::Next(count.unwrap_or(1)),
+            'k' => VimCommand::Previous(count.unwrap_or(1)),
+            _ => {
+                self.reset();
+                return VimKey::Unhandled;
+            }
+        };
+
+        self.reset();
+        VimKey::Command(command)
+    }
+
+    /// Whether a command is partially typed.
+    pub fn is_pending(&self) -> bool {
+        self.count.is_some() || self.g
+    }
+
+    /// Discard a partially typed command.
+    pub fn reset(&mut self) {
+        self.count = None;
+        self.g = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VimCommand, VimKey, VimKeys};
+
+    fn keys(keys: &str) -> Vec<VimKey> {
+        let mut vim = VimKeys::new();
+        keys.chars().map(|key| vim.key(key)).collect()
+    }
+
+    #[test]
+    fn counts_prefix_commands() {
+        assert_eq!(
+            keys("42G"),
+            vec![
+                VimKey::Pending,
+                VimKey::Pending,
+                VimKey::Command(VimCommand::Goto(42))
+            ]
+        );
+        assert_eq!(keys("3j")[1], VimKey::Command(VimCommand::Next(3)));
+        assert_eq!(keys("10k")[2], VimKey::Command(VimCommand::Previous(10)));
+        assert_eq!(keys("7gg")[2], VimKey::Command(VimCommand::Goto(7)));
+    }
+
+    #[test]
+    fn moves_without_count() {
+        assert_eq!(
+            keys("jkGgg"),
+            vec![
+                VimKey::Command(VimCommand::Next(1)),
+                VimKey::Command(VimCommand::Previous(1)),
+                VimKey::Command(VimCommand::Last),
+                VimKey::Pending,
+                VimKey::Command(VimCommand::First)
+            ]
+        );
+    }
+
+    #[test]
+    fn other_keys_reset_the_sequence() {
+        assert_eq!(keys("0"), vec![VimKey::Unhandled]);
+        assert_eq!(
+            keys("4xj")[1..],
+            [VimKey::Unhandled, VimKey::Command(VimCommand::Next(1))]
+        );
+        assert_eq!(
+            keys("gjG")[1..],
+            [VimKey::Unhandled, VimKey::Command(VimCommand::Last)]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+extern crate conllx_view_core;
+extern crate eframe;
+extern crate egui_extras;
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::time::Duration;
+
+use conllx_view_core::graph::{Dot, RenderOptions, Svg};
+use conllx_view_core::loader::{LoadOptions, TreebankOpener};
+use conllx_view_core::mainloop::PollingContext;
+use conllx_view_core::model::{ModelUpdate, Seek, StatefulTreebankModel};
+use conllx_view_core::recent::{default_recent_path, RecentFiles};
+use conllx_view_core::search::{MatchOptions, Query};
+use eframe::egui;
+use egui_extras::RetainedImage;
+
+/// Interval at which trees that are read in the background are received.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A minimal viewer for platforms without GTK: trees can be browsed,
+/// searched and saved as dot.
+struct Viewer {
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+
+    /// Reads the treebank while the viewer runs.
+    _opener: TreebankOpener,
+
+    /// Receives the trees that are read, polled once per frame.
+    context: Rc<PollingContext>,
+
+    render_options: RenderOptions,
+    query: String,
+    status: String,
+
+    /// The rendering of the tree that is shown, with the tree index and
+    /// the selected token that it was rendered for.
+    tree: Option<(usize, Option<usize>, RetainedImage)>,
+}
+
+impl Viewer {
+    fn new(
+        treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+        opener: TreebankOpener,
+        context: Rc<PollingContext>,
+    ) -> Self {
+        Viewer {
+            treebank_model,
+            _opener: opener,
+            context,
+            render_options: RenderOptions::default(),
+            query: String::new(),
+            status: String::new(),
+            tree: None,
+        }
+    }
+
+    fn seek(&mut self, seek: Seek) {
+        self.treebank_model.borrow_mut().seek(seek);
+    }
+
+    fn search(&mut self) {
+        let query = match self.query.parse::<Query>() {
+            Ok(query) => query,
+            Err(err) => {
+                self.status = format!("Invalid query: {}", err);
+                return;
+            }
+        };
+
+        let mut treebank_model = self.treebank_model.borrow_mut();
+        treebank_model.search(&query, MatchOptions::default());
+        self.status = format!("{} matches", treebank_model.matches().len());
+    }
+
+    fn save_dot(&mut self) {
+        let treebank_model = self.treebank_model.borrow();
+        let graph = match treebank_model.graph() {
+            Some(graph) => graph,
+            None => return,
+        };
+
+        let filename = format!("s{}.dot", treebank_model.idx() + 1);
+        self.status = match graph
+            .dot(&self.render_options)
+            .and_then(|dot| Ok(fs::write(&filename, dot)?))
+        {
+            Ok(()) => format!("Saved tree to: {}", filename),
+            Err(err) => format!("Error writing dot output: {}", err),
+        };
+    }
+
+    /// Render the tree that is shown, when it was not rendered yet.
+    fn update_tree(&mut self) {
+        let treebank_model = self.treebank_model.borrow();
+        let (idx, token) = (treebank_model.idx(), treebank_model.selected_token());
+        match self.tree {
+            Some((tree_idx, tree_token, _)) if (tree_idx, tree_token) == (idx, token) => return,
+            _ => (),
+        }
+
+        let graph = match treebank_model.graph() {
+            Some(graph) => graph,
+            None => return,
+        };
+
+        let mut render_options = self.render_options.clone();
+        render_options.selection = token;
+        let image = graph
+            .svg(&render_options)
+            .map_err(|err| err.to_string())
+            .and_then(|svg| RetainedImage::from_svg_bytes(format!("tree {}", idx + 1), svg.as_bytes()));
+        match image {
+            Ok(image) => self.tree = Some((idx, token, image)),
+            Err(err) => self.status = format!("Cannot render tree: {}", err),
+        }
+    }
+
+    /// Browse with the keys of the GTK viewer, unless a text field has
+    /// the focus.
+    fn handle_keys(&mut self, ctx: &egui::Context) {
+        if ctx.memory(|memory| memory.focus().is_some()) {
+            return;
+        }
+
+        let (next, previous, first, last, next_token) = ctx.input(|input| {
+            (
+                input.key_pressed(egui::Key::N),
+                input.key_pressed(egui::Key::P),
+                input.key_pressed(egui::Key::Home),
+                input.key_pressed(egui::Key::End),
+                input.key_pressed(egui::Key::ArrowRight),
+            )
+        });
+
+        if next {
+            self.seek(Seek::Relative(1));
+        }
+        if previous {
+            self.seek(Seek::Relative(-1));
+        }
+        if first {
+            self.seek(Seek::First);
+        }
+        if last {
+            self.seek(Seek::Last);
+        }
+        if next_token {
+            self.treebank_model.borrow_mut().next_token();
+        }
+    }
+}
+
+impl eframe::App for Viewer {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.context.poll() {
+            ctx.request_repaint_after(POLL_INTERVAL);
+        }
+
+        self.handle_keys(ctx);
+        self.update_tree();
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Previous").clicked() {
+                    self.seek(Seek::Relative(-1));
+                }
+                if ui.button("Next").clicked() {
+                    self.seek(Seek::Relative(1));
+                }
+
+                let (idx, len) = {
+                    let treebank_model = self.treebank_model.borrow();
+                    (treebank_model.idx(), treebank_model.len())
+                };
+                ui.label(format!("{} of {}", idx + 1, len));
+
+                ui.separator();
+                let query = ui.text_edit_singleline(&mut self.query);
+                if query.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                    self.search();
+                }
+                if ui.button("Next match").clicked() {
+                    self.treebank_model.borrow_mut().next_match();
+                }
+
+                ui.separator();
+                if ui.button("Save dot").clicked() {
+                    self.save_dot();
+                }
+
+                ui.label(self.status.as_str());
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().show(ui, |ui| match self.tree {
+                Some((_, _, ref image)) => {
+                    image.show(ui);
+                }
+                None => {
+                    ui.label("Reading treebank\u{2026}");
+                }
+            });
+        });
+    }
+}
+
+fn main() {
+    let paths: Vec<_> = env::args_os().skip(1).map(PathBuf::from).collect();
+
+    let treebank_model = Rc::new(RefCell::new(StatefulTreebankModel::new()));
+    let recent = RecentFiles::open(default_recent_path()).unwrap_or_else(|err| {
+        eprintln!("Cannot read recent files: {}", err);
+        process::exit(1)
+    });
+    let context = Rc::new(PollingContext::new());
+    let opener = TreebankOpener::new(
+        LoadOptions::default(),
+        treebank_model.clone(),
+        recent,
+        context.clone(),
+    );
+    treebank_model.borrow_mut().connect_update(ModelUpdate::ReadError, |model| {
+        if let Some(error) = model.read_error() {
+            eprintln!("{}", error);
+        }
+    });
+    treebank_model.borrow_mut().connect_update(ModelUpdate::ReadWarning, |model| {
+        if let Some(warning) = model.read_warning() {
+            eprintln!("{}", warning);
+        }
+    });
+    opener.open(paths);
+
+    let viewer = Viewer::new(treebank_model, opener, context);
+    if let Err(err) = eframe::run_native(
+        "conllx-view",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Box::new(viewer)),
+    ) {
+        eprintln!("Cannot run viewer: {}", err);
+        process::exit(1);
+    }
+}
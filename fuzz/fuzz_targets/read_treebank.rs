@@ -0,0 +1,50 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate conllx_view_core;
+
+use conllx_view_core::compress::decompress;
+use conllx_view_core::graph::{Dot, RenderOptions, Tikz};
+use conllx_view_core::input::{read_graphs, GraphBatches, InputFormat};
+use conllx_view_core::validate::validate;
+
+const BATCH_SIZE: usize = 16;
+
+const FORMATS: &[InputFormat] = &[
+    InputFormat::Conllx,
+    InputFormat::Conllu,
+    InputFormat::Conll2009,
+    InputFormat::Stanford,
+];
+
+// Arbitrary bytes must be read as trees or give errors, as when the
+// viewer loads a treebank.
+fuzz_target!(|data: &[u8]| {
+    let options = RenderOptions::default();
+
+    if let Ok(mut read) = decompress(data) {
+        if let Ok(format) = InputFormat::detect(&mut read) {
+            for batch in GraphBatches::new(read, format, BATCH_SIZE) {
+                // The loader stops at the first error.
+                let graphs = match batch {
+                    Ok(graphs) => graphs,
+                    Err(_) => break,
+                };
+
+                let sentences: Vec<_> = graphs.iter().map(|graph| graph.to_sentence()).collect();
+                validate(&sentences);
+
+                for graph in graphs {
+                    let _ = graph.dot(&options);
+                    let _ = graph.tikz(&options);
+                }
+            }
+        }
+    }
+
+    for &format in FORMATS {
+        for graph in read_graphs(data, format).filter_map(Result::ok) {
+            let _ = graph.dot(&options);
+        }
+    }
+});
@@ -1,4 +0,0 @@
-#[derive(Debug, Fail)]
-pub enum ViewerError {
-    #[fail(display = "no graph is selected")] NoGraphSelected,
-}
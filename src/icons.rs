@@ -0,0 +1,74 @@
+use gio;
+use glib;
+use gtk::{self, IconThemeExt};
+
+use error::ViewerError;
+
+/// The resource path of the icon set. GTK looks up the icons in the
+/// directories of the hicolor theme below this path, e.g.
+/// `scalable/status`.
+const ICONS_PATH: &str = "/eu/danieldk/conllx-view/icons";
+
+/// The icon set, compiled into a GResource bundle by the build script.
+static ICON_RESOURCES: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/icons.gresource"));
+
+/// An icon of the state of a tree.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreeIcon {
+    /// The tree is flagged by an alert.
+    Alert,
+
+    /// The tree changed since the last commit.
+    Changed,
+
+    /// The tree was edited.
+    Edited,
+
+    /// The tree is marked for review.
+    Marked,
+
+    /// The tree has a note.
+    Noted,
+
+    /// The tree was removed.
+    Removed,
+}
+
+impl TreeIcon {
+    /// The name of the icon in the icon theme.
+    pub fn name(self) -> &'static str {
+        match self {
+            TreeIcon::Alert => "conllx-view-alert-symbolic",
+            TreeIcon::Changed => "conllx-view-changed-symbolic",
+            TreeIcon::Edited => "conllx-view-edited-symbolic",
+            TreeIcon::Marked => "conllx-view-marked-symbolic",
+            TreeIcon::Noted => "conllx-view-noted-symbolic",
+            TreeIcon::Removed => "conllx-view-removed-symbolic",
+        }
+    }
+
+    /// A description of the state, for tooltips.
+    pub fn description(self) -> &'static str {
+        match self {
+            TreeIcon::Alert => "Flagged by an alert",
+            TreeIcon::Changed => "Changed since HEAD",
+            TreeIcon::Edited => "Edited",
+            TreeIcon::Marked => "Marked for review",
+            TreeIcon::Noted => "Has a note",
+            TreeIcon::Removed => "Removed",
+        }
+    }
+}
+
+/// Register the icon set with the default icon theme, so that the
+/// icons can be used by name. GTK must be initialized.
+pub fn register_icons() -> Result<(), ViewerError> {
+    let resource = gio::Resource::new_from_data(&glib::Bytes::from_static(ICON_RESOURCES))?;
+    gio::resources_register(&resource);
+
+    if let Some(theme) = gtk::IconTheme::get_default() {
+        theme.add_resource_path(ICONS_PATH);
+    }
+
+    Ok(())
+}
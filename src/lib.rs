@@ -0,0 +1,35 @@
+//! Viewer for dependency treebanks.
+//!
+//! Besides the `conllx-view` program, the crate provides the GTK widgets
+//! of the viewer. Other gtk-rs applications can embed the tree viewer
+//! with `widgets::TreebankView`, which shows the current tree of a
+//! `model::StatefulTreebankModel`, or show trees that they render with
+//! `graph::RenderOptions` on a `widgets::DependencyTreeWidget`.
+//!
+//! The treebank readers, model, search and rendering are in the
+//! `conllx-view-core` crate, which does not depend on GTK. Its modules
+//! are re-exported here.
+
+extern crate cairo;
+extern crate conllx;
+#[macro_use]
+extern crate conllx_view_core;
+extern crate gdk;
+extern crate gio;
+extern crate glib;
+extern crate glib_sys;
+extern crate gtk;
+extern crate rsvg;
+
+pub use conllx_view_core::{
+    alert, arena, cache, compress, config, conll2009, conllu, consistency, detokenize, diff, dirs,
+    download, edit, error, exercise, export, features, ffi, git, graph, hash, index, input, layer,
+    layout, lazy, library, loader, model, notes, permalink, profile, quiz, recent, recorder,
+    render, report, roundtrip, search, server, similarity, stanford, stats, storage, style,
+    subtrees, symbol, theme, tui, validate, versions, vim,
+};
+pub use conllx_view_core::{clone, ok_or};
+
+pub mod icons;
+pub mod mainloop;
+pub mod widgets;
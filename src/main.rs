@@ -1,61 +1,353 @@
-extern crate cairo;
-extern crate conllx;
 #[macro_use]
-extern crate enum_map;
-#[macro_use]
-extern crate failure;
+extern crate conllx_view;
+extern crate gdk;
 extern crate getopts;
 extern crate gio;
 extern crate glib;
 extern crate gtk;
-extern crate itertools;
 extern crate petgraph;
+extern crate rand;
 extern crate rsvg;
 extern crate stdinout;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
 use std::env::args;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::{channel, Receiver};
-use std::thread;
+use std::time::Duration;
 
-use failure::Error;
-use getopts::Options;
-use gio::{ApplicationExt, ApplicationExtManual};
+use gdk::enums::key;
+use getopts::{Matches, Options};
+use gio::{
+    ActionGroupExt, ActionMapExt, ApplicationExt, ApplicationExtManual, MenuExt, NotificationExt,
+    SimpleActionExt,
+};
 use gtk::prelude::*;
 use gtk::LabelExt;
+use petgraph::graph::NodeIndex;
+use rand::{FromEntropy, SeedableRng, StdRng};
 use rsvg::Handle;
-use stdinout::{Input, OrExit};
+use stdinout::{Input, OrExit, Output};
 
-mod error;
-use error::ViewerError;
+use conllx_view::alert::{Alert, AlertRule};
+use conllx_view::arena::SentenceArena;
+use conllx_view::cache::LruCache;
+use conllx_view::compress::decompress;
+use conllx_view::config::{default_config_path, Config, Preferences};
+use conllx_view::consistency::{find_inconsistencies, write_inconsistencies};
+use conllx_view::detokenize::{detokenize, kwic, pieces, Spacing};
+use conllx_view::diff::{apply_diffs, diff_treebanks, read_diffs};
+use conllx_view::dirs::dot_available;
+use conllx_view::edit::changes_report;
+use conllx_view::error::ViewerError;
+use conllx_view::exercise::exercises;
+use conllx_view::export::{
+    parse_ranges, read_sentences, read_treebank, select_trees, write_graphs, write_sentences,
+    write_subtree, SubtreeFormat,
+};
+use conllx_view::features::{write_feature_csv, FeatureCell, FeatureTable};
+use conllx_view::graph::{
+    DependencyGraph, Diagram, Dot, GraphvizOptions, RenderOptions, Subtree, Tikz, TreeRenderer,
+};
+use conllx_view::hash::{ContentHash, SentenceHash};
+use conllx_view::icons::{register_icons, TreeIcon};
+use conllx_view::input::{detect_format, read_graphs, unsupported_columns, InputFormat};
+use conllx_view::layer::{parse_layers, Layer};
+use conllx_view::layout::{
+    default_layouts_path, Layout, Layouts, PanelLayout, WindowGeometry, ZoomLayout,
+};
+use conllx_view::library::{default_library_dir, parse_tags, Library, LibraryEntry};
+#[cfg(feature = "zmq")]
+use conllx_view::loader::Subscription;
+use conllx_view::loader::{head_revision, LoadOptions, TreebankOpener};
+use conllx_view::mainloop::{channel, GtkMainContext};
+use conllx_view::model::{ModelUpdate, Seek, StatefulTreebankModel};
+use conllx_view::notes::{notes_path, write_notes, SentenceNotes};
+use conllx_view::permalink::{command_line, review_link, sentence_lines};
+use conllx_view::profile::{treebank_language, Palette, Profile};
+use conllx_view::quiz::{Quiz, QuizMode};
+use conllx_view::recent::{default_recent_path, RecentFiles};
+use conllx_view::recorder::{MacroRecorder, MacroStep};
+use conllx_view::render::{Renderer, Rendering};
+use conllx_view::report::{write_html_report, write_interactive_report};
+use conllx_view::roundtrip::TreebankText;
+use conllx_view::search::{MatchOptions, Query, Ranking};
+use conllx_view::server::{render_graph, render_request, serve, ImageFormat};
+use conllx_view::similarity::{Similarity, TokenOverlap, TreeKernel};
+use conllx_view::stats::{
+    write_comparison, write_stats, LengthRange, SentenceStats, TreebankStats,
+};
+use conllx_view::storage::TreebankStorage;
+use conllx_view::style::{toggle_subtrees, TreeStyle};
+use conllx_view::subtrees::{mine_subtrees, write_subtrees};
+use conllx_view::theme::Theme;
+use conllx_view::tui;
+use conllx_view::validate::validate;
+use conllx_view::versions::{
+    compare_versions, read_versioned, write_report, VersionComparison, VersionedTree,
+};
+use conllx_view::vim::{VimCommand, VimKey, VimKeys};
+use conllx_view::widgets::{
+    ConcordanceWidget, DependencyTreeWidget, DetachablePanel, FeatureTableWidget,
+    InconsistenciesWidget, IssuesWidget, KeymapWidget, LibraryWidget, MatchPage, MatchesWidget,
+    Notifier, RecentMenu, SentenceStatsWidget, SentenceWidget, SubtreesWidget, TagColumn,
+    TaggingWidget, TrashMenu, TreeStateIcons, TreeZoom,
+};
 
-mod graph;
-use graph::{DependencyGraph, Dot, Svg, Tikz, Tokens};
+/// The window actions as (name, description, default accelerators).
+const ACTIONS: &[(&str, &str, &[&str])] = &[
+    ("changes", "Save changes of this session", &["c"]),
+    (
+        "collapse",
+        "Collapse/expand the subtree of the token",
+        &["z"],
+    ),
+    (
+        "command-line",
+        "Copy the command line of this view",
+        &["<Primary><Shift>c"],
+    ),
+    (
+        "concordance",
+        "Show/hide the concordance of the matches",
+        &["k"],
+    ),
+    (
+        "copy-diagram",
+        "Copy the tree as a text diagram",
+        &["<Primary><Shift>d"],
+    ),
+    ("deepest", "Deepest tree", &["<Shift>d"]),
+    (
+        "detach-concordance",
+        "Detach the concordance",
+        &["<Shift>k"],
+    ),
+    (
+        "detach-issues",
+        "Detach the validation issues",
+        &["<Shift>i"],
+    ),
+    (
+        "detach-library",
+        "Detach the construction library",
+        &["<Shift>l"],
+    ),
+    ("detach-matches", "Detach the search matches", &["<Shift>m"]),
+    ("detach-notes", "Detach the notes", &["<Primary><Shift>n"]),
+    (
+        "detach-statistics",
+        "Detach the sentence statistics",
+        &["<Shift>a"],
+    ),
+    ("dot", "Save the tree as dot", &["d"]),
+    ("edit", "Show/hide the edit bar", &["e"]),
+    (
+        "export-subtree",
+        "Save the subtree of the token",
+        &["<Shift>x"],
+    ),
+    ("extract", "Add the subtree as a new tree", &["x"]),
+    (
+        "feature-table",
+        "Count feature values by part of speech",
+        &["<Shift>u"],
+    ),
+    ("first", "First tree", &["Home"]),
+    ("follow", "Follow the newest tree", &["f"]),
+    ("git", "Show/hide the git bar", &["g"]),
+    (
+        "inconsistencies",
+        "Find tokens annotated differently in the same context",
+        &["<Shift>v"],
+    ),
+    ("issues", "Show/hide the validation issues", &["i"]),
+    ("last", "Last tree", &["End"]),
+    (
+        "length-filter",
+        "Browse sentences of some lengths",
+        &["<Shift>f"],
+    ),
+    ("library", "Show/hide the construction library", &["l"]),
+    ("longest", "Longest tree", &["<Shift>w"]),
+    ("matches", "Show/hide the search matches", &["m"]),
+    (
+        "morphology",
+        "Show/hide the morphology differences",
+        &["<Shift>o"],
+    ),
+    ("next", "Next tree", &["n"]),
+    ("next-page", "Page down", &["Page_Down"]),
+    ("next-relation", "Next tree with a relation", &["<Shift>r"]),
+    ("next-token", "Next token", &["Right"]),
+    ("notes", "Show/hide the notes of the tree", &["<Shift>n"]),
+    ("open", "Open another treebank", &["<Primary>o"]),
+    ("original", "Show/hide the original tree", &["o"]),
+    ("paste", "Paste sentences", &["<Primary><Shift>v"]),
+    (
+        "path",
+        "Start/clear a dependency path at the token",
+        &["<Shift>p"],
+    ),
+    ("preferences", "Preferences", &["<Primary>comma"]),
+    ("previous", "Previous tree", &["p"]),
+    ("previous-page", "Page up", &["Page_Up"]),
+    ("previous-token", "Previous token", &["Left"]),
+    ("quit", "Quit", &["q", "<Primary>q"]),
+    ("record-macro", "Start/stop recording a macro", &["F3"]),
+    ("replay-macro", "Replay the macro", &["F4"]),
+    ("reveal", "Reveal the answer (quiz)", &["r"]),
+    ("review-link", "Copy the review link", &["u"]),
+    ("revision", "Show/hide the committed tree", &["v"]),
+    ("save", "Save the treebank", &["s", "<Primary>s"]),
+    ("search", "Show/hide the search bar", &["slash"]),
+    (
+        "similar-tokens",
+        "Find trees with similar words",
+        &["<Shift>s"],
+    ),
+    (
+        "similar-trees",
+        "Find trees with a similar structure",
+        &["<Shift>t"],
+    ),
+    ("slideshow", "Start/stop the slideshow", &["F5"]),
+    ("statistics", "Show/hide the sentence statistics", &["a"]),
+    ("subtrees", "Count frequent subtrees", &["<Shift>c"]),
+    (
+        "tagging",
+        "Review lemmas and tags in a table",
+        &["<Shift>e"],
+    ),
+    ("tikz", "Save the tree as TikZ", &["t"]),
+    ("undo", "Restore removed trees", &["<Primary>z"]),
+    (
+        "undo-edit",
+        "Undo the last edit or macro",
+        &["<Primary><Alt>z"],
+    ),
+    ("unselect", "Clear the token selection", &["Escape"]),
+    ("zoom-in", "Zoom in", &["equal"]),
+    ("zoom-out", "Zoom out", &["minus"]),
+];
 
-#[macro_use]
-mod macros;
+/// Number of threads that render trees with Graphviz.
+const RENDER_WORKERS: usize = 2;
+
+/// Number of characters of context on either side of a match in the
+/// concordance.
+const CONCORDANCE_CONTEXT: usize = 40;
+
+/// Number of search matches that are shown at a time.
+const MATCHES_PAGE_SIZE: usize = 200;
+
+/// Number of similar trees that are listed in the matches panel.
+const SIMILAR_TREES: usize = 50;
+
+/// Maximum number of arcs below the head of subtree patterns, by default
+/// and at most.
+const DEFAULT_SUBTREE_DEPTH: usize = 2;
+const MAX_SUBTREE_DEPTH: usize = 5;
+
+/// Number of occurrences of the subtree patterns that are listed, at
+/// least.
+const SUBTREE_MIN_COUNT: usize = 2;
+
+/// Number of subtree patterns that are listed, at most.
+const SUBTREE_PATTERNS: usize = 1000;
+
+/// Number of words on either side of the tokens of inconsistencies, by
+/// default and at most.
+const DEFAULT_CONTEXT_WINDOW: usize = 1;
+const MAX_CONTEXT_WINDOW: usize = 5;
+
+/// Number of inconsistencies that are listed, at most.
+const INCONSISTENCIES: usize = 1000;
+
+/// Number of rendered trees that are kept for redisplaying.
+const TREE_CACHE_SIZE: usize = 32;
+
+/// Number of zoomed trees whose zoom level and scroll position are
+/// restored when they are shown again.
+const TREE_VIEW_CACHE_SIZE: usize = 256;
+
+/// Seconds between the trees of the slideshow.
+const DEFAULT_SLIDESHOW_INTERVAL: f64 = 5.0;
+
+/// Largest number of trees that the page stride can be set to in the
+/// preferences.
+const MAX_PAGE_STRIDE: usize = 1000;
+
+/// Number of removed trees that are listed in the trash menu.
+const TRASH_MENU_SIZE: usize = 20;
+
+/// Number of characters of the sentences in the trash menu.
+const TRASH_SENTENCE_LEN: usize = 60;
 
-mod model;
-use model::{ModelUpdate, StatefulTreebankModel};
+/// The state of the viewer window that its handlers share. It is made
+/// with the window and passed to the handlers as an `Rc<Viewer>`.
+struct Viewer {
+    /// The notification bar and error dialogs of the window.
+    notifier: Notifier,
 
-mod widgets;
-use widgets::{DependencyTreeWidget, SentenceWidget};
+    /// The preferences of the configuration, which apply to the trees
+    /// that are shown and saved.
+    preferences: RefCell<Preferences>,
 
-const DOT_KEY: u32 = 100;
-const NEXT_KEY: u32 = 110;
-const PREVIOUS_KEY: u32 = 112;
-const QUIT_KEY: u32 = 113;
-const TIKZ_KEY: u32 = 116;
-const ZOOM_IN_KEY: u32 = 61;
-const ZOOM_OUT_KEY: u32 = 45;
+    /// Graphviz dot could not be run when the viewer started, so trees
+    /// are drawn as text diagrams.
+    dot_missing: Cell<bool>,
+}
+
+impl Viewer {
+    /// Show a message in the notification bar.
+    fn notify(&self, message: &str) {
+        self.notifier.info(message);
+    }
+
+    /// Show an error in the notification bar.
+    fn notify_error(&self, message: &str) {
+        self.notifier.error(message);
+    }
+
+    /// Show an error in a dialog, for failures that should not go
+    /// unnoticed, such as a treebank that was not saved.
+    fn error_dialog(&self, message: &str, err: &ViewerError) {
+        self.notifier.error_dialog(message, &err.to_string());
+    }
+
+    /// Override render options with the preferences. Without a theme in
+    /// the preferences, trees follow the GTK theme. Without Graphviz,
+    /// trees are drawn as text diagrams.
+    fn apply_preferences(&self, options: &mut RenderOptions) {
+        let preferences = self.preferences.borrow();
+        preferences.apply(options);
+        if preferences.theme.is_none() {
+            options.theme = gtk_theme();
+        }
+        if self.dot_missing.get() {
+            options.renderer = TreeRenderer::Diagram;
+        }
+    }
+
+    /// The path of a saved file in the export directory of the
+    /// preferences.
+    fn export_path(&self, filename: &str) -> String {
+        self.preferences
+            .borrow()
+            .export_path(filename)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] EXPR [INPUT_FILE]", program);
+    let brief = format!("Usage: {} [options] EXPR [INPUT_FILE...]", program);
     print!("{}", opts.usage(&brief));
 }
 
@@ -63,15 +355,222 @@ fn main() {
     let args: Vec<String> = args().collect();
     let program = args[0].clone();
 
+    match args.get(1).map(String::as_str) {
+        Some("convert") => return convert_command(&program, &args[2..]),
+        Some("diff") => return diff_command(&program, &args[2..]),
+        Some("inconsistencies") => return inconsistencies_command(&program, &args[2..]),
+        Some("patch") => return patch_command(&program, &args[2..]),
+        Some("render") => return render_command(&program, &args[2..]),
+        Some("stats") => return stats_command(&program, &args[2..]),
+        Some("subtrees") => return subtrees_command(&program, &args[2..]),
+        Some("versions") => return versions_command(&program, &args[2..]),
+        _ => (),
+    }
+
     let mut opts = Options::new();
+    opts.optmulti(
+        "",
+        "alert",
+        "flag the trees that meet CONDITION, e.g. \"root cpos=PUNCT\" or length>200",
+        "CONDITION",
+    );
+    opts.optflag(
+        "",
+        "enhanced",
+        "draw enhanced dependencies (CoNLL-U DEPS) as a second layer of arcs",
+    );
+    opts.optopt(
+        "",
+        "exercises",
+        "save exercises with answer key as PREFIX-{exercises,answers}.tex",
+        "PREFIX",
+    );
+    opts.optflag(
+        "",
+        "follow",
+        "show the newest tree as trees are read, e.g. from a parser writing to stdin",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optopt(
+        "",
+        "graphviz",
+        "path of the Graphviz program (default: dot)",
+        "PATH",
+    );
+    opts.optmulti(
+        "",
+        "graphviz-arg",
+        "extra argument of the Graphviz program, e.g. -Gnodesep=0.5",
+        "ARG",
+    );
     opts.optflag("h", "help", "print this help menu");
+    opts.optflag(
+        "",
+        "index",
+        "search with an index, stored as INPUT_FILE.index (built on first use)",
+    );
+    opts.optopt(
+        "",
+        "keep",
+        "keep only the N most recent trees, e.g. when following a parser (default: all)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "lang",
+        "display profile of a language, e.g. de or he (default: from # lang metadata)",
+        "LANG",
+    );
+    opts.optopt(
+        "",
+        "layout",
+        "Graphviz layout engine: dot, neato, fdp, or twopi (default: dot)",
+        "ENGINE",
+    );
+    opts.optflag(
+        "",
+        "lazy",
+        "read trees from INPUT_FILE on demand, for treebanks that do not fit in memory",
+    );
     opts.optopt(
         "l",
         "layer",
         "layer: form, lemma, cpos, pos, headrel, or pheadrel (default: form)",
         "LAYER",
     );
-    let matches = opts.parse(&args[1..])
+    opts.optopt(
+        "",
+        "length",
+        "only browse sentences with a number of tokens in RANGE, e.g. 5-15",
+        "RANGE",
+    );
+    opts.optopt(
+        "",
+        "library",
+        "construction library directory (default: ~/.local/share/conllx-view/library)",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "previous-version",
+        "browse the trees that changed since the version OLD of the treebank",
+        "OLD",
+    );
+    opts.optopt(
+        "",
+        "quiz",
+        "quiz mode, hiding relations or heads: labels or heads",
+        "MODE",
+    );
+    opts.optopt(
+        "",
+        "listen",
+        "also add the trees that clients send to the Unix socket PATH",
+        "PATH",
+    );
+    opts.optflag(
+        "",
+        "mmap",
+        "like --lazy, but read the trees from a memory map of INPUT_FILE",
+    );
+    opts.optflag("", "no-basic", "do not draw the basic dependencies");
+    opts.optflag(
+        "",
+        "note-comments",
+        "keep notes in # note comments of the treebank, rather than in a notes file (with --strict)",
+    );
+    opts.optflag(
+        "",
+        "offsets",
+        "with --lazy, store sentence offsets as INPUT_FILE.idx for instant reopening",
+    );
+    opts.optflag(
+        "",
+        "projective-heads",
+        "draw projective heads (PHEAD/PDEPREL) as dashed edges",
+    );
+    opts.optflag("", "root", "draw a root node with the root attachments");
+    opts.optopt(
+        "",
+        "sample",
+        "export a random sample of N sentences (default: all)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "search",
+        "open the search bar with QUERY, e.g. \"root cpos=VERB\"",
+        "QUERY",
+    );
+    opts.optopt("", "seed", "random seed for sampling sentences", "SEED");
+    opts.optopt(
+        "",
+        "sentences",
+        "export the given sentences, e.g. 1-10,15 (default: all)",
+        "RANGES",
+    );
+    opts.optopt(
+        "",
+        "slideshow",
+        "advance to the next tree every SECONDS seconds, F5 stops the slideshow",
+        "SECONDS",
+    );
+    opts.optopt(
+        "",
+        "spacing",
+        "punctuation spacing of sentences without SpaceAfter: default, french, or none",
+        "RULES",
+    );
+    opts.optopt(
+        "",
+        "review-url",
+        "template for review links, with {path}, {start} and {end} placeholders",
+        "TEMPLATE",
+    );
+    opts.optopt(
+        "r",
+        "rows",
+        "extra label rows under the form, e.g. lemma,pos (default: none)",
+        "LAYERS",
+    );
+    opts.optflag(
+        "",
+        "strict",
+        "save unchanged sentences exactly as they were read, in any input format",
+    );
+    #[cfg(feature = "zmq")]
+    {
+        opts.optopt(
+            "",
+            "subscribe",
+            "also add the sentences of the ZeroMQ publisher at ENDPOINT",
+            "ENDPOINT",
+        );
+        opts.optopt(
+            "",
+            "topic",
+            "with --subscribe, only add the messages of TOPIC (default: all)",
+            "TOPIC",
+        );
+    }
+    opts.optflag(
+        "",
+        "tikz-fragment",
+        "save TikZ without document preamble, for use with \\input",
+    );
+    opts.optopt("", "tree", "show tree N as soon as it is read", "N");
+    opts.optflag(
+        "",
+        "tui",
+        "browse the trees in the terminal, e.g. over SSH without a display",
+    );
+    let mut matches = opts
+        .parse(&args[1..])
         .or_exit("Could not parse command-line arguments", 1);
 
     if matches.opt_present("h") {
@@ -79,36 +578,259 @@ fn main() {
         return;
     }
 
-    if matches.free.len() > 1 {
-        print_usage(&program, opts);
-        process::exit(1);
+    let load_options = LoadOptions {
+        format: matches.opt_str("f").map(|format| {
+            format
+                .parse::<InputFormat>()
+                .or_exit("Cannot parse input format", 1)
+        }),
+        index: matches.opt_present("index"),
+        lazy: matches.opt_present("lazy"),
+        listen: matches.opt_str("listen").map(PathBuf::from),
+        mmap: matches.opt_present("mmap"),
+        note_comments: matches.opt_present("note-comments"),
+        offsets: matches.opt_present("offsets"),
+        review_url: matches.opt_str("review-url"),
+        strict: matches.opt_present("strict"),
+        #[cfg(feature = "zmq")]
+        subscribe: matches.opt_str("subscribe").map(|endpoint| Subscription {
+            endpoint,
+            topic: matches.opt_str("topic").unwrap_or_default(),
+        }),
+    };
+
+    // Without treebank files, the viewer continues with the treebank
+    // that was opened last, unless trees are piped or sent to it.
+    let mut startup = Vec::new();
+    let recent = RecentFiles::open(default_recent_path()).unwrap_or_else(|err| {
+        startup.push(StartupMessage::Error(format!(
+            "Cannot read recent files: {}",
+            err
+        )));
+        RecentFiles::default()
+    });
+    if matches.free.is_empty()
+        && !load_options.has_live_sources()
+        && !matches.opt_present("exercises")
+        && !matches.opt_present("previous-version")
+        && io::stdin().is_terminal()
+    {
+        if let Some(last) = recent.treebanks().first() {
+            matches.free = last.iter().map(|path| path.display().to_string()).collect();
+        }
+    }
+
+    let rows = matches
+        .opt_str("r")
+        .map(|rows| parse_layers(&rows).or_exit("Cannot parse label rows", 1))
+        .unwrap_or_default();
+
+    let mut profile: Profile = match matches.opt_str("lang") {
+        Some(lang) => lang.parse().or_exit("Cannot select language profile", 1),
+        None => matches
+            .free
+            .first()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| decompress(BufReader::new(file)).ok())
+            .and_then(treebank_language)
+            .and_then(|lang| lang.parse().ok())
+            .unwrap_or_default(),
+    };
+    if let Some(spacing) = matches.opt_str("spacing") {
+        profile.spacing = spacing.parse().or_exit("Cannot parse spacing rules", 1);
     }
 
-    let input = Input::from(matches.free.get(0));
+    let render_options = RenderOptions {
+        basic: !matches.opt_present("no-basic"),
+        enhanced: matches.opt_present("enhanced"),
+        projective_heads: matches.opt_present("projective-heads"),
+        root: matches.opt_present("root"),
+        rows,
+        profile,
+        tikz_preamble: !matches.opt_present("tikz-fragment"),
+        graphviz: graphviz_options(&matches),
+        ..RenderOptions::default()
+    };
+
+    let quiz_mode = matches.opt_str("quiz").map(|mode| {
+        mode.parse::<QuizMode>()
+            .or_exit("Cannot parse quiz mode", 1)
+    });
 
-    let treebank_model = Arc::new(Mutex::new(StatefulTreebankModel::new()));
+    if let Some(prefix) = matches.opt_str("exercises") {
+        let ranges = matches
+            .opt_str("sentences")
+            .map(|ranges| parse_ranges(&ranges).or_exit("Cannot parse sentence ranges", 1));
+        let sample = matches
+            .opt_str("sample")
+            .map(|n| n.parse::<usize>().or_exit("Cannot parse sample size", 1));
+        let mut rng = match matches.opt_str("seed") {
+            Some(seed) => StdRng::seed_from_u64(seed.parse().or_exit("Cannot parse seed", 1)),
+            None => StdRng::from_entropy(),
+        };
 
-    gtk::init().or_exit("Failed to initialize GTK", 1);
+        let graphs = read_inputs(&matches.free, load_options.format);
+        let indices = select_trees(graphs.len(), ranges.as_deref(), sample, &mut rng);
 
-    thread::spawn(clone!(treebank_model => move || {
-        let reader = conllx::Reader::new(input.buf_read().or_exit("Cannot open input for reading", 1));
+        save_exercises(
+            &prefix,
+            &graphs,
+            &indices,
+            quiz_mode.unwrap_or(QuizMode::Labels),
+            &render_options,
+        )
+        .or_exit("Cannot save exercises", 1);
 
-        let dep_graph_iter = reader.into_iter().map(|sent| {
-            let sent = sent.or_exit("Cannot read sentence", 1);
-            sent.into()
-        });
+        return;
+    }
+
+    let library_dir = matches
+        .opt_str("library")
+        .map(PathBuf::from)
+        .or_else(default_library_dir)
+        .or_exit("Cannot determine library directory, use --library", 1);
+    let library = Rc::new(RefCell::new(
+        Library::open(library_dir).or_exit("Cannot open construction library", 1),
+    ));
+
+    let treebank_model = Rc::new(RefCell::new(StatefulTreebankModel::new()));
+    treebank_model
+        .borrow_mut()
+        .set_quiz(quiz_mode.map(Quiz::new));
+    treebank_model
+        .borrow_mut()
+        .set_follow(matches.opt_present("follow"));
+    treebank_model
+        .borrow_mut()
+        .set_slideshow(matches.opt_str("slideshow").map(|interval| {
+            slideshow_interval(interval.parse().unwrap_or(0.0)).or_exit(
+                "The slideshow interval should be a positive number of seconds",
+                1,
+            )
+        }));
+    treebank_model.borrow_mut().set_length_filter(
+        matches
+            .opt_str("length")
+            .map(|range| range.parse().or_exit("Cannot parse length range", 1)),
+    );
+    treebank_model
+        .borrow_mut()
+        .set_capacity(matches.opt_str("keep").map(|keep| {
+            keep.parse()
+                .or_exit("Cannot parse the number of trees to keep", 1)
+        }));
+    treebank_model.borrow_mut().set_alerts(
+        matches
+            .opt_strs("alert")
+            .into_iter()
+            .map(|condition| {
+                Alert::new(AlertRule {
+                    name: condition.clone(),
+                    condition,
+                    notify: false,
+                })
+                .or_exit("Cannot parse alert", 1)
+            })
+            .collect(),
+    );
+
+    let start = matches.opt_str("tree").map(|tree| {
+        tree.parse::<usize>()
+            .ok()
+            .and_then(|tree| tree.checked_sub(1))
+            .or_exit("The tree should be a number from 1", 1)
+    });
+    let search = matches.opt_str("search");
 
-        for graph in dep_graph_iter {
-            treebank_model.lock().unwrap().push(graph);
+    if matches.opt_present("tui") {
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.extend(read_inputs(&matches.free, load_options.format));
+        if let Some(start) = start {
+            treebank_model.set_start(start);
         }
-    }));
+        if let Some(query) = search {
+            let query = query.parse::<Query>().or_exit("Cannot parse query", 1);
+            treebank_model.search(&query, MatchOptions::default());
+            treebank_model.next_match();
+        }
+
+        for message in &startup {
+            eprintln!("{}", message.text());
+        }
+
+        tui::run(&mut treebank_model, render_options.profile.spacing)
+            .or_exit("Cannot browse in the terminal", 1);
+        return;
+    }
+
+    gtk::init().or_exit("Failed to initialize GTK", 1);
+
+    let format = load_options.format;
+    let opener = Rc::new(TreebankOpener::new(
+        load_options,
+        treebank_model.clone(),
+        recent,
+        Rc::new(GtkMainContext),
+    ));
+    match matches.opt_str("previous-version") {
+        Some(old) => {
+            if matches.free.len() != 1 {
+                eprintln!(
+                    "Cannot open treebank: {}",
+                    ViewerError::RequiresSingleFile("--previous-version".to_owned())
+                );
+                process::exit(1);
+            }
+            let comparison = open_changed_trees(
+                &mut treebank_model.borrow_mut(),
+                &old,
+                &matches.free[0],
+                format,
+            );
+            startup.push(StartupMessage::Info(format!(
+                "{} added, {} removed, {} changed, {} unchanged trees",
+                comparison.added.len(),
+                comparison.removed.len(),
+                comparison.changed.len(),
+                comparison.unchanged
+            )));
+        }
+        None => opener.open(matches.free.iter().map(PathBuf::from).collect()),
+    }
 
     let application =
         gtk::Application::new("eu.danieldk.conllx-view", gio::ApplicationFlags::empty())
             .expect("Initialization failed");
 
     application.connect_startup(move |app| {
-        create_gui(app, 800, 600, treebank_model.clone());
+        let icons = register_icons();
+
+        let (window, viewer) = create_gui(
+            app,
+            800,
+            600,
+            treebank_model.clone(),
+            render_options.clone(),
+            library.clone(),
+            opener.clone(),
+        );
+
+        for message in &startup {
+            match *message {
+                StartupMessage::Info(ref message) => viewer.notify(message),
+                StartupMessage::Error(ref message) => viewer.notify_error(message),
+            }
+        }
+        if let Err(ref err) = icons {
+            viewer.notify_error(&format!("Cannot register icons: {}", err));
+        }
+
+        if let Some(start) = start {
+            treebank_model.borrow_mut().set_start(start);
+        }
+        if let Some(ref query) = search {
+            window.activate_action("find", &query.to_variant());
+        }
     });
 
     application.connect_activate(|_| {});
@@ -116,224 +838,4519 @@ fn main() {
     application.run(&args);
 }
 
-fn create_gui(
-    application: &gtk::Application,
-    width: i32,
-    height: i32,
-    treebank_model: Arc<Mutex<StatefulTreebankModel>>,
-) {
-    let glade_src = include_str!("viewer.glade");
-    let builder = gtk::Builder::new_from_string(glade_src);
-    builder.set_application(application);
+/// A message of starting the viewer, which is shown once the window is
+/// created.
+enum StartupMessage {
+    Info(String),
+    Error(String),
+}
 
-    let window: gtk::ApplicationWindow = builder
-        .get_object("viewer_window")
-        .expect("Cannot get main window");
-    window.set_application(application);
+impl StartupMessage {
+    fn text(&self) -> &str {
+        match *self {
+            StartupMessage::Info(ref text) | StartupMessage::Error(ref text) => text,
+        }
+    }
+}
 
-    let dep_widget = create_dependency_tree_widget(&mut treebank_model.lock().unwrap(), &builder);
+/// Read the treebank files, or standard input when there are no files.
+/// Several treebank files are concatenated.
+fn read_inputs(paths: &[String], format: Option<InputFormat>) -> Vec<DependencyGraph> {
+    let inputs = if paths.is_empty() {
+        vec![Input::from(None::<String>)]
+    } else {
+        paths.iter().map(|path| Input::from(Some(path))).collect()
+    };
 
-    setup_sentence_widget(&mut treebank_model.lock().unwrap(), &builder);
-    setup_header_bar(&mut treebank_model.lock().unwrap(), &builder);
-    setup_key_event_handling(&window, treebank_model.clone(), dep_widget.clone());
+    let mut graphs = Vec::new();
+    for input in inputs {
+        let mut read = decompress(input.buf_read().or_exit("Cannot open input for reading", 1))
+            .or_exit("Cannot decompress input", 1);
+        let format = match format {
+            Some(format) => format,
+            None => InputFormat::detect(&mut read).or_exit("Cannot detect input format", 1),
+        };
+        graphs.extend(read_treebank(read, format).or_exit("Cannot read treebank", 1));
+    }
 
-    window.set_default_size(width, height);
-    window.connect_delete_event(|_, _| {
-        gtk::main_quit();
-        Inhibit(false)
-    });
+    graphs
+}
 
-    window.show_all();
+fn convert_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "from",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optopt(
+        "",
+        "graphviz",
+        "path of the Graphviz program (default: dot)",
+        "PATH",
+    );
+    opts.optmulti(
+        "",
+        "graphviz-arg",
+        "extra argument of the Graphviz program, e.g. -Gnodesep=0.5",
+        "ARG",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optflag(
+        "",
+        "interactive",
+        "with --to html, show one tree at a time with keyboard navigation and a query box",
+    );
+    opts.optopt(
+        "",
+        "lang",
+        "display profile of a language, e.g. de or he (default: none)",
+        "LANG",
+    );
+    opts.optopt(
+        "",
+        "layout",
+        "Graphviz layout engine: dot, neato, fdp, or twopi (default: dot)",
+        "ENGINE",
+    );
+    opts.optflag("", "root", "draw a root node with the root attachments");
+    opts.optopt(
+        "r",
+        "rows",
+        "extra label rows under the form, e.g. lemma,pos (default: none)",
+        "LAYERS",
+    );
+    opts.optopt(
+        "",
+        "sentences",
+        "only convert the sentences in RANGES, e.g. 1-10,15",
+        "RANGES",
+    );
+    opts.optopt(
+        "",
+        "theme",
+        "colors of images: light, dark, or high-contrast (default: light)",
+        "THEME",
+    );
+    opts.optflag(
+        "",
+        "tikz-fragment",
+        "write TikZ figures without a document preamble",
+    );
+    opts.optopt(
+        "",
+        "to",
+        "output format: conllx, conllu, conll2009, stanford, dot, tikz, svg, png, diagram, or html",
+        "FORMAT",
+    );
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
 
-    treebank_model.lock().unwrap().first();
-}
+    if matches.opt_present("h") || !matches.opt_present("to") || matches.free.len() > 2 {
+        let brief = format!(
+            "Usage: {} convert [options] --to FORMAT [INPUT] [OUTPUT]",
+            program
+        );
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
 
-thread_local!(
-    static TREE_INDEX_KEY: RefCell<Option<(gtk::Label, Receiver<(usize, usize)>)>> = RefCell::new(None)
-);
+    let to = matches.opt_str("to").unwrap();
+    let to = match to.as_str() {
+        "html" => ConvertFormat::Report,
+        _ => match to.parse::<InputFormat>() {
+            Ok(format) => ConvertFormat::Treebank(format),
+            Err(_) => ConvertFormat::Image(to.parse().or_exit("Cannot parse output format", 1)),
+        },
+    };
 
-fn setup_header_bar(treebank_model: &mut StatefulTreebankModel, builder: &gtk::Builder) {
-    let idx_label: gtk::Label = builder
-        .get_object("idx_label")
-        .expect("Cannot get sentence index label");
+    let ranges = matches
+        .opt_str("sentences")
+        .map(|ranges| parse_ranges(&ranges).or_exit("Cannot parse sentence ranges", 1));
 
-    let (tx, rx) = channel();
+    let input = Input::from(matches.free.first());
+    let mut read = decompress(input.buf_read().or_exit("Cannot open input for reading", 1))
+        .or_exit("Cannot decompress input", 1);
+    let sample =
+        String::from_utf8_lossy(read.fill_buf().or_exit("Cannot read input", 1)).into_owned();
+    let from = match matches.opt_str("from") {
+        Some(from) => from.parse().or_exit("Cannot parse input format", 1),
+        None => detect_format(&sample),
+    };
 
-    TREE_INDEX_KEY.with(clone!(idx_label => move |global| {
-        *global.borrow_mut() = Some((idx_label, rx))
-    }));
+    let columns = unsupported_columns(&sample, from);
+    if !columns.is_empty() {
+        eprintln!("Ignoring {}", columns.join(", "));
+    }
 
-    treebank_model.connect_update(ModelUpdate::Any, move |model| {
-        tx.send((model.idx(), model.len()))
-            .expect("Could not send data to channel");
-        glib::idle_add(|| {
-            TREE_INDEX_KEY.with(|key| {
-                if let Some((ref label, ref rx)) = *key.borrow() {
-                    if let Ok((index, len)) = rx.try_recv() {
-                        label.set_text(&format!("{} of {}", index + 1, len));
-                    }
-                }
-            });
+    let mut graphs = read_treebank(read, from).or_exit("Cannot read treebank", 1);
+    let indices = select_trees(
+        graphs.len(),
+        ranges.as_deref(),
+        None,
+        &mut rand::thread_rng(),
+    );
+    if ranges.is_some() {
+        graphs = indices.iter().map(|&idx| graphs[idx].clone()).collect();
+    }
 
-            glib::Continue(false)
-        });
-    });
+    if let ConvertFormat::Treebank(format) = to {
+        let output = Output::from(matches.free.get(1));
+        let writer = output.write().or_exit("Cannot open output for writing", 1);
+        write_graphs(writer, &graphs, format).or_exit("Cannot write treebank", 1);
+        return;
+    }
+
+    let render_options = RenderOptions {
+        root: matches.opt_present("root"),
+        rows: matches
+            .opt_str("r")
+            .map(|rows| parse_layers(&rows).or_exit("Cannot parse label rows", 1))
+            .unwrap_or_default(),
+        profile: matches
+            .opt_str("lang")
+            .map(|lang| lang.parse().or_exit("Cannot select language profile", 1))
+            .unwrap_or_default(),
+        theme: matches
+            .opt_str("theme")
+            .map(|theme| theme.parse().or_exit("Cannot select theme", 1))
+            .unwrap_or_default(),
+        tikz_preamble: !matches.opt_present("tikz-fragment"),
+        graphviz: graphviz_options(&matches),
+        ..RenderOptions::default()
+    };
+
+    match to {
+        ConvertFormat::Image(image_format) => write_images(
+            matches.free.get(1).map(String::as_str),
+            &graphs,
+            image_format,
+            &render_options,
+        )
+        .or_exit("Cannot write images", 1),
+        ConvertFormat::Report => {
+            let title = matches
+                .free
+                .first()
+                .and_then(|path| Path::new(path).file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Treebank".to_owned());
+            let output = Output::from(matches.free.get(1));
+            let writer = output.write().or_exit("Cannot open output for writing", 1);
+            if matches.opt_present("interactive") {
+                write_interactive_report(writer, &title, &graphs, &indices, &render_options)
+            } else {
+                write_html_report(writer, &title, &graphs, &indices, &render_options)
+            }
+            .or_exit("Cannot write report", 1);
+        }
+        ConvertFormat::Treebank(_) => unreachable!(),
+    }
 }
 
-thread_local!(
-    static DEPTREE_KEY: RefCell<Option<(Rc<RefCell<DependencyTreeWidget>>, Receiver<DependencyGraph>)>> = RefCell::new(None)
-);
+/// A format that `convert` writes trees in.
+enum ConvertFormat {
+    Image(ImageFormat),
+    /// An HTML page with all trees.
+    Report,
+    Treebank(InputFormat),
+}
 
-fn create_dependency_tree_widget(
-    treebank_model: &mut StatefulTreebankModel,
-    builder: &gtk::Builder,
-) -> Rc<RefCell<DependencyTreeWidget>> {
-    let drawing_area: gtk::DrawingArea = builder
-        .get_object("dependency_tree_area")
-        .expect("Cannot get drawing area for dependency trees");
-    let dep_widget = Rc::new(RefCell::new(DependencyTreeWidget::from_drawing_area(
-        &drawing_area,
-    )));
+/// Write trees as images. Graphviz graphs, TikZ figures and diagrams are
+/// written one after another to `output` or the standard output. An SVG or
+/// PNG file holds a single tree, so several trees are written to numbered
+/// files that use `output` as a prefix.
+fn write_images(
+    output: Option<&str>,
+    graphs: &[DependencyGraph],
+    image_format: ImageFormat,
+    render_options: &RenderOptions,
+) -> Result<(), ViewerError> {
+    match image_format {
+        ImageFormat::Png | ImageFormat::Svg if graphs.len() > 1 => {
+            let prefix = output.ok_or_else(|| {
+                ViewerError::RequiresOutputPrefix(format!(
+                    "--to {} with several sentences",
+                    image_format.extension()
+                ))
+            })?;
+            for (idx, graph) in graphs.iter().enumerate() {
+                let filename = format!("{}-{}.{}", prefix, idx + 1, image_format.extension());
+                let mut writer = BufWriter::new(File::create(&filename)?);
+                writer.write_all(&render_graph(graph, image_format, render_options)?)?;
+            }
+        }
+        _ => {
+            let output = Output::from(output);
+            let mut writer = output.write()?;
+            for (idx, graph) in graphs.iter().enumerate() {
+                if idx != 0 && image_format != ImageFormat::Diagram {
+                    writer.write_all(b"\n")?;
+                }
+                writer.write_all(&render_graph(graph, image_format, render_options)?)?;
+            }
+        }
+    }
 
-    let (tx, rx) = channel();
+    Ok(())
+}
 
-    DEPTREE_KEY.with(clone!(dep_widget => move |global| {
-        *global.borrow_mut() = Some((dep_widget, rx));
-    }));
+fn diff_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
 
-    // Notify widget when another tree is selected.
-    treebank_model.connect_update(ModelUpdate::TreeSelection, move |model| {
-        let graph = ok_or!(model.graph(), return);
-        tx.send(graph.clone())
-            .expect("Could not send data to channel");
-        glib::idle_add(|| {
-            DEPTREE_KEY.with(|key| {
-                if let Some((ref widget, ref rx)) = *key.borrow() {
-                    if let Ok(graph) = rx.try_recv() {
-                        match graph.svg() {
-                            Ok(svg) => {
-                                if let Ok(handle) = Handle::new_from_data(svg.as_bytes()) {
-                                    widget.borrow_mut().update(handle);
-                                }
-                            }
-                            Err(err) => {
-                                eprintln!("Cannot render tree: {}", err);
-                            }
-                        }
-                    }
-                }
-            });
+    if matches.opt_present("h") || matches.free.len() < 2 || matches.free.len() > 3 {
+        let brief = format!("Usage: {} diff [options] ORIGINAL EDITED [OUTPUT]", program);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
 
-            glib::Continue(false)
-        });
-    });
+    let original = Input::from(matches.free.first());
+    let read = decompress(
+        original
+            .buf_read()
+            .or_exit("Cannot open original treebank", 1),
+    )
+    .or_exit("Cannot decompress original treebank", 1);
+    let original = read_sentences(read).or_exit("Cannot read original treebank", 1);
+    let edited = Input::from(matches.free.get(1));
+    let read = decompress(edited.buf_read().or_exit("Cannot open edited treebank", 1))
+        .or_exit("Cannot decompress edited treebank", 1);
+    let edited = read_sentences(read).or_exit("Cannot read edited treebank", 1);
 
-    dep_widget
-}
+    let diffs = diff_treebanks(&original, &edited).or_exit("Cannot compare treebanks", 1);
 
-thread_local!(
-    static SENTENCE_KEY: RefCell<Option<(SentenceWidget, Receiver<DependencyGraph>)>> = RefCell::new(None)
-);
+    let output = Output::from(matches.free.get(2));
+    let mut writer = output.write().or_exit("Cannot open output for writing", 1);
+    for diff in diffs {
+        write!(writer, "{}", diff).or_exit("Cannot write diff", 1);
+    }
+}
 
-fn setup_sentence_widget(treebank_model: &mut StatefulTreebankModel, builder: &gtk::Builder) {
-    let sentence_view: gtk::TextView = builder
-        .get_object("sentence_view")
-        .expect("Cannot get sentence text view");
-    let sent_widget = SentenceWidget::from_text_view(&sentence_view);
+fn patch_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
 
-    let (tx, rx) = channel();
+    if matches.opt_present("h") || matches.free.len() < 2 || matches.free.len() > 3 {
+        let brief = format!("Usage: {} patch [options] TREEBANK PATCH [OUTPUT]", program);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
 
-    SENTENCE_KEY.with(move |global| {
-        *global.borrow_mut() = Some((sent_widget, rx));
-    });
+    let treebank = Input::from(matches.free.first());
+    let read = decompress(treebank.buf_read().or_exit("Cannot open treebank", 1))
+        .or_exit("Cannot decompress treebank", 1);
+    let mut sentences = read_sentences(read).or_exit("Cannot read treebank", 1);
+    let patch = Input::from(matches.free.get(1));
+    let diffs = read_diffs(patch.buf_read().or_exit("Cannot open patch", 1))
+        .or_exit("Cannot read patch", 1);
 
-    treebank_model.connect_update(ModelUpdate::TreeSelection, move |model| {
-        let graph = ok_or!(model.graph(), return);
-        tx.send(graph.clone())
-            .expect("Could not send data to channel");
-        glib::idle_add(|| {
-            SENTENCE_KEY.with(|key| {
-                if let Some((ref mut widget, ref rx)) = *key.borrow_mut() {
-                    if let Ok(graph) = rx.try_recv() {
-                        let tokens = graph.tokens();
-                        widget.update(tokens.join(" "));
-                    }
-                }
-            });
+    apply_diffs(&mut sentences, &diffs).or_exit("Cannot apply patch", 1);
 
-            glib::Continue(false)
-        });
-    });
+    let output = Output::from(matches.free.get(2));
+    write_sentences(
+        output.write().or_exit("Cannot open output for writing", 1),
+        &sentences,
+    )
+    .or_exit("Cannot write treebank", 1);
 }
 
-fn setup_key_event_handling(
-    window: &gtk::ApplicationWindow,
-    treebank_model: Arc<Mutex<StatefulTreebankModel>>,
-    dep_widget: Rc<RefCell<DependencyTreeWidget>>,
-) {
-    let window_clone = window.clone();
-
-    window.connect_key_press_event(move |_, key_event| {
-        println!("key: {}", key_event.get_keyval());
-        match key_event.get_keyval() {
-            DOT_KEY => match save_dot(&treebank_model.lock().unwrap()) {
-                Ok(filename) => println!("Saved tree to: {}", filename),
-                Err(err) => eprintln!("Error writing dot output: {}", err),
-            },
-            NEXT_KEY => {
-                treebank_model.lock().unwrap().next();
-            }
-            PREVIOUS_KEY => {
-                treebank_model.lock().unwrap().previous();
-            }
-            QUIT_KEY => {
-                window_clone.destroy();
-            }
-            TIKZ_KEY => match save_tikz(&treebank_model.lock().unwrap()) {
-                Ok(filename) => println!("Saved tree to: {}", filename),
-                Err(err) => eprintln!("Error writing dot output: {}", err),
-            },
-            ZOOM_IN_KEY => {
-                let mut widget_mut = dep_widget.borrow_mut();
-                widget_mut.zoom_in();
-                widget_mut.queue_draw();
-            }
-            ZOOM_OUT_KEY => {
-                let mut widget_mut = dep_widget.borrow_mut();
-                widget_mut.zoom_out();
-                widget_mut.queue_draw();
-            }
-            _ => (),
-        }
-        Inhibit(false)
+fn stats_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optflag(
+        "",
+        "compare",
+        "compare the distributions of treebank A to those of treebank B",
+    );
+    opts.optflag(
+        "",
+        "features",
+        "write the counts of feature values by UPOS tag as CSV",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt(
+        "l",
+        "layers",
+        "layers to count, e.g. cpos,headrel (default: cpos,pos,headrel)",
+        "LAYERS",
+    );
+    opts.optopt(
+        "",
+        "threshold",
+        "with --compare, mark relative changes above PERCENT (default: 50)",
+        "PERCENT",
+    );
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
+
+    let n_treebanks = if matches.opt_present("compare") { 2 } else { 1 };
+    let features = matches.opt_present("features");
+    if matches.opt_present("h")
+        || matches.free.len() != n_treebanks
+        || (features && n_treebanks != 1)
+    {
+        let brief = format!(
+            "Usage: {0} stats [options] TREEBANK\n       {0} stats [options] --compare A B\n       {0} stats --features TREEBANK",
+            program
+        );
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let format = matches.opt_str("f").map(|format| {
+        format
+            .parse::<InputFormat>()
+            .or_exit("Cannot parse input format", 1)
+    });
+    let layers = matches
+        .opt_str("l")
+        .map(|layers| parse_layers(&layers).or_exit("Cannot parse layers", 1))
+        .unwrap_or_else(|| vec![Layer::CPos, Layer::Pos, Layer::HeadRel]);
+    let threshold = matches
+        .opt_str("threshold")
+        .map(|threshold| {
+            threshold
+                .parse::<f64>()
+                .or_exit("Cannot parse threshold", 1)
+        })
+        .unwrap_or(50.0);
+
+    let stats: Vec<_> = matches
+        .free
+        .iter()
+        .map(|path| {
+            let input = Input::from(Some(path));
+            let mut read = decompress(input.buf_read().or_exit("Cannot open treebank", 1))
+                .or_exit("Cannot decompress treebank", 1);
+            let format = match format {
+                Some(format) => format,
+                None => detect_format(&String::from_utf8_lossy(
+                    read.fill_buf().or_exit("Cannot read treebank", 1),
+                )),
+            };
+
+            let mut stats = TreebankStats::new(&layers);
+            let mut feature_table = FeatureTable::new();
+            for graph in read_graphs(read, format) {
+                let graph = graph.or_exit("Cannot read treebank", 1);
+                stats.add(&graph);
+                if features {
+                    feature_table.add(&graph);
+                }
+            }
+
+            (stats, feature_table)
+        })
+        .collect();
+
+    let stdout = io::stdout();
+    let writer = stdout.lock();
+    match stats.as_slice() {
+        [(_, feature_table)] if features => write_feature_csv(writer, feature_table),
+        [(a, _), (b, _)] => write_comparison(writer, a, b, threshold / 100.0),
+        [(stats, _)] => write_stats(writer, stats),
+        _ => unreachable!(),
+    }
+    .or_exit("Cannot write statistics", 1);
+}
+
+fn inconsistencies_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt(
+        "w",
+        "window",
+        "compare tokens with the same N words on either side (default: 1)",
+        "N",
+    );
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
+
+    if matches.opt_present("h") || matches.free.len() > 2 {
+        let brief = format!(
+            "Usage: {} inconsistencies [options] [TREEBANK] [OUTPUT]",
+            program
+        );
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let window = matches
+        .opt_str("w")
+        .map(|window| window.parse().or_exit("Cannot parse context window", 1))
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+
+    let input = Input::from(matches.free.first());
+    let mut read = decompress(input.buf_read().or_exit("Cannot open treebank", 1))
+        .or_exit("Cannot decompress treebank", 1);
+    let format = match matches.opt_str("f") {
+        Some(format) => format.parse().or_exit("Cannot parse input format", 1),
+        None => InputFormat::detect(&mut read).or_exit("Cannot detect input format", 1),
+    };
+
+    let mut arena = SentenceArena::new();
+    for graph in read_graphs(read, format) {
+        arena.push(&graph.or_exit("Cannot read treebank", 1));
+    }
+    let inconsistencies = find_inconsistencies(&arena, (0..arena.len()).collect(), window);
+
+    let output = Output::from(matches.free.get(1));
+    let writer = output.write().or_exit("Cannot open output for writing", 1);
+    write_inconsistencies(writer, &inconsistencies).or_exit("Cannot write inconsistencies", 1);
+}
+
+fn subtrees_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optopt(
+        "d",
+        "depth",
+        "count patterns of up to DEPTH arcs below their head (default: 2)",
+        "DEPTH",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt(
+        "",
+        "min-count",
+        "only list patterns that occur at least N times (default: 2)",
+        "N",
+    );
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
+
+    if matches.opt_present("h") || matches.free.len() > 2 {
+        let brief = format!("Usage: {} subtrees [options] [TREEBANK] [OUTPUT]", program);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let depth = matches
+        .opt_str("d")
+        .map(|depth| depth.parse().or_exit("Cannot parse depth", 1))
+        .unwrap_or(DEFAULT_SUBTREE_DEPTH);
+    let min_count = matches
+        .opt_str("min-count")
+        .map(|count| count.parse().or_exit("Cannot parse minimum count", 1))
+        .unwrap_or(SUBTREE_MIN_COUNT);
+
+    let input = Input::from(matches.free.first());
+    let mut read = decompress(input.buf_read().or_exit("Cannot open treebank", 1))
+        .or_exit("Cannot decompress treebank", 1);
+    let format = match matches.opt_str("f") {
+        Some(format) => format.parse().or_exit("Cannot parse input format", 1),
+        None => InputFormat::detect(&mut read).or_exit("Cannot detect input format", 1),
+    };
+
+    let mut arena = SentenceArena::new();
+    for graph in read_graphs(read, format) {
+        arena.push(&graph.or_exit("Cannot read treebank", 1));
+    }
+    let patterns = mine_subtrees(&arena, (0..arena.len()).collect(), depth, min_count);
+
+    let output = Output::from(matches.free.get(1));
+    let writer = output.write().or_exit("Cannot open output for writing", 1);
+    write_subtrees(writer, &patterns).or_exit("Cannot write subtrees", 1);
+}
+
+fn versions_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
+
+    if matches.opt_present("h") || matches.free.len() < 2 || matches.free.len() > 3 {
+        let brief = format!("Usage: {} versions [options] OLD NEW [OUTPUT]", program);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let format = matches.opt_str("f").map(|format| {
+        format
+            .parse::<InputFormat>()
+            .or_exit("Cannot parse input format", 1)
+    });
+    let old = read_version(&matches.free[0], format);
+    let new = read_version(&matches.free[1], format);
+    let comparison = compare_versions(&old, &new);
+
+    let output = Output::from(matches.free.get(2));
+    let writer = output.write().or_exit("Cannot open output for writing", 1);
+    write_report(writer, &old, &new, &comparison).or_exit("Cannot write report", 1);
+}
+
+/// Read a version of a treebank for comparison.
+fn read_version(path: &str, format: Option<InputFormat>) -> Vec<VersionedTree> {
+    let input = Input::from(Some(&path.to_owned()));
+    let mut read = decompress(input.buf_read().or_exit("Cannot open treebank", 1))
+        .or_exit("Cannot decompress treebank", 1);
+    let format = match format {
+        Some(format) => format,
+        None => InputFormat::detect(&mut read).or_exit("Cannot detect input format", 1),
+    };
+
+    read_versioned(read, format).or_exit("Cannot read treebank", 1)
+}
+
+/// Show the trees of the new version of a treebank that changed since
+/// the old version. The old versions of the trees are the revision, so
+/// that they can be shown with `v` and as overlay with `o`. Returns the
+/// comparison of the versions.
+fn open_changed_trees(
+    treebank_model: &mut StatefulTreebankModel,
+    old: &str,
+    new: &str,
+    format: Option<InputFormat>,
+) -> VersionComparison {
+    let old = read_version(old, format);
+    let new = read_version(new, format);
+    let comparison = compare_versions(&old, &new);
+
+    let revision = comparison
+        .changed
+        .iter()
+        .map(|&(old_idx, _)| old[old_idx].graph.to_sentence())
+        .collect();
+    treebank_model.extend(
+        comparison
+            .changed
+            .iter()
+            .map(|&(_, new_idx)| new[new_idx].graph.clone()),
+    );
+    treebank_model.set_revision(Some(revision));
+
+    comparison
+}
+
+fn render_command(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optflag(
+        "",
+        "ascii",
+        "draw diagrams with ASCII rather than box-drawing characters",
+    );
+    opts.optflag(
+        "",
+        "enhanced",
+        "draw enhanced dependencies (CoNLL-U DEPS) as a second layer of arcs",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "input format: conllx, conllu, conll2009, or stanford (default: detected)",
+        "FORMAT",
+    );
+    opts.optopt(
+        "",
+        "graphviz",
+        "path of the Graphviz program (default: dot)",
+        "PATH",
+    );
+    opts.optmulti(
+        "",
+        "graphviz-arg",
+        "extra argument of the Graphviz program, e.g. -Gnodesep=0.5",
+        "ARG",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt(
+        "",
+        "label-width",
+        "abbreviate diagram labels and relations to N characters (default: no limit)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "lang",
+        "display profile of a language, e.g. de or he (default: none)",
+        "LANG",
+    );
+    opts.optopt(
+        "",
+        "layout",
+        "Graphviz layout engine: dot, neato, fdp, or twopi (default: dot)",
+        "ENGINE",
+    );
+    opts.optflag("", "no-basic", "do not draw the basic dependencies");
+    opts.optopt(
+        "o",
+        "output-format",
+        "image format: svg, png, dot, tikz, or diagram (plain text) (default: svg)",
+        "FORMAT",
+    );
+    opts.optflag(
+        "",
+        "projective-heads",
+        "draw projective heads (PHEAD/PDEPREL) as dashed edges",
+    );
+    opts.optflag("", "root", "draw a root node with the root attachments");
+    opts.optopt(
+        "r",
+        "rows",
+        "extra label rows under the form, e.g. lemma,pos (default: none)",
+        "LAYERS",
+    );
+    opts.optopt(
+        "",
+        "socket",
+        "serve requests on a Unix socket, one sentence per connection",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "theme",
+        "colors of the image: light, dark, or high-contrast (default: light)",
+        "THEME",
+    );
+    opts.optopt(
+        "",
+        "width",
+        "cut off diagram lines after N characters (default: no limit)",
+        "N",
+    );
+    let matches = opts
+        .parse(args)
+        .or_exit("Could not parse command-line arguments", 1);
+
+    if matches.opt_present("h") || !matches.free.is_empty() {
+        let brief = format!("Usage: {} render [options] < SENTENCE > IMAGE", program);
+        print!("{}", opts.usage(&brief));
+        process::exit(if matches.opt_present("h") { 0 } else { 1 });
+    }
+
+    let format = matches.opt_str("f").map(|format| {
+        format
+            .parse::<InputFormat>()
+            .or_exit("Cannot parse input format", 1)
+    });
+    let image_format = matches
+        .opt_str("o")
+        .map(|format| format.parse().or_exit("Cannot parse image format", 1))
+        .unwrap_or(ImageFormat::Svg);
+
+    let rows = matches
+        .opt_str("r")
+        .map(|rows| parse_layers(&rows).or_exit("Cannot parse label rows", 1))
+        .unwrap_or_default();
+    let profile = matches
+        .opt_str("lang")
+        .map(|lang| lang.parse().or_exit("Cannot select language profile", 1))
+        .unwrap_or_default();
+    let theme = matches
+        .opt_str("theme")
+        .map(|theme| theme.parse().or_exit("Cannot select theme", 1))
+        .unwrap_or_default();
+
+    let render_options = RenderOptions {
+        basic: !matches.opt_present("no-basic"),
+        enhanced: matches.opt_present("enhanced"),
+        projective_heads: matches.opt_present("projective-heads"),
+        root: matches.opt_present("root"),
+        rows,
+        profile,
+        diagram_ascii: matches.opt_present("ascii"),
+        diagram_label_width: matches
+            .opt_str("label-width")
+            .map(|width| width.parse().or_exit("Cannot parse label width", 1)),
+        diagram_width: matches
+            .opt_str("width")
+            .map(|width| width.parse().or_exit("Cannot parse diagram width", 1)),
+        theme,
+        graphviz: graphviz_options(&matches),
+        ..RenderOptions::default()
+    };
+
+    match matches.opt_str("socket") {
+        Some(path) => serve(Path::new(&path), format, image_format, &render_options)
+            .or_exit("Cannot serve rendering requests", 1),
+        None => render_request(
+            io::stdin(),
+            io::stdout(),
+            format,
+            image_format,
+            &render_options,
+        )
+        .or_exit("Cannot render sentence", 1),
+    }
+}
+
+/// The Graphviz program, layout engine and extra arguments of the
+/// command line.
+fn graphviz_options(matches: &Matches) -> GraphvizOptions {
+    GraphvizOptions {
+        program: matches.opt_str("graphviz").map(PathBuf::from),
+        engine: matches
+            .opt_str("layout")
+            .map(|engine| engine.parse().or_exit("Cannot select layout engine", 1)),
+        args: matches.opt_strs("graphviz-arg"),
+    }
+}
+
+fn create_gui(
+    application: &gtk::Application,
+    width: i32,
+    height: i32,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    render_options: RenderOptions,
+    library: Rc<RefCell<Library>>,
+    opener: Rc<TreebankOpener>,
+) -> (gtk::ApplicationWindow, Rc<Viewer>) {
+    let glade_src = include_str!("viewer.glade");
+    let builder = gtk::Builder::new_from_string(glade_src);
+    builder.set_application(application);
+
+    let window: gtk::ApplicationWindow = builder
+        .get_object("viewer_window")
+        .expect("Cannot get main window");
+    window.set_application(application);
+
+    let notification_bar: gtk::InfoBar = builder
+        .get_object("notification_bar")
+        .expect("Cannot get notification bar");
+    let notification_label: gtk::Label = builder
+        .get_object("notification_label")
+        .expect("Cannot get notification label");
+    let viewer = Rc::new(Viewer {
+        notifier: Notifier::new(
+            &window.clone().upcast(),
+            &notification_bar,
+            &notification_label,
+        ),
+        preferences: RefCell::new(Preferences::default()),
+        dot_missing: Cell::new(false),
+    });
+
+    let config = Config::open(default_config_path()).unwrap_or_else(|err| {
+        viewer.notify_error(&format!("Cannot read configuration: {}", err));
+        Config::default()
+    });
+    *viewer.preferences.borrow_mut() = config.preferences.clone();
+    check_graphviz(&viewer, &render_options);
+
+    // The layout of the treebank is restored, and stored when the viewer
+    // is closed.
+    let layouts = Layouts::open(default_layouts_path()).unwrap_or_else(|err| {
+        viewer.notify_error(&format!("Cannot read layouts: {}", err));
+        Layouts::default()
+    });
+    let layout = layouts.get(&opener.paths()).cloned().unwrap_or_default();
+    let layouts = RefCell::new(layouts);
+
+    let dep_widget = create_dependency_tree_widget(
+        &viewer,
+        &mut treebank_model.borrow_mut(),
+        &builder,
+        render_options.clone(),
+        layout.tree.zip(layout.zoom).map(|(tree, zoom)| {
+            let view = TreeZoom {
+                scale: zoom.scale,
+                scroll: zoom.scroll,
+            };
+            (tree, view)
+        }),
+    );
+    setup_token_clicks(
+        viewer.clone(),
+        treebank_model.clone(),
+        dep_widget.clone(),
+        &builder,
+    );
+
+    setup_sentence_widget(
+        &mut treebank_model.borrow_mut(),
+        &builder,
+        render_options.profile,
+    );
+    setup_header_bar(&viewer, &mut treebank_model.borrow_mut(), &builder);
+    setup_path_label(&mut treebank_model.borrow_mut(), &builder);
+    setup_follow_button(treebank_model.clone(), &builder);
+    setup_open_button(viewer.clone(), &window, &builder, opener.clone());
+    setup_paste_menu_item(viewer.clone(), treebank_model.clone(), &builder);
+    setup_trash_menu(
+        treebank_model.clone(),
+        &builder,
+        render_options.profile.spacing,
+    );
+    let library_widget = setup_library_panel(
+        viewer.clone(),
+        treebank_model.clone(),
+        library.clone(),
+        &builder,
+    );
+    setup_quiz_bar(treebank_model.clone(), &builder);
+    setup_edit_bar(viewer.clone(), &window, treebank_model.clone(), &builder);
+    setup_tagging_table(&viewer, &window, treebank_model.clone(), &builder);
+    setup_git_bar(viewer.clone(), treebank_model.clone(), &builder);
+    setup_search_bar(viewer.clone(), treebank_model.clone(), &builder);
+    setup_matches_panel(
+        viewer.clone(),
+        treebank_model.clone(),
+        library,
+        library_widget,
+        &builder,
+        render_options.profile.spacing,
+    );
+    setup_concordance_panel(
+        treebank_model.clone(),
+        &builder,
+        render_options.profile.spacing,
+    );
+    let issues_widget = setup_issues_panel(treebank_model.clone(), &builder);
+    setup_statistics_panel(treebank_model.clone(), &builder);
+    setup_notes_panel(treebank_model.clone(), &builder);
+    let panels = SidePanels::from_builder(&builder, issues_widget);
+    setup_actions(
+        viewer.clone(),
+        &window,
+        &builder,
+        treebank_model.clone(),
+        dep_widget.clone(),
+        panels.clone(),
+        render_options,
+        opener.clone(),
+    );
+    if let Some(stride) = config.page_stride {
+        treebank_model.borrow_mut().set_page_stride(stride);
+    }
+    setup_theme_tracking(treebank_model.clone());
+    if config.vim {
+        setup_vim_keys(&window, treebank_model.clone());
+    }
+    setup_alerts(
+        &viewer,
+        application,
+        &mut treebank_model.borrow_mut(),
+        &builder,
+        &config,
+    );
+    setup_slideshow(&window, treebank_model.clone(), &config);
+    setup_keymap(
+        viewer.clone(),
+        &window,
+        treebank_model.clone(),
+        Rc::new(RefCell::new(config)),
+    );
+    setup_macros(viewer.clone(), &window, treebank_model.clone());
+
+    let search_revealer: gtk::Revealer = builder
+        .get_object("search_revealer")
+        .expect("Cannot get search revealer");
+    let search_entry: gtk::Entry = builder
+        .get_object("search_entry")
+        .expect("Cannot get search entry");
+
+    match layout.window {
+        Some(geometry) => {
+            window.set_default_size(geometry.width, geometry.height);
+            window.move_(geometry.x, geometry.y);
+        }
+        None => window.set_default_size(width, height),
+    }
+    window.connect_delete_event(
+        clone!(viewer, treebank_model, dep_widget, panels => move |window, _| {
+            let mut layout = panels.layout(window);
+
+            // The session continues where it left off.
+            let treebank_model = treebank_model.borrow();
+            if !treebank_model.is_empty() {
+                layout.tree = Some(treebank_model.idx());
+                layout.zoom = dep_widget.borrow().view().map(|view| ZoomLayout {
+                    scale: view.scale,
+                    scroll: view.scroll,
+                });
+            }
+            layout.query = search_entry
+                .get_text()
+                .filter(|query| search_revealer.get_reveal_child() && !query.trim().is_empty());
+
+            if let Err(err) = layouts.borrow_mut().set(&opener.paths(), layout) {
+                viewer.notify_error(&format!("Cannot save layout: {}", err));
+            }
+
+            gtk::main_quit();
+            Inhibit(false)
+        }),
+    );
+
+    window.show_all();
+    panels.restore(&layout);
+
+    treebank_model.borrow_mut().seek(Seek::First);
+    if let Some(ref query) = layout.query {
+        window.activate_action("find", &query.to_variant());
+    }
+    if let Some(tree) = layout.tree {
+        treebank_model.borrow_mut().set_start(tree);
+    }
+
+    (window, viewer)
+}
+
+/// The side panels of the main window.
+#[derive(Clone)]
+struct SidePanels {
+    concordance: DetachablePanel,
+    issues: DetachablePanel,
+
+    /// The issues are validated when the issues panel is shown.
+    issues_widget: IssuesWidget,
+
+    library: DetachablePanel,
+    matches: DetachablePanel,
+    notes: DetachablePanel,
+    statistics: DetachablePanel,
+}
+
+impl SidePanels {
+    fn from_builder(builder: &gtk::Builder, issues_widget: IssuesWidget) -> Self {
+        let panel = |id: &str, title: &str| {
+            let revealer: gtk::Revealer = builder
+                .get_object(id)
+                .unwrap_or_else(|| panic!("Cannot get {}", id));
+            DetachablePanel::from_revealer(&revealer, title)
+        };
+
+        SidePanels {
+            concordance: panel("concordance_revealer", "Concordance"),
+            issues: panel("issues_revealer", "Validation issues"),
+            issues_widget,
+            library: panel("library_revealer", "Construction library"),
+            matches: panel("matches_revealer", "Search matches"),
+            notes: panel("notes_revealer", "Notes"),
+            statistics: panel("statistics_revealer", "Sentence statistics"),
+        }
+    }
+
+    /// The panels by their names in the layout.
+    fn named(&self) -> [(&'static str, &DetachablePanel); 6] {
+        [
+            ("concordance", &self.concordance),
+            ("issues", &self.issues),
+            ("library", &self.library),
+            ("matches", &self.matches),
+            ("notes", &self.notes),
+            ("statistics", &self.statistics),
+        ]
+    }
+
+    /// Get the layout of the main window and the panels.
+    fn layout(&self, window: &gtk::ApplicationWindow) -> Layout {
+        let (x, y) = window.get_position();
+        let (width, height) = window.get_size();
+
+        Layout {
+            window: Some(WindowGeometry {
+                x,
+                y,
+                width,
+                height,
+            }),
+            panels: self
+                .named()
+                .iter()
+                .map(|&(name, panel)| {
+                    let layout = PanelLayout {
+                        shown: panel.is_shown(),
+                        detached: panel.geometry(),
+                    };
+                    (name.to_owned(), layout)
+                })
+                .collect(),
+            ..Layout::default()
+        }
+    }
+
+    /// Show and detach the panels as in a layout.
+    fn restore(&self, layout: &Layout) {
+        for &(name, panel) in &self.named() {
+            let panel_layout = ok_or!(layout.panels.get(name), continue);
+            match panel_layout.detached {
+                Some(ref geometry) => panel.detach(Some(geometry)),
+                None => panel.set_reveal_child(panel_layout.shown),
+            }
+        }
+    }
+}
+
+/// Flag trees with the alerts of the configuration, besides the alerts
+/// from the command line. The number of flagged trees is shown in the
+/// header bar, trees that are flagged by alerts with `notify` are
+/// reported with a desktop notification.
+fn setup_alerts(
+    viewer: &Viewer,
+    application: &gtk::Application,
+    treebank_model: &mut StatefulTreebankModel,
+    builder: &gtk::Builder,
+    config: &Config,
+) {
+    if !config.alerts.is_empty() {
+        let rules = treebank_model
+            .alerts()
+            .iter()
+            .map(|alert| alert.rule.clone())
+            .chain(config.alerts.iter().cloned());
+        let mut alerts = Vec::new();
+        for rule in rules {
+            match Alert::new(rule.clone()) {
+                Ok(alert) => alerts.push(alert),
+                Err(err) => {
+                    viewer.notify_error(&format!("Cannot parse alert {}: {}", rule.name, err))
+                }
+            }
+        }
+        treebank_model.set_alerts(alerts);
+    }
+
+    let header_bar: gtk::HeaderBar = builder
+        .get_object("header_bar")
+        .expect("Cannot get header bar");
+
+    treebank_model.connect_update(ModelUpdate::Any, move |model| {
+        let counts: Vec<_> = model
+            .alerts()
+            .iter()
+            .zip(model.alert_counts())
+            .map(|(alert, count)| format!("{}: {}", alert.rule.name, count))
+            .collect();
+        if counts.is_empty() {
+            header_bar.set_subtitle(None);
+        } else {
+            header_bar.set_subtitle(Some(format!("\u{26a0} {}", counts.join(", ")).as_str()));
+        }
+    });
+
+    let application = application.clone();
+    treebank_model.connect_update(ModelUpdate::Alert, move |model| {
+        let flagged: Vec<_> = model
+            .new_alerts()
+            .iter()
+            .map(|&(idx, alert)| (idx, &model.alerts()[alert].rule))
+            .filter(|(_, rule)| rule.notify)
+            .collect();
+        let &(idx, rule) = ok_or!(flagged.first(), return);
+
+        let notification = gio::Notification::new(&rule.name);
+        let body = if flagged.len() == 1 {
+            format!("Tree {} was flagged", idx + 1)
+        } else {
+            format!(
+                "Tree {} and {} other trees were flagged",
+                idx + 1,
+                flagged.len() - 1
+            )
+        };
+        notification.set_body(body.as_str());
+
+        // A notification replaces the previous one.
+        application.send_notification("alert", &notification);
+    });
+}
+
+fn setup_header_bar(
+    viewer: &Rc<Viewer>,
+    treebank_model: &mut StatefulTreebankModel,
+    builder: &gtk::Builder,
+) {
+    let idx_label: gtk::Label = builder
+        .get_object("idx_label")
+        .expect("Cannot get sentence index label");
+
+    let state_icons = TreeStateIcons::from_box(
+        &builder
+            .get_object("state_icons")
+            .expect("Cannot get tree state icons"),
+    );
+
+    treebank_model.connect_update(ModelUpdate::Any, move |model| {
+        let idx = model.idx();
+        let mut states: Vec<_> = [
+            (TreeIcon::Removed, model.is_removed(idx)),
+            (TreeIcon::Marked, model.is_marked(idx)),
+            (TreeIcon::Noted, model.has_note(idx)),
+            (TreeIcon::Edited, model.is_edited(idx)),
+        ]
+        .iter()
+        .filter(|&&(_, state)| state)
+        .map(|&(icon, _)| (icon, icon.description().to_owned()))
+        .collect();
+        states.extend(
+            model
+                .tree_alerts(idx)
+                .iter()
+                .map(|&alert| (TreeIcon::Alert, model.alerts()[alert].rule.name.clone())),
+        );
+        state_icons.update(&states);
+
+        let filter = model
+            .length_filter()
+            .map(|range| format!(" ({} tokens)", range))
+            .unwrap_or_default();
+        idx_label.set_text(&format!("{} of {}{}", idx + 1, model.len(), filter));
+    });
+
+    let download_progress: gtk::ProgressBar = builder
+        .get_object("download_progress")
+        .expect("Cannot get download progress bar");
+
+    treebank_model.connect_update(ModelUpdate::Download, move |model| {
+        let download = match model.download() {
+            Some(download) => download,
+            None => {
+                download_progress.hide();
+                return;
+            }
+        };
+
+        let name = download.url.rsplit('/').next().unwrap_or(&download.url);
+        let received = download.received as f64 / 1_000_000.;
+        match download.size {
+            Some(size) if size > 0 => {
+                download_progress.set_fraction(download.received as f64 / size as f64);
+                download_progress.set_text(
+                    format!(
+                        "{}: {:.1} of {:.1} MB",
+                        name,
+                        received,
+                        size as f64 / 1_000_000.
+                    )
+                    .as_str(),
+                );
+            }
+            _ => {
+                download_progress.pulse();
+                download_progress.set_text(format!("{}: {:.1} MB", name, received).as_str());
+            }
+        }
+
+        download_progress.show();
+    });
+
+    treebank_model.connect_update(
+        ModelUpdate::ReadError,
+        clone!(viewer => move |model| {
+            if let Some(error) = model.read_error() {
+                viewer.notify_error(error);
+            }
+        }),
+    );
+
+    treebank_model.connect_update(
+        ModelUpdate::ReadWarning,
+        clone!(viewer => move |model| {
+            if let Some(warning) = model.read_warning() {
+                viewer.notify(warning);
+            }
+        }),
+    );
+}
+
+fn setup_follow_button(treebank_model: Rc<RefCell<StatefulTreebankModel>>, builder: &gtk::Builder) {
+    let follow_button: gtk::ToggleButton = builder
+        .get_object("follow_button")
+        .expect("Cannot get follow button");
+
+    follow_button.set_active(treebank_model.borrow().follow());
+    follow_button.connect_toggled(move |button| {
+        treebank_model.borrow_mut().set_follow(button.get_active());
+    });
+}
+
+/// Rendered trees are cached by the sentence index and the dot source
+/// (or the diagram and theme), so that trees that were edited or are
+/// shown with a different token selection or theme are rendered anew.
+type TreeCacheKey = (usize, String);
+
+/// State of the dependency tree area.
+struct DependencyTreeState {
+    viewer: Rc<Viewer>,
+    widget: Rc<RefCell<DependencyTreeWidget>>,
+    spinner: gtk::Spinner,
+    renderer: Renderer,
+    cache: LruCache<TreeCacheKey, Handle>,
+
+    /// The generation and cache key of the rendering that is waited for.
+    pending: Option<(usize, TreeCacheKey)>,
+
+    /// The zoom levels and scroll positions of zoomed trees, by their
+    /// content.
+    views: LruCache<ContentHash, TreeZoom>,
+
+    /// The tree that is shown, and the tree that was requested last.
+    shown: Option<ContentHash>,
+    requested: Option<ContentHash>,
+
+    /// The view of the tree with this index in the last session, which
+    /// is restored when the tree is first shown.
+    restored: Option<(usize, TreeZoom)>,
+}
+
+impl DependencyTreeState {
+    /// Show a tree from the cache, or request a rendering.
+    fn request(
+        &mut self,
+        key: TreeCacheKey,
+        graph: Arc<DependencyGraph>,
+        options: RenderOptions,
+        reset_zoom: bool,
+    ) {
+        let cached = self.cache.get(&key).cloned();
+        self.requested = Some(graph.content_hash());
+        self.widget.borrow_mut().set_theme(options.theme);
+
+        if self.restored.is_some_and(|(tree, _)| tree == key.0) {
+            if let Some((_, view)) = self.restored.take() {
+                self.views.insert(graph.content_hash(), view);
+            }
+        }
+
+        if let Some(handle) = cached {
+            // Drop renderings of trees that were requested earlier.
+            self.renderer.cancel();
+            self.pending = None;
+            self.spinner.stop();
+            self.spinner.hide();
+            self.show(handle, reset_zoom);
+            return;
+        }
+
+        self.spinner.show();
+        self.spinner.start();
+
+        let generation = self.renderer.render(graph, options, reset_zoom);
+        self.pending = Some((generation, key));
+    }
+
+    /// Show a rendering, unless the user moved on in the meanwhile.
+    fn receive(&mut self, rendering: Rendering) {
+        if !self.renderer.is_current(&rendering) {
+            return;
+        }
+
+        self.spinner.stop();
+        self.spinner.hide();
+
+        let pending = self.pending.take();
+
+        match rendering.svg {
+            Ok(svg) => {
+                if let Ok(handle) = Handle::new_from_data(svg.as_bytes()) {
+                    if let Some((generation, key)) = pending {
+                        if generation == rendering.generation {
+                            self.cache.insert(key, handle.clone());
+                        }
+                    }
+
+                    self.show(handle, rendering.reset_zoom);
+                }
+            }
+            Err(err) => {
+                self.viewer
+                    .notify_error(&format!("Cannot render tree: {}", err));
+            }
+        }
+    }
+
+    /// Show a rendering. When another tree is shown, the view of the
+    /// previous tree is remembered and the view of the new tree is
+    /// restored.
+    fn show(&mut self, handle: Handle, reset_zoom: bool) {
+        let mut widget = self.widget.borrow_mut();
+
+        if reset_zoom {
+            if let Some(shown) = self.shown {
+                match widget.view() {
+                    Some(view) => self.views.insert(shown, view),
+                    None => {
+                        self.views.remove(&shown);
+                    }
+                }
+            }
+
+            widget.update(handle);
+
+            let views = &mut self.views;
+            if let Some(view) = self
+                .requested
+                .and_then(|requested| views.get(&requested).cloned())
+            {
+                widget.set_view(view);
+            }
+        } else {
+            widget.refresh(handle);
+        }
+
+        self.shown = self.requested;
+    }
+}
+
+fn create_dependency_tree_widget(
+    viewer: &Rc<Viewer>,
+    treebank_model: &mut StatefulTreebankModel,
+    builder: &gtk::Builder,
+    render_options: RenderOptions,
+    restored: Option<(usize, TreeZoom)>,
+) -> Rc<RefCell<DependencyTreeWidget>> {
+    let drawing_area: gtk::DrawingArea = builder
+        .get_object("dependency_tree_area")
+        .expect("Cannot get drawing area for dependency trees");
+    let spinner: gtk::Spinner = builder
+        .get_object("render_spinner")
+        .expect("Cannot get render spinner");
+    let dep_widget = Rc::new(RefCell::new(DependencyTreeWidget::from_drawing_area(
+        &drawing_area,
+    )));
+
+    // Renderings are made on worker threads and passed to the main
+    // thread.
+    let (tx, rx) = channel();
+    let tx = Mutex::new(tx);
+    let renderer = Renderer::new(RENDER_WORKERS, move |rendering| {
+        tx.lock()
+            .unwrap()
+            .send(rendering)
+            .expect("Could not send data to channel");
+    });
+
+    let state = Rc::new(RefCell::new(DependencyTreeState {
+        viewer: viewer.clone(),
+        widget: dep_widget.clone(),
+        spinner,
+        renderer,
+        cache: LruCache::new(TREE_CACHE_SIZE),
+        pending: None,
+        views: LruCache::new(TREE_VIEW_CACHE_SIZE),
+        shown: None,
+        requested: None,
+        restored,
+    }));
+
+    rx.attach(
+        &GtkMainContext,
+        clone!(state => move |rendering| {
+            state.borrow_mut().receive(rendering);
+        }),
+    );
+
+    // Show the tree when another tree or token is selected.
+    for &update in &[
+        ModelUpdate::Quiz,
+        ModelUpdate::TokenSelection,
+        ModelUpdate::TreeEdit,
+        ModelUpdate::TreeSelection,
+    ] {
+        let state = state.clone();
+        let render_options = render_options.clone();
+        let viewer = viewer.clone();
+
+        treebank_model.connect_update(update, move |model| {
+            let graph = match model.shown_graph() {
+                Some(graph) => graph,
+                None => {
+                    if let Some(error) = model.take_tree_error() {
+                        viewer.notify_error(&error);
+                    }
+                    return;
+                }
+            };
+            let render_options =
+                model.render_options(&tree_options(&viewer, model, &render_options));
+
+            let source = match render_options.renderer {
+                TreeRenderer::Graphviz => match graph.dot(&render_options) {
+                    Ok(dot) => dot,
+                    Err(err) => {
+                        viewer.notify_error(&format!("Cannot render tree: {}", err));
+                        return;
+                    }
+                },
+                TreeRenderer::Diagram => format!(
+                    "{:?}\n{}",
+                    render_options.theme,
+                    graph.diagram(&render_options)
+                ),
+            };
+
+            state.borrow_mut().request(
+                (model.idx(), source),
+                graph,
+                render_options,
+                update == ModelUpdate::TreeSelection,
+            );
+        });
+    }
+
+    dep_widget
+}
+
+/// Clicking a token selects it, clicking a collapsed subtree expands it.
+fn setup_token_clicks(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    dep_widget: Rc<RefCell<DependencyTreeWidget>>,
+    builder: &gtk::Builder,
+) {
+    let drawing_area: gtk::DrawingArea = builder
+        .get_object("dependency_tree_area")
+        .expect("Cannot get drawing area for dependency trees");
+
+    drawing_area.connect_button_press_event(move |_, event| {
+        if event.get_button() != 1 {
+            return Inhibit(false);
+        }
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        let n_tokens = ok_or!(treebank_model.graph(), return Inhibit(false))
+            .0
+            .node_count();
+        let token = ok_or!(
+            dep_widget.borrow().token_at(event.get_position(), n_tokens),
+            return Inhibit(false)
+        );
+
+        let collapsed = toggle_subtrees(
+            &tree_style(&viewer, &treebank_model).collapsed,
+            treebank_model.collapsed(),
+        );
+        if collapsed.contains(&token) {
+            treebank_model.toggle_collapsed(token);
+        } else {
+            treebank_model.select_token(Some(token));
+        }
+
+        Inhibit(true)
+    });
+}
+
+/// The style of the current tree, from the style comments in its
+/// treebank file.
+fn tree_style(viewer: &Viewer, treebank_model: &StatefulTreebankModel) -> TreeStyle {
+    treebank_model.tree_style().unwrap_or_else(|err| {
+        viewer.notify_error(&format!("Cannot read style of tree: {}", err));
+        TreeStyle::default()
+    })
+}
+
+/// The theme of trees that follows the GTK theme.
+fn gtk_theme() -> Theme {
+    match gtk::Settings::get_default() {
+        Some(settings) => Theme::from_gtk_theme(
+            &settings.get_property_gtk_theme_name().unwrap_or_default(),
+            settings.get_property_gtk_application_prefer_dark_theme(),
+        ),
+        None => Theme::Light,
+    }
+}
+
+/// Check that the Graphviz program of the command line or the
+/// preferences can be run. Otherwise, explain how to install Graphviz
+/// and fall back to text diagrams for the session.
+fn check_graphviz(viewer: &Viewer, render_options: &RenderOptions) {
+    let mut options = render_options.clone();
+    viewer.apply_preferences(&mut options);
+    if dot_available(options.graphviz.program.as_deref()) {
+        return;
+    }
+
+    viewer.dot_missing.set(true);
+    viewer.notifier.error_dialog(
+        "Graphviz dot was not found",
+        "Trees are drawn with Graphviz, which is not installed or not in the PATH. \
+         Until then, trees are drawn as text diagrams.\n\n\
+         Install Graphviz (e.g. the graphviz package of your distribution), or set \
+         CONLLX_VIEW_DOT or the graphviz setting of the configuration to the path of \
+         dot, and start the viewer again.",
+    );
+}
+
+/// Redraw the tree when the GTK theme changes.
+fn setup_theme_tracking(treebank_model: Rc<RefCell<StatefulTreebankModel>>) {
+    let settings = ok_or!(gtk::Settings::get_default(), return);
+    settings.connect_property_gtk_theme_name_notify(clone!(treebank_model => move |_| {
+        treebank_model.borrow_mut().redraw();
+    }));
+    settings.connect_property_gtk_application_prefer_dark_theme_notify(move |_| {
+        treebank_model.borrow_mut().redraw();
+    });
+}
+
+/// The render options of the current tree, with the preferences, its
+/// style and collapsed subtrees. Collapsing a subtree that the style
+/// collapses expands it.
+fn tree_options(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+    render_options: &RenderOptions,
+) -> RenderOptions {
+    let mut options = render_options.clone();
+    viewer.apply_preferences(&mut options);
+    tree_style(viewer, treebank_model).apply(&mut options);
+    options.collapsed = toggle_subtrees(&options.collapsed, treebank_model.collapsed());
+    options
+}
+
+/// Show the relations of the dependency path between the start of the
+/// path and the selected token.
+fn setup_path_label(treebank_model: &mut StatefulTreebankModel, builder: &gtk::Builder) {
+    let path_label: gtk::Label = builder
+        .get_object("path_label")
+        .expect("Cannot get dependency path label");
+
+    for &update in &[
+        ModelUpdate::TokenSelection,
+        ModelUpdate::TreeEdit,
+        ModelUpdate::TreeSelection,
+    ] {
+        let path_label = path_label.clone();
+
+        treebank_model.connect_update(update, move |model| {
+            if model.path_start().is_none() {
+                path_label.hide();
+                return;
+            }
+
+            match model.dependency_path() {
+                Some(path) => path_label.set_text(&format!("Path: {}", path)),
+                None if model.selected_token() == model.path_start() => {
+                    path_label.set_text("Path: select the token that the path ends at")
+                }
+                None => path_label.set_text("Path: the tokens are not connected"),
+            }
+            path_label.show();
+        });
+    }
+}
+
+fn setup_sentence_widget(
+    treebank_model: &mut StatefulTreebankModel,
+    builder: &gtk::Builder,
+    profile: Profile,
+) {
+    let sentence_view: gtk::TextView = builder
+        .get_object("sentence_view")
+        .expect("Cannot get sentence text view");
+    if profile.rtl {
+        sentence_view.set_direction(gtk::TextDirection::Rtl);
+    }
+    let sent_widget = SentenceWidget::from_text_view(&sentence_view);
+
+    treebank_model.connect_update(ModelUpdate::TreeSelection, move |model| {
+        let graph = ok_or!(model.graph(), return);
+        sent_widget.update(detokenize(&graph, profile.spacing));
+    });
+}
+
+fn setup_open_button(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    builder: &gtk::Builder,
+    opener: Rc<TreebankOpener>,
+) {
+    let open_button: gtk::Button = builder
+        .get_object("open_button")
+        .expect("Cannot get open button");
+    let file_menu_button: gtk::MenuButton = builder
+        .get_object("file_menu_button")
+        .expect("Cannot get file menu button");
+    let open_menu_item: gtk::MenuItem = builder
+        .get_object("open_menu_item")
+        .expect("Cannot get open menu item");
+    let open_url_menu_item: gtk::MenuItem = builder
+        .get_object("open_url_menu_item")
+        .expect("Cannot get open URL menu item");
+    let recent_menu = RecentMenu::from_menu(
+        &builder
+            .get_object("recent_menu")
+            .expect("Cannot get recent files menu"),
+        clone!(viewer, opener => move |paths| open_treebank(&viewer, &opener, paths.to_vec())),
+    );
+
+    open_button.connect_clicked(clone!(viewer, window, opener => move |_| {
+        open_treebank_dialog(&viewer, &window, &opener)
+    }));
+    open_menu_item.connect_activate(clone!(viewer, window, opener => move |_| {
+        open_treebank_dialog(&viewer, &window, &opener)
+    }));
+    open_url_menu_item.connect_activate(clone!(viewer, window, opener => move |_| {
+        open_url_dialog(&viewer, &window, &opener)
+    }));
+
+    // The recent files can change while the viewer runs.
+    file_menu_button.connect_toggled(move |button| {
+        if button.get_active() {
+            recent_menu.update(&opener.recent());
+        }
+    });
+}
+
+fn setup_paste_menu_item(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let paste_menu_item: gtk::MenuItem = builder
+        .get_object("paste_menu_item")
+        .expect("Cannot get paste menu item");
+
+    paste_menu_item.connect_activate(move |_| {
+        report_paste(&viewer, paste_sentences(&mut treebank_model.borrow_mut()));
+    });
+}
+
+fn setup_trash_menu(
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+    spacing: Spacing,
+) {
+    let file_menu_button: gtk::MenuButton = builder
+        .get_object("file_menu_button")
+        .expect("Cannot get file menu button");
+    let trash_menu = TrashMenu::from_menu(
+        &builder
+            .get_object("trash_menu")
+            .expect("Cannot get trash menu"),
+        clone!(treebank_model => move |trees| treebank_model.borrow_mut().restore(trees)),
+    );
+
+    file_menu_button.connect_toggled(move |button| {
+        if !button.get_active() {
+            return;
+        }
+
+        let treebank_model = treebank_model.borrow();
+        let removed = treebank_model.removed();
+        let trees: Vec<_> = removed
+            .iter()
+            .take(TRASH_MENU_SIZE)
+            .filter_map(|&idx| {
+                let sentence = detokenize(&*treebank_model.graph_at(idx)?, spacing);
+                let mut chars = sentence.chars();
+                let mut text: String = chars.by_ref().take(TRASH_SENTENCE_LEN).collect();
+                if chars.next().is_some() {
+                    text.push('\u{2026}');
+                }
+                Some((idx, text))
+            })
+            .collect();
+        trash_menu.update(&trees, &removed);
+    });
+}
+
+/// Let the user choose treebank files and open them, replacing the
+/// treebank that is viewed.
+fn open_treebank_dialog(viewer: &Viewer, window: &gtk::ApplicationWindow, opener: &TreebankOpener) {
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some("Open treebank"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Open", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_select_multiple(true);
+
+    let response = gtk::ResponseType::from(dialog.run());
+    let paths = dialog.get_filenames();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Accept || paths.is_empty() {
+        return;
+    }
+
+    open_treebank(viewer, opener, paths);
+}
+
+/// Let the user enter the URL of a treebank and download it, replacing
+/// the treebank that is viewed.
+fn open_url_dialog(viewer: &Viewer, window: &gtk::ApplicationWindow, opener: &TreebankOpener) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Open URL"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel.into()),
+            ("_Open", gtk::ResponseType::Accept.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept.into());
+
+    let url_entry = gtk::Entry::new();
+    url_entry.set_placeholder_text("https://");
+    url_entry.set_activates_default(true);
+    url_entry.set_width_chars(50);
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.add(&url_entry);
+    dialog.show_all();
+
+    let response = gtk::ResponseType::from(dialog.run());
+    let url = url_entry
+        .get_text()
+        .map(|url| url.trim().to_owned())
+        .unwrap_or_default();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Accept || url.is_empty() {
+        return;
+    }
+
+    open_treebank(viewer, opener, vec![PathBuf::from(url)]);
+}
+
+fn open_treebank(viewer: &Viewer, opener: &TreebankOpener, paths: Vec<PathBuf>) {
+    let names: Vec<_> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    opener.open(paths);
+    viewer.notify(&format!("Opening treebank: {}", names.join(", ")));
+}
+
+fn setup_library_panel(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    library: Rc<RefCell<Library>>,
+    builder: &gtk::Builder,
+) -> LibraryWidget {
+    let library_view: gtk::TreeView = builder
+        .get_object("library_view")
+        .expect("Cannot get library view");
+    let name_entry: gtk::Entry = builder
+        .get_object("library_name_entry")
+        .expect("Cannot get library name entry");
+    let tags_entry: gtk::Entry = builder
+        .get_object("library_tags_entry")
+        .expect("Cannot get library tags entry");
+    let add_button: gtk::Button = builder
+        .get_object("library_add_button")
+        .expect("Cannot get library add button");
+
+    let library_widget = LibraryWidget::from_tree_view(&library_view);
+    library_widget.update(library.borrow().entries());
+
+    add_button.connect_clicked(
+        clone!(viewer, treebank_model, library, library_widget, name_entry, tags_entry => move |_| {
+            let treebank_model = treebank_model.borrow();
+            let graph = ok_or!(treebank_model.graph(), return);
+
+            let name = name_entry
+                .get_text()
+                .map(|name| name.trim().to_owned())
+                .unwrap_or_default();
+            let name = if name.is_empty() {
+                format!("Tree {}", treebank_model.idx() + 1)
+            } else {
+                name
+            };
+            let tags = parse_tags(&tags_entry.get_text().unwrap_or_default());
+
+            let mut library = library.borrow_mut();
+            match library.add(LibraryEntry { name, tags }, graph.to_sentence()) {
+                Ok(()) => {
+                    library_widget.update(library.entries());
+                    name_entry.set_text("");
+                    tags_entry.set_text("");
+                }
+                Err(err) => viewer.notify_error(&format!("Cannot add tree to library: {}", err)),
+            }
+        }),
+    );
+
+    // Open an example by appending it to the treebank.
+    library_view.connect_row_activated(clone!(treebank_model, library => move |_, path, _| {
+        let idx = ok_or!(path.get_indices().first().cloned(), return);
+        let sentence = ok_or!(library.borrow().sentence(idx as usize).cloned(), return);
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.push(sentence.into());
+        treebank_model.seek(Seek::Last);
+    }));
+
+    library_view.connect_key_press_event(clone!(viewer, library, library_widget => move |_, key_event| {
+        if key_event.get_keyval() != key::Delete {
+            return Inhibit(false);
+        }
+
+        let idx = ok_or!(library_widget.selected(), return Inhibit(false));
+        let mut library = library.borrow_mut();
+        match library.remove(idx) {
+            Ok(()) => library_widget.update(library.entries()),
+            Err(err) => viewer.notify_error(&format!("Cannot remove tree from library: {}", err)),
+        }
+
+        Inhibit(true)
+    }));
+
+    library_widget
+}
+
+fn setup_search_bar(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let search_entry: gtk::Entry = builder
+        .get_object("search_entry")
+        .expect("Cannot get search entry");
+    let status_label: gtk::Label = builder
+        .get_object("search_status_label")
+        .expect("Cannot get search status label");
+    let checks: Vec<gtk::CheckButton> = [
+        "search_case_check",
+        "search_diacritics_check",
+        "search_lemma_check",
+    ]
+    .iter()
+    .map(|id| builder.get_object(id).expect("Cannot get search option"))
+    .collect();
+
+    // The last query and options, pressing enter again moves to the
+    // next match.
+    let last_search: Rc<RefCell<Option<(String, MatchOptions)>>> = Rc::new(RefCell::new(None));
+
+    let ranking_combo: gtk::ComboBoxText = builder
+        .get_object("search_ranking_combo")
+        .expect("Cannot get search ranking combo box");
+    ranking_combo.connect_changed(clone!(viewer, treebank_model => move |combo| {
+        let ranking = ok_or!(combo.get_active_id(), return);
+        match ranking.parse::<Ranking>() {
+            Ok(ranking) => treebank_model.borrow_mut().rank_matches(ranking),
+            Err(err) => viewer.notify_error(&format!("Cannot rank matches: {}", err)),
+        }
+    }));
+
+    search_entry.connect_activate(
+        clone!(viewer, treebank_model, last_search, checks => move |entry| {
+            let text = entry.get_text().unwrap_or_default();
+            let options = MatchOptions {
+                case_insensitive: checks[0].get_active(),
+                diacritic_insensitive: checks[1].get_active(),
+                lemma_fallback: checks[2].get_active(),
+            };
+
+            let mut treebank_model = treebank_model.borrow_mut();
+            let search = Some((text.clone(), options));
+            if *last_search.borrow() != search {
+                match text.parse::<Query>() {
+                    Ok(query) => treebank_model.search(&query, options),
+                    Err(err) => {
+                        viewer.notify_error(&format!("Cannot parse query: {}", err));
+                        return;
+                    }
+                }
+                *last_search.borrow_mut() = search;
+            }
+
+            treebank_model.next_match();
+        }),
+    );
+
+    for &update in &[ModelUpdate::MatchSelection, ModelUpdate::Search] {
+        let status_label = status_label.clone();
+
+        treebank_model
+            .borrow_mut()
+            .connect_update(update, move |model| {
+                let len = model.matches().len();
+                match model.current_match() {
+                    _ if len == 0 => status_label.set_text("No matches"),
+                    Some(current) => {
+                        status_label.set_text(&format!("{} of {} matches", current + 1, len))
+                    }
+                    None => status_label.set_text(&format!("{} matches", len)),
+                }
+            });
+    }
+}
+
+fn setup_matches_panel(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    library: Rc<RefCell<Library>>,
+    library_widget: LibraryWidget,
+    builder: &gtk::Builder,
+    spacing: Spacing,
+) {
+    let matches_view: gtk::TreeView = builder
+        .get_object("matches_view")
+        .expect("Cannot get matches view");
+    let page_label: gtk::Label = builder
+        .get_object("matches_page_label")
+        .expect("Cannot get matches page label");
+    let previous_button: gtk::Button = builder
+        .get_object("matches_previous_button")
+        .expect("Cannot get previous matches button");
+    let next_button: gtk::Button = builder
+        .get_object("matches_next_button")
+        .expect("Cannot get next matches button");
+    let export_button: gtk::Button = builder
+        .get_object("matches_export_button")
+        .expect("Cannot get export matches button");
+    let remove_button: gtk::Button = builder
+        .get_object("matches_remove_button")
+        .expect("Cannot get remove matches button");
+    let mark_button: gtk::Button = builder
+        .get_object("matches_mark_button")
+        .expect("Cannot get mark matches button");
+    let library_button: gtk::Button = builder
+        .get_object("matches_library_button")
+        .expect("Cannot get library matches button");
+    let tags_entry: gtk::Entry = builder
+        .get_object("library_tags_entry")
+        .expect("Cannot get library tags entry");
+    let matches_widget = MatchesWidget::from_widgets(&matches_view, &page_label);
+
+    export_button.connect_clicked(clone!(viewer, treebank_model, matches_widget => move |_| {
+        let treebank_model = treebank_model.borrow();
+        let trees = selected_trees(&treebank_model, &matches_widget);
+        match save_selection(&viewer, &treebank_model, &trees) {
+            Ok(filename) => viewer.notify(&format!("Saved {} trees to: {}", trees.len(), filename)),
+            Err(err) => viewer.notify_error(&format!("Error writing selection: {}", err)),
+        }
+    }));
+
+    remove_button.connect_clicked(clone!(treebank_model, matches_widget => move |_| {
+        let trees = selected_trees(&treebank_model.borrow(), &matches_widget);
+        treebank_model.borrow_mut().remove(&trees);
+    }));
+
+    mark_button.connect_clicked(clone!(treebank_model, matches_widget => move |_| {
+        let trees = selected_trees(&treebank_model.borrow(), &matches_widget);
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.toggle_marks(&trees);
+
+        let page = ok_or!(matches_widget.page(), return);
+        matches_widget.update(&match_page(&treebank_model, page, spacing));
+    }));
+
+    library_button.connect_clicked(
+        clone!(viewer, treebank_model, matches_widget, tags_entry => move |_| {
+            let treebank_model = treebank_model.borrow();
+            let tags = parse_tags(&tags_entry.get_text().unwrap_or_default());
+
+            let mut library = library.borrow_mut();
+            for idx in selected_trees(&treebank_model, &matches_widget) {
+                let graph = ok_or!(treebank_model.graph_at(idx), continue);
+                let entry = LibraryEntry {
+                    name: format!("Tree {}", idx + 1),
+                    tags: tags.clone(),
+                };
+
+                if let Err(err) = library.add(entry, graph.to_sentence()) {
+                    viewer.notify_error(&format!("Cannot add tree to library: {}", err));
+                    break;
+                }
+            }
+
+            library_widget.update(library.entries());
+        }),
+    );
+
+    matches_view.connect_row_activated(
+        clone!(treebank_model, matches_widget => move |_, path, _| {
+            let row = ok_or!(path.get_indices().first().cloned(), return);
+            let page = matches_widget.page().unwrap_or(0);
+            treebank_model.borrow_mut().select_match(page * MATCHES_PAGE_SIZE + row as usize);
+        }),
+    );
+
+    previous_button.connect_clicked(clone!(treebank_model, matches_widget => move |_| {
+        let page = ok_or!(matches_widget.page(), return);
+        if page > 0 {
+            matches_widget.update(&match_page(&treebank_model.borrow(), page - 1, spacing));
+        }
+    }));
+
+    next_button.connect_clicked(clone!(treebank_model, matches_widget => move |_| {
+        let page = ok_or!(matches_widget.page(), return);
+        let page = match_page(&treebank_model.borrow(), page + 1, spacing);
+        if page.page < page.n_pages {
+            matches_widget.update(&page);
+        }
+    }));
+
+    // Show the page of the current match. New results are always shown,
+    // a selected match only when it is on another page.
+    for &update in &[ModelUpdate::MatchSelection, ModelUpdate::Search] {
+        let matches_widget = matches_widget.clone();
+
+        treebank_model
+            .borrow_mut()
+            .connect_update(update, move |model| {
+                let page = model.current_match().unwrap_or(0) / MATCHES_PAGE_SIZE;
+                if update == ModelUpdate::Search || matches_widget.page() != Some(page) {
+                    matches_widget.update(&match_page(model, page, spacing));
+                }
+            });
+    }
+}
+
+fn setup_concordance_panel(
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+    spacing: Spacing,
+) {
+    let concordance_view: gtk::TreeView = builder
+        .get_object("concordance_view")
+        .expect("Cannot get concordance view");
+    let concordance_widget = ConcordanceWidget::from_tree_view(&concordance_view);
+
+    concordance_view.connect_row_activated(
+        clone!(treebank_model, concordance_widget => move |_, path, _| {
+            let row = ok_or!(path.get_indices().first().cloned(), return);
+            let page = concordance_widget.page().unwrap_or(0);
+            treebank_model.borrow_mut().select_match(page * MATCHES_PAGE_SIZE + row as usize);
+        }),
+    );
+
+    // Like the matches panel, show the page of the current match.
+    for &update in &[ModelUpdate::MatchSelection, ModelUpdate::Search] {
+        let concordance_widget = concordance_widget.clone();
+
+        treebank_model
+            .borrow_mut()
+            .connect_update(update, move |model| {
+                let page = model.current_match().unwrap_or(0) / MATCHES_PAGE_SIZE;
+                if update != ModelUpdate::Search && concordance_widget.page() == Some(page) {
+                    return;
+                }
+
+                let lines: Vec<_> = model
+                    .matches()
+                    .iter()
+                    .skip(page * MATCHES_PAGE_SIZE)
+                    .take(MATCHES_PAGE_SIZE)
+                    .filter_map(|m| {
+                        let graph = model.graph_at(m.sentence)?;
+                        Some((
+                            m.sentence,
+                            kwic(&graph, m.token, spacing, CONCORDANCE_CONTEXT),
+                        ))
+                    })
+                    .collect();
+                concordance_widget.update(page, &lines);
+            });
+    }
+}
+
+/// Get a page of the search matches. Only the rows of the page are
+/// built, with the matching token highlighted in the sentence.
+fn match_page(model: &StatefulTreebankModel, page: usize, spacing: Spacing) -> MatchPage {
+    let matches = model.matches();
+    let n_pages = matches.len().div_ceil(MATCHES_PAGE_SIZE);
+
+    let rows = matches
+        .iter()
+        .skip(page * MATCHES_PAGE_SIZE)
+        .take(MATCHES_PAGE_SIZE)
+        .filter_map(|m| {
+            let graph = model.graph_at(m.sentence)?;
+            let token = graph.0[NodeIndex::new(m.token)].token.form().to_owned();
+            let mut markup = String::new();
+            for piece in pieces(&graph, spacing) {
+                let text = glib::markup_escape_text(piece.text);
+                if piece.contains(m.token) {
+                    markup.push_str(&format!("<b>{}</b>", text));
+                } else {
+                    markup.push_str(&text);
+                }
+
+                if piece.space_after {
+                    markup.push(' ');
+                }
+            }
+            Some((m.sentence, tree_icon(model, m.sentence), token, markup))
+        })
+        .collect();
+
+    MatchPage {
+        page,
+        n_pages,
+        rows,
+    }
+}
+
+/// The icon of the most important state of a tree, if any: removed,
+/// marked for review, with a note, edited or changed since the last
+/// commit.
+fn tree_icon(model: &StatefulTreebankModel, idx: usize) -> Option<TreeIcon> {
+    if model.is_removed(idx) {
+        Some(TreeIcon::Removed)
+    } else if model.is_marked(idx) {
+        Some(TreeIcon::Marked)
+    } else if model.has_note(idx) {
+        Some(TreeIcon::Noted)
+    } else if model.is_edited(idx) {
+        Some(TreeIcon::Edited)
+    } else if model.is_changed_since_revision(idx) == Some(true) {
+        Some(TreeIcon::Changed)
+    } else {
+        None
+    }
+}
+
+/// Get the trees of the selected matches, in treebank order.
+fn selected_trees(model: &StatefulTreebankModel, matches_widget: &MatchesWidget) -> Vec<usize> {
+    let offset = matches_widget.page().unwrap_or(0) * MATCHES_PAGE_SIZE;
+    let mut trees: Vec<_> = matches_widget
+        .selected()
+        .into_iter()
+        .filter_map(|row| model.matches().get(offset + row))
+        .map(|m| m.sentence)
+        .collect();
+    trees.sort();
+    trees.dedup();
+    trees
+}
+
+fn setup_issues_panel(
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) -> IssuesWidget {
+    let issues_view: gtk::TreeView = builder
+        .get_object("issues_view")
+        .expect("Cannot get issues view");
+    let issues_widget = IssuesWidget::from_tree_view(&issues_view);
+
+    // Jump to the token of an issue.
+    issues_view.connect_row_activated(clone!(treebank_model, issues_widget => move |_, path, _| {
+        let row = ok_or!(path.get_indices().first().cloned(), return);
+        let issue = ok_or!(issues_widget.issue(row as usize), return);
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.select_tree(issue.sentence);
+        treebank_model.select_token(Some(issue.token));
+    }));
+
+    // Revalidate after edits.
+    treebank_model.borrow_mut().connect_update(
+        ModelUpdate::TreeEdit,
+        clone!(issues_widget => move |model| {
+            issues_widget.update(validate(&model.sentences()));
+        }),
+    );
+
+    issues_widget
+}
+
+fn setup_statistics_panel(
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let statistics_view: gtk::TreeView = builder
+        .get_object("statistics_view")
+        .expect("Cannot get statistics view");
+    let statistics_widget = SentenceStatsWidget::from_tree_view(&statistics_view);
+
+    let mut treebank_model = treebank_model.borrow_mut();
+    for &update in &[ModelUpdate::TreeSelection, ModelUpdate::TreeEdit] {
+        treebank_model.connect_update(
+            update,
+            clone!(statistics_widget => move |model| {
+                statistics_widget.update(model.graph().map(|graph| SentenceStats::new(&graph)));
+            }),
+        );
+    }
+}
+
+/// Edit the note of the current tree in the notes panel.
+fn setup_notes_panel(treebank_model: Rc<RefCell<StatefulTreebankModel>>, builder: &gtk::Builder) {
+    let notes_view: gtk::TextView = builder
+        .get_object("notes_view")
+        .expect("Cannot get notes view");
+    let buffer = notes_view.get_buffer().expect("Cannot get notes buffer");
+
+    // The buffer is not an edit while it shows the note of another tree.
+    let showing = Rc::new(Cell::new(false));
+
+    buffer.connect_changed(clone!(treebank_model, showing => move |buffer| {
+        if showing.get() {
+            return;
+        }
+
+        let (start, end) = buffer.get_bounds();
+        let note = buffer.get_text(&start, &end, false).unwrap_or_default();
+        let mut treebank_model = treebank_model.borrow_mut();
+        let idx = treebank_model.idx();
+        treebank_model.set_note(idx, &note);
+    }));
+
+    let mut treebank_model = treebank_model.borrow_mut();
+    for &update in &[ModelUpdate::TreeSelection, ModelUpdate::Notes] {
+        treebank_model.connect_update(
+            update,
+            clone!(buffer, showing => move |model| {
+                let note = model.note(model.idx()).unwrap_or("");
+                let (start, end) = buffer.get_bounds();
+                let text = buffer.get_text(&start, &end, false).unwrap_or_default();
+                if text.trim() == note.trim() {
+                    return;
+                }
+
+                showing.set(true);
+                buffer.set_text(note);
+                showing.set(false);
+            }),
+        );
+    }
+}
+
+fn setup_quiz_bar(treebank_model: Rc<RefCell<StatefulTreebankModel>>, builder: &gtk::Builder) {
+    let quiz_revealer: gtk::Revealer = builder
+        .get_object("quiz_revealer")
+        .expect("Cannot get quiz revealer");
+    let quiz_entry: gtk::Entry = builder
+        .get_object("quiz_entry")
+        .expect("Cannot get quiz entry");
+    let score_label: gtk::Label = builder
+        .get_object("quiz_score_label")
+        .expect("Cannot get quiz score label");
+
+    quiz_revealer.set_reveal_child(treebank_model.borrow().quiz().is_some());
+
+    quiz_entry.connect_activate(clone!(treebank_model => move |entry| {
+        let guess = entry.get_text().unwrap_or_default();
+        treebank_model.borrow_mut().guess(&guess);
+        entry.set_text("");
+    }));
+
+    treebank_model
+        .borrow_mut()
+        .connect_update(ModelUpdate::Quiz, move |model| {
+            let (correct, answered) = ok_or!(model.quiz(), return).score();
+            score_label.set_text(&format!("{} of {} correct", correct, answered));
+        });
+}
+
+fn setup_edit_bar(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let head_entry: gtk::Entry = builder
+        .get_object("edit_head_entry")
+        .expect("Cannot get head entry");
+    let relation_entry: gtk::Entry = builder
+        .get_object("edit_relation_entry")
+        .expect("Cannot get relation entry");
+
+    // Edits are actions, so that they can be recorded in macros.
+    add_string_action(
+        window,
+        "set-head",
+        clone!(viewer, treebank_model => move |head| {
+            let result = match head.parse::<usize>() {
+                Ok(head) => treebank_model.borrow_mut().set_head(head),
+                Err(_) => Err(ViewerError::InvalidHead(0)),
+            };
+
+            if let Err(err) = result {
+                viewer.notify_error(&format!("Cannot change head: {}", err));
+            }
+        }),
+    );
+    add_string_action(
+        window,
+        "set-relation",
+        clone!(viewer, treebank_model => move |rel| {
+            if let Err(err) = treebank_model.borrow_mut().set_relation(rel) {
+                viewer.notify_error(&format!("Cannot change relation: {}", err));
+            }
+        }),
+    );
+
+    head_entry.connect_activate(clone!(window => move |entry| {
+        let head = entry.get_text().unwrap_or_default();
+        window.activate_action("set-head", &head.trim().to_variant());
+    }));
+    relation_entry.connect_activate(clone!(window => move |entry| {
+        let rel = entry.get_text().unwrap_or_default();
+        window.activate_action("set-relation", &rel.trim().to_variant());
+    }));
+
+    // Show the attachment of the selected token.
+    for &update in &[
+        ModelUpdate::TokenSelection,
+        ModelUpdate::TreeEdit,
+        ModelUpdate::TreeSelection,
+    ] {
+        let head_entry = head_entry.clone();
+        let relation_entry = relation_entry.clone();
+
+        treebank_model
+            .borrow_mut()
+            .connect_update(update, move |model| {
+                let (head, rel) = model
+                    .graph()
+                    .and_then(|graph| {
+                        let token = &graph.0[NodeIndex::new(model.selected_token()?)].token;
+                        Some((
+                            token
+                                .head()
+                                .map(|head| head.to_string())
+                                .unwrap_or_default(),
+                            token.head_rel().unwrap_or_default().to_owned(),
+                        ))
+                    })
+                    .unwrap_or_default();
+
+                head_entry.set_text(&head);
+                relation_entry.set_text(&rel);
+            });
+    }
+}
+
+/// The tagging table replaces the tree with a table of the tokens, in
+/// which lemmas and part-of-speech tags are edited. Enter edits a tag,
+/// and after every tag the next tag is edited, until Esc is pressed.
+fn setup_tagging_table(
+    viewer: &Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let view_stack: gtk::Stack = builder
+        .get_object("view_stack")
+        .expect("Cannot get view stack");
+    let sentence_view: gtk::TextView = builder
+        .get_object("sentence_view")
+        .expect("Cannot get sentence text view");
+    let tagging_view: gtk::TreeView = builder
+        .get_object("tagging_view")
+        .expect("Cannot get tagging view");
+    let tagging_widget = TaggingWidget::from_tree_view(&tagging_view);
+
+    // Edits are actions, so that they can be recorded in macros.
+    add_tag_action(
+        viewer,
+        window,
+        &treebank_model,
+        "set-lemma",
+        StatefulTreebankModel::set_lemma,
+    );
+    add_tag_action(
+        viewer,
+        window,
+        &treebank_model,
+        "set-upos",
+        StatefulTreebankModel::set_upos,
+    );
+    add_tag_action(
+        viewer,
+        window,
+        &treebank_model,
+        "set-xpos",
+        StatefulTreebankModel::set_xpos,
+    );
+
+    // In the tagging table, the sentence is shown above the tokens.
+    add_action(
+        window,
+        "tagging",
+        clone!(tagging_widget => move || {
+            let tagging = view_stack.get_visible_child_name().as_deref() != Some("tagging");
+            view_stack.set_visible_child_name(if tagging { "tagging" } else { "tree" });
+            if let Some(parent) = sentence_view.get_parent().and_then(|parent| parent.downcast::<gtk::Box>().ok()) {
+                parent.reorder_child(&sentence_view, if tagging { 0 } else { 1 });
+            }
+            if tagging {
+                tagging_widget.grab_focus();
+            }
+        }),
+    );
+
+    // The table is not an edit while it shows the model.
+    let showing = Rc::new(Cell::new(false));
+
+    tagging_widget.connect_token_selected(clone!(treebank_model, showing => move |token| {
+        if !showing.get() {
+            treebank_model.borrow_mut().select_token(Some(token));
+        }
+    }));
+    tagging_widget.connect_activated(clone!(tagging_widget => move |token, column| {
+        tagging_widget.edit(token, column)
+    }));
+    tagging_widget.connect_edited(
+        clone!(window, treebank_model, tagging_widget => move |token, column, tag| {
+            let tag = if tag == "_" { "" } else { tag };
+            let graph = ok_or!(treebank_model.borrow().graph(), return);
+            let current = {
+                let token = &graph.0[NodeIndex::new(token)].token;
+                match column {
+                    TagColumn::Lemma => token.lemma(),
+                    TagColumn::Upos => token.cpos(),
+                    TagColumn::Xpos => token.pos(),
+                }
+            };
+
+            if current.unwrap_or("") != tag {
+                let action = match column {
+                    TagColumn::Lemma => "set-lemma",
+                    TagColumn::Upos => "set-upos",
+                    TagColumn::Xpos => "set-xpos",
+                };
+                treebank_model.borrow_mut().select_token(Some(token));
+                window.activate_action(action, &tag.to_variant());
+            }
+
+            // Continue with the next tag, which is edited once the table
+            // shows the edit.
+            let (token, column) = match column.next() {
+                Some(next) => (token, next),
+                None => (token + 1, TagColumn::Lemma),
+            };
+            if token < graph.0.node_count() {
+                gtk::idle_add(clone!(tagging_widget => move || {
+                    tagging_widget.edit(token, column);
+                    Continue(false)
+                }));
+            }
+        }),
+    );
+
+    let mut treebank_model = treebank_model.borrow_mut();
+    for &update in &[ModelUpdate::TreeSelection, ModelUpdate::TreeEdit] {
+        treebank_model.connect_update(
+            update,
+            clone!(tagging_widget, showing => move |model| {
+                let tokens = model.graph().map(|graph| graph.to_sentence()).unwrap_or_default();
+                showing.set(true);
+                tagging_widget.update(&tokens, model.selected_token());
+                showing.set(false);
+            }),
+        );
+    }
+    treebank_model.connect_update(ModelUpdate::TokenSelection, move |model| {
+        showing.set(true);
+        tagging_widget.select(model.selected_token());
+        showing.set(false);
+    });
+}
+
+/// Add an action that changes a tag of the selected token.
+fn add_tag_action<F>(
+    viewer: &Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: &Rc<RefCell<StatefulTreebankModel>>,
+    name: &str,
+    set_tag: F,
+) where
+    F: Fn(&mut StatefulTreebankModel, &str) -> Result<(), ViewerError> + 'static,
+{
+    add_string_action(
+        window,
+        name,
+        clone!(viewer, treebank_model => move |tag| {
+            if let Err(err) = set_tag(&mut treebank_model.borrow_mut(), tag) {
+                viewer.notify_error(&format!("Cannot change tag: {}", err));
+            }
+        }),
+    );
+}
+
+fn setup_git_bar(
+    viewer: Rc<Viewer>,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    builder: &gtk::Builder,
+) {
+    let status_label: gtk::Label = builder
+        .get_object("git_status_label")
+        .expect("Cannot get git status label");
+    let state_icons = TreeStateIcons::from_box(
+        &builder
+            .get_object("git_state_icons")
+            .expect("Cannot get git state icons"),
+    );
+    let commit_entry: gtk::Entry = builder
+        .get_object("git_commit_entry")
+        .expect("Cannot get commit message entry");
+
+    commit_entry.connect_activate(clone!(viewer, treebank_model => move |entry| {
+        let message = entry.get_text().unwrap_or_default();
+        if message.trim().is_empty() {
+            return;
+        }
+
+        // Commit the file of the tree that is shown.
+        let treebank_file = {
+            let treebank_model = treebank_model.borrow();
+            let (file, _) = ok_or!(treebank_model.file(treebank_model.idx()), return);
+            file.clone()
+        };
+        let git = ok_or!(treebank_file.git.as_ref(), return);
+
+        let result = git.commit(message.trim()).and_then(|_| {
+            let treebank_model = treebank_model.borrow();
+            let files: Option<Vec<_>> = treebank_model
+                .files()
+                .into_iter()
+                .map(|(file, _)| file)
+                .collect();
+            match files {
+                Some(files) => head_revision(files.into_iter().map(|file| &**file)),
+                None => Ok(None),
+            }
+        });
+        match result {
+            Ok(sentences) => {
+                treebank_model.borrow_mut().set_revision(sentences);
+                entry.set_text("");
+                viewer.notify(&format!("Committed: {}", git.path().display()));
+            }
+            Err(err) => viewer.notify_error(&format!("Cannot commit treebank: {}", err)),
+        }
+    }));
+
+    treebank_model
+        .borrow_mut()
+        .connect_update(ModelUpdate::Any, move |model| {
+            let states = match model.is_changed_since_revision(model.idx()) {
+                Some(true) => vec![(
+                    TreeIcon::Changed,
+                    "This tree is changed since HEAD".to_owned(),
+                )],
+                _ => Vec::new(),
+            };
+            state_icons.update(&states);
+            status_label.set_text(&format!(
+                "{} trees changed since HEAD",
+                model.n_changed_since_revision().unwrap_or(0)
+            ));
+        });
+}
+
+/// Add the actions of the window, with their accelerators and the menu
+/// bar.
+#[allow(clippy::too_many_arguments)]
+fn setup_actions(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    builder: &gtk::Builder,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    dep_widget: Rc<RefCell<DependencyTreeWidget>>,
+    panels: SidePanels,
+    render_options: RenderOptions,
+    opener: Rc<TreebankOpener>,
+) {
+    let SidePanels {
+        concordance: concordance_panel,
+        issues: issues_panel,
+        issues_widget,
+        library: library_panel,
+        matches: matches_panel,
+        notes: notes_panel,
+        statistics: statistics_panel,
+    } = panels;
+
+    let edit_revealer: gtk::Revealer = builder
+        .get_object("edit_revealer")
+        .expect("Cannot get edit revealer");
+    let edit_relation_entry: gtk::Entry = builder
+        .get_object("edit_relation_entry")
+        .expect("Cannot get relation entry");
+    let git_revealer: gtk::Revealer = builder
+        .get_object("git_revealer")
+        .expect("Cannot get git revealer");
+    let git_commit_entry: gtk::Entry = builder
+        .get_object("git_commit_entry")
+        .expect("Cannot get commit message entry");
+    let search_revealer: gtk::Revealer = builder
+        .get_object("search_revealer")
+        .expect("Cannot get search revealer");
+    let search_entry: gtk::Entry = builder
+        .get_object("search_entry")
+        .expect("Cannot get search entry");
+    let follow_button: gtk::ToggleButton = builder
+        .get_object("follow_button")
+        .expect("Cannot get follow button");
+
+    add_action(
+        window,
+        "changes",
+        clone!(viewer, treebank_model => move || {
+            match save_changes(&viewer, &treebank_model.borrow()) {
+                Ok(filename) => viewer.notify(&format!("Saved changes to: {}", filename)),
+                Err(err) => viewer.notify_error(&format!("Error writing changes: {}", err)),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "collapse",
+        clone!(window, treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            match treebank_model.selected_token() {
+                Some(token) => treebank_model.toggle_collapsed(token),
+                None => window.error_bell(),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "command-line",
+        clone!(viewer, treebank_model, render_options, opener, search_entry => move || {
+            let query = search_entry.get_text().unwrap_or_default();
+            let command = create_command_line(&treebank_model.borrow(), &render_options, &opener, &query);
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&command);
+            viewer.notify(&format!("Copied command line: {}", command));
+        }),
+    );
+    add_action(
+        window,
+        "concordance",
+        clone!(concordance_panel => move || concordance_panel.toggle()),
+    );
+    add_action(
+        window,
+        "copy-diagram",
+        clone!(viewer, window, treebank_model, render_options => move || {
+            let graph = ok_or!(treebank_model.borrow().graph(), return window.error_bell());
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&graph.diagram(&render_options));
+            viewer.notify(&format!("Copied tree {} as a diagram", treebank_model.borrow().idx() + 1));
+        }),
+    );
+    add_action(
+        window,
+        "deepest",
+        clone!(window, treebank_model => move || {
+            if !treebank_model.borrow_mut().seek_max(|summary| summary.depth) {
+                window.error_bell();
+            }
+        }),
+    );
+    add_action(
+        window,
+        "detach-concordance",
+        clone!(concordance_panel => move || {
+            concordance_panel.toggle_detached()
+        }),
+    );
+    add_action(
+        window,
+        "detach-issues",
+        clone!(treebank_model, issues_panel, issues_widget => move || {
+            if !issues_panel.is_shown() {
+                issues_widget.update(validate(&treebank_model.borrow().sentences()));
+            }
+            issues_panel.toggle_detached();
+        }),
+    );
+    add_action(
+        window,
+        "detach-library",
+        clone!(library_panel => move || {
+            library_panel.toggle_detached()
+        }),
+    );
+    add_action(
+        window,
+        "detach-matches",
+        clone!(matches_panel => move || {
+            matches_panel.toggle_detached()
+        }),
+    );
+    add_action(
+        window,
+        "detach-notes",
+        clone!(notes_panel => move || {
+            notes_panel.toggle_detached()
+        }),
+    );
+    add_action(
+        window,
+        "detach-statistics",
+        clone!(statistics_panel => move || {
+            statistics_panel.toggle_detached()
+        }),
+    );
+    add_action(
+        window,
+        "dot",
+        clone!(viewer, treebank_model, render_options => move || {
+            match save_dot(&viewer, &treebank_model.borrow(), &render_options) {
+                Ok(filename) => viewer.notify(&format!("Saved tree to: {}", filename)),
+                Err(err) => viewer.notify_error(&format!("Error writing dot output: {}", err)),
+            }
+        }),
+    );
+    add_action(window, "edit", move || {
+        let reveal = !edit_revealer.get_reveal_child();
+        edit_revealer.set_reveal_child(reveal);
+        if reveal {
+            edit_relation_entry.grab_focus();
+        }
+    });
+    add_action(
+        window,
+        "export-subtree",
+        clone!(viewer, window, treebank_model, render_options => move || {
+            let format = ok_or!(subtree_format_dialog(&viewer, &window), return);
+            match save_subtree(&viewer, &treebank_model.borrow(), &render_options, format) {
+                Ok(filename) => viewer.notify(&format!("Saved subtree to: {}", filename)),
+                Err(err) => viewer.notify_error(&format!("Error writing subtree: {}", err)),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "extract",
+        clone!(viewer, treebank_model => move || {
+            match extract_subtree(&mut treebank_model.borrow_mut()) {
+                Ok(idx) => viewer.notify(&format!("Added subtree as tree: {}", idx + 1)),
+                Err(err) => viewer.notify_error(&format!("Error extracting subtree: {}", err)),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "feature-table",
+        clone!(viewer, window, treebank_model, matches_panel => move || {
+            feature_table_dialog(viewer.clone(), &window, treebank_model.clone(), matches_panel.clone())
+        }),
+    );
+    add_string_action(
+        window,
+        "find",
+        clone!(search_revealer, search_entry => move |query| {
+            search_entry.set_text(query);
+            search_revealer.set_reveal_child(true);
+            search_entry.grab_focus();
+        }),
+    );
+    add_action(
+        window,
+        "first",
+        clone!(window, treebank_model => move || {
+            seek(&window, &treebank_model, Seek::First)
+        }),
+    );
+    add_action(window, "follow", move || {
+        follow_button.set_active(!follow_button.get_active())
+    });
+    add_action(
+        window,
+        "git",
+        clone!(viewer, treebank_model => move || {
+            let tracked = {
+                let treebank_model = treebank_model.borrow();
+                treebank_model
+                    .file(treebank_model.idx())
+                    .is_some_and(|(file, _)| file.git.is_some())
+            };
+            if tracked {
+                let reveal = !git_revealer.get_reveal_child();
+                git_revealer.set_reveal_child(reveal);
+                if reveal {
+                    git_commit_entry.grab_focus();
+                }
+            } else {
+                viewer.notify_error("The treebank is not tracked in a git repository");
+            }
+        }),
+    );
+    add_action(
+        window,
+        "inconsistencies",
+        clone!(window, treebank_model, matches_panel => move || {
+            inconsistencies_dialog(&window, treebank_model.clone(), matches_panel.clone())
+        }),
+    );
+    add_action(
+        window,
+        "issues",
+        clone!(treebank_model => move || {
+            if !issues_panel.is_shown() {
+                issues_widget.update(validate(&treebank_model.borrow().sentences()));
+            }
+            issues_panel.toggle();
+        }),
+    );
+    add_action(
+        window,
+        "last",
+        clone!(window, treebank_model => move || {
+            seek(&window, &treebank_model, Seek::Last)
+        }),
+    );
+    add_action(window, "library", move || library_panel.toggle());
+    add_action(
+        window,
+        "length-filter",
+        clone!(viewer, window, treebank_model => move || {
+            let current = treebank_model.borrow().length_filter();
+            if let Some(range) = length_filter_dialog(&viewer, &window, current) {
+                treebank_model.borrow_mut().set_length_filter(range);
+            }
+        }),
+    );
+    add_action(
+        window,
+        "longest",
+        clone!(window, treebank_model => move || {
+            if !treebank_model.borrow_mut().seek_max(|summary| summary.len) {
+                window.error_bell();
+            }
+        }),
+    );
+    add_action(
+        window,
+        "matches",
+        clone!(matches_panel => move || matches_panel.toggle()),
+    );
+    add_action(
+        window,
+        "morphology",
+        clone!(treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            let show = !treebank_model.show_morphology();
+            treebank_model.set_show_morphology(show);
+        }),
+    );
+    add_action(
+        window,
+        "next",
+        clone!(window, treebank_model => move || {
+            seek(&window, &treebank_model, Seek::Relative(1))
+        }),
+    );
+    add_action(
+        window,
+        "next-page",
+        clone!(window, treebank_model => move || {
+            let stride = treebank_model.borrow().page_stride() as isize;
+            seek(&window, &treebank_model, Seek::Relative(stride))
+        }),
+    );
+    add_action(
+        window,
+        "next-relation",
+        clone!(window, treebank_model => move || {
+            if let Some(rel) = relation_dialog(&window, &treebank_model.borrow()) {
+                window.activate_action("seek-relation", &rel.to_variant());
+            }
+        }),
+    );
+    add_action(
+        window,
+        "next-token",
+        clone!(treebank_model => move || {
+            treebank_model.borrow_mut().next_token()
+        }),
+    );
+    add_action(window, "notes", move || notes_panel.toggle());
+    add_action(
+        window,
+        "open",
+        clone!(viewer, window => move || {
+            open_treebank_dialog(&viewer, &window, &opener)
+        }),
+    );
+    add_action(
+        window,
+        "original",
+        clone!(treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            let show = !treebank_model.show_original();
+            treebank_model.set_show_original(show);
+        }),
+    );
+    add_action(
+        window,
+        "paste",
+        clone!(viewer, treebank_model => move || {
+            report_paste(&viewer, paste_sentences(&mut treebank_model.borrow_mut()))
+        }),
+    );
+    add_action(
+        window,
+        "path",
+        clone!(window, treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            if treebank_model.path_start().is_some() {
+                treebank_model.set_path_start(None);
+            } else if let Some(token) = treebank_model.selected_token() {
+                treebank_model.set_path_start(Some(token));
+            } else {
+                window.error_bell();
+            }
+        }),
+    );
+    add_action(
+        window,
+        "previous",
+        clone!(window, treebank_model => move || {
+            seek(&window, &treebank_model, Seek::Relative(-1))
+        }),
+    );
+    add_action(
+        window,
+        "previous-page",
+        clone!(window, treebank_model => move || {
+            let stride = treebank_model.borrow().page_stride() as isize;
+            seek(&window, &treebank_model, Seek::Relative(-stride))
+        }),
+    );
+    add_action(
+        window,
+        "previous-token",
+        clone!(treebank_model => move || {
+            treebank_model.borrow_mut().previous_token()
+        }),
+    );
+    // Closing the window, rather than destroying it, stores the layout.
+    add_action(window, "quit", clone!(window => move || window.close()));
+    add_action(
+        window,
+        "reveal",
+        clone!(treebank_model => move || {
+            treebank_model.borrow_mut().reveal()
+        }),
+    );
+    add_action(
+        window,
+        "review-link",
+        clone!(viewer, treebank_model => move || {
+            match create_review_link(&treebank_model.borrow()) {
+                Ok(link) => {
+                    gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&link);
+                    viewer.notify(&format!("Copied review link: {}", link));
+                }
+                Err(err) => viewer.notify_error(&format!("Error creating review link: {}", err)),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "revision",
+        clone!(treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            let show = !treebank_model.show_revision();
+            treebank_model.set_show_revision(show);
+        }),
+    );
+    add_action(
+        window,
+        "save",
+        clone!(viewer, treebank_model => move || {
+            match save_treebank(&treebank_model.borrow()) {
+                Ok(filename) => viewer.notify(&format!("Saved treebank to: {}", filename)),
+                Err(err) => {
+                    viewer.error_dialog("Cannot save treebank", &err);
+
+                    let treebank_model = treebank_model.borrow();
+                    if treebank_model.has_notes() {
+                        match save_notes(&treebank_model) {
+                            Ok(filename) => viewer.notify(&format!("Saved notes to: {}", filename)),
+                            Err(err) => viewer.notify_error(&format!("Error saving notes: {}", err)),
+                        }
+                    }
+                }
+            }
+        }),
+    );
+    add_action(window, "search", move || {
+        let reveal = !search_revealer.get_reveal_child();
+        search_revealer.set_reveal_child(reveal);
+        if reveal {
+            search_entry.grab_focus();
+        }
+    });
+    add_string_action(
+        window,
+        "seek-relation",
+        clone!(window, treebank_model => move |rel| {
+            if !treebank_model.borrow_mut().seek_relation(rel) {
+                window.error_bell();
+            }
+        }),
+    );
+    add_action(
+        window,
+        "similar-tokens",
+        clone!(treebank_model, matches_panel => move || {
+            find_similar(&treebank_model, &matches_panel, &TokenOverlap)
+        }),
+    );
+    add_action(
+        window,
+        "similar-trees",
+        clone!(treebank_model, matches_panel => move || {
+            find_similar(&treebank_model, &matches_panel, &TreeKernel)
+        }),
+    );
+    add_action(window, "statistics", move || statistics_panel.toggle());
+    add_action(
+        window,
+        "subtrees",
+        clone!(window, treebank_model => move || {
+            subtrees_dialog(&window, treebank_model.clone(), matches_panel.clone())
+        }),
+    );
+    add_action(
+        window,
+        "tikz",
+        clone!(viewer, treebank_model => move || {
+            match save_tikz(&viewer, &treebank_model.borrow(), &render_options) {
+                Ok(filename) => viewer.notify(&format!("Saved tree to: {}", filename)),
+                Err(err) => viewer.notify_error(&format!("Error writing TikZ output: {}", err)),
+            }
+        }),
+    );
+    add_action(
+        window,
+        "undo",
+        clone!(treebank_model => move || {
+            treebank_model.borrow_mut().undo_removal()
+        }),
+    );
+    add_action(
+        window,
+        "undo-edit",
+        clone!(treebank_model => move || {
+            treebank_model.borrow_mut().undo_edit()
+        }),
+    );
+    add_action(
+        window,
+        "unselect",
+        clone!(treebank_model => move || {
+            let mut treebank_model = treebank_model.borrow_mut();
+            treebank_model.set_path_start(None);
+            treebank_model.select_token(None);
+        }),
+    );
+    add_action(
+        window,
+        "zoom-in",
+        clone!(dep_widget => move || dep_widget.borrow_mut().zoom_in()),
+    );
+    add_action(window, "zoom-out", move || {
+        dep_widget.borrow_mut().zoom_out()
+    });
+
+    window
+        .get_application()
+        .expect("Window does not belong to the application")
+        .set_menubar(&viewer_menu());
+
+    // Accelerators are single keys, so keys that are typed in text
+    // entries go to the entry first. Escape leaves the entry.
+    window.connect_key_press_event(|window, key_event| {
+        if let Some(focus) = window.get_focus() {
+            if is_text_input(&focus) {
+                if key_event.get_keyval() == key::Escape {
+                    window.set_focus(None::<&gtk::Widget>);
+                    return Inhibit(true);
+                }
+
+                return Inhibit(window.propagate_key_event(key_event));
+            }
+        }
+
+        Inhibit(false)
+    });
+}
+
+/// Whether a widget takes typed text, such as an entry or the notes.
+fn is_text_input(widget: &gtk::Widget) -> bool {
+    widget.is::<gtk::Entry>()
+        || widget
+            .clone()
+            .downcast::<gtk::TextView>()
+            .is_ok_and(|view| view.get_editable())
+}
+
+/// Navigate with Vim-style keys. Keys that are not part of a Vim-style
+/// command go to the accelerators as usual.
+fn setup_vim_keys(
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+) {
+    let vim = RefCell::new(VimKeys::new());
+
+    window.connect_key_press_event(move |window, key_event| {
+        let in_entry = window
+            .get_focus()
+            .is_some_and(|focus| is_text_input(&focus));
+        let modifiers = gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::MOD1_MASK;
+        let key = match gdk::keyval_to_unicode(key_event.get_keyval()) {
+            Some(key) if !in_entry && !key_event.get_state().intersects(modifiers) => key,
+            _ => {
+                vim.borrow_mut().reset();
+                return Inhibit(false);
+            }
+        };
+
+        let command = match vim.borrow_mut().key(key) {
+            VimKey::Command(command) => command,
+            VimKey::Pending => return Inhibit(true),
+            VimKey::Unhandled => return Inhibit(false),
+        };
+
+        let target = match command {
+            VimCommand::Next(n) => Seek::Relative(n as isize),
+            VimCommand::Previous(n) => Seek::Relative(-(n as isize)),
+            VimCommand::First => Seek::First,
+            VimCommand::Last => Seek::Last,
+            VimCommand::Goto(n) => Seek::Absolute(n - 1),
+        };
+        seek(window, &treebank_model, target);
+
+        Inhibit(true)
+    });
+}
+
+/// Add the slideshow action. The interval is that of `--slideshow`, or
+/// otherwise that of the configuration. A slideshow that was started with
+/// `--slideshow` runs right away.
+fn setup_slideshow(
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    config: &Config,
+) {
+    let interval = treebank_model
+        .borrow()
+        .slideshow()
+        .or_else(|| config.slideshow_interval.and_then(slideshow_interval))
+        .or_else(|| slideshow_interval(DEFAULT_SLIDESHOW_INTERVAL))
+        .expect("Invalid default slideshow interval");
+
+    // Every slideshow gets a generation, so that the timer of a stopped
+    // slideshow does not keep running when a new slideshow is started.
+    let generation = Rc::new(Cell::new(0));
+    run_slideshow(treebank_model.clone(), generation.clone());
+
+    add_action(window, "slideshow", move || {
+        let running = treebank_model.borrow().slideshow().is_some();
+        treebank_model
+            .borrow_mut()
+            .set_slideshow(if running { None } else { Some(interval) });
+        run_slideshow(treebank_model.clone(), generation.clone());
+    });
+}
+
+/// Advance to the next tree at the interval of the slideshow, starting
+/// over after the last tree.
+fn run_slideshow(treebank_model: Rc<RefCell<StatefulTreebankModel>>, generation: Rc<Cell<usize>>) {
+    generation.set(generation.get() + 1);
+    let current = generation.get();
+
+    let interval = ok_or!(treebank_model.borrow().slideshow(), return);
+    let interval_ms = interval.as_secs() as u32 * 1000 + interval.subsec_millis();
+    gtk::timeout_add(interval_ms, move || {
+        if generation.get() != current || treebank_model.borrow().slideshow().is_none() {
+            return Continue(false);
+        }
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        if !treebank_model.seek(Seek::Relative(1)) {
+            treebank_model.seek(Seek::First);
+        }
+
+        Continue(true)
+    });
+}
+
+/// The slideshow interval of a number of seconds, `None` when the number
+/// is not positive.
+fn slideshow_interval(secs: f64) -> Option<Duration> {
+    if secs > 0.0 && secs.is_finite() {
+        Some(Duration::from_millis(
+            (secs * 1000.0).round().max(1.0) as u64
+        ))
+    } else {
+        None
+    }
+}
+
+/// List the trees that are most similar to the current tree in the
+/// matches panel.
+fn find_similar(
+    treebank_model: &RefCell<StatefulTreebankModel>,
+    matches_panel: &DetachablePanel,
+    similarity: &dyn Similarity,
+) {
+    treebank_model
+        .borrow_mut()
+        .find_similar(similarity, SIMILAR_TREES);
+    if !matches_panel.is_shown() {
+        matches_panel.toggle();
+    }
+}
+
+/// Show the frequent subtree patterns of the treebank. Activating a
+/// pattern lists the tokens that head it in the matches panel.
+fn subtrees_dialog(
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    matches_panel: DetachablePanel,
+) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Frequent subtrees"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("_Close", gtk::ResponseType::Close.into())],
+    );
+    dialog.set_default_size(600, 500);
+
+    let subtrees_widget = SubtreesWidget::from_tree_view(&gtk::TreeView::new());
+    let update = {
+        let treebank_model = treebank_model.clone();
+        let subtrees_widget = subtrees_widget.clone();
+        move |depth: usize| {
+            let mut patterns = treebank_model
+                .borrow()
+                .subtree_patterns(depth, SUBTREE_MIN_COUNT);
+            patterns.truncate(SUBTREE_PATTERNS);
+            subtrees_widget.update(patterns);
+        }
+    };
+    update(DEFAULT_SUBTREE_DEPTH);
+
+    let depth_button = gtk::SpinButton::new_with_range(1.0, MAX_SUBTREE_DEPTH as f64, 1.0);
+    depth_button.set_value(DEFAULT_SUBTREE_DEPTH as f64);
+    depth_button.connect_value_changed(move |button| update(button.get_value_as_int() as usize));
+
+    subtrees_widget.connect_row_activated(clone!(subtrees_widget => move |_, path, _| {
+        let row = ok_or!(path.get_indices().first().cloned(), return);
+        let pattern = ok_or!(subtrees_widget.pattern(row as usize), return);
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.find_subtrees(&pattern);
+        treebank_model.next_match();
+        if !matches_panel.is_shown() {
+            matches_panel.toggle();
+        }
+    }));
+
+    let depth_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    depth_box.pack_start(&gtk::Label::new("Maximum depth"), false, false, 0);
+    depth_box.pack_start(&depth_button, false, false, 0);
+    let scrolled_window = gtk::ScrolledWindow::new(None, None);
+    scrolled_window.add(&*subtrees_widget);
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.set_spacing(6);
+    content_area.pack_start(&depth_box, false, false, 0);
+    content_area.pack_start(&scrolled_window, true, true, 0);
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.show_all();
+}
+
+fn inconsistencies_dialog(
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    matches_panel: DetachablePanel,
+) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Inconsistent annotations"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("_Close", gtk::ResponseType::Close.into())],
+    );
+    dialog.set_default_size(600, 500);
+
+    let inconsistencies_widget = InconsistenciesWidget::from_tree_view(&gtk::TreeView::new());
+    let update = {
+        let treebank_model = treebank_model.clone();
+        let inconsistencies_widget = inconsistencies_widget.clone();
+        move |window: usize| {
+            let mut inconsistencies = treebank_model.borrow().inconsistencies(window);
+            inconsistencies.truncate(INCONSISTENCIES);
+            inconsistencies_widget.update(inconsistencies);
+        }
+    };
+    update(DEFAULT_CONTEXT_WINDOW);
+
+    let window_button = gtk::SpinButton::new_with_range(0.0, MAX_CONTEXT_WINDOW as f64, 1.0);
+    window_button.set_value(DEFAULT_CONTEXT_WINDOW as f64);
+    window_button.connect_value_changed(move |button| update(button.get_value_as_int() as usize));
+
+    // Activating a context shows all its tokens, activating an annotation
+    // the tokens with that annotation.
+    inconsistencies_widget.connect_row_activated(clone!(inconsistencies_widget => move |_, path, _| {
+        let (inconsistency, variant) = ok_or!(inconsistencies_widget.inconsistency(path), return);
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.find_inconsistency(&inconsistency, variant);
+        treebank_model.next_match();
+        if !matches_panel.is_shown() {
+            matches_panel.toggle();
+        }
+    }));
+
+    let window_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    window_box.pack_start(
+        &gtk::Label::new("Context words on either side"),
+        false,
+        false,
+        0,
+    );
+    window_box.pack_start(&window_button, false, false, 0);
+    let scrolled_window = gtk::ScrolledWindow::new(None, None);
+    scrolled_window.add(&*inconsistencies_widget);
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.set_spacing(6);
+    content_area.pack_start(&window_box, false, false, 0);
+    content_area.pack_start(&scrolled_window, true, true, 0);
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.show_all();
+}
+
+/// The file to which the feature table is exported.
+const FEATURE_TABLE_FILENAME: &str = "features.csv";
+
+fn feature_table_dialog(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    matches_panel: DetachablePanel,
+) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Feature values by part of speech"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("_Export CSV", gtk::ResponseType::Apply.into()),
+            ("_Close", gtk::ResponseType::Close.into()),
+        ],
+    );
+    dialog.set_default_size(600, 500);
+
+    let table = treebank_model.borrow().feature_table();
+    let table_widget = FeatureTableWidget::from_tree_view(&gtk::TreeView::new());
+    table_widget.update(table.cells());
+
+    table_widget.connect_row_activated(clone!(table_widget => move |_, path, _| {
+        let row = ok_or!(path.get_indices().first().cloned(), return);
+        let cell: FeatureCell = ok_or!(table_widget.cell(row as usize), return);
+
+        let mut treebank_model = treebank_model.borrow_mut();
+        treebank_model.find_feature(&cell);
+        treebank_model.next_match();
+        if !matches_panel.is_shown() {
+            matches_panel.toggle();
+        }
+    }));
+
+    let scrolled_window = gtk::ScrolledWindow::new(None, None);
+    scrolled_window.add(&*table_widget);
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.pack_start(&scrolled_window, true, true, 0);
+    dialog.connect_response(move |dialog, response| {
+        if gtk::ResponseType::from(response) != gtk::ResponseType::Apply {
+            dialog.destroy();
+            return;
+        }
+
+        let filename = viewer.export_path(FEATURE_TABLE_FILENAME);
+        let written = File::create(&filename)
+            .map_err(ViewerError::from)
+            .and_then(|file| write_feature_csv(BufWriter::new(file), &table));
+        match written {
+            Ok(()) => viewer.notify(&format!("Saved feature table to: {}", filename)),
+            Err(err) => viewer.notify_error(&format!("Error writing feature table: {}", err)),
+        }
+    });
+    dialog.show_all();
+}
+
+/// Add the actions that record and replay a keyboard macro. Every other
+/// window action is recorded while recording, with its parameter. The
+/// edits of a replay are undone together.
+fn setup_macros(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+) {
+    let recorder = Rc::new(RefCell::new(MacroRecorder::new()));
+
+    for name in window.list_actions() {
+        let action = ok_or!(
+            window
+                .lookup_action(&name)
+                .and_then(|action| action.downcast::<gio::SimpleAction>().ok()),
+            continue
+        );
+        action.connect_activate(clone!(recorder => move |_, parameter| {
+            recorder.borrow_mut().record(MacroStep {
+                action: name.clone(),
+                parameter: parameter
+                    .as_ref()
+                    .and_then(|parameter| parameter.get_str())
+                    .map(str::to_owned),
+            });
+        }));
+    }
+
+    add_action(
+        window,
+        "record-macro",
+        clone!(viewer, recorder => move || {
+            let mut recorder = recorder.borrow_mut();
+            if recorder.is_recording() {
+                recorder.stop();
+                viewer.notify(&format!("Recorded a macro of {} steps", recorder.steps().len()));
+            } else {
+                recorder.start();
+                viewer.notify("Recording a macro, press F3 to stop");
+            }
+        }),
+    );
+
+    add_action(
+        window,
+        "replay-macro",
+        clone!(viewer, window => move || {
+            if recorder.borrow().is_recording() {
+                viewer.notify_error("Cannot replay the macro while recording");
+                return;
+            }
+
+            let steps = recorder.borrow().steps().to_vec();
+            treebank_model.borrow_mut().begin_edits();
+            for step in steps {
+                let parameter = step.parameter.as_ref().map(|parameter| parameter.to_variant());
+                window.activate_action(&step.action, parameter.as_ref());
+            }
+            treebank_model.borrow_mut().end_edits();
+        }),
+    );
+}
+
+/// Move to another tree. The error bell signals that the first or last
+/// tree was reached already.
+/// Let the user enter a relation to look for in the next trees. The
+/// relation of the selected token is filled in.
+fn relation_dialog(
+    window: &gtk::ApplicationWindow,
+    treebank_model: &StatefulTreebankModel,
+) -> Option<String> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Next tree with relation"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel.into()),
+            ("_Find", gtk::ResponseType::Accept.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept.into());
+
+    let relation_entry = gtk::Entry::new();
+    relation_entry.set_placeholder_text("nsubj");
+    relation_entry.set_activates_default(true);
+    let selected = treebank_model.graph().and_then(|graph| {
+        let token = &graph.0[NodeIndex::new(treebank_model.selected_token()?)].token;
+        token.head_rel().map(str::to_owned)
+    });
+    if let Some(rel) = selected {
+        relation_entry.set_text(&rel);
+    }
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.add(&relation_entry);
+    dialog.show_all();
+
+    let response = gtk::ResponseType::from(dialog.run());
+    let rel = relation_entry
+        .get_text()
+        .map(|rel| rel.trim().to_owned())
+        .unwrap_or_default();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Accept || rel.is_empty() {
+        return None;
+    }
+
+    Some(rel)
+}
+
+/// Let the user choose the format that a subtree is saved in.
+fn subtree_format_dialog(
+    viewer: &Viewer,
+    window: &gtk::ApplicationWindow,
+) -> Option<SubtreeFormat> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Save subtree"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel.into()),
+            ("_Save", gtk::ResponseType::Accept.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept.into());
+
+    let format_combo = gtk::ComboBoxText::new();
+    for &(id, label) in &[
+        ("conll", "CoNLL fragment"),
+        ("dot", "Graphviz dot"),
+        ("tikz", "TikZ dependency"),
+        ("svg", "SVG image"),
+    ] {
+        format_combo.append(Some(id), label);
+    }
+    let format = viewer.preferences.borrow().export_format;
+    format_combo.set_active_id(Some(format.unwrap_or(SubtreeFormat::Conll).extension()));
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.add(&format_combo);
+    dialog.show_all();
+
+    let response = gtk::ResponseType::from(dialog.run());
+    let format = format_combo.get_active_id().and_then(|id| id.parse().ok());
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Accept {
+        return None;
+    }
+
+    format
+}
+
+/// Let the user enter the range of sentence lengths to browse. Returns
+/// the new filter, `None` when the filter is cleared, or nothing when
+/// the dialog is canceled.
+fn length_filter_dialog(
+    viewer: &Viewer,
+    window: &gtk::ApplicationWindow,
+    current: Option<LengthRange>,
+) -> Option<Option<LengthRange>> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Filter by length"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel.into()),
+            ("_Filter", gtk::ResponseType::Accept.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept.into());
+
+    let label = gtk::Label::new("Number of tokens, empty for all sentences:");
+    label.set_halign(gtk::Align::Start);
+    let range_entry = gtk::Entry::new();
+    range_entry.set_placeholder_text("5-15");
+    range_entry.set_activates_default(true);
+    if let Some(range) = current {
+        range_entry.set_text(&range.to_string());
+    }
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.set_spacing(6);
+    content_area.add(&label);
+    content_area.add(&range_entry);
+    dialog.show_all();
+
+    let response = gtk::ResponseType::from(dialog.run());
+    let range = range_entry
+        .get_text()
+        .map(|range| range.trim().to_owned())
+        .unwrap_or_default();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Accept {
+        return None;
+    }
+    if range.is_empty() {
+        return Some(None);
+    }
+
+    match range.parse() {
+        Ok(range) => Some(Some(range)),
+        Err(err) => {
+            viewer.notify_error(&format!("Cannot filter by length: {}", err));
+            None
+        }
+    }
+}
+
+fn seek(
+    window: &gtk::ApplicationWindow,
+    treebank_model: &RefCell<StatefulTreebankModel>,
+    seek: Seek,
+) {
+    if !treebank_model.borrow_mut().seek(seek) {
+        window.error_bell();
+    }
+}
+
+/// Set the accelerators of the actions from the keymap of the
+/// configuration, and add the preferences for editing the keymap and
+/// the other settings.
+fn setup_keymap(
+    viewer: Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: Rc<RefCell<StatefulTreebankModel>>,
+    config: Rc<RefCell<Config>>,
+) {
+    for action in config.borrow().keymap.keys() {
+        if !ACTIONS.iter().any(|&(name, _, _)| name == action) {
+            viewer.notify_error(&format!("Unknown action in keymap: {}", action));
+        }
+    }
+
+    let application = window
+        .get_application()
+        .expect("Window does not belong to the application");
+    apply_keymap(&viewer, &application, &config.borrow().keymap);
+
+    add_action(
+        window,
+        "preferences",
+        clone!(window => move || {
+            preferences_dialog(&viewer, &window, &treebank_model, &config)
+        }),
+    );
+}
+
+fn apply_keymap(
+    viewer: &Viewer,
+    application: &gtk::Application,
+    keymap: &BTreeMap<String, Vec<String>>,
+) {
+    for &(action, _, _) in ACTIONS {
+        let accels: Vec<_> = action_accels(keymap, action)
+            .into_iter()
+            .filter(|accel| {
+                let valid = gtk::accelerator_parse(accel).0 != 0;
+                if !valid {
+                    viewer.notify_error(&format!("Invalid accelerator for {}: {}", action, accel));
+                }
+                valid
+            })
+            .collect();
+        let accels: Vec<_> = accels.iter().map(String::as_str).collect();
+        application.set_accels_for_action(&format!("win.{}", action), &accels);
+    }
+}
+
+/// Get the accelerators of an action, the default accelerators when the
+/// keymap does not have the action.
+fn action_accels(keymap: &BTreeMap<String, Vec<String>>, action: &str) -> Vec<String> {
+    match keymap.get(action) {
+        Some(accels) => accels.clone(),
+        None => ACTIONS
+            .iter()
+            .find(|&&(name, _, _)| name == action)
+            .map(|&(_, _, accels)| accels.iter().map(|&accel| accel.to_owned()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Set the accelerator of an action in the keymap, `None` to clear it.
+/// The accelerator is taken away from other actions that have it.
+fn set_action_accel(keymap: &mut BTreeMap<String, Vec<String>>, action: &str, accel: Option<&str>) {
+    if let Some(accel) = accel {
+        let parsed = gtk::accelerator_parse(accel);
+        for &(other, _, _) in ACTIONS {
+            let accels = action_accels(keymap, other);
+            if other != action
+                && accels
+                    .iter()
+                    .any(|other| gtk::accelerator_parse(other) == parsed)
+            {
+                let accels = accels
+                    .into_iter()
+                    .filter(|other| gtk::accelerator_parse(other) != parsed)
+                    .collect();
+                keymap.insert(other.to_owned(), accels);
+            }
+        }
+    }
+
+    keymap.insert(
+        action.to_owned(),
+        accel.map(ToOwned::to_owned).into_iter().collect(),
+    );
+}
+
+/// The actions with their descriptions and first accelerators.
+fn keymap_rows(
+    keymap: &BTreeMap<String, Vec<String>>,
+) -> Vec<(&'static str, &'static str, Option<String>)> {
+    ACTIONS
+        .iter()
+        .map(|&(action, description, _)| {
+            (
+                action,
+                description,
+                action_accels(keymap, action).into_iter().next(),
+            )
+        })
+        .collect()
+}
+
+/// Change the preferences of the configuration, apply them to the trees
+/// that are shown and saved, and save the configuration.
+fn change_preferences<F>(
+    viewer: &Viewer,
+    treebank_model: &RefCell<StatefulTreebankModel>,
+    config: &RefCell<Config>,
+    change: F,
+) where
+    F: FnOnce(&mut Preferences),
+{
+    let mut config = config.borrow_mut();
+    change(&mut config.preferences);
+    *viewer.preferences.borrow_mut() = config.preferences.clone();
+    treebank_model.borrow_mut().redraw();
+
+    if let Err(err) = config.save() {
+        viewer.notify_error(&format!("Cannot save configuration: {}", err));
+    }
+}
+
+/// The page of the preferences dialog with the render, export and
+/// navigation settings.
+fn general_preferences(
+    viewer: &Rc<Viewer>,
+    treebank_model: &Rc<RefCell<StatefulTreebankModel>>,
+    config: &Rc<RefCell<Config>>,
+) -> gtk::Grid {
+    let preferences = config.borrow().preferences.clone();
+
+    let renderer_combo = gtk::ComboBoxText::new();
+    renderer_combo.append(Some("graphviz"), "Graphviz");
+    renderer_combo.append(Some("diagram"), "Text diagram");
+    renderer_combo.set_active_id(Some(match preferences.renderer {
+        Some(TreeRenderer::Diagram) => "diagram",
+        _ => "graphviz",
+    }));
+    renderer_combo.connect_changed(clone!(viewer, treebank_model, config => move |combo| {
+        let renderer = match combo.get_active_id().as_deref() {
+            Some("diagram") => TreeRenderer::Diagram,
+            _ => TreeRenderer::Graphviz,
+        };
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.renderer = Some(renderer));
+    }));
+
+    let theme_combo = gtk::ComboBoxText::new();
+    for &(id, label) in &[
+        ("", "Follow GTK theme"),
+        ("light", "Light"),
+        ("dark", "Dark"),
+        ("high-contrast", "High contrast"),
+    ] {
+        theme_combo.append(Some(id), label);
+    }
+    theme_combo.set_active_id(Some(match preferences.theme {
+        None => "",
+        Some(Theme::Light) => "light",
+        Some(Theme::Dark) => "dark",
+        Some(Theme::HighContrast) => "high-contrast",
+    }));
+    theme_combo.connect_changed(clone!(viewer, treebank_model, config => move |combo| {
+        let theme = combo.get_active_id().and_then(|id| id.parse().ok());
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.theme = theme);
+    }));
+
+    let font_entry = gtk::Entry::new();
+    font_entry.set_placeholder_text("Font of the language");
+    font_entry.set_text(preferences.font.as_deref().unwrap_or(""));
+    font_entry.connect_changed(clone!(viewer, treebank_model, config => move |entry| {
+        let font = entry.get_text().filter(|font| !font.trim().is_empty());
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.font = font);
+    }));
+
+    let palette_combo = gtk::ComboBoxText::new();
+    for &(id, label) in &[
+        ("", "Colors of the language"),
+        ("plain", "No colors"),
+        ("universal", "Universal tags"),
+        ("stts", "STTS tags"),
+    ] {
+        palette_combo.append(Some(id), label);
+    }
+    palette_combo.set_active_id(Some(match preferences.palette {
+        None => "",
+        Some(Palette::Plain) => "plain",
+        Some(Palette::Universal) => "universal",
+        Some(Palette::Stts) => "stts",
+    }));
+    palette_combo.connect_changed(clone!(viewer, treebank_model, config => move |combo| {
+        let palette = match combo.get_active_id().as_deref() {
+            Some("plain") => Some(Palette::Plain),
+            Some("universal") => Some(Palette::Universal),
+            Some("stts") => Some(Palette::Stts),
+            _ => None,
+        };
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.palette = palette);
+    }));
+
+    let mark_entry = gtk::Entry::new();
+    mark_entry.set_placeholder_text("mark");
+    mark_entry.set_text(preferences.mark_feature.as_deref().unwrap_or(""));
+    mark_entry.connect_changed(clone!(viewer, treebank_model, config => move |entry| {
+        let feature = entry.get_text().map(|feature| feature.trim().to_owned()).filter(|feature| !feature.is_empty());
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.mark_feature = feature);
+    }));
+
+    let format_combo = gtk::ComboBoxText::new();
+    for &(id, label) in &[
+        ("conll", "CoNLL fragment"),
+        ("dot", "Graphviz dot"),
+        ("tikz", "TikZ dependency"),
+        ("svg", "SVG image"),
+    ] {
+        format_combo.append(Some(id), label);
+    }
+    format_combo.set_active_id(Some(
+        preferences
+            .export_format
+            .unwrap_or(SubtreeFormat::Conll)
+            .extension(),
+    ));
+    format_combo.connect_changed(clone!(viewer, treebank_model, config => move |combo| {
+        let format = combo.get_active_id().and_then(|id| id.parse().ok());
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.export_format = format);
+    }));
+
+    let directory_button =
+        gtk::FileChooserButton::new("Export directory", gtk::FileChooserAction::SelectFolder);
+    if let Some(ref dir) = preferences.export_directory {
+        directory_button.set_filename(dir);
+    }
+    directory_button.connect_file_set(clone!(viewer, treebank_model, config => move |button| {
+        let dir = button.get_filename();
+        change_preferences(&viewer, &treebank_model, &config, |preferences| preferences.export_directory = dir);
+    }));
+
+    let stride_button = gtk::SpinButton::new_with_range(1.0, MAX_PAGE_STRIDE as f64, 1.0);
+    stride_button.set_value(treebank_model.borrow().page_stride() as f64);
+    stride_button.connect_value_changed(clone!(viewer, treebank_model, config => move |button| {
+        let stride = button.get_value_as_int() as usize;
+        treebank_model.borrow_mut().set_page_stride(stride);
+
+        let mut config = config.borrow_mut();
+        config.page_stride = Some(stride);
+        if let Err(err) = config.save() {
+            viewer.notify_error(&format!("Cannot save configuration: {}", err));
+        }
+    }));
+
+    let grid = gtk::Grid::new();
+    grid.set_border_width(12);
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    let rows: Vec<(&str, gtk::Widget)> = vec![
+        ("_Renderer", renderer_combo.upcast()),
+        ("_Theme", theme_combo.upcast()),
+        ("_Font", font_entry.upcast()),
+        ("_Colors", palette_combo.upcast()),
+        ("_Mark feature", mark_entry.upcast()),
+        ("Subtree _format", format_combo.upcast()),
+        ("Export _directory", directory_button.upcast()),
+        ("_Page stride", stride_button.upcast()),
+    ];
+    for (row, (label, widget)) in rows.into_iter().enumerate() {
+        let label = gtk::Label::new_with_mnemonic(Some(label));
+        label.set_halign(gtk::Align::Start);
+        label.set_mnemonic_widget(Some(&widget));
+        widget.set_hexpand(true);
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(&widget, 1, row as i32, 1, 1);
+    }
+
+    grid
+}
+
+fn preferences_dialog(
+    viewer: &Rc<Viewer>,
+    window: &gtk::ApplicationWindow,
+    treebank_model: &Rc<RefCell<StatefulTreebankModel>>,
+    config: &Rc<RefCell<Config>>,
+) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Preferences"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[("_Close", gtk::ResponseType::Close.into())],
+    );
+    dialog.set_default_size(450, 500);
+
+    let application = window
+        .get_application()
+        .expect("Window does not belong to the application");
+    let keymap_widget = KeymapWidget::from_tree_view(&gtk::TreeView::new());
+    keymap_widget.update(&keymap_rows(&config.borrow().keymap));
+    keymap_widget.connect_edited(
+        clone!(viewer, config, keymap_widget => move |action, accel| {
+            let mut config = config.borrow_mut();
+            set_action_accel(&mut config.keymap, action, accel);
+            apply_keymap(&viewer, &application, &config.keymap);
+            keymap_widget.update(&keymap_rows(&config.keymap));
+
+            if let Err(err) = config.save() {
+                viewer.notify_error(&format!("Cannot save configuration: {}", err));
+            }
+        }),
+    );
+
+    let scrolled_window = gtk::ScrolledWindow::new(None, None);
+    scrolled_window.add(&*keymap_widget);
+    let notebook = gtk::Notebook::new();
+    notebook.append_page(
+        &general_preferences(viewer, treebank_model, config),
+        Some(&gtk::Label::new("General")),
+    );
+    notebook.append_page(
+        &scrolled_window,
+        Some(&gtk::Label::new("Keyboard shortcuts")),
+    );
+
+    let content_area = dialog.get_content_area();
+    content_area.set_border_width(6);
+    content_area.pack_start(&notebook, true, true, 0);
+    dialog.show_all();
+
+    dialog.run();
+    dialog.destroy();
+}
+
+fn add_action<F>(window: &gtk::ApplicationWindow, name: &str, activate: F)
+where
+    F: Fn() + 'static,
+{
+    let action = gio::SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| activate());
+    window.add_action(&action);
+}
+
+/// Add a window action with a string parameter.
+fn add_string_action<F>(window: &gtk::ApplicationWindow, name: &str, activate: F)
+where
+    F: Fn(&str) + 'static,
+{
+    let action = gio::SimpleAction::new(name, glib::VariantTy::new("s").ok());
+    action.connect_activate(move |_, parameter| {
+        if let Some(parameter) = parameter.as_ref().and_then(|parameter| parameter.get_str()) {
+            activate(parameter);
+        }
     });
+    window.add_action(&action);
+}
+
+/// The menu bar, with the window actions.
+fn viewer_menu() -> gio::Menu {
+    let menu = gio::Menu::new();
+    menu.append_submenu(
+        "_File",
+        &menu_sections(&[
+            &[
+                ("_Open…", "win.open"),
+                ("_Paste sentences", "win.paste"),
+                ("_Save", "win.save"),
+            ],
+            &[
+                ("Save _changes", "win.changes"),
+                ("Save tree as _dot", "win.dot"),
+                ("Save tree as _TikZ", "win.tikz"),
+                ("Copy tree as te_xt", "win.copy-diagram"),
+                ("Save su_btree…", "win.export-subtree"),
+                ("Copy review _link", "win.review-link"),
+                ("Co_mmit…", "win.git"),
+            ],
+            &[("_Quit", "win.quit")],
+        ]),
+    );
+    menu.append_submenu(
+        "_Edit",
+        &menu_sections(&[
+            &[
+                ("_Edit relation…", "win.edit"),
+                ("E_xtract subtree", "win.extract"),
+                ("_Undo removal", "win.undo"),
+            ],
+            &[
+                ("Unselect _token", "win.unselect"),
+                ("_Reveal answers", "win.reveal"),
+            ],
+            &[("_Preferences…", "win.preferences")],
+        ]),
+    );
+    menu.append_submenu(
+        "_Go",
+        &menu_sections(&[
+            &[
+                ("_Next tree", "win.next"),
+                ("_Previous tree", "win.previous"),
+                ("Page _down", "win.next-page"),
+                ("Page _up", "win.previous-page"),
+                ("F_irst tree", "win.first"),
+                ("_Last tree", "win.last"),
+            ],
+            &[
+                ("Lon_gest tree", "win.longest"),
+                ("D_eepest tree", "win.deepest"),
+                ("Next tree with rel_ation…", "win.next-relation"),
+                ("Filter by le_ngth…", "win.length-filter"),
+            ],
+            &[
+                ("Next t_oken", "win.next-token"),
+                ("Previous to_ken", "win.previous-token"),
+            ],
+            &[
+                ("_Search…", "win.search"),
+                ("Similar _words", "win.similar-tokens"),
+                ("Similar st_ructure", "win.similar-trees"),
+                ("Frequent su_btrees…", "win.subtrees"),
+                ("Feature _values by tag…", "win.feature-table"),
+                ("In_consistent annotations…", "win.inconsistencies"),
+                ("_Follow new trees", "win.follow"),
+                ("S_lideshow", "win.slideshow"),
+            ],
+        ]),
+    );
+    menu.append_submenu(
+        "_View",
+        &menu_sections(&[
+            &[("Zoom _in", "win.zoom-in"), ("Zoom _out", "win.zoom-out")],
+            &[
+                ("Collapse/expand _subtree", "win.collapse"),
+                ("Ta_gging table", "win.tagging"),
+            ],
+            &[
+                ("Original _tree", "win.original"),
+                ("Morp_hology differences", "win.morphology"),
+                ("Last _revision", "win.revision"),
+            ],
+            &[
+                ("_Validation issues", "win.issues"),
+                ("Construction _library", "win.library"),
+                ("Search _matches", "win.matches"),
+                ("_Concordance", "win.concordance"),
+                ("Sentence st_atistics", "win.statistics"),
+                ("_Notes", "win.notes"),
+            ],
+            &[
+                ("Detach validation issues", "win.detach-issues"),
+                ("Detach construction library", "win.detach-library"),
+                ("Detach search matches", "win.detach-matches"),
+                ("Detach concordance", "win.detach-concordance"),
+                ("Detach sentence statistics", "win.detach-statistics"),
+                ("Detach notes", "win.detach-notes"),
+            ],
+        ]),
+    );
+
+    menu
+}
+
+/// Create a menu of sections of (label, action) items.
+fn menu_sections(sections: &[&[(&str, &str)]]) -> gio::Menu {
+    let menu = gio::Menu::new();
+    for items in sections {
+        let section = gio::Menu::new();
+        for &(label, action) in items.iter() {
+            section.append(label, action);
+        }
+        menu.append_section(None, &section);
+    }
+
+    menu
+}
+
+/// Add the subtree of the selected token to the treebank as a new
+/// tree. Returns the index of the new tree.
+fn extract_subtree(treebank_model: &mut StatefulTreebankModel) -> Result<usize, ViewerError> {
+    let token = match treebank_model.selected_token() {
+        Some(token) => token,
+        None => return Err(ViewerError::NoTokenSelected),
+    };
+
+    let subtree = match treebank_model.graph() {
+        Some(graph) => graph.subtree(token),
+        None => return Err(ViewerError::NoGraphSelected),
+    };
+
+    treebank_model.push(subtree.into());
+
+    Ok(treebank_model.len() - 1)
+}
+
+fn create_review_link(treebank_model: &StatefulTreebankModel) -> Result<String, ViewerError> {
+    let (treebank_file, idx) = match treebank_model.file(treebank_model.idx()) {
+        Some(file) => file,
+        None => {
+            return Err(ViewerError::NoReviewLink(
+                "tree was not read from a file".to_owned(),
+            ))
+        }
+    };
+
+    // Links into a repository browser are relative to the repository.
+    let path = match treebank_file.git {
+        Some(ref git) => git.path().to_owned(),
+        None => treebank_file.path.clone(),
+    };
+
+    if treebank_file.compression.is_some() {
+        return Err(ViewerError::NoReviewLink(
+            "treebank is compressed".to_owned(),
+        ));
+    }
+
+    let lines = sentence_lines(BufReader::new(File::open(&treebank_file.path)?))?;
+    let lines = match lines.get(idx) {
+        Some(&lines) => lines,
+        None => {
+            return Err(ViewerError::NoReviewLink(
+                "tree is not in the treebank file".to_owned(),
+            ))
+        }
+    };
+
+    Ok(review_link(
+        treebank_file.review_url.as_deref(),
+        &path.to_string_lossy(),
+        lines,
+    ))
+}
+
+/// Create a command line that opens the treebank with the current
+/// tree, search query and drawing options.
+fn create_command_line(
+    treebank_model: &StatefulTreebankModel,
+    render_options: &RenderOptions,
+    opener: &TreebankOpener,
+    query: &str,
+) -> String {
+    let mut args = vec!["conllx-view".to_owned()];
+    if let Some(format) = opener.options().format {
+        args.extend(vec!["--format".to_owned(), format.to_string()]);
+    }
+
+    let flags = [
+        (!render_options.basic, "--no-basic"),
+        (render_options.enhanced, "--enhanced"),
+        (render_options.projective_heads, "--projective-heads"),
+        (render_options.root, "--root"),
+    ];
+    args.extend(
+        flags
+            .iter()
+            .filter(|&&(set, _)| set)
+            .map(|&(_, flag)| flag.to_owned()),
+    );
+
+    if !render_options.rows.is_empty() {
+        let rows: Vec<_> = render_options.rows.iter().map(Layer::to_string).collect();
+        args.extend(vec!["--rows".to_owned(), rows.join(",")]);
+    }
+    if let Some(range) = treebank_model.length_filter() {
+        args.extend(vec!["--length".to_owned(), range.to_string()]);
+    }
+    if !query.is_empty() {
+        args.extend(vec!["--search".to_owned(), query.to_owned()]);
+    }
+    if !treebank_model.is_empty() {
+        args.extend(vec![
+            "--tree".to_owned(),
+            (treebank_model.idx() + 1).to_string(),
+        ]);
+    }
+    args.extend(
+        opener
+            .paths()
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned()),
+    );
+
+    command_line(&args)
+}
+
+/// Add the sentences on the clipboard to the treebank and select the
+/// first of them. Returns the number of sentences that were added.
+fn paste_sentences(treebank_model: &mut StatefulTreebankModel) -> Result<usize, ViewerError> {
+    let text = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+        .wait_for_text()
+        .unwrap_or_default();
+    let graphs = read_treebank(text.as_bytes(), detect_format(&text))?;
+    if graphs.is_empty() {
+        return Err(ViewerError::NoSentences("the clipboard".to_owned()));
+    }
+
+    let first = treebank_model.len();
+    let n_graphs = graphs.len();
+    treebank_model.extend(graphs);
+    treebank_model.select_tree(first);
+
+    Ok(n_graphs)
+}
+
+fn report_paste(viewer: &Viewer, result: Result<usize, ViewerError>) {
+    match result {
+        Ok(n_graphs) => viewer.notify(&format!("Pasted {} trees", n_graphs)),
+        Err(err) => viewer.notify_error(&format!("Cannot paste sentences: {}", err)),
+    }
+}
+
+fn save_exercises(
+    prefix: &str,
+    graphs: &[DependencyGraph],
+    indices: &[usize],
+    mode: QuizMode,
+    render_options: &RenderOptions,
+) -> Result<(), ViewerError> {
+    let selected: Vec<_> = indices.iter().map(|&idx| &graphs[idx]).collect();
+    let exercises = exercises(&selected, mode, render_options)?;
+
+    for &(suffix, doc) in &[
+        ("exercises", &exercises.exercises),
+        ("answers", &exercises.answers),
+    ] {
+        let filename = format!("{}-{}.tex", prefix, suffix);
+        let mut writer = BufWriter::new(File::create(&filename)?);
+        writer.write_all(doc.as_bytes())?;
+        println!("Saved {} to: {}", suffix, filename);
+    }
+
+    Ok(())
+}
+
+fn save_changes(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+) -> Result<String, ViewerError> {
+    let filename = viewer.export_path("changes.diff");
+    let mut writer = BufWriter::new(File::create(&filename)?);
+    writer.write_all(changes_report(treebank_model).as_bytes())?;
+
+    Ok(filename)
+}
+
+fn save_selection(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+    trees: &[usize],
+) -> Result<String, ViewerError> {
+    let sentences: Vec<_> = trees
+        .iter()
+        .filter_map(|&idx| treebank_model.graph_at(idx))
+        .map(|graph| graph.to_sentence())
+        .collect();
+
+    let filename = viewer.export_path("selection.conll");
+    write_sentences(BufWriter::new(File::create(&filename)?), &sentences)?;
+
+    Ok(filename)
+}
+
+fn save_treebank(treebank_model: &StatefulTreebankModel) -> Result<String, ViewerError> {
+    let files = treebank_model.files();
+    if files.is_empty() {
+        return Err(ViewerError::CannotSave(
+            "treebank was read from stdin".to_owned(),
+        ));
+    }
+
+    let files: Vec<_> = match files
+        .into_iter()
+        .map(|(file, trees)| Some((file?, trees)))
+        .collect()
+    {
+        Some(files) => files,
+        None => {
+            return Err(ViewerError::CannotSave(
+                "downloaded treebanks cannot be saved".to_owned(),
+            ))
+        }
+    };
+
+    if files.iter().any(|&(file, _)| file.compression.is_some()) {
+        return Err(ViewerError::CannotSave(
+            "compressed treebanks cannot be saved".to_owned(),
+        ));
+    }
+
+    if files
+        .iter()
+        .any(|&(file, _)| !file.strict && file.format != InputFormat::Conllx)
+    {
+        return Err(ViewerError::CannotSave(
+            "only CoNLL-X treebanks can be saved without --strict".to_owned(),
+        ));
+    }
+
+    // Every file gets its own trees back, except the removed trees.
+    let sentences = treebank_model.sentences();
+    for &(file, ref trees) in &files {
+        let is_removed = |idx| treebank_model.is_removed(trees.start + idx);
+
+        if file.strict {
+            let mut text = TreebankText::read(File::open(&file.path)?, file.format)?;
+            if file.note_comments {
+                text.set_notes(treebank_model.range_notes(trees.clone()));
+            }
+            let writer = BufWriter::new(File::create(&file.path)?);
+            text.write(writer, &sentences[trees.clone()], is_removed)?;
+        } else {
+            let kept: Vec<_> = sentences[trees.clone()]
+                .iter()
+                .enumerate()
+                .filter(|&(idx, _)| !is_removed(idx))
+                .map(|(_, sentence)| sentence.clone())
+                .collect();
+            let writer = BufWriter::new(File::create(&file.path)?);
+            write_sentences(writer, &kept)?;
+        }
+
+        // Notes in comments replace the notes file.
+        let notes = if file.strict && file.note_comments {
+            SentenceNotes::new()
+        } else {
+            treebank_model
+                .range_notes(trees.clone())
+                .into_iter()
+                .filter(|&(idx, _)| !is_removed(idx))
+                .map(|(idx, note)| ((0..idx).filter(|&idx| !is_removed(idx)).count(), note))
+                .collect()
+        };
+        write_notes(&file.path, &notes)?;
+    }
+
+    Ok(files
+        .iter()
+        .map(|&(file, _)| file.path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
+/// Save the notes of the treebank files to their notes files, for
+/// treebanks that are not saved.
+fn save_notes(treebank_model: &StatefulTreebankModel) -> Result<String, ViewerError> {
+    let mut paths = Vec::new();
+    for (file, trees) in treebank_model.files() {
+        let file = ok_or!(file, continue);
+        write_notes(&file.path, &treebank_model.range_notes(trees))?;
+        paths.push(notes_path(&file.path).display().to_string());
+    }
+
+    if paths.is_empty() {
+        return Err(ViewerError::CannotSave(
+            "notes of treebanks without a file cannot be saved".to_owned(),
+        ));
+    }
+
+    Ok(paths.join(", "))
 }
 
-fn save_dot(treebank_model: &StatefulTreebankModel) -> Result<String, Error> {
+fn save_dot(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+    render_options: &RenderOptions,
+) -> Result<String, ViewerError> {
     let graph = match treebank_model.graph() {
         Some(graph) => graph,
-        None => return Err(ViewerError::NoGraphSelected.into()),
+        None => return Err(ViewerError::NoGraphSelected),
     };
 
-    let filename = format!("s{}.dot", treebank_model.idx() + 1);
+    let filename = viewer.export_path(&format!("s{}.dot", treebank_model.idx() + 1));
     let mut writer = BufWriter::new(File::create(&filename)?);
 
-    let dot = graph.dot()?;
+    let dot = graph.dot(&tree_options(viewer, treebank_model, render_options))?;
     writer.write_all(dot.as_bytes())?;
 
     Ok(filename)
 }
 
-fn save_tikz(treebank_model: &StatefulTreebankModel) -> Result<String, Error> {
+/// Save the subtree of the selected token. CoNLL fragments are saved in
+/// the format of the treebank file.
+fn save_subtree(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+    render_options: &RenderOptions,
+    subtree_format: SubtreeFormat,
+) -> Result<String, ViewerError> {
+    let token = match treebank_model.selected_token() {
+        Some(token) => token,
+        None => return Err(ViewerError::NoTokenSelected),
+    };
+
+    let graph = match treebank_model.graph() {
+        Some(graph) => graph,
+        None => return Err(ViewerError::NoGraphSelected),
+    };
+
+    let format = treebank_model
+        .file(treebank_model.idx())
+        .map(|(file, _)| file.format)
+        .unwrap_or(InputFormat::Conllx);
+
+    let filename = viewer.export_path(&format!(
+        "s{}-t{}.{}",
+        treebank_model.idx() + 1,
+        token + 1,
+        subtree_format.extension()
+    ));
+    let mut options = render_options.clone();
+    viewer.apply_preferences(&mut options);
+    let mut writer = BufWriter::new(File::create(&filename)?);
+    write_subtree(&mut writer, &graph, token, subtree_format, format, &options)?;
+
+    Ok(filename)
+}
+
+fn save_tikz(
+    viewer: &Viewer,
+    treebank_model: &StatefulTreebankModel,
+    render_options: &RenderOptions,
+) -> Result<String, ViewerError> {
     let graph = match treebank_model.graph() {
         Some(graph) => graph,
-        None => return Err(ViewerError::NoGraphSelected.into()),
+        None => return Err(ViewerError::NoGraphSelected),
     };
 
-    let filename = format!("s{}.tikz", treebank_model.idx() + 1);
+    let filename = viewer.export_path(&format!("s{}.tikz", treebank_model.idx() + 1));
     let mut writer = BufWriter::new(File::create(&filename)?);
 
-    let tikz = graph.tikz()?;
+    let tikz = graph.tikz(&tree_options(viewer, treebank_model, render_options))?;
     writer.write_all(tikz.as_bytes())?;
 
     Ok(filename)
@@ -0,0 +1,101 @@
+use std::mem;
+use std::ptr;
+
+use glib_sys::{self, gboolean, gpointer, GSource, GSourceFunc, GSourceFuncs};
+
+pub use conllx_view_core::mainloop::{channel, MainContext, Waker};
+
+/// The default main context of GLib, which the GTK main loop runs.
+/// Receivers that are attached to it are dispatched on the main thread.
+///
+/// This stands in for `glib::MainContext::channel`, which is not
+/// available in the glib version that we use. Like that channel, every
+/// receiver is a source that is ready as soon as a message is sent.
+pub struct GtkMainContext;
+
+impl MainContext for GtkMainContext {
+    fn attach(&self, dispatch: Box<dyn FnMut() -> bool>) -> Waker {
+        unsafe {
+            let source = glib_sys::g_source_new(
+                &RECEIVER_SOURCE_FUNCS as *const GSourceFuncs as *mut GSourceFuncs,
+                mem::size_of::<ReceiverSource>() as u32,
+            );
+            ptr::write(
+                &mut (*(source as *mut ReceiverSource)).dispatch,
+                Some(dispatch),
+            );
+            // Events and redrawing go first, so that the interface stays
+            // responsive while a large treebank is read.
+            glib_sys::g_source_set_priority(source, glib_sys::G_PRIORITY_DEFAULT_IDLE);
+            glib_sys::g_source_attach(source, ptr::null_mut());
+
+            // The reference of the new source is released with the waker.
+            let source = SourceRef(source);
+            Box::new(move || source.set_ready())
+        }
+    }
+}
+
+/// A GLib source that dispatches a receiver.
+#[repr(C)]
+struct ReceiverSource {
+    source: GSource,
+
+    /// Dropped on the main thread when the receiver is detached.
+    dispatch: Option<Box<dyn FnMut() -> bool>>,
+}
+
+static RECEIVER_SOURCE_FUNCS: GSourceFuncs = GSourceFuncs {
+    prepare: None,
+    check: None,
+    dispatch: Some(dispatch_receiver),
+    finalize: Some(finalize_receiver),
+    closure_callback: None,
+    closure_marshal: None,
+};
+
+unsafe extern "C" fn dispatch_receiver(
+    source: *mut GSource,
+    _callback: GSourceFunc,
+    _user_data: gpointer,
+) -> gboolean {
+    // Messages that are sent while dispatching make the source ready again.
+    glib_sys::g_source_set_ready_time(source, -1);
+
+    let source = &mut *(source as *mut ReceiverSource);
+    let attached = match source.dispatch {
+        Some(ref mut dispatch) => dispatch(),
+        None => false,
+    };
+
+    if attached {
+        glib_sys::G_SOURCE_CONTINUE
+    } else {
+        source.dispatch = None;
+        glib_sys::G_SOURCE_REMOVE
+    }
+}
+
+unsafe extern "C" fn finalize_receiver(source: *mut GSource) {
+    ptr::drop_in_place(&mut (*(source as *mut ReceiverSource)).dispatch);
+}
+
+/// A reference to a receiver source, which is used by the senders to wake
+/// the main loop.
+struct SourceRef(*mut GSource);
+
+// Setting the ready time of a source and releasing it are thread-safe.
+unsafe impl Send for SourceRef {}
+unsafe impl Sync for SourceRef {}
+
+impl SourceRef {
+    fn set_ready(&self) {
+        unsafe { glib_sys::g_source_set_ready_time(self.0, 0) }
+    }
+}
+
+impl Drop for SourceRef {
+    fn drop(&mut self) {
+        unsafe { glib_sys::g_source_unref(self.0) }
+    }
+}
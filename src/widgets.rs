@@ -1,15 +1,57 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
+use conllx::Token;
+use gdk::{EventMask, ModifierType, ScrollDirection};
+use glib::{self, StaticType};
 use gtk::prelude::*;
-use gtk::{DrawingArea, TextView};
+use gtk::{
+    Adjustment, ButtonsType, CellRendererAccel, CellRendererPixbuf, CellRendererText, DialogFlags,
+    DrawingArea, GestureZoom, IconSize, Image, InfoBar, Label, ListStore, Menu, MenuItem,
+    MessageDialog, MessageType, Revealer, SelectionMode, SeparatorMenuItem, TextView, TreePath,
+    TreeStore, TreeView, TreeViewColumn, Type, Viewport, Widget, Window, WindowType,
+};
 use rsvg::{Handle, HandleExt};
 
+use consistency::Inconsistency;
+use detokenize::KwicLine;
+use error::ViewerError;
+use features::FeatureCell;
+use graph::{DependencyGraph, RenderOptions, SvgHandle};
+use icons::TreeIcon;
+use layout::WindowGeometry;
+use library::LibraryEntry;
+use model::{ModelUpdate, StatefulTreebankModel};
+use stats::SentenceStats;
+use style::toggle_subtrees;
+use subtrees::SubtreePattern;
+use theme::Theme;
+use validate::Issue;
+
+/// Factor by which a zoom step or a scroll wheel step zooms out.
+const ZOOM_STEP: f64 = 0.90;
+
+/// The zoom level and scroll position of a zoomed tree.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TreeZoom {
+    pub scale: f64,
+
+    /// The horizontal and vertical scroll offsets.
+    pub scroll: (f64, f64),
+}
+
+/// Drawing area that shows a dependency tree, scaled to fit on the
+/// drawing area until it is zoomed. Ctrl+scrolling and pinching zoom
+/// around the pointer.
 pub struct DependencyTreeWidget {
     drawing_area: DrawingArea,
     handle: Rc<RefCell<Option<Handle>>>,
     scale: Rc<RefCell<Option<f64>>>,
+    theme: Rc<Cell<Theme>>,
+    zoom_gesture: GestureZoom,
 }
 
 impl Deref for DependencyTreeWidget {
@@ -20,15 +62,35 @@ impl Deref for DependencyTreeWidget {
     }
 }
 
+impl Default for DependencyTreeWidget {
+    fn default() -> Self {
+        DependencyTreeWidget::new()
+    }
+}
+
 impl DependencyTreeWidget {
+    /// Create the widget with a new drawing area, for embedding the
+    /// widget in other containers.
+    pub fn new() -> Self {
+        DependencyTreeWidget::from_drawing_area(&DrawingArea::new())
+    }
+
+    /// Create the widget on an existing drawing area, e.g. one of a
+    /// `gtk::Builder` interface.
     pub fn from_drawing_area(drawing_area: &DrawingArea) -> Self {
         let mut widget = DependencyTreeWidget {
             drawing_area: drawing_area.clone(),
             handle: Rc::new(RefCell::new(None)),
             scale: Rc::new(RefCell::new(None)),
+            theme: Rc::new(Cell::new(Theme::Light)),
+            zoom_gesture: GestureZoom::new(drawing_area),
         };
 
         widget.setup_drawing_area();
+        widget.setup_zooming();
+        widget
+            .drawing_area
+            .add_events(EventMask::BUTTON_PRESS_MASK.bits() as i32);
 
         widget
     }
@@ -36,82 +98,501 @@ impl DependencyTreeWidget {
     fn setup_drawing_area(&mut self) {
         let scale = self.scale.clone();
         let handle = self.handle.clone();
+        let theme = self.theme.clone();
 
         self.drawing_area.connect_draw(move |drawing_area, cr| {
             // FIXME: clone handle?
             let handle = handle.borrow();
             let handle = ok_or!(handle.as_ref(), return Inhibit(false));
 
-            // White canvas.
-            cr.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+            // Canvas in the background color of the theme.
+            let (red, green, blue) = theme.get().background_rgb();
+            cr.set_source_rgba(red, green, blue, 1.0);
             cr.paint();
 
             cr.save();
 
             // Translate to center SVG.
-            let (x_offset, y_offset) = compute_centering_offset(drawing_area, &handle);
+            let (x_offset, y_offset) = compute_centering_offset(drawing_area, handle);
             cr.translate(x_offset, y_offset);
 
             // Scale the surface.
-            let scale = *scale
-                .borrow_mut()
-                .get_or_insert(compute_scale(drawing_area, &handle));
+            let zoom = *scale.borrow();
+            let scale = zoom.unwrap_or_else(|| compute_scale(drawing_area, handle));
             cr.scale(scale, scale);
 
             // Paint the SVG.
             cr.paint_with_alpha(0.0);
-            handle.render_cairo(&cr);
+            handle.render_cairo(cr);
 
             cr.restore();
 
-            // Set size request, this is required for computing the scroll bars.
-            let svg_dims = handle.get_dimensions();
-            drawing_area.set_size_request(
-                (svg_dims.width as f64 * scale).ceil() as i32,
-                (svg_dims.height as f64 * scale).ceil() as i32,
-            );
+            // Set size request, this is required for computing the scroll
+            // bars. A tree that is scaled to fit has no size of its own,
+            // so that it follows the size of the window.
+            if zoom.is_some() {
+                let svg_dims = handle.get_dimensions();
+                drawing_area.set_size_request(
+                    (svg_dims.width as f64 * scale).ceil() as i32,
+                    (svg_dims.height as f64 * scale).ceil() as i32,
+                );
+            } else {
+                drawing_area.set_size_request(-1, -1);
+            }
 
             Inhibit(false)
         });
     }
 
+    fn setup_zooming(&mut self) {
+        self.drawing_area
+            .add_events((EventMask::SCROLL_MASK | EventMask::SMOOTH_SCROLL_MASK).bits() as i32);
+
+        let scale = self.scale.clone();
+        let handle = self.handle.clone();
+        self.drawing_area
+            .connect_scroll_event(move |drawing_area, event| {
+                if !event.get_state().contains(ModifierType::CONTROL_MASK) {
+                    return Inhibit(false);
+                }
+
+                let factor = match event.get_direction() {
+                    ScrollDirection::Up => 1.0 / ZOOM_STEP,
+                    ScrollDirection::Down => ZOOM_STEP,
+                    ScrollDirection::Smooth => ZOOM_STEP.powf(event.get_delta().1),
+                    _ => return Inhibit(false),
+                };
+
+                if let Some(ref handle) = *handle.borrow() {
+                    zoom_around(drawing_area, handle, &scale, factor, event.get_position());
+                }
+
+                Inhibit(true)
+            });
+
+        // The scale of the gesture is relative to its start, the last
+        // scale is kept to zoom by the change of the scale.
+        let last_scale = Arc::new(Mutex::new(1.0));
+        self.zoom_gesture
+            .connect(
+                "begin",
+                false,
+                clone!(last_scale => move |_| {
+                    *last_scale.lock().unwrap() = 1.0;
+                    None
+                }),
+            )
+            .expect("Cannot connect to the begin signal of the zoom gesture");
+
+        let scale = self.scale.clone();
+        let handle = self.handle.clone();
+        let drawing_area = self.drawing_area.clone();
+        self.zoom_gesture
+            .connect_scale_changed(move |gesture, gesture_scale| {
+                let mut last_scale = last_scale.lock().unwrap();
+                let factor = gesture_scale / *last_scale;
+                *last_scale = gesture_scale;
+
+                let handle = handle.borrow();
+                let handle = ok_or!(handle.as_ref(), return);
+                let center = ok_or!(gesture.get_bounding_box_center(), return);
+                zoom_around(&drawing_area, handle, &scale, factor, center);
+            });
+    }
+
+    /// Show the SVG of another tree, scaled to fit the drawing area.
     pub fn update(&mut self, handle: Handle) {
         *self.handle.borrow_mut() = Some(handle);
         *self.scale.borrow_mut() = None;
         self.drawing_area.queue_draw();
     }
 
+    /// Replace the SVG of the current tree, keeping the zoom level.
+    pub fn refresh(&mut self, handle: Handle) {
+        *self.handle.borrow_mut() = Some(handle);
+        self.drawing_area.queue_draw();
+    }
+
+    /// Set the theme of the canvas around the tree.
+    pub fn set_theme(&mut self, theme: Theme) {
+        if self.theme.replace(theme) != theme {
+            self.drawing_area.queue_draw();
+        }
+    }
+
+    /// Render a tree and show it. The selected token is set with
+    /// `RenderOptions::selection`. The zoom level is reset when
+    /// `reset_zoom` is true.
+    pub fn show_graph(
+        &mut self,
+        graph: &DependencyGraph,
+        options: &RenderOptions,
+        reset_zoom: bool,
+    ) -> Result<(), ViewerError> {
+        let handle = graph.svg_handle(options)?;
+        self.set_theme(options.theme);
+
+        if reset_zoom {
+            self.update(handle);
+        } else {
+            self.refresh(handle);
+        }
+
+        Ok(())
+    }
+
+    /// Get the token at a position of the drawing area, such as the
+    /// position of a click, from the first `n_tokens` tokens. Tokens are
+    /// found by the node identifiers of the rendered tree (`n0`, `n1`,
+    /// ...).
+    pub fn token_at(&self, (x, y): (f64, f64), n_tokens: usize) -> Option<usize> {
+        let handle = self.handle.borrow();
+        let handle = handle.as_ref()?;
+
+        let scale = self
+            .scale
+            .borrow()
+            .unwrap_or_else(|| compute_scale(&self.drawing_area, handle));
+        let (x_offset, y_offset) = compute_centering_offset(&self.drawing_area, handle);
+        let (x, y) = ((x - x_offset) / scale, (y - y_offset) / scale);
+
+        (0..n_tokens).find(|token| {
+            let id = format!("#n{}", token);
+            match (
+                handle.get_position_sub(&id),
+                handle.get_dimensions_sub(id.as_str()),
+            ) {
+                (Some(position), Some(dimensions)) => {
+                    x >= position.x as f64
+                        && x <= (position.x + dimensions.width) as f64
+                        && y >= position.y as f64
+                        && y <= (position.y + dimensions.height) as f64
+                }
+                _ => false,
+            }
+        })
+    }
+
+    /// Get the zoom level, `None` when the tree is scaled to fit the
+    /// drawing area.
+    pub fn zoom(&self) -> Option<f64> {
+        *self.scale.borrow()
+    }
+
+    /// Zoom to a scale, where 1 shows the tree in its rendered size.
+    pub fn set_zoom(&mut self, scale: f64) {
+        *self.scale.borrow_mut() = Some(scale);
+        self.drawing_area.queue_draw();
+    }
+
+    /// Scale the tree to fit the drawing area again.
+    pub fn reset_zoom(&mut self) {
+        *self.scale.borrow_mut() = None;
+        self.drawing_area.queue_draw();
+    }
+
+    /// Get the zoom level and scroll position, `None` when the tree is
+    /// scaled to fit the drawing area.
+    pub fn view(&self) -> Option<TreeZoom> {
+        let scale = (*self.scale.borrow())?;
+        let scroll = viewport_adjustments(&self.drawing_area)
+            .map(|(hadjustment, vadjustment)| (hadjustment.get_value(), vadjustment.get_value()))
+            .unwrap_or((0.0, 0.0));
+
+        Some(TreeZoom { scale, scroll })
+    }
+
+    /// Restore a zoom level and scroll position.
+    pub fn set_view(&mut self, view: TreeZoom) {
+        *self.scale.borrow_mut() = Some(view.scale);
+
+        if let Some(ref handle) = *self.handle.borrow() {
+            scroll_zoomed(&self.drawing_area, handle, view.scale, |_, _| view.scroll);
+        }
+
+        self.drawing_area.queue_draw();
+    }
+
+    /// Zoom in by a step, as Ctrl+scrolling up does.
     pub fn zoom_in(&mut self) {
-        let mut opt_scale = self.scale.borrow_mut();
-        *opt_scale = opt_scale.map(|scale| scale / 0.90);
+        self.zoom_by(1.0 / ZOOM_STEP);
     }
 
+    /// Zoom out by a step, as Ctrl+scrolling down does.
     pub fn zoom_out(&mut self) {
-        let mut opt_scale = self.scale.borrow_mut();
-        *opt_scale = opt_scale.map(|scale| scale * 0.90);
+        self.zoom_by(ZOOM_STEP);
+    }
+
+    fn zoom_by(&mut self, factor: f64) {
+        let handle = self.handle.borrow();
+        let handle = ok_or!(handle.as_ref(), return);
+        let scale = self
+            .scale
+            .borrow()
+            .unwrap_or_else(|| compute_scale(&self.drawing_area, handle));
+        *self.scale.borrow_mut() = Some(scale * factor);
+        self.drawing_area.queue_draw();
     }
 }
 
-pub fn compute_scale(drawing_area: &DrawingArea, handle: &Handle) -> f64 {
+type RenderErrorCallback = Rc<RefCell<Option<Box<dyn Fn(&ViewerError)>>>>;
+
+/// A view of the current tree of a treebank model, for embedding the
+/// tree viewer in other GTK applications. The view follows the model:
+/// the tree is rendered anew when another tree or token is selected and
+/// when it is edited. Clicking a token selects it, clicking a collapsed
+/// subtree expands it.
+///
+/// Trees are rendered on the main thread. The viewer itself renders on
+/// worker threads with `render::Renderer` instead, so that it does not
+/// wait for Graphviz.
+pub struct TreebankView {
+    drawing_area: DrawingArea,
+    model: Rc<RefCell<StatefulTreebankModel>>,
+    widget: Rc<RefCell<DependencyTreeWidget>>,
+    options: Rc<RefCell<RenderOptions>>,
+    on_error: RenderErrorCallback,
+}
+
+impl Deref for TreebankView {
+    type Target = DrawingArea;
+
+    fn deref(&self) -> &DrawingArea {
+        &self.drawing_area
+    }
+}
+
+impl TreebankView {
+    /// Create a view of a model that renders trees with `options`. The
+    /// selected token, dependency path and quiz answers of the options
+    /// are those of the model.
+    pub fn new(model: Rc<RefCell<StatefulTreebankModel>>, options: RenderOptions) -> Self {
+        let widget = DependencyTreeWidget::new();
+        let view = TreebankView {
+            drawing_area: (*widget).clone(),
+            model,
+            widget: Rc::new(RefCell::new(widget)),
+            options: Rc::new(RefCell::new(options)),
+            on_error: Rc::new(RefCell::new(None)),
+        };
+
+        view.connect_model();
+        view.connect_clicks();
+
+        show_model_tree(
+            &view.model.borrow(),
+            &view.widget,
+            &view.options.borrow(),
+            &view.on_error,
+            true,
+        );
+
+        view
+    }
+
+    fn connect_model(&self) {
+        for &update in &[
+            ModelUpdate::Quiz,
+            ModelUpdate::TokenSelection,
+            ModelUpdate::TreeEdit,
+            ModelUpdate::TreeSelection,
+        ] {
+            let widget = self.widget.clone();
+            let options = self.options.clone();
+            let on_error = self.on_error.clone();
+
+            self.model
+                .borrow_mut()
+                .connect_update(update, move |model| {
+                    show_model_tree(
+                        model,
+                        &widget,
+                        &options.borrow(),
+                        &on_error,
+                        update == ModelUpdate::TreeSelection,
+                    );
+                });
+        }
+    }
+
+    fn connect_clicks(&self) {
+        let model = self.model.clone();
+        let widget = self.widget.clone();
+        let options = self.options.clone();
+
+        self.drawing_area
+            .connect_button_press_event(move |_, event| {
+                if event.get_button() != 1 {
+                    return Inhibit(false);
+                }
+
+                let mut model = model.borrow_mut();
+                let n_tokens = ok_or!(model.graph(), return Inhibit(false)).0.node_count();
+                let token = ok_or!(
+                    widget.borrow().token_at(event.get_position(), n_tokens),
+                    return Inhibit(false)
+                );
+
+                if toggle_subtrees(&options.borrow().collapsed, model.collapsed()).contains(&token)
+                {
+                    model.toggle_collapsed(token);
+                } else {
+                    model.select_token(Some(token));
+                }
+
+                Inhibit(true)
+            });
+    }
+
+    /// The model of the view. Trees are browsed and edited with the
+    /// methods of the model, e.g. `seek` and `select_token`.
+    pub fn model(&self) -> Rc<RefCell<StatefulTreebankModel>> {
+        self.model.clone()
+    }
+
+    /// The tree widget of the view, e.g. for zooming.
+    pub fn widget(&self) -> Rc<RefCell<DependencyTreeWidget>> {
+        self.widget.clone()
+    }
+
+    /// Render trees with other options, e.g. another theme or label
+    /// rows, and show the current tree again.
+    pub fn set_options(&self, options: RenderOptions) {
+        *self.options.borrow_mut() = options;
+        self.model.borrow_mut().redraw();
+    }
+
+    /// Call `callback` when a tree cannot be rendered, e.g. because
+    /// Graphviz is not installed. The previous tree stays visible.
+    pub fn connect_render_error<F>(&self, callback: F)
+    where
+        F: Fn(&ViewerError) + 'static,
+    {
+        *self.on_error.borrow_mut() = Some(Box::new(callback));
+    }
+}
+
+/// Show the current tree of a model on a tree widget.
+fn show_model_tree(
+    model: &StatefulTreebankModel,
+    widget: &RefCell<DependencyTreeWidget>,
+    options: &RenderOptions,
+    on_error: &RenderErrorCallback,
+    reset_zoom: bool,
+) {
+    let graph = ok_or!(model.shown_graph(), return);
+    let mut options = model.render_options(options);
+    options.collapsed = toggle_subtrees(&options.collapsed, model.collapsed());
+
+    if let Err(err) = widget.borrow_mut().show_graph(&graph, &options, reset_zoom) {
+        if let Some(ref callback) = *on_error.borrow() {
+            callback(&err);
+        }
+    }
+}
+
+/// Zoom by `factor`, keeping the point `(x, y)` of the drawing area at
+/// the same position in the window.
+fn zoom_around(
+    drawing_area: &DrawingArea,
+    handle: &Handle,
+    scale: &RefCell<Option<f64>>,
+    factor: f64,
+    (x, y): (f64, f64),
+) {
+    let old_scale = scale
+        .borrow()
+        .unwrap_or_else(|| compute_scale(drawing_area, handle));
+    let new_scale = old_scale * factor;
+    *scale.borrow_mut() = Some(new_scale);
+
+    // The point of the tree under the pointer.
+    let (x_offset, y_offset) = compute_centering_offset(drawing_area, handle);
+    let tree_x = (x - x_offset) / old_scale;
+    let tree_y = (y - y_offset) / old_scale;
+
+    scroll_zoomed(
+        drawing_area,
+        handle,
+        new_scale,
+        |(x_offset, y_offset), (x_scroll, y_scroll)| {
+            (
+                x_offset + tree_x * new_scale - (x - x_scroll),
+                y_offset + tree_y * new_scale - (y - y_scroll),
+            )
+        },
+    );
+
+    drawing_area.queue_draw();
+}
+
+/// Scroll the viewport of a tree that is zoomed to `scale`. `scroll`
+/// computes the scroll offsets from the centering offsets of the zoomed
+/// tree and the current scroll offsets.
+fn scroll_zoomed<F>(drawing_area: &DrawingArea, handle: &Handle, scale: f64, scroll: F)
+where
+    F: FnOnce((f64, f64), (f64, f64)) -> (f64, f64),
+{
+    let (hadjustment, vadjustment) = ok_or!(viewport_adjustments(drawing_area), return);
+
+    // The drawing area is resized when it is drawn, compute the size that
+    // it will get to scroll now.
     let svg_dims = handle.get_dimensions();
+    let width = (svg_dims.width as f64 * scale)
+        .ceil()
+        .max(hadjustment.get_page_size());
+    let height = (svg_dims.height as f64 * scale)
+        .ceil()
+        .max(vadjustment.get_page_size());
+    let (x, y) = scroll(
+        centering_offset(width, height, handle),
+        (hadjustment.get_value(), vadjustment.get_value()),
+    );
+    scroll_to(&hadjustment, width, x);
+    scroll_to(&vadjustment, height, y);
+}
+
+/// The adjustments of the viewport that the drawing area is scrolled in.
+fn viewport_adjustments(drawing_area: &DrawingArea) -> Option<(Adjustment, Adjustment)> {
+    let viewport = drawing_area.get_parent()?.downcast::<Viewport>().ok()?;
+    Some((viewport.get_hadjustment()?, viewport.get_vadjustment()?))
+}
+
+fn scroll_to(adjustment: &Adjustment, upper: f64, value: f64) {
+    adjustment.set_upper(upper);
+    adjustment.set_value(value);
+}
+
+/// The scale at which a tree fits the drawing area.
+pub fn compute_scale(drawing_area: &DrawingArea, handle: &Handle) -> f64 {
     let rect = drawing_area.get_allocation();
+    fit_scale(rect.width as f64, rect.height as f64, handle)
+}
 
-    let scale_x = rect.width as f64 / svg_dims.width as f64;
-    let scale_y = rect.height as f64 / svg_dims.height as f64;
+fn fit_scale(width: f64, height: f64, handle: &Handle) -> f64 {
+    let svg_dims = handle.get_dimensions();
+
+    let scale_x = width / svg_dims.width as f64;
+    let scale_y = height / svg_dims.height as f64;
 
     scale_x.min(scale_y)
 }
 
 /// Computes the offset/translation for centering the SVG in the drawing area.
 fn compute_centering_offset(drawing_area: &DrawingArea, handle: &Handle) -> (f64, f64) {
-    let svg_dims = handle.get_dimensions();
-    let scale = compute_scale(drawing_area, handle);
-
     let rect = drawing_area.get_allocation();
+    centering_offset(rect.width as f64, rect.height as f64, handle)
+}
+
+/// Computes the offset for centering the SVG in a drawing area of the
+/// given size.
+fn centering_offset(width: f64, height: f64, handle: &Handle) -> (f64, f64) {
+    let svg_dims = handle.get_dimensions();
+    let scale = fit_scale(width, height, handle);
 
     (
-        rect.width as f64 * 0.5 - svg_dims.width as f64 * scale * 0.5,
-        rect.height as f64 * 0.5 - svg_dims.height as f64 * scale * 0.5,
+        width * 0.5 - svg_dims.width as f64 * scale * 0.5,
+        height * 0.5 - svg_dims.height as f64 * scale * 0.5,
     )
 }
 
@@ -134,7 +615,1173 @@ impl SentenceWidget {
         }
     }
 
-    pub fn update(&mut self, sentence: String) {
+    pub fn update(&self, sentence: String) {
         self.text_view.get_buffer().unwrap().set_text(&sentence);
     }
 }
+
+/// Editable list of the accelerators of actions.
+#[derive(Clone)]
+pub struct KeymapWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    cell: CellRendererAccel,
+}
+
+impl Deref for KeymapWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl KeymapWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[
+            Type::String,
+            Type::String,
+            Type::U32,
+            ModifierType::static_type(),
+        ]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Action", 1);
+
+        let cell = CellRendererAccel::new();
+        cell.set_property_editable(true);
+        let tree_column = TreeViewColumn::new();
+        tree_column.set_title("Shortcut");
+        tree_column.pack_start(&cell, true);
+        tree_column.add_attribute(&cell, "accel-key", 2);
+        tree_column.add_attribute(&cell, "accel-mods", 3);
+        tree_view.append_column(&tree_column);
+
+        KeymapWidget {
+            tree_view: tree_view.clone(),
+            store,
+            cell,
+        }
+    }
+
+    /// Call `f` with the action and its new accelerator when the
+    /// accelerator of an action is edited, `None` when it is cleared.
+    pub fn connect_edited<F>(&self, f: F)
+    where
+        F: Fn(&str, Option<&str>) + 'static,
+    {
+        let f = Rc::new(f);
+
+        let store = self.store.clone();
+        let edited = f.clone();
+        self.cell
+            .connect_accel_edited(move |_, path, key, mods, _| {
+                let iter = ok_or!(store.get_iter(&path), return);
+                let action = ok_or!(store.get_value(&iter, 0).get::<String>(), return);
+                let accel = ok_or!(gtk::accelerator_name(key, mods), return);
+                edited(&action, Some(&accel));
+            });
+
+        let store = self.store.clone();
+        self.cell.connect_accel_cleared(move |_, path| {
+            let iter = ok_or!(store.get_iter(&path), return);
+            let action = ok_or!(store.get_value(&iter, 0).get::<String>(), return);
+            f(&action, None);
+        });
+    }
+
+    /// Show the actions as (action, description, accelerator) triples.
+    pub fn update(&self, actions: &[(&str, &str, Option<String>)]) {
+        self.store.clear();
+
+        for &(action, description, ref accel) in actions {
+            let (key, mods) = accel
+                .as_ref()
+                .map(|accel| gtk::accelerator_parse(accel))
+                .unwrap_or((0, ModifierType::empty()));
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3],
+                &[&action, &description, &key, &mods],
+            );
+        }
+    }
+}
+
+/// List of the examples in the construction library.
+#[derive(Clone)]
+pub struct LibraryWidget {
+    tree_view: TreeView,
+    store: ListStore,
+}
+
+impl Deref for LibraryWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl LibraryWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Name", 0);
+        append_text_column(tree_view, "Tags", 1);
+
+        LibraryWidget {
+            tree_view: tree_view.clone(),
+            store,
+        }
+    }
+
+    /// Return the index of the selected example.
+    pub fn selected(&self) -> Option<usize> {
+        let (path, _) = self.tree_view.get_cursor();
+        path.and_then(|path| path.get_indices().first().map(|&idx| idx as usize))
+    }
+
+    pub fn update(&self, entries: &[LibraryEntry]) {
+        self.store.clear();
+
+        for entry in entries {
+            self.store
+                .insert_with_values(None, &[0, 1], &[&entry.name, &entry.tags.join(", ")]);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IssuesWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    issues: Rc<RefCell<Vec<Issue>>>,
+}
+
+impl Deref for IssuesWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl IssuesWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Tree", 0);
+        append_text_column(tree_view, "Token", 1);
+        append_text_column(tree_view, "Issue", 2);
+
+        IssuesWidget {
+            tree_view: tree_view.clone(),
+            store,
+            issues: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Return the issue in the given row.
+    pub fn issue(&self, row: usize) -> Option<Issue> {
+        self.issues.borrow().get(row).cloned()
+    }
+
+    pub fn update(&self, issues: Vec<Issue>) {
+        self.store.clear();
+
+        for issue in &issues {
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2],
+                &[
+                    &(issue.sentence + 1).to_string(),
+                    &(issue.token + 1).to_string(),
+                    &issue.kind.to_string(),
+                ],
+            );
+        }
+
+        *self.issues.borrow_mut() = issues;
+    }
+}
+
+/// List of subtree patterns with their frequencies.
+#[derive(Clone)]
+pub struct SubtreesWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    patterns: Rc<RefCell<Vec<SubtreePattern>>>,
+}
+
+impl Deref for SubtreesWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl SubtreesWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Count", 0);
+        append_text_column(tree_view, "Depth", 1);
+        append_text_column(tree_view, "Pattern", 2);
+
+        SubtreesWidget {
+            tree_view: tree_view.clone(),
+            store,
+            patterns: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Return the pattern in the given row.
+    pub fn pattern(&self, row: usize) -> Option<SubtreePattern> {
+        self.patterns.borrow().get(row).cloned()
+    }
+
+    pub fn update(&self, patterns: Vec<SubtreePattern>) {
+        self.store.clear();
+
+        for pattern in &patterns {
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2],
+                &[
+                    &pattern.count.to_string(),
+                    &pattern.depth.to_string(),
+                    &pattern.pattern,
+                ],
+            );
+        }
+
+        *self.patterns.borrow_mut() = patterns;
+    }
+}
+
+/// Tokens that are annotated differently in the same context, with a row
+/// per context and its annotations below it.
+#[derive(Clone)]
+pub struct InconsistenciesWidget {
+    tree_view: TreeView,
+    store: TreeStore,
+    inconsistencies: Rc<RefCell<Vec<Inconsistency>>>,
+}
+
+impl Deref for InconsistenciesWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl InconsistenciesWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = TreeStore::new(&[Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Count", 0);
+        append_text_column(tree_view, "Context or annotation", 1);
+
+        InconsistenciesWidget {
+            tree_view: tree_view.clone(),
+            store,
+            inconsistencies: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Return the inconsistency of a row, with the index of the variant
+    /// for the rows of annotations.
+    pub fn inconsistency(&self, path: &TreePath) -> Option<(Inconsistency, Option<usize>)> {
+        let indices = path.get_indices();
+        let inconsistency = self
+            .inconsistencies
+            .borrow()
+            .get(*indices.first()? as usize)?
+            .clone();
+        Some((
+            inconsistency,
+            indices.get(1).map(|&variant| variant as usize),
+        ))
+    }
+
+    pub fn update(&self, inconsistencies: Vec<Inconsistency>) {
+        self.store.clear();
+
+        for inconsistency in &inconsistencies {
+            let iter = self.store.insert_with_values(
+                None,
+                None,
+                &[0, 1],
+                &[&inconsistency.count().to_string(), &inconsistency.context()],
+            );
+
+            for variant in &inconsistency.variants {
+                self.store.insert_with_values(
+                    Some(&iter),
+                    None,
+                    &[0, 1],
+                    &[&variant.matches.len().to_string(), &variant.annotation()],
+                );
+            }
+        }
+
+        *self.inconsistencies.borrow_mut() = inconsistencies;
+    }
+}
+
+/// Counts of feature values by universal part-of-speech tag, with the
+/// share of the tokens with the feature value that have the tag.
+#[derive(Clone)]
+pub struct FeatureTableWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    cells: Rc<RefCell<Vec<FeatureCell>>>,
+}
+
+impl Deref for FeatureTableWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl FeatureTableWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::String,
+        ]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Feature", 0);
+        append_text_column(tree_view, "Value", 1);
+        append_text_column(tree_view, "UPOS", 2);
+        append_text_column(tree_view, "Count", 3);
+        append_text_column(tree_view, "Share", 4);
+
+        FeatureTableWidget {
+            tree_view: tree_view.clone(),
+            store,
+            cells: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Return the cell in the given row.
+    pub fn cell(&self, row: usize) -> Option<FeatureCell> {
+        self.cells.borrow().get(row).cloned()
+    }
+
+    pub fn update(&self, cells: Vec<FeatureCell>) {
+        self.store.clear();
+
+        for cell in &cells {
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3, 4],
+                &[
+                    &cell.feature,
+                    &cell.value,
+                    &cell.upos,
+                    &cell.count.to_string(),
+                    &format!("{:.1}%", cell.share() * 100.0),
+                ],
+            );
+        }
+
+        *self.cells.borrow_mut() = cells;
+    }
+}
+
+/// Statistics of the selected tree, with the counts of its relations.
+#[derive(Clone)]
+pub struct SentenceStatsWidget {
+    tree_view: TreeView,
+    store: ListStore,
+}
+
+impl Deref for SentenceStatsWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl SentenceStatsWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Statistic", 0);
+        append_text_column(tree_view, "Value", 1);
+
+        SentenceStatsWidget {
+            tree_view: tree_view.clone(),
+            store,
+        }
+    }
+
+    pub fn update(&self, stats: Option<SentenceStats>) {
+        self.store.clear();
+
+        let stats = ok_or!(stats, return);
+        let mut rows = vec![
+            ("Depth".to_owned(), stats.depth),
+            ("Longest dependency".to_owned(), stats.max_dependency_length),
+            ("Non-projective arcs".to_owned(), stats.non_projective),
+        ];
+        for rel in stats.relations.values() {
+            rows.push((rel.to_owned(), stats.relations.count(rel)));
+        }
+
+        for (statistic, value) in rows {
+            self.store
+                .insert_with_values(None, &[0, 1], &[&statistic, &value.to_string()]);
+        }
+    }
+}
+
+/// A tag of a token that can be edited in the tagging table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TagColumn {
+    Lemma,
+    Upos,
+    Xpos,
+}
+
+impl TagColumn {
+    /// The tag that is edited after this tag, `None` for the last tag of
+    /// a row.
+    pub fn next(self) -> Option<TagColumn> {
+        match self {
+            TagColumn::Lemma => Some(TagColumn::Upos),
+            TagColumn::Upos => Some(TagColumn::Xpos),
+            TagColumn::Xpos => None,
+        }
+    }
+
+    fn store_column(self) -> i32 {
+        match self {
+            TagColumn::Lemma => 2,
+            TagColumn::Upos => 3,
+            TagColumn::Xpos => 4,
+        }
+    }
+}
+
+/// Table of the tokens of a tree with editable lemmas and part-of-speech
+/// tags, for reviewing tagging and lemmatization without the tree. Every
+/// row is a token.
+#[derive(Clone)]
+pub struct TaggingWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    cells: Vec<(TagColumn, CellRendererText, TreeViewColumn)>,
+}
+
+impl Deref for TaggingWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl TaggingWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::String,
+            Type::String,
+        ]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "ID", 0);
+        append_text_column(tree_view, "Form", 1);
+
+        let tags = [
+            (TagColumn::Lemma, "Lemma"),
+            (TagColumn::Upos, "UPOS"),
+            (TagColumn::Xpos, "XPOS"),
+        ];
+        let cells = tags
+            .iter()
+            .map(|&(column, title)| {
+                let cell = CellRendererText::new();
+                cell.set_property_editable(true);
+                let tree_column = TreeViewColumn::new();
+                tree_column.set_title(title);
+                tree_column.pack_start(&cell, true);
+                tree_column.add_attribute(&cell, "text", column.store_column());
+                tree_view.append_column(&tree_column);
+                (column, cell, tree_column)
+            })
+            .collect();
+
+        TaggingWidget {
+            tree_view: tree_view.clone(),
+            store,
+            cells,
+        }
+    }
+
+    /// Call `f` with the token, the tag and its new value when a tag is
+    /// edited.
+    pub fn connect_edited<F>(&self, f: F)
+    where
+        F: Fn(usize, TagColumn, &str) + 'static,
+    {
+        let f = Rc::new(f);
+
+        for &(column, ref cell, _) in &self.cells {
+            let f = f.clone();
+            cell.connect_edited(move |_, path, text| {
+                let row = ok_or!(path.get_indices().first().cloned(), return);
+                f(row as usize, column, text.trim());
+            });
+        }
+    }
+
+    /// Call `f` with the token and the tag when a row is activated. Rows
+    /// that are activated outside the tags edit the lemma.
+    pub fn connect_activated<F>(&self, f: F)
+    where
+        F: Fn(usize, TagColumn) + 'static,
+    {
+        let cells = self.cells.clone();
+        self.tree_view
+            .connect_row_activated(move |_, path, tree_column| {
+                let row = ok_or!(path.get_indices().first().cloned(), return);
+                let column = cells
+                    .iter()
+                    .find(|(_, _, column)| column == tree_column)
+                    .map(|&(column, _, _)| column)
+                    .unwrap_or(TagColumn::Lemma);
+                f(row as usize, column);
+            });
+    }
+
+    /// Call `f` with the token of the row that the cursor moves to.
+    pub fn connect_token_selected<F>(&self, f: F)
+    where
+        F: Fn(usize) + 'static,
+    {
+        self.tree_view.connect_cursor_changed(move |tree_view| {
+            let path = ok_or!(tree_view.get_cursor().0, return);
+            let row = ok_or!(path.get_indices().first().cloned(), return);
+            f(row as usize);
+        });
+    }
+
+    /// Start editing a tag of a token.
+    pub fn edit(&self, token: usize, column: TagColumn) {
+        let tree_column = self
+            .cells
+            .iter()
+            .find(|&&(tag, _, _)| tag == column)
+            .map(|(_, _, tree_column)| tree_column);
+        self.tree_view.grab_focus();
+        self.tree_view.set_cursor(
+            &TreePath::new_from_indicesv(&[token as i32]),
+            tree_column,
+            true,
+        );
+    }
+
+    /// Move the cursor to a token.
+    pub fn select(&self, token: Option<usize>) {
+        let cursor = self
+            .tree_view
+            .get_cursor()
+            .0
+            .and_then(|path| path.get_indices().first().cloned());
+        match token {
+            Some(token) if cursor != Some(token as i32) => {
+                self.tree_view.set_cursor(
+                    &TreePath::new_from_indicesv(&[token as i32]),
+                    None,
+                    false,
+                );
+            }
+            Some(_) => (),
+            None => self.tree_view.get_selection().unselect_all(),
+        }
+    }
+
+    /// Show the tokens of a tree, with the cursor on the selected token.
+    pub fn update(&self, tokens: &[Token], selected: Option<usize>) {
+        self.store.clear();
+
+        for (idx, token) in tokens.iter().enumerate() {
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3, 4],
+                &[
+                    &(idx + 1).to_string(),
+                    &token.form(),
+                    &token.lemma().unwrap_or("_"),
+                    &token.cpos().unwrap_or("_"),
+                    &token.pos().unwrap_or("_"),
+                ],
+            );
+        }
+
+        self.select(selected);
+    }
+}
+
+/// Icons of the states of a tree, such as marked for review or edited,
+/// with a tooltip for each state.
+#[derive(Clone)]
+pub struct TreeStateIcons {
+    icons: gtk::Box,
+}
+
+impl Deref for TreeStateIcons {
+    type Target = gtk::Box;
+
+    fn deref(&self) -> &Self::Target {
+        &self.icons
+    }
+}
+
+impl TreeStateIcons {
+    pub fn from_box(icons: &gtk::Box) -> Self {
+        TreeStateIcons {
+            icons: icons.clone(),
+        }
+    }
+
+    /// Show the given states, as (icon, tooltip) pairs.
+    pub fn update(&self, states: &[(TreeIcon, String)]) {
+        for child in self.icons.get_children() {
+            self.icons.remove(&child);
+        }
+
+        for &(icon, ref tooltip) in states {
+            let image = Image::new_from_icon_name(icon.name(), IconSize::Menu.into());
+            image.set_tooltip_text(Some(tooltip.as_str()));
+            image.show();
+            self.icons.add(&image);
+        }
+    }
+}
+
+/// A keyword-in-context concordance of search matches, a page at a
+/// time. The matches are aligned on the matching token.
+#[derive(Clone)]
+pub struct ConcordanceWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    page: Rc<Cell<Option<usize>>>,
+}
+
+impl Deref for ConcordanceWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl ConcordanceWidget {
+    pub fn from_tree_view(tree_view: &TreeView) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String, Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        append_text_column(tree_view, "Tree", 0);
+
+        let left = CellRendererText::new();
+        left.set_alignment(1.0, 0.5);
+        let left_column = TreeViewColumn::new();
+        left_column.set_title("Left");
+        left_column.pack_start(&left, true);
+        left_column.add_attribute(&left, "text", 1);
+        tree_view.append_column(&left_column);
+
+        append_column(tree_view, "Match", "markup", 2);
+        append_text_column(tree_view, "Right", 3);
+
+        ConcordanceWidget {
+            tree_view: tree_view.clone(),
+            store,
+            page: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// The page that is shown, if any.
+    pub fn page(&self) -> Option<usize> {
+        self.page.get()
+    }
+
+    /// Show a page of concordance lines, as (tree index, line) pairs.
+    pub fn update(&self, page: usize, lines: &[(usize, KwicLine)]) {
+        self.store.clear();
+
+        for &(sentence, ref line) in lines {
+            let keyword = format!("<b>{}</b>", glib::markup_escape_text(&line.keyword));
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3],
+                &[
+                    &(sentence + 1).to_string(),
+                    &line.left,
+                    &keyword,
+                    &line.right,
+                ],
+            );
+        }
+
+        self.page.set(Some(page));
+    }
+}
+
+/// A page of search matches.
+pub struct MatchPage {
+    pub page: usize,
+    pub n_pages: usize,
+
+    /// The matches as (tree index, state of the tree, token, sentence
+    /// markup) tuples.
+    pub rows: Vec<(usize, Option<TreeIcon>, String, String)>,
+}
+
+/// List of search matches, shown a page at a time.
+#[derive(Clone)]
+pub struct MatchesWidget {
+    tree_view: TreeView,
+    store: ListStore,
+    page_label: Label,
+    page: Rc<Cell<Option<usize>>>,
+}
+
+impl Deref for MatchesWidget {
+    type Target = TreeView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree_view
+    }
+}
+
+impl MatchesWidget {
+    pub fn from_widgets(tree_view: &TreeView, page_label: &Label) -> Self {
+        let store = ListStore::new(&[Type::String, Type::String, Type::String, Type::String]);
+        tree_view.set_model(Some(&store));
+
+        let icon = CellRendererPixbuf::new();
+        let cell = CellRendererText::new();
+        let tree_column = TreeViewColumn::new();
+        tree_column.set_title("Tree");
+        tree_column.pack_start(&cell, true);
+        tree_column.add_attribute(&cell, "text", 0);
+        tree_column.pack_start(&icon, false);
+        tree_column.add_attribute(&icon, "icon-name", 1);
+        tree_view.append_column(&tree_column);
+
+        append_text_column(tree_view, "Token", 2);
+        append_column(tree_view, "Sentence", "markup", 3);
+
+        tree_view.get_selection().set_mode(SelectionMode::Multiple);
+
+        MatchesWidget {
+            tree_view: tree_view.clone(),
+            store,
+            page_label: page_label.clone(),
+            page: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// The page that is shown, if any.
+    pub fn page(&self) -> Option<usize> {
+        self.page.get()
+    }
+
+    /// The selected rows of the page that is shown.
+    pub fn selected(&self) -> Vec<usize> {
+        let (paths, _) = self.tree_view.get_selection().get_selected_rows();
+        paths
+            .iter()
+            .filter_map(|path| path.get_indices().first().map(|&row| row as usize))
+            .collect()
+    }
+
+    pub fn update(&self, page: &MatchPage) {
+        self.store.clear();
+
+        for &(sentence, state, ref token, ref markup) in &page.rows {
+            let icon = state.map(TreeIcon::name);
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3],
+                &[&(sentence + 1).to_string(), &icon, token, markup],
+            );
+        }
+
+        self.page.set(Some(page.page));
+        self.page_label.set_text(&format!(
+            "Page {} of {}",
+            page.page + 1,
+            page.n_pages.max(1)
+        ));
+    }
+}
+
+/// A side panel that can be detached into a window of its own, for
+/// instance to show it on another monitor. The panel keeps its widgets,
+/// so that it stays synchronized with the model.
+#[derive(Clone)]
+pub struct DetachablePanel {
+    revealer: Revealer,
+    content: Widget,
+    title: String,
+    window: Rc<RefCell<Option<Window>>>,
+}
+
+impl Deref for DetachablePanel {
+    type Target = Revealer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.revealer
+    }
+}
+
+impl DetachablePanel {
+    pub fn from_revealer(revealer: &Revealer, title: &str) -> Self {
+        DetachablePanel {
+            revealer: revealer.clone(),
+            content: revealer.get_child().expect("Panel without content"),
+            title: title.to_owned(),
+            window: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.window.borrow().is_some()
+    }
+
+    /// Return whether the panel is shown, in the main window or its own
+    /// window.
+    pub fn is_shown(&self) -> bool {
+        self.is_detached() || self.revealer.get_reveal_child()
+    }
+
+    /// Show or hide the panel in the main window. A detached panel is
+    /// brought to the front instead.
+    pub fn toggle(&self) {
+        match *self.window.borrow() {
+            Some(ref window) => window.present(),
+            None => {
+                let reveal = !self.revealer.get_reveal_child();
+                self.revealer.set_reveal_child(reveal);
+            }
+        }
+    }
+
+    /// Move the panel into a window of its own, or back into the main
+    /// window when it is detached. Closing the window also moves the
+    /// panel back.
+    pub fn toggle_detached(&self) {
+        let window = self.window.borrow_mut().take();
+        match window {
+            Some(window) => {
+                self.attach(&window);
+                window.destroy();
+            }
+            None => self.detach(None),
+        }
+    }
+
+    /// Move the panel into a window of its own, with the given position
+    /// and size.
+    pub fn detach(&self, geometry: Option<&WindowGeometry>) {
+        if self.is_detached() {
+            return;
+        }
+
+        let window = Window::new(WindowType::Toplevel);
+        window.set_title(&self.title);
+        match geometry {
+            Some(geometry) => {
+                window.set_default_size(geometry.width, geometry.height);
+                window.move_(geometry.x, geometry.y);
+            }
+            None => window.set_default_size(400, 600),
+        }
+
+        self.revealer.set_reveal_child(false);
+        self.revealer.remove(&self.content);
+        window.add(&self.content);
+
+        let panel = self.clone();
+        window.connect_delete_event(move |window, _| {
+            if panel.window.borrow_mut().take().is_some() {
+                panel.attach(window);
+            }
+
+            Inhibit(false)
+        });
+
+        window.show_all();
+        *self.window.borrow_mut() = Some(window);
+    }
+
+    /// Get the position and size of the window of a detached panel.
+    pub fn geometry(&self) -> Option<WindowGeometry> {
+        let window = self.window.borrow();
+        let window = window.as_ref()?;
+        let (x, y) = window.get_position();
+        let (width, height) = window.get_size();
+
+        Some(WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn attach(&self, window: &Window) {
+        window.remove(&self.content);
+        self.revealer.add(&self.content);
+        self.revealer.set_reveal_child(true);
+    }
+}
+
+type RecentCallback = dyn Fn(&[PathBuf]);
+
+/// Menu with the treebanks that were opened recently.
+pub struct RecentMenu {
+    menu: Menu,
+    activate: Rc<RecentCallback>,
+}
+
+impl Deref for RecentMenu {
+    type Target = Menu;
+
+    fn deref(&self) -> &Self::Target {
+        &self.menu
+    }
+}
+
+impl RecentMenu {
+    /// Create the menu, `activate` is called with the files of the
+    /// treebank that is chosen.
+    pub fn from_menu<F>(menu: &Menu, activate: F) -> Self
+    where
+        F: Fn(&[PathBuf]) + 'static,
+    {
+        RecentMenu {
+            menu: menu.clone(),
+            activate: Rc::new(activate),
+        }
+    }
+
+    pub fn update(&self, treebanks: &[Vec<PathBuf>]) {
+        for child in self.menu.get_children() {
+            self.menu.remove(&child);
+        }
+
+        for paths in treebanks {
+            let names: Vec<_> = paths
+                .iter()
+                .map(|path| match path.file_name() {
+                    Some(name) => name.to_string_lossy().into_owned(),
+                    None => path.display().to_string(),
+                })
+                .collect();
+            let full: Vec<_> = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+
+            let item = MenuItem::new_with_label(&names.join(", "));
+            item.set_tooltip_text(Some(full.join("\n").as_str()));
+
+            let activate = self.activate.clone();
+            let paths = paths.clone();
+            item.connect_activate(move |_| activate(&paths));
+
+            self.menu.append(&item);
+        }
+
+        if treebanks.is_empty() {
+            let item = MenuItem::new_with_label("No recent files");
+            item.set_sensitive(false);
+            self.menu.append(&item);
+        }
+
+        self.menu.show_all();
+    }
+}
+
+type TrashCallback = dyn Fn(&[usize]);
+
+/// Menu with the trees that were removed, to restore them.
+pub struct TrashMenu {
+    menu: Menu,
+    restore: Rc<TrashCallback>,
+}
+
+impl Deref for TrashMenu {
+    type Target = Menu;
+
+    fn deref(&self) -> &Self::Target {
+        &self.menu
+    }
+}
+
+impl TrashMenu {
+    /// Create the menu, `restore` is called with the trees that are
+    /// restored.
+    pub fn from_menu<F>(menu: &Menu, restore: F) -> Self
+    where
+        F: Fn(&[usize]) + 'static,
+    {
+        TrashMenu {
+            menu: menu.clone(),
+            restore: Rc::new(restore),
+        }
+    }
+
+    /// Update the menu with removed trees and their sentences. `all` are
+    /// all removed trees, which can be more than the trees that are
+    /// listed.
+    pub fn update(&self, trees: &[(usize, String)], all: &[usize]) {
+        for child in self.menu.get_children() {
+            self.menu.remove(&child);
+        }
+
+        for &(idx, ref sentence) in trees {
+            let item = MenuItem::new_with_label(&format!("{}: {}", idx + 1, sentence));
+
+            let restore = self.restore.clone();
+            item.connect_activate(move |_| restore(&[idx]));
+
+            self.menu.append(&item);
+        }
+
+        if all.is_empty() {
+            let item = MenuItem::new_with_label("Trash is empty");
+            item.set_sensitive(false);
+            self.menu.append(&item);
+        } else {
+            if all.len() > trees.len() {
+                let item =
+                    MenuItem::new_with_label(&format!("{} more trees", all.len() - trees.len()));
+                item.set_sensitive(false);
+                self.menu.append(&item);
+            }
+
+            self.menu.append(&SeparatorMenuItem::new());
+
+            let item = MenuItem::new_with_label("Restore all");
+            let restore = self.restore.clone();
+            let all = all.to_vec();
+            item.connect_activate(move |_| restore(&all));
+            self.menu.append(&item);
+        }
+
+        self.menu.show_all();
+    }
+}
+
+/// Number of seconds after which messages disappear from the
+/// notification bar. Errors stay until they are closed.
+const NOTIFICATION_SECONDS: u32 = 5;
+
+/// Feedback to the user: messages and errors in the notification bar of
+/// the viewer, and error dialogs for failures that need attention.
+#[derive(Clone)]
+pub struct Notifier {
+    window: Window,
+    info_bar: InfoBar,
+    label: Label,
+
+    /// Incremented for every message, so that a message is not hidden
+    /// by the timeout of an earlier message.
+    generation: Rc<Cell<usize>>,
+}
+
+impl Notifier {
+    pub fn new(window: &Window, info_bar: &InfoBar, label: &Label) -> Self {
+        info_bar.set_show_close_button(true);
+        info_bar.connect_response(|info_bar, _| info_bar.hide());
+
+        Notifier {
+            window: window.clone(),
+            info_bar: info_bar.clone(),
+            label: label.clone(),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    fn show(&self, message_type: MessageType, message: &str) -> usize {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+
+        self.label.set_text(message);
+        self.info_bar.set_message_type(message_type);
+        self.info_bar.show();
+
+        generation
+    }
+
+    /// Show a message, which disappears after a few seconds.
+    pub fn info(&self, message: &str) {
+        let generation = self.show(MessageType::Info, message);
+
+        let info_bar = self.info_bar.clone();
+        let current = self.generation.clone();
+        gtk::timeout_add_seconds(NOTIFICATION_SECONDS, move || {
+            if current.get() == generation {
+                info_bar.hide();
+            }
+            glib::Continue(false)
+        });
+    }
+
+    /// Show an error, which stays until it is closed or replaced.
+    pub fn error(&self, message: &str) {
+        self.show(MessageType::Error, message);
+    }
+
+    /// Show an error in a dialog, with details under the message.
+    pub fn error_dialog(&self, message: &str, details: &str) {
+        let dialog = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Error,
+            ButtonsType::Close,
+            message,
+        );
+        dialog.set_property_secondary_text(Some(details));
+        dialog.connect_response(|dialog, _| dialog.destroy());
+        dialog.show_all();
+    }
+}
+
+fn append_text_column(tree_view: &TreeView, title: &str, column: i32) {
+    append_column(tree_view, title, "text", column)
+}
+
+fn append_column(tree_view: &TreeView, title: &str, attribute: &str, column: i32) {
+    let cell = CellRendererText::new();
+    let tree_column = TreeViewColumn::new();
+    tree_column.set_title(title);
+    tree_column.pack_start(&cell, true);
+    tree_column.add_attribute(&cell, attribute, column);
+    tree_view.append_column(&tree_column);
+}